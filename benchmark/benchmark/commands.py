@@ -43,35 +43,61 @@ class CommandMaker:
         return f'./node generate_keys --filename {filename}'
 
     @staticmethod
-    def run_primary(parameters, committees, shardid, keys, store, debug=False):
+    def deal_threshold_keys(key_files, filename):
+        assert isinstance(key_files, list)
+        assert isinstance(filename, str)
+        keys = ' '.join(key_files)
+        return f'./node deal_threshold_keys --keys {keys} --filename {filename}'
+
+    @staticmethod
+    def generate_account_keys(accounts, seed, filename):
+        assert isinstance(accounts, list)
+        assert isinstance(seed, int) and seed >= 0
+        assert isinstance(filename, str)
+        accounts = ' '.join(accounts)
+        return f'./node generate_account_keys --accounts {accounts} --seed {seed} --filename {filename}'
+
+    @staticmethod
+    def run_primary(parameters, committees, shardid, keys, store, debug=False, metrics=None):
         assert isinstance(keys, str)
         assert isinstance(committees, str)
         assert isinstance(parameters, str)
         assert isinstance(debug, bool)
         v = '-vvv' if debug else '-vv'
+        m = f'--metrics {metrics} ' if metrics else ''
         return (f'./node {v} run --keys {keys} --committee {committees} --shardid {shardid} '
-                f'--store {store} --parameters {parameters} primary')
+                f'--store {store} --parameters {parameters} {m}primary')
 
     @staticmethod
-    def run_worker(executor_type, state_store_type, acc_shard_type, append_type, parameters, committees, shardid, id, cs_faults, is_cs_fault, keys, store, ftstore, acc2shard, actacc2shard, epoch, debug=False):
+    def run_worker(executor_type, state_store_type, acc_shard_type, append_type, parameters, committees, shardid, id, cs_faults, is_cs_fault, keys, store, ftstore, acc2shard, actacc2shard, epoch, debug=False, metrics=None, account_keys=None):
         assert isinstance(keys, str)
         assert isinstance(committees, str)
         assert isinstance(parameters, str)
         assert isinstance(acc2shard, str)
+        assert isinstance(account_keys, str)
         assert isinstance(debug, bool)
         v = '-vvv' if debug else '-vv'
+        m = f'--metrics {metrics} ' if metrics else ''
         return (f'./node {v} run --keys {keys} --committee {committees} --shardid {shardid} '
-                f'--store {store} --parameters {parameters} worker --id {id} --cs_faults {cs_faults} --is_cs_fault {is_cs_fault} --acc2shard {acc2shard} --actacc2shard {actacc2shard} --ftstore {ftstore} --state_store_type {state_store_type} --executor_type {executor_type} --acc_shard_type {acc_shard_type} --append_type {append_type} --epoch {epoch} ')
+                f'--store {store} --parameters {parameters} {m}worker --id {id} --cs_faults {cs_faults} --is_cs_fault {is_cs_fault} --acc2shard {acc2shard} --actacc2shard {actacc2shard} --account_keys {account_keys} --ftstore {ftstore} --state_store_type {state_store_type} --executor_type {executor_type} --acc_shard_type {acc_shard_type} --append_type {append_type} --epoch {epoch} ')
 
     @staticmethod
-    def run_client(executor_type, acc_shard_type, committees, client_addr, size, rate, total_txs, workload, acc2shard, brokers, nodes, epoch):
+    def run_client(executor_type, acc_shard_type, committees, client_addr, size, rate, total_txs, workload, acc2shard, brokers, nodes, epoch, metrics=None, keys_seed=None):
         assert isinstance(size, int) and size > 0
+        assert isinstance(keys_seed, int) and keys_seed >= 0
         assert isinstance(rate, int) and rate >= 0
         assert isinstance(nodes, list)
         assert all(isinstance(x, str) for x in nodes)
         assert isinstance(workload, str)
         nodes = f'--nodes {" ".join(nodes)}' if nodes else ''
-        return f'./benchmark_client --executor_type {executor_type} --acc_shard_type {acc_shard_type} --committee {committees} --client_addr {client_addr} --size {size} --rate {rate} --totaltxs {total_txs} --workload {workload} --acc2shard {acc2shard} --brokers {brokers} --epoch {epoch} {nodes}'
+        m = f'--metrics {metrics} ' if metrics else ''
+        return f'./benchmark_client {m} --executor_type {executor_type} --acc_shard_type {acc_shard_type} --committee {committees} --client_addr {client_addr} --size {size} --rate {rate} --totaltxs {total_txs} --workload {workload} --acc2shard {acc2shard} --brokers {brokers} --keys_seed {keys_seed} --epoch {epoch} {nodes}'
+
+    @staticmethod
+    def scrape_metrics(port, filename):
+        assert isinstance(port, int)
+        assert isinstance(filename, str)
+        return f'curl -sf http://127.0.0.1:{port}/metrics -o {filename}'
 
     @staticmethod
     def kill():
@@ -23,11 +23,19 @@ class Key:
             data = load(f)
         return cls(data['name'], data['secret'])
 
+# 分片的阈值公钥集合
+class ThresholdKeys:
+    @staticmethod
+    def from_file(filename):
+        assert isinstance(filename, str)
+        with open(filename, 'r') as f:
+            return load(f)
+
 # 委员会类，用于管理分布式系统中的多个委员会
 class Committees:
-  def __init__(self, committees, client_addr, shard_num, shard_size):
+  def __init__(self, committees, client_addr, shard_num, shard_size, threshold_keys):
     # 初始化委员会类
-    self.json = {'shards': committees, 'client': client_addr, 'shard_num': shard_num, 'shard_size': shard_size}
+    self.json = {'shards': committees, 'client': client_addr, 'shard_num': shard_num, 'shard_size': shard_size, 'threshold_keys': threshold_keys}
 
   def shard(self, shard_id):
     # 返回分片数据
@@ -58,7 +66,8 @@ class Committee:
                         "cross_shard_worker": "x.x.x.x:x",
                         "primary_to_worker": x.x.x.x:x,
                         "worker_to_worker": x.x.x.x:x,
-                        "transactions": x.x.x.x:x
+                        "transactions": x.x.x.x:x,
+                        "query": x.x.x.x:x
                     },
                     ...
                 }
@@ -66,6 +75,7 @@ class Committee:
             ...
         }
     """
+    METRICS_PORT_OFFSET = 10000
 
     def __init__(self, addresses, base_port_of_nodes_list, shardid):
         """ The `addresses` field looks as follows:
@@ -113,8 +123,9 @@ class Committee:
                     'transactions': f'{host}:{port + 1}',
                     'worker_to_worker': f'{host}:{port + 2}',
                     'cross_shard_worker': f'{host}:{port + 3}',
+                    'query': f'{host}:{port + 4}',
                 }
-                port += 4
+                port += 5
 
             # 将节点的权限和工作节点信息添加到委员会数据中
             # self.json[f'{shardid}']['authorities'][name]
@@ -202,6 +213,13 @@ class Committee:
         assert isinstance(address, str)
         return address.split(':')[0]
 
+    @staticmethod
+    def metrics_port(address):
+        # a node serves its metrics on the port of its primary or worker address, shifted out of
+        # the ports of the committees
+        assert isinstance(address, str)
+        return int(address.split(':')[1]) + Committee.METRICS_PORT_OFFSET
+
 # LocalCommittee是Committee类的子类，专门处理本地地址的委员会
 class LocalCommittee(Committee):
     def __init__(self, names, port, workers, shardid):
@@ -214,7 +232,7 @@ class LocalCommittee(Committee):
         # base_port: port
         base_port_of_nodes = []
         for i in range(len(names)):
-          base_port_of_nodes.append(port + i * 6)
+          base_port_of_nodes.append(port + i * 7)
 
         # print(base_port_of_nodes)
         # os._exit(0)
@@ -289,7 +307,7 @@ class BenchParameters:
             # executor_type
             executor_type = json['executor_type']
             executor_type = executor_type if isinstance(executor_type, list) else [executor_type]
-            if not executor_type or any(x < 0 or x > 2 for x in executor_type):
+            if not executor_type or any(x < 0 or x > 3 for x in executor_type):
                 raise ConfigError('Missing or invalid executor_type')
             self.executor_type = [int(x) for x in executor_type]
             
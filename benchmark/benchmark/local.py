@@ -4,9 +4,10 @@ import subprocess
 from os.path import basename, splitext
 from time import sleep
 import time
+import random
 from os.path import join
 from .commands import CommandMaker
-from .config import Key, Committees, LocalCommittee, NodeParameters, BenchParameters, ConfigError
+from .config import Key, ThresholdKeys, Committee, Committees, LocalCommittee, NodeParameters, BenchParameters, ConfigError
 from .logs import LogParser, ParseError
 from .utils import Print, BenchError, PathMaker, progress_bar
 from collections import OrderedDict
@@ -67,6 +68,7 @@ class LocalBench:
         # 初始化配置
         committees = OrderedDict()  # 保证分片顺序一致
         committeeList = []  # 存储每个分片的委员会信息
+        threshold_keys = OrderedDict()  # 每个分片的阈值公钥集合
 
         print("Node name list: ")
         shardid = 0
@@ -79,6 +81,12 @@ class LocalBench:
                 subprocess.run(cmd, check=True)  # 执行密钥生成命令
                 keys += [Key.from_file(filename)]  # 从文件中加载密钥
 
+            # 为分片内的节点分发阈值签名密钥份额
+            threshold_file = PathMaker.threshold_keys_file(shardid)
+            cmd = CommandMaker.deal_threshold_keys(key_files, threshold_file).split()
+            subprocess.run(cmd, check=True)
+            threshold_keys[shardid] = ThresholdKeys.from_file(threshold_file)
+
             names = [x.name for x in keys]  # 获取节点名称
             print("shard", shardid, sorted(names))  # 输出分片信息
             # 创建本地委员会对象
@@ -90,10 +98,16 @@ class LocalBench:
             shardid += 1
             self.BASE_PORT += 6 * shard_size  # 更新端口以适配更多节点
 
+        # 生成账户的创世密钥注册表，客户端用同一个种子派生账户私钥
+        self.keys_seed = random.randrange(2 ** 63)
+        accounts = [PathMaker.acc2shard_file_default(), PathMaker.brokers_file()]
+        cmd = CommandMaker.generate_account_keys(accounts, self.keys_seed, PathMaker.account_keys_file()).split()
+        subprocess.run(cmd, check=True)
+
         # 生成节点参数文件
         self.node_parameters.print(PathMaker.parameters_file())
         # 生成委员会配置文件
-        committees = Committees(committees, self.client_addr, shard_number, shard_size)
+        committees = Committees(committees, self.client_addr, shard_number, shard_size, threshold_keys)
         committees.print(PathMaker.committees_file())
 
         return committeeList  # 返回委员会列表
@@ -109,6 +123,7 @@ class LocalBench:
 
       # Run committees
       all_running_worker_addrs = []
+      metrics = [] # (metrics port, log file) of each running process
       shardid = 0
       while shardid < shard_num:        
           workers_addresses = committeeList[shardid].workers_addresses(self.faults)   
@@ -121,9 +136,11 @@ class LocalBench:
                   shardid,
                   PathMaker.key_file(nodeid, shardid),
                   PathMaker.db_path(nodeid, shardid),
-                  debug
+                  debug,
+                  metrics=f'0.0.0.0:{Committee.metrics_port(address)}',
               )
               log_file = PathMaker.primary_log_file(nodeid, shardid)
+              metrics.append((Committee.metrics_port(address), log_file))
               self._background_run(cmd, log_file)
 
           # Run the workers (except the faulty ones).
@@ -154,9 +171,12 @@ class LocalBench:
                       # PathMaker.actacc2shard_file(epoch, shard_num),
                       epoch,
                       debug,
+                      metrics=f'0.0.0.0:{Committee.metrics_port(address)}',
+                      account_keys=PathMaker.account_keys_file(),
                   )
                   # print(cmd)
                   log_file = PathMaker.worker_log_file(nodeid, id, shardid)
+                  metrics.append((Committee.metrics_port(address), log_file))
                   self._background_run(cmd, log_file)
           shardid += 1
 
@@ -174,8 +194,11 @@ class LocalBench:
           PathMaker().brokers_file(),
           all_running_worker_addrs,
           epoch,
+          metrics=f'0.0.0.0:{Committee.metrics_port(self.client_addr)}',
+          keys_seed=self.keys_seed,
       )
       log_file = PathMaker.client_log_file()
+      metrics.append((Committee.metrics_port(self.client_addr), log_file))
       self._background_run(cmd, log_file)
 
       # Wait for all transactions to be processed.
@@ -184,6 +207,11 @@ class LocalBench:
       for _ in progress_bar(range(self.duration), prefix=f'Running benchmark ({self.duration} sec):'):
           sleep(1) 
       print(time.strftime('%Y-%m-%d %H:%M:%S',time.localtime(time.time())), "Done!")
+
+      # Scrape the metrics of the processes before killing them.
+      for port, log_file in metrics:
+          cmd = CommandMaker.scrape_metrics(port, PathMaker.metrics_file(log_file))
+          subprocess.run([cmd], shell=True, stderr=subprocess.DEVNULL)
       self._kill_nodes()
 
 
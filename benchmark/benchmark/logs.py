@@ -14,8 +14,29 @@ class ParseError(Exception):
     pass
 
 
+class Metrics:
+    ''' The samples scraped from the metrics endpoint of a process. '''
+
+    def __init__(self, text):
+        self.samples = {}
+        for line in text.splitlines():
+            if line and not line.startswith('#'):
+                key, value = line.rsplit(' ', 1)
+                self.samples[key] = float(value)
+
+    def get(self, name, **labels):
+        # a counter without observations is not exported yet
+        if labels:
+            name += '{' + ','.join(f'{k}="{v}"' for k, v in sorted(labels.items())) + '}'
+        return self.samples.get(name, 0)
+
+    @staticmethod
+    def total(metrics, name, **labels):
+        return sum(m.get(name, **labels) for m in metrics)
+
+
 class LogParser:
-    def __init__(self, clients, primaries, workers, epoch, faults, cs_faults, total_txs, shard_nums, duration, sample_interval, res_ledger_MB, res_state_MB):
+    def __init__(self, clients, primaries, workers, client_metrics, primary_metrics, worker_metrics, epoch, faults, cs_faults, total_txs, shard_nums, duration, sample_interval, res_ledger_MB, res_state_MB):
         ## storage cost
         assert isinstance(res_ledger_MB, list)
         self.res_ledger_MB = res_ledger_MB
@@ -27,6 +48,12 @@ class LogParser:
         assert all(isinstance(x, list) for x in inputs)
         assert all(isinstance(x, str) for y in inputs for x in y)
         assert all(x for x in inputs)
+        # every process serves its metrics, they are missing if it crashed before the scrape
+        if (len(client_metrics), len(primary_metrics), len(worker_metrics)) != (len(clients), len(primaries), len(workers)):
+            raise ParseError('Failed to scrape the metrics of all the processes')
+        client_metrics = [Metrics(x) for x in client_metrics]
+        primary_metrics = [Metrics(x) for x in primary_metrics]
+        worker_metrics = [Metrics(x) for x in worker_metrics]
         self.duration = duration
         self.faults = faults
         self.cs_faults = cs_faults
@@ -53,12 +80,12 @@ class LogParser:
         except (ValueError, IndexError, AttributeError) as e:
             raise ParseError(f'Failed to parse clients\' logs: {e}')
         # 解包客户端日志解析结果，将这些元组的同一位置的元素组合成新的元组
-        size, rate, self.start, misses, self.sent_samples, total_sent_txs \
+        size, rate, self.start, misses, self.sent_samples \
             = zip(*results)
         self.size = size[0]
         self.misses = sum(misses)
         self.rate = sum(rate)
-        self.total_sent_txs = sum(total_sent_txs)   
+        self.total_sent_txs = Metrics.total(client_metrics, 'client_txs_sent_total')
 
         # Parse the primaries logs.
         try:
@@ -67,6 +94,10 @@ class LogParser:
         except (ValueError, IndexError, AttributeError) as e:
             raise ParseError(f'Failed to parse nodes\' logs: {e}')
         proposals, commits, self.configs, primary_ips = zip(*results)
+        certificates = Metrics.total(primary_metrics, 'consensus_commit_latency_ms_count')
+        self.certificate_commit_latency = 0
+        if certificates != 0:
+          self.certificate_commit_latency = Metrics.total(primary_metrics, 'consensus_commit_latency_ms_sum') / certificates
         self.proposals = self._merge_results([x.items() for x in proposals]) # {batch.digest, timestamp}
         self.commits = self._merge_results([x.items() for x in commits]) # {batch.digest, timestamp}
         
@@ -76,7 +107,7 @@ class LogParser:
                 results = p.map(self._parse_workers, workers) # a list
         except (ValueError, IndexError, AttributeError) as e:
             raise ParseError(f'Failed to parse workers\' logs: {e}')
-        workers_ips, sizes, self.submitted_tx_nums, self.total_packaged_external_txs, self.received_samples, executed_txs, self.execution_end_t, self.total_general_txs, self.total_external_txs, self.total_cs_txs, self.total_commit_txs, self.total_aborted_txs, self.cache_storage_cost, append_delay, append_type = zip(*results) # batch
+        workers_ips, sizes, self.submitted_tx_nums, self.received_samples, executed_txs, self.execution_end_t, self.total_general_txs, self.total_external_txs, self.total_cs_txs, self.cache_storage_cost, append_type = zip(*results) # batch
 
         # 过滤并生成已提交批次的大小字典
         # sizes: ({batch.digest: batch size}, {}, {}, {})
@@ -88,7 +119,7 @@ class LogParser:
         self.collocate = set(primary_ips) == set(workers_ips)
 
         # 计算打包的外部交易总数
-        self.total_packaged_external_txs = int(sum(self.total_packaged_external_txs))
+        self.total_packaged_external_txs = int(Metrics.total(worker_metrics, 'worker_batch_external_txs_sum'))
         # print("total_packaged_external_txs: ", self.total_packaged_external_txs)
 
         # 合并执行交易的结果数据
@@ -117,20 +148,14 @@ class LogParser:
         self.total_general_txs = int(sum(self.total_general_txs) / self.committee_size)
         self.total_external_txs = int(sum(self.total_external_txs) / self.committee_size)
         self.total_cs_txs = int(sum(self.total_cs_txs) / self.committee_size)
-        self.total_commit_txs = int(sum(self.total_commit_txs) / self.committee_size)
-        self.total_aborted_txs = int(sum(self.total_aborted_txs) / self.committee_size)
+        self.total_commit_txs = int(Metrics.total(worker_metrics, 'worker_executed_txs_total', status='committed') / self.committee_size)
+        self.total_aborted_txs = int(Metrics.total(worker_metrics, 'worker_executed_txs_total', status='aborted') / self.committee_size)
 
         # 计算追加延迟的平均值
-        # print("all append delay: ", append_delay)
-        avg_delay = []
-        for tmp in append_delay:
-          if len(tmp) != 0:
-             avg_delay.append(mean(tmp))
+        appended = Metrics.total(worker_metrics, 'worker_csmsg_append_delay_ms_count')
         self.avg_csmsg_append_delay = 0
-        if len(avg_delay) != 0:
-          self.avg_csmsg_append_delay = mean(avg_delay)
-        # print(avg_delay)
-        # print(self.avg_csmsg_append_delay)
+        if appended != 0:
+          self.avg_csmsg_append_delay = Metrics.total(worker_metrics, 'worker_csmsg_append_delay_ms_sum') / appended
 
         # 设置追加类型
         self.append_type = append_type[0]
@@ -169,13 +194,8 @@ class LogParser:
         # 提取日志中所有采样交易的信息
         tmp = findall(r'\[(.*Z) .* sample transaction (\d+)', log)
         samples = {int(s): self._to_posix(t) for t, s in tmp} # all sample tx -> dict. samples = {sample_tx.counter: timestamp}
-        total_sent_sample_txs = len(tmp)
-
-        # 提取采样间隔
-        sample_interval = int(search(r'sample interval: one per (\d+) txs', log).group(1))
-        total_sent_txs = total_sent_sample_txs * sample_interval
 
-        return size, rate, start, misses, samples, total_sent_txs
+        return size, rate, start, misses, samples
 
     def _parse_primaries(self, log):
         # 检查日志中是否有panicked或者error，如有则说明节点崩溃
@@ -232,13 +252,9 @@ class LogParser:
         append_type = search(r'append_type: ([^ ]+) ', log).group(1)
 
         ### batch maker
-        tmp = findall(r'Batch ([^ ]+) contains (\d+) B and (\d+) external txs, (\d+) total_packaged_external_txs', log)
-        sizes = {d: int(s) for d, s, nums, total_nums in tmp} # {batch.digest: batch size}
-        submitted_tx_nums = {d: int(nums) for d, s, nums, total_nums in tmp} # {batch.digest: external txs}
-        total_packaged_external_txs = 0
-        if len(tmp) != 0:
-          _, _, _, total_packaged_external_txs = tmp[-1]
-        total_packaged_external_txs = int(total_packaged_external_txs)
+        tmp = findall(r'Batch ([^ ]+) contains (\d+) B and (\d+) external txs', log)
+        sizes = {d: int(s) for d, s, nums in tmp} # {batch.digest: batch size}
+        submitted_tx_nums = {d: int(nums) for d, s, nums in tmp} # {batch.digest: external txs}
         
         tmp = findall(r'Batch ([^ ]+) contains sample tx (\d+)', log) 
         samples = {int(s): d for d, s in tmp} # {sample_tx.counter: batch.digest}
@@ -249,25 +265,19 @@ class LogParser:
         tmp = [(int(tx), self._to_posix(t)) for t, tx in tmp] # [(sample tx, executed_timestamp)]
         executed_txs = self._merge_results([tmp]) # executed_txs is a list. {sample tx: executed_timestamp}
 
-        tmp = findall(r'\[(.*Z) .* total_general_txs: (\d+), total_external_txs: (\d+), total_cross_shard_txs: (\d+), total_commit_txs: (\d+), total_aborted_txs: (\d+)', log)
-        end_t, total_general_txs, total_external_txs, total_cs_txs, total_commit_txs, total_aborted_txs = tmp[-1]
+        tmp = findall(r'\[(.*Z) .* total_general_txs: (\d+), total_external_txs: (\d+), total_cross_shard_txs: (\d+)', log)
+        end_t, total_general_txs, total_external_txs, total_cs_txs = tmp[-1]
         end_t = self._to_posix(end_t)
         total_general_txs = int(total_general_txs)
         total_external_txs = int(total_external_txs)
         total_cs_txs = int(total_cs_txs)
-        total_commit_txs = int(total_commit_txs)
-        total_aborted_txs = int(total_aborted_txs)
 
         tmp = findall(r'Storage cost of acc caching: (\d+) KB', log)
         cache_storage_cost = [int(cost) for cost in tmp]
         # print(cache_storage_cost)
         # print("max_storage_cost: ", max(cache_storage_cost))
 
-        tmp = findall(r'append delay: (\d+) ms', log) # t, tx       
-        append_delay = [int(x) for x in tmp]
-        # print("csmsg appending delay", append_delay)
-
-        return ip, sizes, submitted_tx_nums, total_packaged_external_txs, samples, executed_txs, end_t, total_general_txs, total_external_txs, total_cs_txs, total_commit_txs, total_aborted_txs, cache_storage_cost, append_delay, append_type
+        return ip, sizes, submitted_tx_nums, samples, executed_txs, end_t, total_general_txs, total_external_txs, total_cs_txs, cache_storage_cost, append_type
 
     def _to_posix(self, string):
         x = datetime.fromisoformat(string.replace('Z', '+00:00'))
@@ -398,6 +408,7 @@ class LogParser:
             f' Consensus TPS: {round(consensus_tps):,} tx/s\n'
             f' Consensus BPS: {round(consensus_bps):,} B/s\n'
             f' Consensus latency: {round(consensus_latency):,} ms\n'
+            f' Certificate commit latency: {round(self.certificate_commit_latency):,} ms\n'
             '\n'
             '------End-to-end Consensus Performance------\n'
             f' End-to-end TPS: {round(end_to_end_tps):,} tx/s\n'
@@ -457,9 +468,15 @@ class LogParser:
         for filename in sorted(glob(join(directory, 'worker-*.log'))):
             with open(filename, 'r') as f:
                 workers += [f.read()]
-        
 
-        return cls(clients, primaries, workers, epoch, faults, cs_faults, total_txs, shard_nums, duration, sample_interval, res_ledger_MB, res_state_MB)
+        # The metrics scraped from the endpoints of the processes.
+        client_metrics, primary_metrics, worker_metrics = [], [], []
+        for pattern, metrics in [('client.prom', client_metrics), ('primary-*.prom', primary_metrics), ('worker-*.prom', worker_metrics)]:
+            for filename in sorted(glob(join(directory, pattern))):
+                with open(filename, 'r') as f:
+                    metrics += [f.read()]
+
+        return cls(clients, primaries, workers, client_metrics, primary_metrics, worker_metrics, epoch, faults, cs_faults, total_txs, shard_nums, duration, sample_interval, res_ledger_MB, res_state_MB)
 
 if __name__ == "__main__":
 
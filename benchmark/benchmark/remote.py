@@ -8,8 +8,9 @@ from os.path import basename, splitext
 from time import sleep
 import subprocess
 import math
+import random
 from os.path import join
-from .config import Committee, Committees, Key, NodeParameters, BenchParameters, ConfigError
+from .config import Committee, Committees, Key, ThresholdKeys, NodeParameters, BenchParameters, ConfigError
 from .utils import BenchError, Print, PathMaker, progress_bar
 from .commands import CommandMaker
 from .logs import LogParser, ParseError
@@ -260,6 +261,7 @@ class RemoteBench:
         # Generate configuration files.
         committees = OrderedDict()
         committeeList = []
+        threshold_keys = OrderedDict()
         
         # generate configuration files for shard temp
         for shardid in range(shard_number):
@@ -270,6 +272,12 @@ class RemoteBench:
                 subprocess.run(cmd, check=True)
                 keys += [Key.from_file(filename)]
                 # print(keys)  # for debug
+
+            # deal the threshold key shares of shard `shardid`
+            threshold_file = PathMaker.threshold_keys_file(shardid)
+            cmd = CommandMaker.deal_threshold_keys(key_files, threshold_file).split()
+            subprocess.run(cmd, check=True)
+            threshold_keys[shardid] = ThresholdKeys.from_file(threshold_file)
             
             names = [x.name for x in keys]
             print("shard ", shardid, sorted(names))
@@ -294,7 +302,7 @@ class RemoteBench:
             base_port_of_each_node = []
             for host_ip in temp_ips:
               port = base_port_for_used_hosts[host_ip]
-              base_port_for_used_hosts[host_ip] += 6 # a node needs 6 ports
+              base_port_for_used_hosts[host_ip] += 7 # a node needs 7 ports
               base_port_of_each_node.append(port)
 
 
@@ -305,10 +313,16 @@ class RemoteBench:
             committeeList.append(committee)
             committees[shardid] = committee.json       
         
+        # generate the genesis key registry of the accounts, the client derives their keys from the seed
+        self.keys_seed = random.randrange(2 ** 63)
+        accounts = [PathMaker.acc2shard_file_default(), PathMaker.brokers_file()]
+        cmd = CommandMaker.generate_account_keys(accounts, self.keys_seed, PathMaker.account_keys_file()).split()
+        subprocess.run(cmd, check=True)
+
         # generate .parameters.json
         node_parameters.print(PathMaker.parameters_file())
         # generate .committees.json
-        committees = Committees(committees, self.client_addr, shard_number, shard_size)
+        committees = Committees(committees, self.client_addr, shard_number, shard_size, threshold_keys)
         committees.print(PathMaker.committees_file())
 
 
@@ -321,6 +335,7 @@ class RemoteBench:
             g.run(f'{CommandMaker.cleanup_config()} || true', hide=True)
             g.put(PathMaker.committees_file(), PathMaker.configs_path())
             g.put(PathMaker.parameters_file(), PathMaker.configs_path())
+            g.put(PathMaker.account_keys_file(), PathMaker.configs_path())
         except (GroupException, ExecutionError) as e:
             e = FabricError(e) if isinstance(e, GroupException) else e
             raise BenchError('Failed to upload config file on testbed', e)
@@ -344,6 +359,7 @@ class RemoteBench:
         self.kill(hosts=used_hosts, delete_logs=True, delete_dbs=True)
 
         all_running_worker_addrs = []
+        metrics = [] # (host, metrics port, log file) of each running node
         # Run committees
         print(time.strftime('%Y-%m-%d %H:%M:%S',time.localtime(time.time())), "Booting nodes...")
         progress = progress_bar(committeeList, prefix=f'Booting all shards:')
@@ -361,9 +377,11 @@ class RemoteBench:
                   shardid,
                   PathMaker.key_file(nodeid, shardid),
                   PathMaker.db_path(nodeid, shardid),
-                  debug
+                  debug,
+                  metrics=f'0.0.0.0:{Committee.metrics_port(address)}',
               )
               log_file = PathMaker.primary_log_file(nodeid, shardid)
+              metrics.append((host, Committee.metrics_port(address), log_file))
               # print("run primary: ", address, "host: ", host, cmd, log_file)
               self._background_run(host, cmd, log_file)
 
@@ -399,8 +417,11 @@ class RemoteBench:
                       epoch,
                       self.bench_parameters.sample_interval,
                       debug,
+                      metrics=f'0.0.0.0:{Committee.metrics_port(address)}',
+                      account_keys=PathMaker.account_keys_file(),
                   )
                   log_file = PathMaker.worker_log_file(nodeid, id, shardid)
+                  metrics.append((host, Committee.metrics_port(address), log_file))
                   # print("run worker: ", address, "host: ", host, cmd, log_file)
                   self._background_run(host, cmd, log_file)
 
@@ -418,6 +439,8 @@ class RemoteBench:
           PathMaker().brokers_file(),
           all_running_worker_addrs,
           epoch,
+          metrics=f'0.0.0.0:{Committee.metrics_port(self.client_addr)}',
+          keys_seed=self.keys_seed,
         )
         log_file = PathMaker.client_log_file()
         self._background_run_client(cmd, log_file)
@@ -428,6 +451,15 @@ class RemoteBench:
         for _ in progress_bar(range(duration), prefix=f'Running benchmark ({duration} sec):'):
             sleep(1)            
         print(time.strftime('%Y-%m-%d %H:%M:%S',time.localtime(time.time())), "Done...\n")
+
+        # Scrape the metrics of the nodes and the client before killing them.
+        for host, port, log_file in metrics:
+            c = Connection(host, user='root', connect_kwargs=self.connect)
+            c.run(CommandMaker.scrape_metrics(port, PathMaker.metrics_file(log_file)), hide=True, warn=True)
+        cmd = CommandMaker.scrape_metrics(
+            Committee.metrics_port(self.client_addr), PathMaker.metrics_file(PathMaker.client_log_file())
+        )
+        subprocess.run([cmd], shell=True, stderr=subprocess.DEVNULL)
         self.kill(hosts=used_hosts, delete_logs=False, delete_dbs=False)
 
 
@@ -455,6 +487,8 @@ class RemoteBench:
                       PathMaker.worker_log_file(nodeid, id, shardid), 
                       local=PathMaker.worker_log_file(nodeid, id, shardid)
                   )
+                  metrics_file = PathMaker.metrics_file(PathMaker.worker_log_file(nodeid, id, shardid))
+                  c.get(metrics_file, local=metrics_file)
 
                   # measure storage cost 
                   # 1. get ledger storage cost of node
@@ -484,6 +518,8 @@ class RemoteBench:
                   PathMaker.primary_log_file(nodeid, shardid), 
                   local=PathMaker.primary_log_file(nodeid, shardid)
               )
+              metrics_file = PathMaker.metrics_file(PathMaker.primary_log_file(nodeid, shardid))
+              c.get(metrics_file, local=metrics_file)
         ## end of for shardid
 
         # Parse logs and return the parser.
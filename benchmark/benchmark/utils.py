@@ -51,6 +51,15 @@ class PathMaker:
         assert isinstance(shardid, int) and shardid >= 0
         return join(PathMaker.configs_path(), f'.node-{i}-{shardid}.json')
 
+    @staticmethod
+    def account_keys_file(): # the genesis key registry of the accounts
+        return join(PathMaker.configs_path(), '.account-keys.csv')
+
+    @staticmethod
+    def threshold_keys_file(shardid):
+        assert isinstance(shardid, int) and shardid >= 0
+        return join(PathMaker.configs_path(), f'.threshold-{shardid}.json')
+
     @staticmethod
     def dbs_path():
         return 'dbs'
@@ -91,6 +100,11 @@ class PathMaker:
     def client_log_file():
         return join(PathMaker.logs_path(), f'client.log')
 
+    @staticmethod
+    def metrics_file(log_file): # the metrics scraped from the process logging to `log_file`
+        assert log_file.endswith('.log')
+        return f'{log_file[:-len(".log")]}.prom'
+
 
     # workload 
     @staticmethod
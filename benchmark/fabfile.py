@@ -24,7 +24,7 @@ def local(ctx, debug=False):
         'tx_size': 512, # B 事务大小为512B
 
         'acc_shard_type': 0, # 0: Hash, 1: Graph
-        'executor_type': 0, # 0: SharDAG, 1: Monoxide, 2: Broker
+        'executor_type': 0, # 0: SharDAG, 1: Monoxide, 2: Broker, 3: TwoPhaseCommit
         'state_store_type': 0, # 0: TStore, 1: MStore. Monoxide and Broker use MStore.
         'append_type': 0, # 0: Dual-mode, 1: Serial
     }
@@ -35,7 +35,19 @@ def local(ctx, debug=False):
         'sync_retry_delay': 10_000,  # ms
         'sync_retry_nodes': 3,  # number of nodes
         'batch_size': 500_000,  # bytes 最大块大小为500MB
-        'max_batch_delay': 200  # ms
+        'max_batch_delay': 200,  # ms
+        'csmsg_gc_depth': 10_000,  # csmsgs of each source shard
+        'csmsg_retry_delay': 5_000,  # ms
+        'csmsg_ack_interval': 1_000,  # ms
+        'sender_gc_depth': 1_000,  # heights
+        'commit_timeout': 50,  # heights
+        'agg_interval': 100,  # heights, 0 disables
+        'agg_max_bytes': 1_000_000,  # bytes, 0 disables
+        'agg_max_accounts': 10_000,  # accounts per target shard, 0 disables
+        'agg_max_age': 50,  # heights, 0 disables
+        'agg_deltas': False,  # include the avatars of each height in the agg txs
+        'evict_interval': 100,  # heights, 0 disables
+        'evict_idle': 1_000,  # heights
     }
     try:
         ret = LocalBench(bench_params, node_params).run(debug)
@@ -63,7 +75,7 @@ def remote(ctx, debug=False, remote_recompile=True):
         'tx_size': 512, # B
 
         'acc_shard_type': 0, # 0: Hash, 1: Graph
-        'executor_type': [0, 1, 2], # 0: SharDAG, 1: Monoxide, 2: Broker
+        'executor_type': [0, 1, 2], # 0: SharDAG, 1: Monoxide, 2: Broker, 3: TwoPhaseCommit
         'state_store_type': 1, # 0: TStore, 1: MStore
         'append_type': 1, # 0: Dual-mode, 1: Serial
     }
@@ -74,7 +86,19 @@ def remote(ctx, debug=False, remote_recompile=True):
         'sync_retry_delay': 10_000,  # ms
         'sync_retry_nodes': 3,  # number of nodes
         'batch_size': 500_000,  # bytes
-        'max_batch_delay': 200  # ms
+        'max_batch_delay': 200,  # ms
+        'csmsg_gc_depth': 10_000,  # csmsgs of each source shard
+        'csmsg_retry_delay': 5_000,  # ms
+        'csmsg_ack_interval': 1_000,  # ms
+        'sender_gc_depth': 1_000,  # heights
+        'commit_timeout': 50,  # heights
+        'agg_interval': 100,  # heights, 0 disables
+        'agg_max_bytes': 1_000_000,  # bytes, 0 disables
+        'agg_max_accounts': 10_000,  # accounts per target shard, 0 disables
+        'agg_max_age': 50,  # heights, 0 disables
+        'agg_deltas': False,  # include the avatars of each height in the agg txs
+        'evict_interval': 100,  # heights, 0 disables
+        'evict_idle': 1_000,  # heights
     }
     try:
         RemoteBench(ctx).run(bench_params, node_params, debug, remote_recompile)
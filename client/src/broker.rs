@@ -1,13 +1,15 @@
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use config::ShardId;
 use log::{info, debug, warn};
 use store::StoreError;
-use worker::{Transaction, RWSet, Account2Shard, Account2ShardType, Account2ShardHash, Account2ShardGraph, Frame, CoreTx, Address};
+use worker::{Transaction, Amount, RWSet, Account2Shard, Account2ShardType, Account2ShardHash, Account2ShardGraph, Frame, CoreTx, Address, epoch_file};
 use tokio::sync::mpsc::{channel, Sender};
 use tokio::sync::oneshot;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::broker_manager::BrokerManager;
+use crate::tx_signer::TxSigner;
 
 // 定义通用结果类型，封装存储操作可能产生的错误
 pub type StoreResult<T> = Result<T, StoreError>;
@@ -18,6 +20,8 @@ pub enum BrokerCommand {
   ConvertTx(CoreTx, oneshot::Sender<StoreResult<(Transaction, ShardId)>>), 
   // 处理跨分片事务（第一阶段的事务）
   ProcessTx1(Transaction, oneshot::Sender<StoreResult<(Transaction, ShardId)>>),
+  // 切换到下一个 epoch：重新加载账户分片映射并重新选择 Broker（在线策略下应用各分片的账户迁移）
+  SetEpoch(usize, BTreeMap<Address, ShardId>, oneshot::Sender<()>),
 }
 
 
@@ -32,29 +36,38 @@ impl Broker {
   pub fn new(
     acc_shard_type: Account2ShardType,
     shardnum: usize,
-    acc2shard_file: String,
+    acc2shard_file: String, // may contain `{}`, which is replaced by the epoch number
     brokers_file: String,
     epoch: usize,
+    keys_seed: u64, // the seed of the keys of the accounts
   ) -> Self {
 
     info!("Create a broker!"); // 日志记录 Broker 的创建
     // crate acc_shard according to specified sharding policy
     // 根据分片策略类型创建账户分片映射器
-    let acc2shard: Arc<dyn Account2Shard + Send + Sync>;
+    let mut acc2shard: Arc<dyn Account2Shard + Send + Sync>;
     match acc_shard_type {
       Account2ShardType::HashPolicy => { // 哈希分片策略
         info!("Account2Shard: HashPolicy");
         acc2shard = Arc::new(Account2ShardHash::new(shardnum));
       },
+      // the online mapping starts from the hash policy, then follows the moves of the shards
+      Account2ShardType::OnlinePolicy => {
+        info!("Account2Shard: OnlinePolicy");
+        acc2shard = Arc::new(Account2ShardHash::new(shardnum));
+      },
       Account2ShardType::GraphPolicy => { // 图分片策略
         info!("Account2Shard: GraphPolicy");
-        acc2shard = Arc::new(Account2ShardGraph::new(shardnum, &acc2shard_file));
+        acc2shard = Arc::new(Account2ShardGraph::new(shardnum, &epoch_file(&acc2shard_file, epoch as u64)));
       }
     }
 
     // init broker addresses
     // 初始化 Broker 管理器
     let mut broker_manager = BrokerManager::new(brokers_file, epoch);
+    // a broker has an avatar in every shard, each with its own nonce
+    let mut signer = TxSigner::new(keys_seed);
+    let mut broker_signers: HashMap<ShardId, TxSigner> = HashMap::new();
 
     // 创建异步通道
     let (tx, mut rx) = channel(1000);
@@ -112,10 +125,16 @@ impl Broker {
                   }
                 }
                 // assemble tx 组装事务
-                let tx = assemble_tx(
+                let sender_is_broker = broker_manager.is_broker(&s);
+                let mut tx = assemble_tx(
                   s, r, amount, 
                   core_tx.sample, core_tx.counter, target_shard, involved_shard_num,
                   original_sender, final_receiver);
+                if sender_is_broker {
+                  broker_signers.entry(target_shard).or_insert_with(|| TxSigner::new(keys_seed)).sign(&mut tx);
+                } else {
+                  signer.sign(&mut tx);
+                }
                 let _ = sender.send(Ok((tx, target_shard))
               );
               }
@@ -141,15 +160,31 @@ impl Broker {
                       let broker = broker_manager.get_broker(); // in tx2，broker as sender
 
                       // assemble tx
-                      let tx = assemble_tx(
+                      let mut tx = assemble_tx(
                         broker, tx1_msg.core_tx.receiver, tx1_msg.core_tx.amount, 
                         tx1_msg.core_tx.sample, tx1_msg.core_tx.counter, target_shard, 2,
                         original_sender, final_receiver);
+                      broker_signers.entry(target_shard).or_insert_with(|| TxSigner::new(keys_seed)).sign(&mut tx);
                       let _ = sender.send(Ok((tx, target_shard)));
                   }
                 }
 
               }
+              BrokerCommand::SetEpoch(epoch, moves, sender) => {
+                info!("Broker enters epoch {}", epoch);
+                match acc_shard_type {
+                  Account2ShardType::GraphPolicy => {
+                    acc2shard = Arc::new(Account2ShardGraph::new(shardnum, &epoch_file(&acc2shard_file, epoch as u64)));
+                  },
+                  Account2ShardType::OnlinePolicy => {
+                    acc2shard = Arc::new(Account2ShardGraph::with_moves(acc2shard.as_ref(), moves));
+                  },
+                  Account2ShardType::HashPolicy => {},
+                }
+                // pending tx1s are kept: their tx2s are routed with the new map
+                broker_manager.set_epoch(epoch);
+                let _ = sender.send(());
+              }
           }
       }
   });
@@ -177,6 +212,17 @@ impl Broker {
         .await
         .expect("Failed to receive reply to ProcessTx1 command from BrokerStore")
   }
+
+  // 切换到下一个 epoch
+  pub async fn set_epoch(&mut self, epoch: usize, moves: BTreeMap<Address, ShardId>) {
+    let (sender, receiver) = oneshot::channel();
+    if let Err(e) = self.channel.send(BrokerCommand::SetEpoch(epoch, moves, sender)).await {
+        panic!("Failed to send SetEpoch command to BrokerStore: {}", e);
+    }
+    receiver
+        .await
+        .expect("Failed to receive reply to SetEpoch command from BrokerStore")
+  }
 }
 
 
@@ -185,7 +231,7 @@ impl Broker {
 pub fn assemble_tx(
   sender: Address,
   receiver: Address,
-  amount: f64,
+  amount: Amount,
   tx_sample: u8,
   tx_counter: u64,
   target_shard: ShardId,
@@ -195,7 +241,7 @@ pub fn assemble_tx(
 ) -> Transaction {
     let payload_len = 2;
     let mut rwsets: Vec<RWSet> = Vec::new();
-    rwsets.push(RWSet { addr: sender.clone(), value: amount * -1.0 });
+    rwsets.push(RWSet { addr: sender.clone(), value: amount.checked_neg().unwrap() });
     rwsets.push(RWSet { addr: receiver.clone(), value: amount});
     let payload: Vec<Frame> = vec![Frame { shardid: target_shard, rwset: rwsets }];
     let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_micros();
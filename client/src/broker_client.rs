@@ -18,7 +18,9 @@ use futures::future::join_all;
 use futures::sink::SinkExt as _;
 use network::{MessageHandler, Receiver, Writer};
 use std::error::Error;
+use crate::metrics;
 use crate::broker::Broker;
+use crate::tx_signer::sender_node;
 use crate::common_client::rawtx2tx;
 use crate::tx1_processor::Tx1Processor;
 use crate::tx1_verifier::Tx1Verifier;
@@ -44,7 +46,6 @@ pub struct BrokerClient {
 
     // about tx sending 
     nodes: Vec<SocketAddr>,
-    next_node_id: Vec<usize>,
     transports: HashMap<ShardId, Vec<Framed<TcpStream, LengthDelimitedCodec>>>,
 
     // broker
@@ -65,6 +66,7 @@ impl BrokerClient {
       rate: u64,
       total_txs: u32,
       send_tx_duration_ms: u32,
+      keys_seed: u64, // the seed of the keys of the accounts
       //
       mut client_addr: SocketAddr,
       all_committees: Committees,
@@ -78,6 +80,7 @@ impl BrokerClient {
         acc2shard_file,
         brokers_file,
         epoch,
+        keys_seed,
       );
 
       let mut client = BrokerClient {
@@ -90,7 +93,6 @@ impl BrokerClient {
         send_tx_duration_ms,
 
         nodes: nodes.clone(),
-        next_node_id: vec![0; shardnum as usize],
         transports: HashMap::default(),
         
         broker: broker.clone(),
@@ -113,8 +115,6 @@ impl BrokerClient {
       Tx1Verifier::spawn(
         rx_cross_shard_msg,
         tx_process_tx1,
-        all_committees.validity_threshold(),
-        all_committees.shard_size(),
         all_committees,
         csmsg_store,
       );
@@ -181,6 +181,7 @@ impl BrokerClient {
                 let mut tx_counter: u64 = counter;
                 if x == counter % burst {// sample tx
                     info!("Sending sample transaction {}", tx_counter);
+                    metrics::SAMPLE_TXS_SENT.inc();
                 } else { // Standard tx
                     r += 1;
                     tx_sample = 1;
@@ -199,6 +200,7 @@ impl BrokerClient {
                 }
 
                 sent_txs += 1;
+                metrics::TXS_SENT.inc();
 
               }else{
                 break 'main;
@@ -218,13 +220,12 @@ impl BrokerClient {
 
 
   pub async fn send_tx(&mut self, tx: Transaction, target_shard: ShardId) -> bool {
+    let nodeid = sender_node(&tx.sender, self.shardsize);
     let general_tx = GeneralTransaction::TransferTx(tx);
     let bytes = bincode::serialize(&general_tx).expect("Failed to serialize our transaction");
 
     // find the recv worker
-    let index = self.next_node_id.get_mut(target_shard).unwrap();
-    let transport = self.transports.get_mut(&target_shard).unwrap().get_mut((*index) % self.shardsize as usize).unwrap();
-    *index += 1;
+    let transport = self.transports.get_mut(&target_shard).unwrap().get_mut(nodeid).unwrap();
 
     if let Err(e) = transport.send(Bytes::from(bytes)).await {
         warn!("Failed to send transaction: {}", e);
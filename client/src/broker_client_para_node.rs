@@ -2,7 +2,8 @@ use async_trait::async_trait;
 use bytes::{/*BytesMut, */Bytes};
 use anyhow::{Context, Result};
 use config::{ShardId, Committees};
-use worker::{RawTxOld, Transaction, Account2ShardType, CSMsg, CSMsgStore};
+use worker::{Transaction, Account2ShardType, CSMsg, CSMsgStore, epoch_file};
+use std::collections::BTreeMap;
 use log::{info, warn, error, debug};
 use rand::Rng;
 use std::collections::HashMap;
@@ -10,15 +11,18 @@ use std::net::SocketAddr;
 use tokio::net::TcpStream;
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
 use tokio::time::{sleep, interval, Duration, Instant};
-use csv::DeserializeRecordsIter;
-use std::fs::File;
 use tokio::sync::mpsc::{channel, Sender};
+use tokio::sync::watch;
 use futures::future::join_all;
 use network::{MessageHandler, Receiver, Writer};
 use std::error::Error;
+use crate::metrics;
 use crate::broker::Broker;
+use crate::tx_signer::sender_node;
 use crate::common_client::rawtx2tx;
 use crate::common_client_para::CHANNEL_CAPACITY_TX_SENDER;
+use crate::common_client_para_node::{open_workload, EPOCH_POLL_PERIOD_MS};
+use crate::query_client::{get_moves, shard_query_addresses, watch_epoch};
 use crate::tx1_processor::Tx1Processor;
 use crate::tx1_verifier::Tx1Verifier;
 use crate::tx_sender_per_node::TxSenderPerNode;
@@ -31,7 +35,7 @@ pub struct BrokerClientMultiTxSenderPerNode {
     shardnum: usize,
     shardsize: usize,
 
-    // workload
+    // workload: the file may contain `{}`, which is replaced by the epoch number
     workload_file: String,
     // acc_shard: Arc<dyn Account2Shard + Send + Sync>,
     // convert_tx: ConvertTx,
@@ -43,11 +47,19 @@ pub struct BrokerClientMultiTxSenderPerNode {
 
     // about tx sending 
     nodes: Vec<SocketAddr>,
-    next_node_id: Vec<usize>,
     tx_senders: HashMap<(ShardId, NodeId), Sender<Transaction>>,
 
     // broker
     broker: Broker,
+
+    // the brokers of the epoch entered by all shards route the txs (see `watch_epoch`). The
+    // workload of each epoch is sent in turn, up to last_epoch, once the shards entered its epoch
+    epoch: usize,
+    workload_epoch: usize,
+    last_epoch: usize,
+    rx_epoch: watch::Receiver<u64>,
+    query_addresses: Vec<SocketAddr>, // a worker of each shard
+    online: bool, // whether the brokers follow the moves of the online policy
 }
 
 
@@ -63,11 +75,14 @@ impl BrokerClientMultiTxSenderPerNode {
       rate: u64,
       total_txs: u32,
       send_tx_duration_ms: u32,
+      keys_seed: u64, // the seed of the keys of the accounts
       mut client_addr: SocketAddr,
       all_committees: Committees,
       epoch: usize,
+      last_epoch: usize,
     ) -> Result<()> {
 
+      let online = matches!(acc_shard_type, Account2ShardType::OnlinePolicy);
       // crate broker module
       let broker = Broker::new(
         acc_shard_type,
@@ -75,8 +90,10 @@ impl BrokerClientMultiTxSenderPerNode {
         acc2shard_file,
         brokers_file,
         epoch,
+        keys_seed,
       );
 
+      let query_addresses = shard_query_addresses(&all_committees);
       let mut client = BrokerClientMultiTxSenderPerNode {
         shardnum,
         shardsize,
@@ -87,10 +104,15 @@ impl BrokerClientMultiTxSenderPerNode {
         send_tx_duration_ms,
 
         nodes: nodes.clone(),
-        next_node_id: vec![0; shardnum as usize],
         tx_senders: HashMap::default(),
         
         broker: broker.clone(),
+        epoch,
+        workload_epoch: epoch,
+        last_epoch,
+        rx_epoch: watch_epoch(query_addresses.clone(), epoch as u64, Duration::from_millis(EPOCH_POLL_PERIOD_MS)),
+        query_addresses,
+        online,
       };
 
       info!("BrokerClient is running!");
@@ -110,8 +132,6 @@ impl BrokerClientMultiTxSenderPerNode {
       Tx1Verifier::spawn(
         rx_cross_shard_msg,
         tx_process_tx1,
-        all_committees.validity_threshold(),
-        all_committees.shard_size(),
         all_committees,
         csmsg_store,
       );
@@ -152,8 +172,7 @@ impl BrokerClientMultiTxSenderPerNode {
         tokio::pin!(interval);
 
         // open workload file
-        let mut reader = csv::Reader::from_path(self.workload_file.clone()).unwrap();
-        let mut workload_iter: DeserializeRecordsIter<File, RawTxOld> = reader.deserialize().into_iter();
+        let mut workload_iter = open_workload(&epoch_file(&self.workload_file, self.workload_epoch as u64));
 
         // NOTE: This log entry is used to compute performance.
         info!("Start sending transactions");
@@ -167,6 +186,16 @@ impl BrokerClientMultiTxSenderPerNode {
 
             interval.as_mut().tick().await;
             let now = Instant::now();
+            let shards_epoch = *self.rx_epoch.borrow() as usize;
+            if shards_epoch > self.epoch {
+              let moves = match self.online {
+                true => get_moves(&self.query_addresses, self.epoch as u64, shards_epoch as u64).await,
+                false => BTreeMap::new(),
+              };
+              self.epoch = shards_epoch;
+              info!("Enter epoch {}", self.epoch);
+              self.broker.set_epoch(self.epoch, moves).await;
+            }
             for x in 0..burst {
               if let Some(Ok(raw_tx_old)) = workload_iter.next() {
 
@@ -178,6 +207,7 @@ impl BrokerClientMultiTxSenderPerNode {
                 let mut tx_counter: u64 = counter;
                 if x == counter % burst {// sample tx
                     info!("Sending sample transaction {}", tx_counter);
+                    metrics::SAMPLE_TXS_SENT.inc();
                 } else { // Standard tx
                     r += 1;
                     tx_sample = 1;
@@ -192,11 +222,9 @@ impl BrokerClientMultiTxSenderPerNode {
                 debug!("transaction: {:?}, target_shard: {}", tx, target_shard);
 
                 // find the recv worker and send tx
-                let index = self.next_node_id.get_mut(target_shard).unwrap();
-                let nodeid = (*index) % self.shardsize as usize;
+                let nodeid = sender_node(&tx.sender, self.shardsize);
                 debug!("send tx to :({}, {})", target_shard, nodeid);
                 let tx_sender = self.tx_senders.get_mut(&(target_shard, nodeid)).unwrap();
-                *index += 1;
 
                 if let Err(_) = tx_sender.send(tx).await {
                   info!("tx_sender of shard: {} dropped!", target_shard);
@@ -204,8 +232,16 @@ impl BrokerClientMultiTxSenderPerNode {
                 }
 
                 sent_txs += 1;
-              }else{
+                metrics::TXS_SENT.inc();
+              }else if self.workload_epoch >= self.last_epoch {
                 break 'main;
+              }else if self.workload_epoch < self.epoch {
+                // the workload of this epoch is exhausted: move on to that of the next epoch
+                self.workload_epoch += 1;
+                workload_iter = open_workload(&epoch_file(&self.workload_file, self.workload_epoch as u64));
+              }else{
+                // wait for the shards to enter the next epoch
+                break;
               }
             }// end of for
             if now.elapsed().as_millis() > BURST_DURATION as u128 {
@@ -11,7 +11,8 @@ pub const BROKER_NUM: usize = 40; // (rounds)
 
 // broker addr manager
 pub struct BrokerManager {
-  broker_addrs: Vec<Vec<u8>>,
+  all_broker_addrs: Vec<Vec<u8>>, // every broker of brokers_file
+  broker_addrs: Vec<Vec<u8>>, // the brokers selected for the current epoch
   pub tx1s: HashMap<u64, Tx1Msg>, // key is tx.counter
 }
 
@@ -27,25 +28,30 @@ impl BrokerManager {
   pub fn new(brokers_file: String, epoch: usize) -> Self {
     // info!("brokers_file: {:?}", brokers_file);
 
-    let mut broker_addrs: Vec<Vec<u8>> = Vec::default();
+    let mut all_broker_addrs: Vec<Vec<u8>> = Vec::default();
 
     let mut reader = csv::Reader::from_path(brokers_file).unwrap();
     let mut state_iter: DeserializeRecordsIter<File, BrokerItem> = reader.deserialize().into_iter();
     while let Some(Ok(broker_item)) = state_iter.next(){
       let broker = broker_item.account;
-      broker_addrs.push(Vec::from_hex(&broker[2..]).unwrap());
+      all_broker_addrs.push(Vec::from_hex(&broker[2..]).unwrap());
     }
 
-    // random select BROKER_NUM brokers
-    broker_addrs = random_select_brokers(broker_addrs, 100, BROKER_NUM, epoch as u8);
+    let mut broker_manager = Self {
+      all_broker_addrs,
+      broker_addrs: Vec::default(),
+      tx1s: HashMap::default(),
+    };
+    broker_manager.set_epoch(epoch);
+    broker_manager
+  }
 
-    // broker_addrs.push(Vec::from_hex("066BC6836d8AFe1fD20F85cE6a9A9489d0602239").unwrap());
-    info!("Load {:?} brokers!", broker_addrs.len());
+  // random select BROKER_NUM brokers for the epoch
+  pub fn set_epoch(&mut self, epoch: usize) {
+    self.broker_addrs = random_select_brokers(self.all_broker_addrs.clone(), 100, BROKER_NUM, epoch as u8);
 
-    Self {
-      broker_addrs, 
-      tx1s: HashMap::default(),
-    }
+    // broker_addrs.push(Vec::from_hex("066BC6836d8AFe1fD20F85cE6a9A9489d0602239").unwrap());
+    info!("Load {:?} brokers for epoch {}!", self.broker_addrs.len(), epoch);
   }
 
   pub fn add_tx1(&mut self, core_tx: CoreTx, tx_counter: u64, broker: Vec<u8>) {
@@ -2,7 +2,9 @@ use anyhow::{Context, Result};
 use bytes::{/*BytesMut, */Bytes};
 use config::ShardId;
 use hex::FromHex;
+use crate::metrics;
 use crate::convert_tx::ConvertTx;
+use crate::tx_signer::{TxSigner, sender_node};
 use worker::{RawTxOld, RWSet, Account2ShardType, Account2Shard, Account2ShardGraph, Account2ShardHash, CoreTx, Frame, Transaction};
 use futures::future::join_all;
 use futures::sink::SinkExt as _;
@@ -30,6 +32,7 @@ pub struct CommonClient {
     workload_file: String,
     acc_shard: Arc<dyn Account2Shard + Send>,
     _convert_tx: ConvertTx,
+    signer: TxSigner,
 
     // params of tx sending
     rate: u64,
@@ -38,7 +41,6 @@ pub struct CommonClient {
 
     // about tx sending 
     nodes: Vec<SocketAddr>,
-    next_node_id: Vec<usize>,
     transports: HashMap<ShardId, Vec<Framed<TcpStream, LengthDelimitedCodec>>>,    
 }
 
@@ -54,6 +56,7 @@ impl CommonClient {
       rate: u64,
       total_txs: u32,
       send_tx_duration_ms: u32,  
+      keys_seed: u64, // the seed of the keys of the accounts
     ) -> Result<()> {
 
       // crate acc_shard according to specified sharding policy
@@ -63,6 +66,11 @@ impl CommonClient {
           info!("Account2Shard: HashPolicy");
           acc2shard = Arc::new(Account2ShardHash::new(shardnum));
         },
+        // the online mapping is computed by the shards: clients route with the hash policy
+        Account2ShardType::OnlinePolicy => {
+          info!("Account2Shard: OnlinePolicy");
+          acc2shard = Arc::new(Account2ShardHash::new(shardnum));
+        },
         Account2ShardType::GraphPolicy => {
           info!("Account2Shard: GraphPolicy");
           acc2shard = Arc::new(Account2ShardGraph::new(shardnum, &acc2shard_file));
@@ -75,11 +83,11 @@ impl CommonClient {
         workload_file,
         acc_shard: acc2shard,
         _convert_tx: ConvertTx::new(),
+        signer: TxSigner::new(keys_seed),
         rate,
         total_txs, 
         send_tx_duration_ms,
         nodes,
-        next_node_id: vec![0; shardnum as usize],
         transports: HashMap::default(),
       };
 
@@ -153,6 +161,7 @@ impl CommonClient {
                 if x == counter % burst {// sample tx
                     // NOTE: This log entry is used to compute performance.
                     info!("Sending sample transaction {}", tx_counter);
+                    metrics::SAMPLE_TXS_SENT.inc();
                 } else { // Standard tx
                     r += 1;
                     tx_sample = 1;
@@ -164,7 +173,8 @@ impl CommonClient {
                 // generate tx
                 // let core_tx = rawtx2tx(raw_tx_old, tx_sample, tx_counter);
                 // let (tx, target_shard) = self.convert_tx.rawtx2tx(self.acc_shard.clone(), core_tx);
-                let (tx, target_shard) = rawtx2simpletx(raw_tx_old, tx_sample, tx_counter, self.acc_shard.clone());
+                let (mut tx, target_shard) = rawtx2simpletx(raw_tx_old, tx_sample, tx_counter, self.acc_shard.clone());
+                self.signer.sign(&mut tx);
                 // let total_dur = begin.elapsed().as_micros();
                 // sample_convert_dur.push(total_dur);
                 // info!("convert dur: {}", total_dur);
@@ -172,13 +182,12 @@ impl CommonClient {
 
               
                 debug!("transaction: {:?}, target_shard: {}", tx, target_shard);
+                let nodeid = sender_node(&tx.sender, self.shardsize);
                 let general_tx = GeneralTransaction::TransferTx(tx);
                 let bytes = bincode::serialize(&general_tx).expect("Failed to serialize our transaction");
 
                 // find the recv worker
-                let index = self.next_node_id.get_mut(target_shard).unwrap();
-                let transport = self.transports.get_mut(&target_shard).unwrap().get_mut((*index) % self.shardsize as usize).unwrap();
-                *index += 1;
+                let transport = self.transports.get_mut(&target_shard).unwrap().get_mut(nodeid).unwrap();
 
                 if let Err(e) = transport.send(Bytes::from(bytes)).await {
                     warn!("Failed to send transaction: {}", e);
@@ -186,6 +195,7 @@ impl CommonClient {
                 }
                 
                 sent_txs += 1; 
+                metrics::TXS_SENT.inc();
               }else{
                 break 'main;
               }
@@ -229,7 +239,7 @@ pub fn rawtx2tx(
 
   // conver this raw_tx_old to raw_tx
   let mut payload: Vec<RWSet> = Vec::new();
-  payload.push(RWSet { addr: sender.clone(), value: raw_tx_old.amount * -1.0 });
+  payload.push(RWSet { addr: sender.clone(), value: raw_tx_old.amount.checked_neg().unwrap() });
   payload.push(RWSet { addr: receiver.clone(), value: raw_tx_old.amount});
 
   CoreTx::new(tx_sample, tx_counter, sender, receiver, raw_tx_old.amount, payload)
@@ -249,7 +259,7 @@ pub fn rawtx2simpletx(
 
   // conver this raw_tx_old to raw_tx
   let mut frames: Vec<Frame> = Vec::new();
-  let sender_rwset = RWSet { addr: sender.clone(), value: raw_tx_old.amount * -1.0 };
+  let sender_rwset = RWSet { addr: sender.clone(), value: raw_tx_old.amount.checked_neg().unwrap() };
   let s_shardid  = acc_shard.get_shard(&sender) as usize;
 
   frames.push(Frame {shardid: s_shardid, rwset: vec![sender_rwset]});
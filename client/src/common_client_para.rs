@@ -1,7 +1,9 @@
 use anyhow::{Context, Result};
 use config::ShardId;
 use hex::FromHex;
+use crate::metrics;
 use crate::convert_tx::ConvertTx;
+use crate::tx_signer::TxSigner;
 use worker::{RawTxOld, RWSet, Account2ShardType, Account2Shard, Account2ShardGraph, Account2ShardHash, CoreTx, Frame, Transaction};
 use futures::future::join_all;
 use log::{info, warn, debug};
@@ -34,6 +36,7 @@ pub struct CommonClientMultiTxSender {
     workload_file: String,
     acc_shard: Arc<dyn Account2Shard + Send>,
     _convert_tx: ConvertTx,
+    signer: TxSigner,
 
     // params of tx sending
     rate: u64,
@@ -59,6 +62,7 @@ impl CommonClientMultiTxSender {
       rate: u64,
       total_txs: u32,
       send_tx_duration_ms: u32,  
+      keys_seed: u64, // the seed of the keys of the accounts
     ) -> Result<()> {
 
       // crate acc_shard according to specified sharding policy
@@ -68,6 +72,11 @@ impl CommonClientMultiTxSender {
           info!("Account2Shard: HashPolicy");
           acc2shard = Arc::new(Account2ShardHash::new(shardnum));
         },
+        // the online mapping is computed by the shards: clients route with the hash policy
+        Account2ShardType::OnlinePolicy => {
+          info!("Account2Shard: OnlinePolicy");
+          acc2shard = Arc::new(Account2ShardHash::new(shardnum));
+        },
         Account2ShardType::GraphPolicy => {
           info!("Account2Shard: GraphPolicy");
           acc2shard = Arc::new(Account2ShardGraph::new(shardnum, &acc2shard_file));
@@ -80,6 +89,7 @@ impl CommonClientMultiTxSender {
         workload_file,
         acc_shard: acc2shard,
         _convert_tx: ConvertTx::new(),
+        signer: TxSigner::new(keys_seed),
         rate,
         total_txs, 
         send_tx_duration_ms,
@@ -161,6 +171,7 @@ impl CommonClientMultiTxSender {
                 if x == counter % burst {// sample tx
                     // NOTE: This log entry is used to compute performance.
                     info!("Sending sample transaction {}", tx_counter);
+                    metrics::SAMPLE_TXS_SENT.inc();
                 } else { // Standard tx
                     r += 1;
                     tx_sample = 1;
@@ -172,7 +183,8 @@ impl CommonClientMultiTxSender {
                 // generate tx
                 // let core_tx = rawtx2tx(raw_tx_old, tx_sample, tx_counter);
                 // let (tx, target_shard) = self.convert_tx.rawtx2tx(self.acc_shard.clone(), core_tx);
-                let (tx, target_shard) = rawtx2simpletx(raw_tx_old, tx_sample, tx_counter, self.acc_shard.clone());
+                let (mut tx, target_shard) = rawtx2simpletx(raw_tx_old, tx_sample, tx_counter, self.acc_shard.clone());
+                self.signer.sign(&mut tx);
                 // let total_dur = begin.elapsed().as_micros();
                 // sample_convert_dur.push(total_dur);
                 // info!("convert dur: {}", total_dur);
@@ -188,6 +200,7 @@ impl CommonClientMultiTxSender {
                 }
 
                 sent_txs += 1; 
+                metrics::TXS_SENT.inc();
               }else{
                 break 'main;
               }
@@ -231,7 +244,7 @@ pub fn _rawtx2tx(
 
   // conver this raw_tx_old to raw_tx
   let mut payload: Vec<RWSet> = Vec::new();
-  payload.push(RWSet { addr: sender.clone(), value: raw_tx_old.amount * -1.0 });
+  payload.push(RWSet { addr: sender.clone(), value: raw_tx_old.amount.checked_neg().unwrap() });
   payload.push(RWSet { addr: receiver.clone(), value: raw_tx_old.amount});
 
   CoreTx::new(tx_sample, tx_counter, sender, receiver, raw_tx_old.amount, payload)
@@ -250,7 +263,7 @@ pub fn rawtx2simpletx(
 
   // conver this raw_tx_old to raw_tx
   let mut frames: Vec<Frame> = Vec::new();
-  let sender_rwset = RWSet { addr: sender.clone(), value: raw_tx_old.amount * -1.0 };
+  let sender_rwset = RWSet { addr: sender.clone(), value: raw_tx_old.amount.checked_neg().unwrap() };
   let s_shardid  = acc_shard.get_shard(&sender) as usize;
 
   frames.push(Frame {shardid: s_shardid, rwset: vec![sender_rwset]});
@@ -1,8 +1,11 @@
 use anyhow::{Context, Result};
-use config::ShardId;
+use config::{Committees, ShardId};
 use hex::FromHex;
+use crate::metrics;
 use crate::convert_tx::ConvertTx;
-use worker::{RawTxOld, RWSet, Account2ShardType, Account2Shard, Account2ShardGraph, Account2ShardHash, CoreTx, Frame, Transaction};
+use crate::query_client::{get_moves, shard_query_addresses, watch_epoch};
+use crate::tx_signer::{TxSigner, sender_node};
+use worker::{RawTxOld, RWSet, Account2ShardType, Account2Shard, Account2ShardGraph, Account2ShardHash, CoreTx, Frame, Transaction, epoch_file};
 use futures::future::join_all;
 use log::{info, warn, debug};
 use rand::Rng;
@@ -12,15 +15,18 @@ use std::sync::Arc;
 use tokio::net::TcpStream;
 use tokio::time::{interval, sleep, Duration, Instant};
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
-use csv::DeserializeRecordsIter;
+use csv::DeserializeRecordsIntoIter;
 use std::fs::File;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::mpsc::Sender;
 use tokio::sync::mpsc::channel;
+use tokio::sync::watch;
 use crate::tx_sender_per_node::TxSenderPerNode;
 
 // the channel capacity of tx_sender
 pub const CHANNEL_CAPACITY_TX_SENDER: usize = 1000000;
+// the period at which the epoch of the shards is polled
+pub const EPOCH_POLL_PERIOD_MS: u64 = 500;
 type NodeId = usize;
 
 pub struct CommonClientMultiTxSenderPerNode {
@@ -28,10 +34,21 @@ pub struct CommonClientMultiTxSenderPerNode {
     shardnum: usize,
     shardsize: usize,
 
-    // workload
+    // workload: the files may contain `{}`, which is replaced by the epoch number
     workload_file: String,
+    acc2shard_file: String,
+    acc_shard_type: Account2ShardType,
     acc_shard: Arc<dyn Account2Shard + Send>,
     _convert_tx: ConvertTx,
+    signer: TxSigner,
+
+    // the mapping of the epoch entered by all shards routes the txs (see `watch_epoch`). The
+    // workload of each epoch is sent in turn, up to last_epoch, once the shards entered its epoch
+    epoch: usize,
+    workload_epoch: usize,
+    last_epoch: usize,
+    rx_epoch: watch::Receiver<u64>,
+    query_addresses: Vec<SocketAddr>, // a worker of each shard
 
     // params of tx sending
     rate: u64,
@@ -40,7 +57,6 @@ pub struct CommonClientMultiTxSenderPerNode {
 
     // about tx sending 
     nodes: Vec<SocketAddr>,
-    next_node_id: Vec<usize>,
 
     tx_senders: HashMap<(ShardId, NodeId), Sender<Transaction>>,
 }
@@ -57,6 +73,10 @@ impl CommonClientMultiTxSenderPerNode {
       rate: u64,
       total_txs: u32,
       send_tx_duration_ms: u32,  
+      keys_seed: u64, // the seed of the keys of the accounts
+      all_committees: Committees,
+      epoch: usize,
+      last_epoch: usize,
     ) -> Result<()> {
 
       // crate acc_shard according to specified sharding policy
@@ -66,23 +86,36 @@ impl CommonClientMultiTxSenderPerNode {
           info!("Account2Shard: HashPolicy");
           acc2shard = Arc::new(Account2ShardHash::new(shardnum));
         },
+        // the online mapping starts from the hash policy, then follows the moves of the shards
+        Account2ShardType::OnlinePolicy => {
+          info!("Account2Shard: OnlinePolicy");
+          acc2shard = Arc::new(Account2ShardHash::new(shardnum));
+        },
         Account2ShardType::GraphPolicy => {
           info!("Account2Shard: GraphPolicy");
-          acc2shard = Arc::new(Account2ShardGraph::new(shardnum, &acc2shard_file));
+          acc2shard = Arc::new(Account2ShardGraph::new(shardnum, &epoch_file(&acc2shard_file, epoch as u64)));
         }
       }
 
+      let query_addresses = shard_query_addresses(&all_committees);
       let mut client = CommonClientMultiTxSenderPerNode {
         shardnum,
         shardsize,
         workload_file,
+        acc2shard_file,
+        acc_shard_type,
         acc_shard: acc2shard,
         _convert_tx: ConvertTx::new(),
+        signer: TxSigner::new(keys_seed),
+        epoch,
+        workload_epoch: epoch,
+        last_epoch,
+        rx_epoch: watch_epoch(query_addresses.clone(), epoch as u64, Duration::from_millis(EPOCH_POLL_PERIOD_MS)),
+        query_addresses,
         rate,
         total_txs, 
         send_tx_duration_ms,
         nodes,
-        next_node_id: vec![0; shardnum as usize],
         tx_senders: HashMap::default(),
       };
 
@@ -133,8 +166,7 @@ impl CommonClientMultiTxSenderPerNode {
         tokio::pin!(interval);
 
         // open workload file
-        let mut reader = csv::Reader::from_path(self.workload_file.clone()).unwrap();
-        let mut workload_iter: DeserializeRecordsIter<File, RawTxOld> = reader.deserialize().into_iter();
+        let mut workload_iter = open_workload(&epoch_file(&self.workload_file, self.workload_epoch as u64));
 
         // NOTE: This log entry is used to compute performance.
         info!("Start sending transactions");
@@ -148,6 +180,10 @@ impl CommonClientMultiTxSenderPerNode {
 
             interval.as_mut().tick().await;
             let now = Instant::now();
+            let shards_epoch = *self.rx_epoch.borrow() as usize;
+            if shards_epoch > self.epoch {
+              self.enter_epoch(shards_epoch).await;
+            }
             for x in 0..burst {// counter is the group seq (a group = `burst` txs). the sampled tx is determined by counter%burst
               if let Some(Ok(raw_tx_old)) = workload_iter.next() {
 
@@ -159,6 +195,7 @@ impl CommonClientMultiTxSenderPerNode {
                 if x == counter % burst {// sample tx
                     // NOTE: This log entry is used to compute performance.
                     info!("Sending sample transaction {}", tx_counter);
+                    metrics::SAMPLE_TXS_SENT.inc();
                 } else { // Standard tx
                     r += 1;
                     tx_sample = 1;
@@ -170,7 +207,8 @@ impl CommonClientMultiTxSenderPerNode {
                 // generate tx
                 // let core_tx = rawtx2tx(raw_tx_old, tx_sample, tx_counter);
                 // let (tx, target_shard) = self.convert_tx.rawtx2tx(self.acc_shard.clone(), core_tx);
-                let (tx, target_shard) = rawtx2simpletx(raw_tx_old, tx_sample, tx_counter, self.acc_shard.clone());
+                let (mut tx, target_shard) = rawtx2simpletx(raw_tx_old, tx_sample, tx_counter, self.acc_shard.clone());
+                self.signer.sign(&mut tx);
                 // let total_dur = begin.elapsed().as_micros();
                 // sample_convert_dur.push(total_dur);
                 // info!("convert dur: {}", total_dur);
@@ -180,11 +218,9 @@ impl CommonClientMultiTxSenderPerNode {
                 debug!("transaction: {:?}, target_shard: {}", tx, target_shard);
 
                 // find the recv worker and send tx
-                let index = self.next_node_id.get_mut(target_shard).unwrap();
-                let nodeid = (*index) % self.shardsize as usize;
+                let nodeid = sender_node(&tx.sender, self.shardsize);
                 debug!("send tx to :({}, {})", target_shard, nodeid);
                 let tx_sender = self.tx_senders.get_mut(&(target_shard, nodeid)).unwrap();
-                *index += 1;
 
                 if let Err(_) = tx_sender.send(tx).await {
                   info!("tx_sender of shard: {} dropped!", target_shard);
@@ -192,8 +228,16 @@ impl CommonClientMultiTxSenderPerNode {
                 }
 
                 sent_txs += 1; 
-              }else{
+                metrics::TXS_SENT.inc();
+              }else if self.workload_epoch >= self.last_epoch {
                 break 'main;
+              }else if self.workload_epoch < self.epoch {
+                // the workload of this epoch is exhausted: move on to that of the next epoch
+                self.workload_epoch += 1;
+                workload_iter = open_workload(&epoch_file(&self.workload_file, self.workload_epoch as u64));
+              }else{
+                // wait for the shards to enter the next epoch
+                break;
               }
             }// end of for
             if now.elapsed().as_millis() > BURST_DURATION as u128 {
@@ -206,6 +250,24 @@ impl CommonClientMultiTxSenderPerNode {
         Ok(())
     }
 
+    // switch to the acc2shard map of the epoch entered by all shards
+    async fn enter_epoch(&mut self, epoch: usize) {
+      match self.acc_shard_type {
+        Account2ShardType::GraphPolicy => {
+          let acc2shard_file = epoch_file(&self.acc2shard_file, epoch as u64);
+          self.acc_shard = Arc::new(Account2ShardGraph::new(self.shardnum, &acc2shard_file));
+        },
+        Account2ShardType::OnlinePolicy => {
+          let moves = get_moves(&self.query_addresses, self.epoch as u64, epoch as u64).await;
+          info!("{} accounts moved up to epoch {}", moves.len(), epoch);
+          self.acc_shard = Arc::new(Account2ShardGraph::with_moves(self.acc_shard.as_ref(), moves));
+        },
+        Account2ShardType::HashPolicy => {},
+      }
+      self.epoch = epoch;
+      info!("Enter epoch {}", self.epoch);
+    }
+
     pub async fn wait(&self) {
         // Wait for all nodes to be online.
         info!("Waiting for all nodes to be online...");
@@ -224,6 +286,12 @@ impl CommonClientMultiTxSenderPerNode {
 
 
 
+// open the workload file of an epoch
+pub fn open_workload(workload_file: &str) -> DeserializeRecordsIntoIter<File, RawTxOld> {
+  csv::Reader::from_path(workload_file).unwrap().into_deserialize()
+}
+
+
 pub fn _rawtx2tx(
   raw_tx_old: RawTxOld,
   tx_sample: u8, 
@@ -235,7 +303,7 @@ pub fn _rawtx2tx(
 
   // conver this raw_tx_old to raw_tx
   let mut payload: Vec<RWSet> = Vec::new();
-  payload.push(RWSet { addr: sender.clone(), value: raw_tx_old.amount * -1.0 });
+  payload.push(RWSet { addr: sender.clone(), value: raw_tx_old.amount.checked_neg().unwrap() });
   payload.push(RWSet { addr: receiver.clone(), value: raw_tx_old.amount});
 
   CoreTx::new(tx_sample, tx_counter, sender, receiver, raw_tx_old.amount, payload)
@@ -254,7 +322,7 @@ pub fn rawtx2simpletx(
 
   // conver this raw_tx_old to raw_tx
   let mut frames: Vec<Frame> = Vec::new();
-  let sender_rwset = RWSet { addr: sender.clone(), value: raw_tx_old.amount * -1.0 };
+  let sender_rwset = RWSet { addr: sender.clone(), value: raw_tx_old.amount.checked_neg().unwrap() };
   let s_shardid  = acc_shard.get_shard(&sender) as usize;
 
   frames.push(Frame {shardid: s_shardid, rwset: vec![sender_rwset]});
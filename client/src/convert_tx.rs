@@ -31,7 +31,7 @@ impl ConvertTx {
 
     for rwset in payload {
       let shardid  = acc_shard.get_shard(&rwset.addr) as usize;
-      if rwset.value < 0.0 {
+      if rwset.value.is_negative() {
         let rwset_list = shard_rwset_map_sub.entry(shardid).or_insert(Vec::default());
         rwset_list.push(rwset);
         flag_sub[shardid] = true;
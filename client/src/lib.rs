@@ -10,10 +10,17 @@ mod common_client_para_node;
 mod tx_sender_per_node;
 mod broker_client_para_node;
 mod convert_tx;
+mod tx_signer;
+mod query_client;
+mod light;
+mod metrics;
 
 
 pub use crate::common_client::{CommonClient, rawtx2tx};
 pub use crate::broker_client::BrokerClient;
 pub use crate::common_client_para::CommonClientMultiTxSender;
 pub use crate::common_client_para_node::CommonClientMultiTxSenderPerNode;
-pub use crate::broker_client_para_node::BrokerClientMultiTxSenderPerNode;
\ No newline at end of file
+pub use crate::broker_client_para_node::BrokerClientMultiTxSenderPerNode;
+pub use crate::query_client::{query, get_receipt};
+pub use crate::light::{LightClient, LightClientError, verify_account};
+pub use crate::tx_signer::account_keypair;
@@ -0,0 +1,113 @@
+use config::{Committees, ShardId};
+use mpt::{MerklePatriciaTrie, Proof, RootHash, Trie};
+use serde_json::json;
+use std::net::SocketAddr;
+use thiserror::Error;
+use worker::{Account, AccountProof, ExecutionCertificate, ExecutionError, StateProof, StateRoot, INIT_BALANCE};
+use crate::query_client::query;
+
+#[cfg(test)]
+#[path = "tests/light_tests.rs"]
+pub mod light_tests;
+
+
+#[derive(Debug, Error)]
+pub enum LightClientError {
+
+    #[error("Query failed: {0}")]
+    Query(#[from] anyhow::Error),
+
+    #[error("Height {0} of shard {1} is not certified yet")]
+    NotCertified(u64, ShardId),
+
+    #[error("The worker does not serve the proofs of height {0}")]
+    NotServed(u64),
+
+    #[error("Invalid execution certificate: {0}")]
+    InvalidCertificate(#[from] ExecutionError),
+
+    #[error("The roots of height {0} do not match its certificate")]
+    RootMismatch(u64),
+
+    #[error("Invalid proof of account {0}")]
+    InvalidProof(String),
+}
+
+/// A client that trusts the committees only: it reads the accounts of a shard from one of its
+/// workers (its `query` address) and checks them against the roots certified by f+1 replicas
+/// (see `worker::ResultChecker`), so a single faulty worker cannot lie about the state.
+pub struct LightClient {
+  all_committees: Committees,
+  shard_id: ShardId,
+  address: SocketAddr,
+}
+
+impl LightClient {
+  pub fn new(all_committees: Committees, shard_id: ShardId, address: SocketAddr) -> Self {
+    Self { all_committees, shard_id, address }
+  }
+
+  /// The roots of a height of the shard, once its certificate is verified.
+  pub async fn certified_root(&self, height: u64) -> Result<StateRoot, LightClientError> {
+    let certificate: Option<ExecutionCertificate> = query(self.address, "get_certificate", json!([height])).await?;
+    let certificate = certificate.ok_or(LightClientError::NotCertified(height, self.shard_id))?;
+    if certificate.shard_id != self.shard_id || certificate.result.height != height {
+      return Err(LightClientError::RootMismatch(height));
+    }
+    certificate.verify(&self.all_committees)?;
+    Ok(StateRoot {
+      shard_id: certificate.shard_id,
+      height,
+      act_root: certificate.result.act_root,
+      full_root: certificate.result.full_root,
+    })
+  }
+
+  /// The account at the last certified height of the shard, proven against the certified roots of
+  /// that height. The worker serves the proofs of its last certified height only: when it did not
+  /// catch up with that certificate yet, or a later height got certified meanwhile, the call fails
+  /// with `NotServed` and the caller retries.
+  pub async fn get_account(&self, addr: &[u8]) -> Result<Account, LightClientError> {
+    let height: Option<u64> = query(self.address, "get_certified_height", json!([])).await?;
+    let height = height.ok_or(LightClientError::NotCertified(0, self.shard_id))?;
+    let root = self.certified_root(height).await?;
+    let state_proof: Option<StateProof> = query(self.address, "get_proof", json!([hex::encode(addr), height])).await?;
+    let state_proof = state_proof.ok_or(LightClientError::NotServed(height))?;
+    if root != state_proof.root {
+      return Err(LightClientError::RootMismatch(root.height));
+    }
+    let account = verify_account(&root, addr, state_proof.proof)?;
+    // an account that was never touched holds the initial balance
+    Ok(account.unwrap_or(Account { nonce: 0, balance: INIT_BALANCE, key: None }))
+  }
+}
+
+/// Verify the proof of an account, or of its absence, against the roots of a height. With TStore
+/// an account is either proven in act_t, or proven absent from act_t and then proven (or proven
+/// absent) in full_t. With MStore there is no act_t: its root is empty and only full_t is proven.
+pub fn verify_account(root: &StateRoot, addr: &[u8], proof: AccountProof) -> Result<Option<Account>, LightClientError> {
+  let invalid = || LightClientError::InvalidProof(hex::encode(addr));
+  let value = match proof {
+    AccountProof::Active(act_proof) if !root.act_root.is_empty() => {
+      Some(prove(&root.act_root, addr, act_proof).ok_or_else(invalid)?.ok_or_else(invalid)?)
+    },
+    AccountProof::Dormant(act_proof, full_proof) if !root.act_root.is_empty() => {
+      if prove(&root.act_root, addr, act_proof).ok_or_else(invalid)?.is_some() {
+        return Err(invalid());
+      }
+      prove(&root.full_root, addr, full_proof).ok_or_else(invalid)?
+    },
+    AccountProof::Full(full_proof) if root.act_root.is_empty() => {
+      prove(&root.full_root, addr, full_proof).ok_or_else(invalid)?
+    },
+    _ => return Err(invalid()),
+  };
+  value
+    .map(|bytes| bincode::deserialize(&bytes).map_err(|_| invalid()))
+    .transpose()
+}
+
+// the proven value of the key (None if proven absent), or None if the proof is wrong
+fn prove(root: &RootHash, key: &[u8], proof: Proof) -> Option<Option<Vec<u8>>> {
+  MerklePatriciaTrie::verify_proof(root.clone(), key, proof).ok()
+}
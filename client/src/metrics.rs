@@ -0,0 +1,12 @@
+use ::metrics::{lazy_static, register_int_counter, IntCounter};
+
+// The metrics of the client, exported by `metrics::MetricsExporter`.
+lazy_static! {
+  pub static ref TXS_SENT: IntCounter = register_int_counter!(
+    "client_txs_sent_total", "Txs sent by the client"
+  ).unwrap();
+  /// The sample txs, whose end-to-end latency the benchmark measures.
+  pub static ref SAMPLE_TXS_SENT: IntCounter = register_int_counter!(
+    "client_sample_txs_sent_total", "Sample txs sent by the client"
+  ).unwrap();
+}
@@ -0,0 +1,106 @@
+use anyhow::{anyhow, Context, Result};
+use bytes::Bytes;
+use config::{Committees, ShardId};
+use crypto::Digest;
+use futures::sink::SinkExt as _;
+use futures::stream::StreamExt as _;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use futures::future::join_all;
+use log::{debug, info, warn};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use tokio::net::TcpStream;
+use tokio::sync::watch;
+use tokio::time::{sleep, Duration};
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+use worker::{Address, Receipt, RpcRequest, RpcResponse};
+
+
+// call a method of the JSON-RPC query service of the worker listening on `address` (its `query`
+// address), see `worker::QueryService`
+pub async fn query<T: DeserializeOwned>(address: SocketAddr, method: &str, params: Value) -> Result<T> {
+  let stream = TcpStream::connect(address)
+    .await
+    .context(format!("failed to connect to {}", address))?;
+  let mut transport = Framed::new(stream, LengthDelimitedCodec::new());
+
+  let request = serde_json::to_vec(&RpcRequest::new(0, method, params))?;
+  transport.send(Bytes::from(request)).await.context(format!("Failed to send {} query", method))?;
+
+  let bytes = transport
+    .next()
+    .await
+    .ok_or_else(|| anyhow!("{} closed the connection", address))??;
+  let response: RpcResponse = serde_json::from_slice(&bytes)?;
+  response.into_result().map_err(|e| anyhow!("{} failed: {}", method, e))
+}
+
+// the receipt of a tx in the shard of the worker, identified by the client digest of the tx. A tx
+// spanning several shards has a receipt in each of them: the receipt of the last shard of its path
+// tells whether it committed
+pub async fn get_receipt(address: SocketAddr, tx_digest: &Digest) -> Result<Option<Receipt>> {
+  query(address, "get_receipt", json!([hex::encode(tx_digest.0)])).await
+}
+
+// the query address of a worker of each shard, by shard id
+pub fn shard_query_addresses(committees: &Committees) -> Vec<SocketAddr> {
+  (0..committees.shard_num)
+    .filter_map(|shard_id| committees.shards.get(&shard_id))
+    .filter_map(|committee| committee.all_worker_nodes(&0).into_iter().next())
+    .map(|(_, addresses)| addresses.query)
+    .collect()
+}
+
+// the epoch entered by every shard, polled every `period` from a worker of each shard (see
+// `get_epoch`). The shards switch at the first height of an epoch, so a client routes with the
+// mapping of an epoch once none of them executes the previous one
+pub fn watch_epoch(addresses: Vec<SocketAddr>, epoch: u64, period: Duration) -> watch::Receiver<u64> {
+  let (tx_epoch, rx_epoch) = watch::channel(epoch);
+  tokio::spawn(async move {
+    let mut epoch = epoch;
+    loop {
+      sleep(period).await;
+      let epochs = join_all(addresses.iter().map(|address| query::<u64>(*address, "get_epoch", json!([])))).await;
+      let shards_epoch = match epochs.into_iter().collect::<Result<Vec<_>>>() {
+        Ok(epochs) => epochs.into_iter().min().unwrap_or(epoch),
+        Err(e) => {
+          debug!("Failed to poll the epoch of the shards: {}", e);
+          continue;
+        }
+      };
+      if shards_epoch > epoch {
+        epoch = shards_epoch;
+        info!("All shards entered epoch {}", epoch);
+        if tx_epoch.send(epoch).is_err() {
+          break;
+        }
+      }
+    }
+  });
+  rx_epoch
+}
+
+// the accounts moved by the online policy after epoch `from` up to epoch `to`, with their new
+// shard. Each shard only reports the accounts that left it (see `get_moves`), the later epochs
+// override the earlier ones. As every shard entered `to`, a failed query is retried
+pub async fn get_moves(addresses: &[SocketAddr], from: u64, to: u64) -> BTreeMap<Address, ShardId> {
+  let mut moves = BTreeMap::new();
+  for epoch in from + 1..=to {
+    for address in addresses {
+      let shard_moves: Option<BTreeMap<String, ShardId>> = loop {
+        match query(*address, "get_moves", json!([epoch])).await {
+          Ok(shard_moves) => break shard_moves,
+          Err(e) => {
+            warn!("Failed to get the moves of epoch {} from {}: {}", epoch, address, e);
+            sleep(Duration::from_millis(100)).await;
+          }
+        }
+      };
+      for (addr, shard_id) in shard_moves.unwrap_or_default() {
+        moves.insert(hex::decode(addr).expect("Invalid moved address"), shard_id);
+      }
+    }
+  }
+  moves
+}
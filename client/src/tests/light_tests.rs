@@ -0,0 +1,99 @@
+use super::*;
+use mpt::MemoryDB;
+use std::rc::Rc;
+use worker::Amount;
+
+// Fixture: a trie holding the accounts (address byte, balance), with its root.
+fn trie(accounts: &[(u8, i128)]) -> (MerklePatriciaTrie, RootHash) {
+    let mut trie = MerklePatriciaTrie::new(Rc::new(MemoryDB::new()));
+    for (addr, balance) in accounts {
+        trie.insert(vec![*addr; 20], account(*balance)).unwrap();
+    }
+    let root = trie.root().unwrap();
+    (trie, root)
+}
+
+fn account(balance: i128) -> Vec<u8> {
+    bincode::serialize(&Account { nonce: 0, balance: Amount::new(balance), key: None }).unwrap()
+}
+
+fn state_root(act_root: RootHash, full_root: RootHash) -> StateRoot {
+    StateRoot { shard_id: 0, height: 5, act_root, full_root }
+}
+
+fn balance(account: Option<Account>) -> Option<Amount> {
+    account.map(|account| account.balance)
+}
+
+#[test]
+fn verify_active_account() {
+    let (act_t, act_root) = trie(&[(1, 10), (2, 20)]);
+    let (full_t, full_root) = trie(&[(1, 5)]);
+    let root = state_root(act_root, full_root);
+
+    let proof = AccountProof::Active(act_t.get_proof(&[1; 20]).unwrap());
+    assert_eq!(balance(verify_account(&root, &[1; 20], proof).unwrap()), Some(Amount::new(10)));
+
+    // an active account is proven in act_t, not in full_t
+    let proof = AccountProof::Active(full_t.get_proof(&[1; 20]).unwrap());
+    assert!(verify_account(&root, &[1; 20], proof).is_err());
+    let proof = AccountProof::Full(full_t.get_proof(&[1; 20]).unwrap());
+    assert!(verify_account(&root, &[1; 20], proof).is_err());
+}
+
+#[test]
+fn verify_dormant_account() {
+    let (act_t, act_root) = trie(&[(1, 10)]);
+    let (full_t, full_root) = trie(&[(1, 5), (2, 20)]);
+    let root = state_root(act_root, full_root);
+
+    // a dormant account is proven absent from act_t, then proven in full_t
+    let proof = AccountProof::Dormant(act_t.get_proof(&[2; 20]).unwrap(), full_t.get_proof(&[2; 20]).unwrap());
+    assert_eq!(balance(verify_account(&root, &[2; 20], proof).unwrap()), Some(Amount::new(20)));
+
+    // an account absent from both tries was never touched
+    let proof = AccountProof::Dormant(act_t.get_proof(&[3; 20]).unwrap(), full_t.get_proof(&[3; 20]).unwrap());
+    assert_eq!(balance(verify_account(&root, &[3; 20], proof).unwrap()), None);
+
+    // an active account cannot pass for dormant with its stale value in full_t
+    let proof = AccountProof::Dormant(act_t.get_proof(&[1; 20]).unwrap(), full_t.get_proof(&[1; 20]).unwrap());
+    assert!(verify_account(&root, &[1; 20], proof).is_err());
+}
+
+#[test]
+fn verify_full_account() {
+    // MStore has no act_t: its root is empty
+    let (full_t, full_root) = trie(&[(1, 10)]);
+    let root = state_root(Vec::new(), full_root);
+
+    let proof = AccountProof::Full(full_t.get_proof(&[1; 20]).unwrap());
+    assert_eq!(balance(verify_account(&root, &[1; 20], proof).unwrap()), Some(Amount::new(10)));
+    let proof = AccountProof::Full(full_t.get_proof(&[2; 20]).unwrap());
+    assert_eq!(balance(verify_account(&root, &[2; 20], proof).unwrap()), None);
+
+    // without act_t, the tiered proofs are rejected
+    let proof = AccountProof::Active(full_t.get_proof(&[1; 20]).unwrap());
+    assert!(verify_account(&root, &[1; 20], proof).is_err());
+}
+
+#[test]
+fn reject_tampered_proof() {
+    let (act_t, act_root) = trie(&[(1, 10), (2, 20)]);
+    let (_, full_root) = trie(&[]);
+    let root = state_root(act_root, full_root);
+    let proof = act_t.get_proof(&[1; 20]).unwrap();
+
+    // the proof of another account
+    assert!(verify_account(&root, &[2; 20], AccountProof::Active(proof.clone())).is_err());
+
+    // an altered balance
+    let mut tampered = proof.clone();
+    let leaf = tampered.nodes.last_mut().unwrap();
+    *leaf.last_mut().unwrap() ^= 1;
+    assert!(verify_account(&root, &[1; 20], AccountProof::Active(tampered)).is_err());
+
+    // the roots of another state
+    let (_, other_root) = trie(&[(1, 11), (2, 20)]);
+    let root = state_root(other_root, root.full_root);
+    assert!(verify_account(&root, &[1; 20], AccountProof::Active(proof)).is_err());
+}
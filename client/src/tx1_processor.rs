@@ -13,6 +13,7 @@ use tokio::sync::mpsc::Receiver;
 use futures::future::join_all;
 use futures::sink::SinkExt as _;
 use crate::broker::Broker;
+use crate::tx_signer::sender_node;
 
 
 
@@ -21,7 +22,6 @@ pub struct Tx1Processor {
   shardsize: usize,
 
   nodes: Vec<SocketAddr>,
-  next_node_id: Vec<usize>,
   transports: HashMap<ShardId, Vec<Framed<TcpStream, LengthDelimitedCodec>>>,
 
   rx_send_tx: Receiver<Transaction>,
@@ -43,7 +43,6 @@ impl Tx1Processor {
       shardnum,
       shardsize,
       nodes,
-      next_node_id: vec![0; shardnum as usize],
       transports: HashMap::default(),
       rx_send_tx,
       broker,
@@ -79,13 +78,12 @@ impl Tx1Processor {
   }
 
   pub async fn send_tx(&mut self, tx: Transaction, target_shard: ShardId) -> bool {
+    let nodeid = sender_node(&tx.sender, self.shardsize);
     let general_tx = GeneralTransaction::TransferTx(tx);
     let bytes = bincode::serialize(&general_tx).expect("Failed to serialize our transaction");
 
     // find the recv worker
-    let index = self.next_node_id.get_mut(target_shard).unwrap();
-    let transport = self.transports.get_mut(&target_shard).unwrap().get_mut((*index) % self.shardsize as usize).unwrap();
-    *index += 1;
+    let transport = self.transports.get_mut(&target_shard).unwrap().get_mut(nodeid).unwrap();
 
     if let Err(e) = transport.send(Bytes::from(bytes)).await {
         debug!("Failed to send tx2: {}", e);
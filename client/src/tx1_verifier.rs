@@ -1,5 +1,5 @@
-use config::{Stake, Committees};
-use crypto::{assemble_intact_ts_partial, PublicKey, verify_ts_sig};
+use config::Committees;
+use crypto::{assemble_intact_ts_partial, verify_ts_sig};
 use tokio::sync::mpsc::{Receiver, Sender};
 use worker::{CSMsgStore, Transaction};
 use worker::{GeneralTransaction, CSMsg};
@@ -10,9 +10,6 @@ pub struct Tx1Verifier {
     rx_cross_shard_msg: Receiver<CSMsg>,
     tx_process_tx1: Sender<Transaction>,
     all_committees: Committees,
-
-    vote_threshold: Stake,
-    shard_size: usize,
     csmsg_store: CSMsgStore,
 }
 
@@ -20,8 +17,6 @@ impl Tx1Verifier {
   pub fn spawn(
       rx_cross_shard_msg: Receiver<CSMsg>,
       tx_process_tx1: Sender<Transaction>,
-      vote_threshold: Stake,
-      shard_size: usize,
       all_committees: Committees,
       csmsg_store: CSMsgStore,
     ) {
@@ -30,8 +25,6 @@ impl Tx1Verifier {
           rx_cross_shard_msg,
           tx_process_tx1,
           all_committees,
-          vote_threshold,// f+1
-          shard_size,
           csmsg_store,
         }
         .run()
@@ -51,12 +44,9 @@ impl Tx1Verifier {
           cs_msg.csmsg_sequence,
         );
 
-        if cs_msg.verify(&self.all_committees) {
-          self.process_msg(cs_msg).await;
-        } else {
-          warn!(
-            "Threshold signature verification failed",
-          );
+        match cs_msg.verify(&self.all_committees) {
+          Ok(()) => self.process_msg(cs_msg).await,
+          Err(e) => warn!("Invalid csmsg {}: {}", cs_msg, e),
         }
       }
     }
@@ -66,26 +56,35 @@ impl Tx1Verifier {
       csmsg: CSMsg,
     ) {
         // get the msgId of the csmsg
-        let msg_id = format!("[{}-{}]", csmsg.source_shard, csmsg.csmsg_sequence);
+        let msg_id = csmsg.id();
         debug!("process csmsg: {}, counter: {:?}", msg_id, csmsg.get_counter().await);
 
+        // get the threshold public key set of the source shard
+        let key_set = match self.all_committees.threshold_keys(&csmsg.source_shard) {
+          Ok(key_set) => key_set,
+          Err(e) => {
+            warn!("{}", e);
+            return;
+          }
+        };
+
         // try to add this csmsg sig to csmsg_store
-        let (is_added, thres_sig_list) = self.csmsg_store.add_csmsg_sig(msg_id.clone(), csmsg.thres_sig).await.unwrap();
+        let (is_added, thres_sig_list) = self.csmsg_store.add_csmsg_sig(msg_id, csmsg.thres_sig).await.unwrap();
         debug!("res of add_csmsg_sig: {:?}, {:?}", is_added, thres_sig_list);
         if is_added {
           if thres_sig_list.len() != 0 { // reach vote_threshold, 
               // assemble intact ts partial and verify ts
               let inner_tx_hash = csmsg.inner_tx_hash;
-              let intact_sig = assemble_intact_ts_partial(thres_sig_list, PublicKey::default(), &inner_tx_hash, self.vote_threshold, self.shard_size);
+              let intact_sig = assemble_intact_ts_partial(thres_sig_list, key_set, &inner_tx_hash).unwrap_or_default();
 
-              if verify_ts_sig(PublicKey::default(), &inner_tx_hash, &intact_sig) {
+              if verify_ts_sig(&key_set.public_key, &inner_tx_hash, &intact_sig) {
                 // pass verification
                 let mut inner_tx = csmsg.tx;
                 // update intact_sig of inner_tx
-                inner_tx.set_thres_sig(intact_sig, csmsg.source_shard);
+                inner_tx.set_thres_sig(intact_sig, msg_id);
 
                 match inner_tx {
-                  GeneralTransaction::AggTx(_) => {} // ingore
+                  GeneralTransaction::AggTx(_) | GeneralTransaction::CommitMsg(_) | GeneralTransaction::MigrationTx(_) => {} // ingore
                   GeneralTransaction::TransferTx(tx1) => {
                     debug!("sends tx1 {:?} to Tx1Proseccor!", tx1.counter);
                     self
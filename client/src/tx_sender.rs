@@ -6,6 +6,7 @@ use tokio::net::TcpStream;
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
 use worker::{GeneralTransaction, Transaction};
 use tokio::sync::mpsc::Receiver;
+use crate::tx_signer::sender_node;
 
 
 // Manage connections to blockchain nodes and send transactions
@@ -15,7 +16,6 @@ pub struct TxSender {
 
   // send tx
   shardid: ShardId,
-  next_node_id: usize,
   transports: Vec<Framed<TcpStream, LengthDelimitedCodec>>,
 
   rx_send_tx: Receiver<Transaction>,
@@ -34,7 +34,6 @@ impl TxSender {
       _shardnum: shardnum,
       shardsize,
       shardid,
-      next_node_id: 0,
       transports,
       rx_send_tx,
     };
@@ -50,14 +49,13 @@ impl TxSender {
 
     info!("TxSender for shard {} is running!", self.shardid);
     while let Some(tx) = self.rx_send_tx.recv().await {
+        let nodeid = sender_node(&tx.sender, self.shardsize);
         let general_tx = GeneralTransaction::TransferTx(tx);
         let bytes = bincode::serialize(&general_tx).expect("Failed to serialize our transaction");
-        debug!("send tx {:?} to shard {}, node {}", general_tx.get_counter(), self.shardid, self.next_node_id);
+        debug!("send tx {:?} to shard {}, node {}", general_tx.get_counter(), self.shardid, nodeid);
 
         // find the recv worker and send tx
-        let index = self.next_node_id;
-        let transport = self.transports.get_mut(index % self.shardsize as usize).unwrap();
-        self.next_node_id += 1;
+        let transport = self.transports.get_mut(nodeid).unwrap();
 
         if let Err(e) = transport.send(Bytes::from(bytes)).await {
           debug!("Failed to send transaction: {}", e);
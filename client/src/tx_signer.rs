@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use crypto::{generate_keypair, PublicKey, SecretKey};
+use ed25519_dalek::{Digest as _, Sha512};
+use rand::rngs::StdRng;
+use rand::SeedableRng as _;
+use worker::{Transaction, Address};
+
+
+// the key pair of an account. The workloads replay addresses whose keys we don't have: the key of
+// each account is derived from a seed known to the clients, and the genesis key registry of the
+// workers holds the public keys (see `node generate_account_keys`)
+pub fn account_keypair(seed: u64, addr: &Address) -> (PublicKey, SecretKey) {
+  let mut hasher = Sha512::new();
+  hasher.update(seed.to_le_bytes());
+  hasher.update(addr);
+  let mut rng_seed = [0u8; 32];
+  rng_seed.copy_from_slice(&hasher.finalize()[..32]);
+  generate_keypair(&mut StdRng::from_seed(rng_seed))
+}
+
+
+// sign the txs with the keys of their senders. The workers execute the txs of a sender in nonce
+// order, so the signer assigns consecutive nonces to the txs of each sender in the order they are sent.
+// A tx with a `Rejected` receipt did not consume its nonce, it is resubmitted as signed
+pub struct TxSigner {
+  seed: u64,
+  nonces: HashMap<Address, i64>,
+  keys: HashMap<Address, (PublicKey, SecretKey)>,
+}
+
+impl TxSigner {
+  pub fn new(seed: u64) -> Self {
+    Self { seed, nonces: HashMap::new(), keys: HashMap::new() }
+  }
+
+  pub fn sign(&mut self, tx: &mut Transaction) {
+    let nonce = self.nonces.entry(tx.sender.clone()).or_insert(0);
+    tx.nonce = *nonce;
+    *nonce += 1;
+    let seed = self.seed;
+    let (public_key, secret) = self.keys
+      .entry(tx.sender.clone())
+      .or_insert_with(|| account_keypair(seed, &tx.sender));
+    tx.sign(public_key, secret);
+  }
+}
+
+
+// the node receiving the txs of a sender: the txs of a sender are sent to the same node, so that
+// they are packaged in nonce order
+pub fn sender_node(sender: &Address, shardsize: usize) -> usize {
+  let mut hasher = DefaultHasher::new();
+  sender.hash(&mut hasher);
+  (hasher.finish() % shardsize as u64) as usize
+}
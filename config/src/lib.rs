@@ -1,5 +1,5 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
-use crypto::{generate_production_keypair, PublicKey, SecretKey};
+use crypto::{generate_production_keypair, PublicKey, SecretKey, ThresholdPublicKeySet, ThresholdSecretShare};
 use log::info;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
@@ -19,6 +19,9 @@ pub enum ConfigError {
     #[error("Node {0} is not in the committee")]
     NotInCommittee(PublicKey), // 节点不在委员会中
 
+    #[error("No threshold key for shard {0}")]
+    NoThresholdKey(ShardId), // 分片没有阈值公钥
+
     #[error("Unknown worker id {0}")]
     UnknownWorker(WorkerId), // 未知的工作者ID
 
@@ -89,6 +92,98 @@ pub struct Parameters {
     /// The delay after which the workers seal a batch of transactions, even if `max_batch_size`
     /// is not reached. Denominated in ms.
     pub max_batch_delay: u64,
+    /// The number of cross-shard messages of each source shard kept behind the last executed one;
+    /// older messages are pruned, except the missing ones.
+    #[serde(default = "default_csmsg_gc_depth")]
+    pub csmsg_gc_depth: u64,
+    /// The delay after which a cross-shard message that the target shard did not acknowledge is
+    /// retransmitted by every node of the source shard. Denominated in ms.
+    #[serde(default = "default_csmsg_retry_delay")]
+    pub csmsg_retry_delay: u64,
+    /// The interval at which the nodes acknowledge the cross-shard messages executed by their
+    /// shard. Denominated in ms.
+    #[serde(default = "default_csmsg_ack_interval")]
+    pub csmsg_ack_interval: u64,
+    /// The number of heights after which the workers stop retransmitting a message sent to the
+    /// broker client that was not acknowledged.
+    #[serde(default = "default_sender_gc_depth")]
+    pub sender_gc_depth: u64,
+    /// The number of heights the coordinator of a tx waits for the votes of the participants
+    /// before it aborts the tx, with the TwoPhaseCommit executor.
+    #[serde(default = "default_commit_timeout")]
+    pub commit_timeout: u64,
+    /// The SharDAG executor aggregates all its avatar accounts every `agg_interval` heights.
+    /// Zero disables the trigger.
+    #[serde(default = "default_agg_interval")]
+    pub agg_interval: u64,
+    /// The SharDAG executor aggregates all its avatar accounts once they take this many bytes in
+    /// the payloads of the agg txs. Zero disables the trigger.
+    #[serde(default = "default_agg_max_bytes")]
+    pub agg_max_bytes: usize,
+    /// The SharDAG executor aggregates the avatar accounts of a target shard once there are this
+    /// many distinct accounts. Zero disables the trigger.
+    #[serde(default = "default_agg_max_accounts")]
+    pub agg_max_accounts: usize,
+    /// The SharDAG executor aggregates the avatar accounts of a target shard once the oldest one
+    /// was cached this many heights ago. Zero disables the trigger.
+    #[serde(default = "default_agg_max_age")]
+    pub agg_max_age: u64,
+    /// Whether the SharDAG executor includes in its agg txs the avatar accounts cached at each
+    /// height, for the target shard to audit the aggregated values.
+    #[serde(default)]
+    pub agg_deltas: bool,
+    /// The TStore flushes its cold active accounts to full_t every `evict_interval` heights.
+    /// Zero disables the eviction.
+    #[serde(default = "default_evict_interval")]
+    pub evict_interval: u64,
+    /// The active accounts not accessed for this many heights are cold. The accesses are counted
+    /// at each eviction, so the idle time is rounded up to a multiple of `evict_interval`.
+    #[serde(default = "default_evict_idle")]
+    pub evict_idle: u64,
+}
+
+fn default_csmsg_gc_depth() -> u64 {
+    10_000
+}
+
+fn default_csmsg_retry_delay() -> u64 {
+    5_000
+}
+
+fn default_csmsg_ack_interval() -> u64 {
+    1_000
+}
+
+fn default_sender_gc_depth() -> u64 {
+    1_000
+}
+
+fn default_commit_timeout() -> u64 {
+    50
+}
+
+fn default_agg_interval() -> u64 {
+    100
+}
+
+fn default_agg_max_bytes() -> usize {
+    1_000_000
+}
+
+fn default_agg_max_accounts() -> usize {
+    10_000
+}
+
+fn default_agg_max_age() -> u64 {
+    50
+}
+
+fn default_evict_interval() -> u64 {
+    100
+}
+
+fn default_evict_idle() -> u64 {
+    1_000
 }
 
 impl Default for Parameters {
@@ -101,6 +196,18 @@ impl Default for Parameters {
             sync_retry_nodes: 3,
             batch_size: 500_000,
             max_batch_delay: 100,
+            csmsg_gc_depth: default_csmsg_gc_depth(),
+            csmsg_retry_delay: default_csmsg_retry_delay(),
+            csmsg_ack_interval: default_csmsg_ack_interval(),
+            sender_gc_depth: default_sender_gc_depth(),
+            commit_timeout: default_commit_timeout(),
+            agg_interval: default_agg_interval(),
+            agg_max_bytes: default_agg_max_bytes(),
+            agg_max_accounts: default_agg_max_accounts(),
+            agg_max_age: default_agg_max_age(),
+            agg_deltas: false,
+            evict_interval: default_evict_interval(),
+            evict_idle: default_evict_idle(),
         }
     }
 }
@@ -116,6 +223,18 @@ impl Parameters {
         info!("Sync retry nodes set to {} nodes", self.sync_retry_nodes);
         info!("Batch size set to {} B", self.batch_size);
         info!("Max batch delay set to {} ms", self.max_batch_delay);
+        info!("CSMsg garbage collection depth set to {} csmsgs", self.csmsg_gc_depth);
+        info!("CSMsg retry delay set to {} ms", self.csmsg_retry_delay);
+        info!("CSMsg ack interval set to {} ms", self.csmsg_ack_interval);
+        info!("Sender garbage collection depth set to {} heights", self.sender_gc_depth);
+        info!("Commit timeout set to {} heights", self.commit_timeout);
+        info!("Aggregation interval set to {} heights", self.agg_interval);
+        info!("Aggregation max bytes set to {} B", self.agg_max_bytes);
+        info!("Aggregation max accounts set to {} accounts", self.agg_max_accounts);
+        info!("Aggregation max age set to {} heights", self.agg_max_age);
+        info!("Avatar deltas in agg txs {}", if self.agg_deltas { "enabled" } else { "disabled" });
+        info!("Eviction interval set to {} heights", self.evict_interval);
+        info!("Eviction idle time set to {} heights", self.evict_idle);
     }
 }
 
@@ -137,6 +256,8 @@ pub struct WorkerAddresses {
     pub primary_to_worker: SocketAddr,
     /// Address to receive messages from other workers in other shards(WAN).
     pub cross_shard_worker: SocketAddr,
+    /// Address to answer the queries of clients (WAN).
+    pub query: SocketAddr,
 }
 
 // 定义权限结构体，用于存储节点的投票权和地址信息
@@ -294,6 +415,9 @@ pub struct Committees {
     pub client: SocketAddr,
     pub shard_num: usize,
     pub shard_size: usize,
+    /// The threshold public key set of each shard.
+    #[serde(default)]
+    pub threshold_keys: HashMap<ShardId, ThresholdPublicKeySet>,
 }
 
 impl Import for Committees {}
@@ -312,6 +436,13 @@ impl Committees {
         self.shards.len()
     }
 
+    /// Return the threshold public key set of a specific ShardId.
+    pub fn threshold_keys(&self, id: &ShardId) -> Result<&ThresholdPublicKeySet, ConfigError> {
+      self.threshold_keys
+      .get(id)
+      .ok_or_else(|| ConfigError::NoThresholdKey(*id))
+    }
+
     pub fn validity_threshold(&self) -> Stake {
       let committee_0 = self.shards.get(&0).unwrap();
       committee_0.validity_threshold()
@@ -330,15 +461,21 @@ pub struct KeyPair {
     pub name: PublicKey,
     /// The node's secret key.
     pub secret: SecretKey,
+    /// The node's share of its shard's threshold key (dealt by `deal_threshold_keys`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold_share: Option<ThresholdSecretShare>,
 }
 
 impl Import for KeyPair {}
 impl Export for KeyPair {}
 
+impl Import for ThresholdPublicKeySet {}
+impl Export for ThresholdPublicKeySet {}
+
 impl KeyPair {
     pub fn new() -> Self {
         let (name, secret) = generate_production_keypair();
-        Self { name, secret }
+        Self { name, secret, threshold_share: None }
     }
 }
 
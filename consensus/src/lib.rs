@@ -4,9 +4,15 @@ use crypto::Hash as _;
 use crypto::{Digest, PublicKey};
 use log::{debug, info, log_enabled, warn};
 use primary::{Certificate, Round};
+use serde::{Deserialize, Serialize};
 use std::cmp::max;
 use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use store::Store;
 use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::time::Instant;
+
+mod metrics;
 
 #[cfg(test)]
 #[path = "tests/consensus_tests.rs"]
@@ -17,8 +23,25 @@ pub mod consensus_tests;
 // <轮次, <公钥, (摘要, 证书)>>
 type Dag = HashMap<Round, HashMap<PublicKey, (Digest, Certificate)>>;
 
+/// The store key of the consensus state.
+// 共识状态在存储中的键
+const STATE_KEY: &[u8] = b"consensus_state";
+
+/// The store key of the number of certificates output so far.
+// 已输出证书数量在存储中的键
+const DELIVERED_KEY: &[u8] = b"consensus_delivered";
+
+/// The store key of the certificates of a round of the dag.
+// DAG 中某一轮次证书在存储中的键
+fn round_key(round: Round) -> Vec<u8> {
+    let mut key = b"consensus_round_".to_vec();
+    key.extend_from_slice(&round.to_le_bytes());
+    key
+}
+
 /// The state that needs to be persisted for crash-recovery.
 // 用于崩溃恢复的状态持久化结构体
+#[derive(Serialize, Deserialize)]
 struct State {
     /// The last committed round.
     // 上次提交的轮次
@@ -28,10 +51,22 @@ struct State {
     // ensure we don't commit twice the same certificate.
     // 保存每个节点上次提交的轮次，此映射用于清理 DAG，并确保不会提交相同的证书
     last_committed: HashMap<PublicKey, Round>,
-    /// Keeps the latest committed certificate (and its parents) for every authority. Anything older
-    /// must be regularly cleaned up through the function `update`.
-    // 保存每个节点最新的提交证书及其父证书，就数据通过 'update' 定期清理
 
+    /// The last committed sequence, persisted before it is output, and the number of certificates
+    /// output before it: a restarted node outputs the part of it that was not delivered yet.
+    // 最近一次提交的证书序列（输出前持久化）及其之前已输出的证书数量
+    sequence: Vec<Certificate>,
+    sequence_start: u64,
+
+    /// The persisted rounds below this round are deleted (see `prune_rounds`).
+    // 低于该轮次的持久化轮次已被删除
+    gc_round: Round,
+
+    /// Keeps the latest committed certificate (and its parents) for every authority. Anything older
+    /// must be regularly cleaned up through the function `update`. Each round is persisted on its
+    /// own key as its certificates arrive (see `round_key`).
+    // 保存每个节点最新的提交证书及其父证书，就数据通过 'update' 定期清理；每个轮次单独持久化
+    #[serde(skip)]
     dag: Dag,
 }
 
@@ -47,6 +82,9 @@ impl State {
         Self {
             last_committed_round: 0, // 初始化提交轮次为0
             last_committed: genesis.iter().map(|(x, (_, y))| (*x, y.round())).collect(), // 初始化 last_committed 为每个节点的初始提交轮次
+            sequence: Vec::new(),
+            sequence_start: 0,
+            gc_round: 0,
             dag: [(0, genesis)].iter().cloned().collect(), // 初始化 dag 只包含轮次 0 的创世证书信息
         }
     }
@@ -61,8 +99,15 @@ impl State {
             .or_insert_with(|| certificate.round());
 
         // 计算所有节点的最大提交轮次
-        let last_committed_round = *self.last_committed.values().max().unwrap();
-        self.last_committed_round = last_committed_round;
+        self.last_committed_round = *self.last_committed.values().max().unwrap();
+        self.clean(gc_depth);
+    }
+
+    /// Drop the certificates older than the last committed round of their authority, and the
+    /// rounds below the gc depth.
+    // 清理 DAG 中已提交或低于垃圾回收深度的证书
+    fn clean(&mut self, gc_depth: Round) {
+        let last_committed_round = self.last_committed_round;
 
         // 保留dag中满足条件的节点和轮次，清理旧数据
         for (name, round) in &self.last_committed {
@@ -97,6 +142,18 @@ pub struct Consensus {
     /// The genesis certificates.
     // 创世证书
     genesis: Vec<Certificate>,
+
+    /// The persistent storage, holding the consensus state.
+    // 持久化存储，保存共识状态
+    store: Store,
+
+    /// The reception time of the certificates not yet committed, to measure the commit latency.
+    // 尚未提交的证书的接收时间，用于统计提交延迟
+    received: HashMap<Digest, (Round, Instant)>,
+
+    /// The number of certificates output so far (persisted after each of them).
+    // 已输出的证书数量（每输出一个证书后持久化）
+    delivered: u64,
 }
 
 impl Consensus {
@@ -107,6 +164,7 @@ impl Consensus {
         rx_primary: Receiver<Certificate>,
         tx_primary: Sender<Certificate>,
         tx_output: Sender<Certificate>,
+        store: Store,
     ) {
         // 初始化 Consensus 实例，并调用 run 方法
         tokio::spawn(async move {
@@ -117,6 +175,9 @@ impl Consensus {
                 tx_primary,
                 tx_output,
                 genesis: Certificate::genesis(&committee),
+                store,
+                received: HashMap::new(),
+                delivered: 0,
             }
             .run()
             .await;
@@ -126,14 +187,28 @@ impl Consensus {
     // 监听来自主节点的新证书并处理
     async fn run(&mut self) {
         // The consensus state (everything else is immutable).
-        // 初始化 state
-        let mut state = State::new(self.genesis.clone());
+        // 从存储中恢复 state，若不存在则从创世证书开始
+        let mut state = self.load_state().await;
+
+        // Output the part of the last committed sequence that was not delivered before a crash.
+        // 输出崩溃前尚未输出的最近一次提交序列的剩余部分
+        self.delivered = self.load_delivered().await;
+        let delivered = self.delivered.saturating_sub(state.sequence_start) as usize;
+        let pending: Vec<_> = state.sequence.iter().skip(delivered).cloned().collect();
+        if !pending.is_empty() {
+            info!(
+                "Output {} certificates committed before the crash",
+                pending.len()
+            );
+            self.output(pending).await;
+        }
 
         // Listen to incoming certificates.
         // 不断从 rx_primary 接收新证书
         while let Some(certificate) = self.rx_primary.recv().await {
             debug!("Processing {:?}", certificate);
             let round = certificate.round();
+            self.received.insert(certificate.digest(), (round, Instant::now()));
 
             // Add the new certificate to the local storage.
             // 将证书添加到本地存储的dag中
@@ -143,6 +218,10 @@ impl Consensus {
                 .or_insert_with(HashMap::new)
                 .insert(certificate.origin(), (certificate.digest(), certificate));
 
+            // Persist the round of the certificate before processing it: the primary sends it only once.
+            // 持久化新证书所在的轮次，主节点只会发送一次
+            self.persist_round(&state, round).await;
+
             // Try to order the dag to commit. Start from the highest round for which we have at least
             // 2f+1 certificates. This is because we need them to reveal the common coin.
             // 从当前轮次的前一轮开始尝试 DAG 进行排序和提交
@@ -207,31 +286,160 @@ impl Consensus {
                 }
             }
 
+            // Persist the state with the sequence before the output: after a crash, the restarted node
+            // does not commit the sequence again but outputs the certificates it had not delivered.
+            // 在输出前持久化 state 及提交序列，重启后不会重复提交，只输出尚未输出的证书
+            state.sequence = sequence.clone();
+            state.sequence_start = self.delivered;
+            self.persist_state(&state).await;
+            self.prune_rounds(&mut state).await;
+
             // Output the sequence in the right order.
             // 将排序好的证书序列输出到主节点和应用层。
-            for certificate in sequence {
-                #[cfg(not(feature = "benchmark"))]
-                info!("Committed {}", certificate.header.get_digest());
+            self.output(sequence).await;
 
+            // The certificates below the gc depth will never be committed.
+            let last_committed_round = state.last_committed_round;
+            let gc_depth = self.gc_depth;
+            self.received.retain(|_, (r, _)| *r + gc_depth >= last_committed_round);
+            metrics::LAST_COMMITTED_ROUND.set(last_committed_round as i64);
+        }
+    }
 
-                #[cfg(feature = "benchmark")]
-                for digest in certificate.header.payload.keys() {
-                    // NOTE: This log entry is used to compute performance.
-                    info!("Committed {} -> {:?}", certificate.header, digest);
-                }
+    /// Output a committed sequence, and persist the number of certificates delivered after each
+    /// of them.
+    // 输出提交序列，每输出一个证书后持久化已输出的证书数量
+    async fn output(&mut self, sequence: Vec<Certificate>) {
+        for certificate in sequence {
+            metrics::COMMITTED_CERTIFICATES.inc();
+            if let Some((_, received)) = self.received.remove(&certificate.digest()) {
+                metrics::COMMIT_LATENCY.observe(received.elapsed().as_secs_f64() * 1000.0);
+            }
 
-                self.tx_primary
-                    .send(certificate.clone())
-                    .await
-                    .expect("Failed to send certificate to primary");
+            #[cfg(not(feature = "benchmark"))]
+            info!("Committed {}", certificate.header.get_digest());
 
-                if let Err(e) = self.tx_output.send(certificate).await {
-                    warn!("Failed to output certificate: {}", e);
-                }
+
+            #[cfg(feature = "benchmark")]
+            for digest in certificate.header.payload.keys() {
+                // NOTE: This log entry is used to compute performance.
+                info!("Committed {} -> {:?}", certificate.header, digest);
+            }
+
+            self.tx_primary
+                .send(certificate.clone())
+                .await
+                .expect("Failed to send certificate to primary");
+
+            if let Err(e) = self.tx_output.send(certificate).await {
+                warn!("Failed to output certificate: {}", e);
+                continue;
             }
+            self.delivered += 1;
+            self.store
+                .write(
+                    DELIVERED_KEY.to_vec(),
+                    self.delivered.to_le_bytes().to_vec(),
+                )
+                .await;
         }
     }
 
+    /// Load the persisted state, or start from genesis. The dag is rebuilt from its persisted
+    /// rounds above the gc depth, then cleaned up like after the last commit.
+    // 加载持久化的 state，并从持久化的轮次中重建 DAG
+    async fn load_state(&mut self) -> State {
+        let mut state = match self
+            .store
+            .read(STATE_KEY.to_vec())
+            .await
+            .expect("Failed to read consensus state")
+        {
+            Some(bytes) => {
+                let mut state: State =
+                    bincode::deserialize(&bytes).expect("Failed to deserialize consensus state");
+                info!(
+                    "Recovered consensus state: last committed round {}",
+                    state.last_committed_round
+                );
+                state.dag = State::new(self.genesis.clone()).dag;
+                state
+            }
+            None => State::new(self.genesis.clone()),
+        };
+
+        // The rounds are contiguous: the primary sends a certificate only after its parents.
+        // 轮次是连续的：主节点只会在发送父证书之后发送证书
+        let mut round = max(1, state.last_committed_round.saturating_sub(self.gc_depth));
+        while let Some(bytes) = self
+            .store
+            .read(round_key(round))
+            .await
+            .expect("Failed to read consensus round")
+        {
+            let certificates: Vec<Certificate> =
+                bincode::deserialize(&bytes).expect("Failed to deserialize consensus round");
+            state.dag.insert(
+                round,
+                certificates
+                    .into_iter()
+                    .map(|x| (x.origin(), (x.digest(), x)))
+                    .collect(),
+            );
+            round += 1;
+        }
+        state.clean(self.gc_depth);
+        state
+    }
+
+    /// Persist the state, without the dag.
+    // 持久化 state（不包括 DAG）
+    async fn persist_state(&mut self, state: &State) {
+        let bytes = bincode::serialize(state).expect("Failed to serialize consensus state");
+        self.store.write(STATE_KEY.to_vec(), bytes).await;
+    }
+
+    /// Persist the certificates of a round of the dag.
+    // 持久化 DAG 中某一轮次的证书
+    async fn persist_round(&mut self, state: &State, round: Round) {
+        let certificates: Vec<&Certificate> = state
+            .dag
+            .get(&round)
+            .map(|x| x.values().map(|(_, certificate)| certificate).collect())
+            .unwrap_or_default();
+        let bytes = bincode::serialize(&certificates).expect("Failed to serialize consensus round");
+        self.store.write(round_key(round), bytes).await;
+    }
+
+    /// Delete the persisted rounds below the gc depth of the persisted state: they are not loaded
+    /// again (see `load_state`). The deletions interrupted by a crash are resumed at the next
+    /// commit, as `gc_round` is persisted with the next state.
+    // 删除低于垃圾回收深度的持久化轮次
+    async fn prune_rounds(&mut self, state: &mut State) {
+        let gc_round = state.last_committed_round.saturating_sub(self.gc_depth);
+        for round in max(1, state.gc_round)..gc_round {
+            self.store.delete(round_key(round)).await;
+        }
+        state.gc_round = max(state.gc_round, gc_round);
+    }
+
+    /// Load the number of certificates output so far.
+    // 加载已输出的证书数量
+    async fn load_delivered(&mut self) -> u64 {
+        self.store
+            .read(DELIVERED_KEY.to_vec())
+            .await
+            .expect("Failed to read the delivered certificates")
+            .map(|bytes| {
+                u64::from_le_bytes(
+                    bytes[..]
+                        .try_into()
+                        .expect("Corrupted delivered certificates"),
+                )
+            })
+            .unwrap_or(0)
+    }
+
     /// Returns the certificate (and the certificate's digest) originated by the leader of the
     /// specified round (if any).
     fn leader<'a>(&self, round: Round, dag: &'a Dag) -> Option<&'a (Digest, Certificate)> {
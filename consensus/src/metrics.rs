@@ -0,0 +1,25 @@
+use ::metrics::{
+    lazy_static, register_histogram, register_int_counter, register_int_gauge, Histogram, IntCounter,
+    IntGauge, LATENCY_BUCKETS_MS,
+};
+
+// The metrics of the consensus, exported by `metrics::MetricsExporter`.
+lazy_static! {
+    pub static ref COMMITTED_CERTIFICATES: IntCounter = register_int_counter!(
+        "consensus_committed_certificates_total",
+        "Certificates committed by the consensus"
+    )
+    .unwrap();
+    pub static ref LAST_COMMITTED_ROUND: IntGauge = register_int_gauge!(
+        "consensus_last_committed_round",
+        "The last round committed by the consensus"
+    )
+    .unwrap();
+    /// The time between the reception of a certificate by the consensus and its commit.
+    pub static ref COMMIT_LATENCY: Histogram = register_histogram!(
+        "consensus_commit_latency_ms",
+        "Time between the reception of a certificate and its commit in ms",
+        LATENCY_BUCKETS_MS.to_vec()
+    )
+    .unwrap();
+}
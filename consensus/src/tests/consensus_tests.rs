@@ -6,6 +6,7 @@ use primary::Header;
 use rand::rngs::StdRng;
 use rand::SeedableRng as _;
 use std::collections::{BTreeSet, VecDeque};
+use std::fs;
 use tokio::sync::mpsc::channel;
 
 // Fixture
@@ -36,6 +37,12 @@ pub fn mock_committee() -> Committee {
     }
 }
 
+// Fixture: a new test store.
+fn mock_store(path: &str) -> Store {
+    let _ = fs::remove_dir_all(path);
+    Store::new(path).unwrap()
+}
+
 // Fixture
 fn mock_certificate(
     origin: PublicKey,
@@ -105,6 +112,7 @@ async fn commit_one() {
         rx_waiter,
         tx_primary,
         tx_output,
+        mock_store(".db_test_consensus_commit_one"),
     );
     tokio::spawn(async move { while rx_primary.recv().await.is_some() {} });
 
@@ -150,6 +158,7 @@ async fn dead_node() {
         rx_waiter,
         tx_primary,
         tx_output,
+        mock_store(".db_test_consensus_dead_node"),
     );
     tokio::spawn(async move { while rx_primary.recv().await.is_some() {} });
 
@@ -238,6 +247,7 @@ async fn not_enough_support() {
         rx_waiter,
         tx_primary,
         tx_output,
+        mock_store(".db_test_consensus_not_enough_support"),
     );
     tokio::spawn(async move { while rx_primary.recv().await.is_some() {} });
 
@@ -301,6 +311,7 @@ async fn missing_leader() {
         rx_waiter,
         tx_primary,
         tx_output,
+        mock_store(".db_test_consensus_missing_leader"),
     );
     tokio::spawn(async move { while rx_primary.recv().await.is_some() {} });
 
@@ -326,3 +337,145 @@ async fn missing_leader() {
     let certificate = rx_output.recv().await.unwrap();
     assert_eq!(certificate.round(), 4);
 }
+
+// Commit the leader of round 2, then restart the consensus on the same store and commit the leader
+// of round 4. The restarted consensus should not commit again the leader of round 2 nor its parents,
+// and should delete the persisted rounds below the gc depth.
+#[tokio::test]
+async fn recover_state() {
+    let mut keys: Vec<_> = keys().into_iter().map(|(x, _)| x).collect();
+    keys.sort();
+    let genesis = Certificate::genesis(&mock_committee())
+        .iter()
+        .map(|x| x.digest())
+        .collect::<BTreeSet<_>>();
+    let (mut certificates, parents) = make_certificates(1, 4, &genesis, &keys);
+    let (_, certificate) = mock_certificate(keys[0], 5, parents.clone());
+    certificates.push_back(certificate);
+
+    // Commit the leader of round 2.
+    let store = mock_store(".db_test_consensus_recover_state");
+    let (tx_waiter, rx_waiter) = channel(1);
+    let (tx_primary, mut rx_primary) = channel(1);
+    let (tx_output, mut rx_output) = channel(1);
+    Consensus::spawn(
+        mock_committee(),
+        /* gc_depth */ 2,
+        rx_waiter,
+        tx_primary,
+        tx_output,
+        store.clone(),
+    );
+    tokio::spawn(async move { while rx_primary.recv().await.is_some() {} });
+    while let Some(certificate) = certificates.pop_front() {
+        tx_waiter.send(certificate).await.unwrap();
+    }
+    for _ in 1..=4 {
+        let certificate = rx_output.recv().await.unwrap();
+        assert_eq!(certificate.round(), 1);
+    }
+    let certificate = rx_output.recv().await.unwrap();
+    assert_eq!(certificate.round(), 2);
+    drop(tx_waiter);
+
+    // Restart the consensus and feed it rounds 5 to 7 to commit the leader of round 4.
+    let (mut certificates, parents) = make_certificates(5, 6, &parents, &keys);
+    let (_, certificate) = mock_certificate(keys[0], 7, parents);
+    certificates.push_back(certificate);
+
+    let (tx_waiter, rx_waiter) = channel(1);
+    let (tx_primary, mut rx_primary) = channel(1);
+    let (tx_output, mut rx_output) = channel(1);
+    Consensus::spawn(
+        mock_committee(),
+        /* gc_depth */ 2,
+        rx_waiter,
+        tx_primary,
+        tx_output,
+        store.clone(),
+    );
+    tokio::spawn(async move { while rx_primary.recv().await.is_some() {} });
+    while let Some(certificate) = certificates.pop_front() {
+        tx_waiter.send(certificate).await.unwrap();
+    }
+    for _ in 1..=3 {
+        let certificate = rx_output.recv().await.unwrap();
+        assert_eq!(certificate.round(), 2);
+        assert_ne!(certificate.origin(), keys[0]);
+    }
+    for _ in 1..=4 {
+        let certificate = rx_output.recv().await.unwrap();
+        assert_eq!(certificate.round(), 3);
+    }
+    let certificate = rx_output.recv().await.unwrap();
+    assert_eq!(certificate.round(), 4);
+
+    // The rounds below the gc depth of the last commit are deleted before its output.
+    let mut store = store;
+    assert!(store.read(round_key(1)).await.unwrap().is_none());
+    assert!(store.read(round_key(2)).await.unwrap().is_some());
+}
+
+// Commit the leader of round 2, but stop the output after two certificates. The restarted consensus
+// should output the rest of the sequence before anything else, and nothing twice.
+#[tokio::test]
+async fn recover_interrupted_output() {
+    let mut keys: Vec<_> = keys().into_iter().map(|(x, _)| x).collect();
+    keys.sort();
+    let genesis = Certificate::genesis(&mock_committee())
+        .iter()
+        .map(|x| x.digest())
+        .collect::<BTreeSet<_>>();
+    let (mut certificates, parents) = make_certificates(1, 4, &genesis, &keys);
+    let (_, certificate) = mock_certificate(keys[0], 5, parents);
+    certificates.push_back(certificate);
+
+    let store = mock_store(".db_test_consensus_recover_output");
+    let (tx_waiter, rx_waiter) = channel(1);
+    let (tx_primary, mut rx_primary) = channel(1);
+    let (tx_output, mut rx_output) = channel(1);
+    Consensus::spawn(
+        mock_committee(),
+        /* gc_depth */ 50,
+        rx_waiter,
+        tx_primary,
+        tx_output,
+        store.clone(),
+    );
+    let primary = tokio::spawn(async move { while rx_primary.recv().await.is_some() {} });
+    while let Some(certificate) = certificates.pop_front() {
+        tx_waiter.send(certificate).await.unwrap();
+    }
+    let mut output = Vec::new();
+    for _ in 1..=2 {
+        output.push(rx_output.recv().await.unwrap());
+    }
+    // The certificates already in the channel are delivered, the next ones fail.
+    rx_output.close();
+    while let Some(certificate) = rx_output.recv().await {
+        output.push(certificate);
+    }
+    drop(tx_waiter);
+    primary.await.unwrap();
+
+    // Restart the consensus: it outputs the undelivered certificates without new input.
+    let (_tx_waiter, rx_waiter) = channel(1);
+    let (tx_primary, mut rx_primary) = channel(1);
+    let (tx_output, mut rx_output) = channel(1);
+    Consensus::spawn(
+        mock_committee(),
+        /* gc_depth */ 50,
+        rx_waiter,
+        tx_primary,
+        tx_output,
+        store,
+    );
+    tokio::spawn(async move { while rx_primary.recv().await.is_some() {} });
+    while output.len() < 5 {
+        output.push(rx_output.recv().await.unwrap());
+    }
+    let digests: HashSet<_> = output.iter().map(|x| x.digest()).collect();
+    assert_eq!(digests.len(), 5);
+    assert!(output[..4].iter().all(|x| x.round() == 1));
+    assert_eq!(output[4].round(), 2);
+}
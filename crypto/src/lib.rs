@@ -15,6 +15,13 @@ use tokio::sync::oneshot;
 #[path = "tests/crypto_tests.rs"]
 pub mod crypto_tests;
 
+mod threshold;
+
+pub use threshold::{
+    assemble_intact_ts_partial, generate_threshold_keys, verify_ts_sig, PartialSignature,
+    ThresholdPublicKey, ThresholdPublicKeySet, ThresholdSecretShare, ThresholdSignature,
+};
+
 // 类型别名，表示加密错误
 pub type CryptoError = ed25519::Error;
 
@@ -234,17 +241,39 @@ impl Signature {
         }
         dalek::verify_batch(&messages[..], &signatures[..], &keys[..])
     }
+
+    /// Like `verify_batch`, but each signature is over its own digest.
+    pub fn verify_batch_digests<'a, I>(items: I) -> Result<(), CryptoError>
+    where
+        I: IntoIterator<Item = &'a (Digest, PublicKey, Signature)>,
+    {
+        let mut messages: Vec<&[u8]> = Vec::new();
+        let mut signatures: Vec<dalek::Signature> = Vec::new();
+        let mut keys: Vec<dalek::PublicKey> = Vec::new();
+        for (digest, key, sig) in items.into_iter() {
+            messages.push(&digest.0[..]);
+            signatures.push(ed25519::signature::Signature::from_bytes(&sig.flatten())?);
+            keys.push(dalek::PublicKey::from_bytes(&key.0)?);
+        }
+        dalek::verify_batch(&messages[..], &signatures[..], &keys[..])
+    }
 }
 
-/// This service holds the node's private key. It takes digests as input and returns a signature
-/// over the digest (through a oneshot channel).
+/// This service holds the node's private key (and optionally its threshold key share). It takes
+/// digests as input and returns a signature or a partial signature over the digest (through a
+/// oneshot channel).
 #[derive(Clone)]
 pub struct SignatureService {
     channel: Sender<(Digest, oneshot::Sender<Signature>)>,
+    threshold_channel: Option<Sender<(Digest, oneshot::Sender<PartialSignature>)>>,
 }
 
 impl SignatureService {
     pub fn new(secret: SecretKey) -> Self {
+        Self::new_with_threshold_share(secret, None)
+    }
+
+    pub fn new_with_threshold_share(secret: SecretKey, share: Option<ThresholdSecretShare>) -> Self {
         let (tx, mut rx): (Sender<(_, oneshot::Sender<_>)>, _) = channel(100);
         tokio::spawn(async move {
             while let Some((digest, sender)) = rx.recv().await {
@@ -252,7 +281,21 @@ impl SignatureService {
                 let _ = sender.send(signature);
             }
         });
-        Self { channel: tx }
+
+        let threshold_channel = share.map(|share| {
+            let (tx, mut rx): (Sender<(_, oneshot::Sender<_>)>, _) = channel(100);
+            tokio::spawn(async move {
+                while let Some((digest, sender)) = rx.recv().await {
+                    let partial = share.sign(&digest);
+                    let _ = sender.send(partial);
+                }
+            });
+            tx
+        });
+        Self {
+            channel: tx,
+            threshold_channel,
+        }
     }
 
     pub async fn request_signature(&mut self, digest: Digest) -> Signature {
@@ -264,19 +307,18 @@ impl SignatureService {
             .await
             .expect("Failed to receive signature from Signature Service")
     }
-}
-
-
-
-// TODO
-// 组装完整的阈值签名
-pub fn assemble_intact_ts_partial(_ts_sig_list: Vec<Signature>, _ts_pub: PublicKey, _msg_hash: &Digest, _con_thres: usize, _total_nodes: usize) -> Signature {
 
-  return Signature::default();
+    pub async fn request_partial_signature(&mut self, digest: Digest) -> PartialSignature {
+        let channel = self
+            .threshold_channel
+            .as_ref()
+            .expect("Signature Service holds no threshold key share");
+        let (sender, receiver): (oneshot::Sender<_>, oneshot::Receiver<_>) = oneshot::channel();
+        if let Err(e) = channel.send((digest, sender)).await {
+            panic!("Failed to send message Signature Service: {}", e);
+        }
+        receiver
+            .await
+            .expect("Failed to receive partial signature from Signature Service")
+    }
 }
-
-// TODO
-// 验证阈值签名
-pub fn verify_ts_sig(_ts_pub: PublicKey, _msg_hash: &Digest, _intact_sig: &Signature) -> bool {
-  return true;
-}
\ No newline at end of file
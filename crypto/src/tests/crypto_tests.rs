@@ -114,6 +114,28 @@ fn verify_invalid_batch() {
     assert!(Signature::verify_batch(&digest, &signatures).is_err());
 }
 
+#[test]
+fn verify_valid_batch_digests() {
+    // Make signatures over distinct messages.
+    let mut keys = keys();
+    let items: Vec<_> = (0..3u8)
+        .map(|i| {
+            let digest = [i; 8].as_ref().digest();
+            let (public_key, secret_key) = keys.pop().unwrap();
+            let signature = Signature::new(&digest, &secret_key);
+            (digest, public_key, signature)
+        })
+        .collect();
+
+    // Verify the batch.
+    assert!(Signature::verify_batch_digests(&items).is_ok());
+
+    // A signature over another message invalidates the batch.
+    let mut items = items;
+    items[0].0 = [9u8; 8].as_ref().digest();
+    assert!(Signature::verify_batch_digests(&items).is_err());
+}
+
 #[tokio::test]
 async fn signature_service() {
     // Get a keypair.
@@ -130,3 +152,114 @@ async fn signature_service() {
     // Verify the signature we received.
     assert!(signature.verify(&digest, &public_key).is_ok());
 }
+
+pub fn threshold_keys() -> (ThresholdPublicKeySet, Vec<ThresholdSecretShare>) {
+    let mut rng = StdRng::from_seed([0; 32]);
+    generate_threshold_keys(2, 4, &mut rng)
+}
+
+#[test]
+fn import_export_threshold_keys() {
+    let (key_set, mut shares) = threshold_keys();
+    let export = key_set.public_key.encode_base64();
+    let import = ThresholdPublicKey::decode_base64(&export);
+    assert_eq!(import.unwrap(), key_set.public_key);
+
+    let share = shares.pop().unwrap();
+    let import = ThresholdSecretShare::decode_base64(&share.encode_base64()).unwrap();
+    assert_eq!(import.index, share.index);
+    let digest = (b"Hello, world!" as &[u8]).digest();
+    assert_eq!(import.sign(&digest), share.sign(&digest));
+
+    // the secret of a share is a canonical scalar
+    let mut bytes = 0u32.to_le_bytes().to_vec();
+    bytes.extend_from_slice(&[0xff; 32]);
+    assert!(ThresholdSecretShare::decode_base64(&base64::encode(&bytes)).is_err());
+}
+
+#[test]
+fn verify_valid_partial_signature() {
+    let (key_set, shares) = threshold_keys();
+    let message: &[u8] = b"Hello, world!";
+    let digest = message.digest();
+    for share in &shares {
+        assert!(share.sign(&digest).verify(&digest, &key_set).is_ok());
+    }
+}
+
+#[test]
+fn verify_invalid_partial_signature() {
+    let (key_set, shares) = threshold_keys();
+    let message: &[u8] = b"Hello, world!";
+    let digest = message.digest();
+    let mut partial = shares[0].sign(&digest);
+
+    // Wrong message.
+    let bad_message: &[u8] = b"Bad message!";
+    assert!(partial.verify(&bad_message.digest(), &key_set).is_err());
+
+    // Claimed by another share.
+    partial.index = 1;
+    assert!(partial.verify(&digest, &key_set).is_err());
+}
+
+#[test]
+fn verify_valid_threshold_signature() {
+    let (key_set, shares) = threshold_keys();
+    let message: &[u8] = b"Hello, world!";
+    let digest = message.digest();
+
+    // Any two shares assemble the same threshold signature.
+    let partials: Vec<_> = shares.iter().map(|share| share.sign(&digest)).collect();
+    let signature = assemble_intact_ts_partial(partials[..2].to_vec(), &key_set, &digest).unwrap();
+    let other = assemble_intact_ts_partial(partials[2..].to_vec(), &key_set, &digest).unwrap();
+    assert_eq!(signature, other);
+    assert!(verify_ts_sig(&key_set.public_key, &digest, &signature));
+}
+
+#[test]
+fn verify_invalid_threshold_signature() {
+    let (key_set, shares) = threshold_keys();
+    let message: &[u8] = b"Hello, world!";
+    let digest = message.digest();
+    let partials: Vec<_> = shares.iter().map(|share| share.sign(&digest)).collect();
+
+    // Not enough shares.
+    assert!(assemble_intact_ts_partial(partials[..1].to_vec(), &key_set, &digest).is_err());
+
+    // Duplicated shares do not count twice.
+    let duplicated = vec![partials[0].clone(), partials[0].clone()];
+    assert!(ThresholdSignature::combine(&duplicated, key_set.threshold).is_err());
+
+    // Invalid shares are discarded before combining.
+    let mut forged = partials[1].clone();
+    forged.index = 2;
+    let list = vec![partials[0].clone(), forged];
+    assert!(assemble_intact_ts_partial(list, &key_set, &digest).is_err());
+
+    // Wrong message or wrong key.
+    let signature = assemble_intact_ts_partial(partials, &key_set, &digest).unwrap();
+    let bad_message: &[u8] = b"Bad message!";
+    assert!(!verify_ts_sig(&key_set.public_key, &bad_message.digest(), &signature));
+    let mut rng = StdRng::from_seed([1; 32]);
+    let (other_set, _) = generate_threshold_keys(2, 4, &mut rng);
+    assert!(!verify_ts_sig(&other_set.public_key, &digest, &signature));
+    assert!(!verify_ts_sig(&key_set.public_key, &digest, &ThresholdSignature::default()));
+}
+
+#[tokio::test]
+async fn threshold_signature_service() {
+    let (key_set, mut shares) = threshold_keys();
+    let (_, secret_key) = keys().pop().unwrap();
+
+    // Spawn the signature service.
+    let mut service = SignatureService::new_with_threshold_share(secret_key, shares.pop());
+
+    // Request a partial signature from the service.
+    let message: &[u8] = b"Hello, world!";
+    let digest = message.digest();
+    let partial = service.request_partial_signature(digest.clone()).await;
+
+    // Verify the partial signature we received.
+    assert!(partial.verify(&digest, &key_set).is_ok());
+}
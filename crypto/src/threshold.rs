@@ -0,0 +1,337 @@
+use crate::{CryptoError, Digest};
+use bls12_381::hash_to_curve::{ExpandMsgXmd, HashToCurve};
+use bls12_381::{pairing, G1Affine, G1Projective, G2Affine, G2Projective, Scalar};
+use rand::{CryptoRng, RngCore};
+use serde::{de, ser, Deserialize, Serialize};
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::fmt;
+
+/// Domain separation tag used to hash digests onto G1.
+const DST: &[u8] = b"SHARDAG-THRESHOLD-SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_";
+
+// 将摘要映射到 G1 上的点
+fn hash_to_g1(digest: &Digest) -> G1Projective {
+    <G1Projective as HashToCurve<ExpandMsgXmd<sha2::Sha256>>>::hash_to_curve(digest.0, DST)
+}
+
+fn random_scalar<R>(csprng: &mut R) -> Scalar
+where
+    R: CryptoRng + RngCore,
+{
+    let mut bytes = [0u8; 64];
+    csprng.fill_bytes(&mut bytes);
+    Scalar::from_bytes_wide(&bytes)
+}
+
+// 共享份额 i 对应多项式上的点 x = i + 1
+fn share_x(index: u32) -> Scalar {
+    Scalar::from(index as u64 + 1)
+}
+
+/// Represents the threshold public key of a shard, or the public key of one of its shares
+/// (a compressed G2 point).
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct ThresholdPublicKey(pub [u8; 96]);
+
+impl ThresholdPublicKey {
+    pub fn encode_base64(&self) -> String {
+        base64::encode(&self.0[..])
+    }
+
+    pub fn decode_base64(s: &str) -> Result<Self, base64::DecodeError> {
+        let bytes = base64::decode(s)?;
+        let array = bytes
+            .get(..96)
+            .ok_or(base64::DecodeError::InvalidLength)?
+            .try_into()
+            .map_err(|_| base64::DecodeError::InvalidLength)?;
+        Ok(Self(array))
+    }
+
+    fn decompress(&self) -> Result<G2Affine, CryptoError> {
+        Option::from(G2Affine::from_compressed(&self.0)).ok_or_else(CryptoError::new)
+    }
+
+    fn from_point(point: G2Projective) -> Self {
+        Self(G2Affine::from(point).to_compressed())
+    }
+}
+
+impl fmt::Debug for ThresholdPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.encode_base64())
+    }
+}
+
+impl fmt::Display for ThresholdPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.encode_base64().get(0..16).unwrap())
+    }
+}
+
+impl Serialize for ThresholdPublicKey {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_str(&self.encode_base64())
+    }
+}
+
+impl<'de> Deserialize<'de> for ThresholdPublicKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        let value = Self::decode_base64(&s).map_err(|e| de::Error::custom(e.to_string()))?;
+        Ok(value)
+    }
+}
+
+/// The public threshold-signature material of a shard: its threshold public key and the public
+/// key of every share (used to verify partial signatures before combining them).
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct ThresholdPublicKeySet {
+    /// The number of partial signatures required to assemble a threshold signature (f+1).
+    pub threshold: usize,
+    /// The threshold public key of the shard.
+    pub public_key: ThresholdPublicKey,
+    /// The public key of each share, indexed by share index.
+    pub shares: Vec<ThresholdPublicKey>,
+}
+
+impl ThresholdPublicKeySet {
+    /// Returns the public key of a specific share.
+    pub fn share(&self, index: u32) -> Option<&ThresholdPublicKey> {
+        self.shares.get(index as usize)
+    }
+}
+
+/// Represents the secret key share of a node (an index and a scalar).
+pub struct ThresholdSecretShare {
+    pub index: u32,
+    secret: [u8; 32],
+}
+
+impl ThresholdSecretShare {
+    pub fn encode_base64(&self) -> String {
+        let mut bytes = self.index.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&self.secret);
+        base64::encode(&bytes)
+    }
+
+    /// Decode a share, whose secret must be a canonical scalar.
+    pub fn decode_base64(s: &str) -> Result<Self, CryptoError> {
+        let bytes = base64::decode(s).map_err(|_| CryptoError::new())?;
+        if bytes.len() < 36 {
+            return Err(CryptoError::new());
+        }
+        let index = u32::from_le_bytes(bytes[..4].try_into().unwrap());
+        let secret = bytes[4..36].try_into().unwrap();
+        Option::<Scalar>::from(Scalar::from_bytes(&secret)).ok_or_else(CryptoError::new)?;
+        Ok(Self { index, secret })
+    }
+
+    fn scalar(&self) -> Scalar {
+        Option::from(Scalar::from_bytes(&self.secret)).expect("Invalid threshold key share")
+    }
+
+    /// Make a partial signature over the digest.
+    pub fn sign(&self, digest: &Digest) -> PartialSignature {
+        let point = G1Affine::from(hash_to_g1(digest) * self.scalar());
+        PartialSignature::from_point(self.index, point)
+    }
+}
+
+impl Serialize for ThresholdSecretShare {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_str(&self.encode_base64())
+    }
+}
+
+impl<'de> Deserialize<'de> for ThresholdSecretShare {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        let value = Self::decode_base64(&s).map_err(|e| de::Error::custom(e.to_string()))?;
+        Ok(value)
+    }
+}
+
+impl Drop for ThresholdSecretShare {
+    fn drop(&mut self) {
+        self.secret.iter_mut().for_each(|x| *x = 0);
+    }
+}
+
+/// Deal a fresh (threshold, total) key set: a random polynomial of degree `threshold - 1` is
+/// sampled, share `i` is its evaluation at `i + 1`, and the threshold secret is its evaluation at 0.
+pub fn generate_threshold_keys<R>(
+    threshold: usize,
+    total: usize,
+    csprng: &mut R,
+) -> (ThresholdPublicKeySet, Vec<ThresholdSecretShare>)
+where
+    R: CryptoRng + RngCore,
+{
+    assert!(
+        threshold > 0 && threshold <= total,
+        "The threshold must be between 1 and the number of shares"
+    );
+    let coefficients: Vec<Scalar> = (0..threshold).map(|_| random_scalar(csprng)).collect();
+
+    let secret_shares: Vec<_> = (0..total as u32)
+        .map(|index| {
+            // Horner evaluation of the polynomial at x = index + 1.
+            let x = share_x(index);
+            let value = coefficients
+                .iter()
+                .rev()
+                .fold(Scalar::zero(), |acc, coefficient| acc * x + coefficient);
+            ThresholdSecretShare {
+                index,
+                secret: value.to_bytes(),
+            }
+        })
+        .collect();
+
+    let public_key = ThresholdPublicKey::from_point(G2Projective::generator() * coefficients[0]);
+    let shares = secret_shares
+        .iter()
+        .map(|share| ThresholdPublicKey::from_point(G2Projective::generator() * share.scalar()))
+        .collect();
+    let key_set = ThresholdPublicKeySet {
+        threshold,
+        public_key,
+        shares,
+    };
+    (key_set, secret_shares)
+}
+
+// BLS 验证：e(sig, g2) == e(H(m), pk)
+fn verify_bls(digest: &Digest, signature: &G1Affine, public_key: &ThresholdPublicKey) -> Result<(), CryptoError> {
+    let key = public_key.decompress()?;
+    let message = G1Affine::from(hash_to_g1(digest));
+    match pairing(signature, &G2Affine::generator()) == pairing(&message, &key) {
+        true => Ok(()),
+        false => Err(CryptoError::new()),
+    }
+}
+
+/// Represents a partial signature made with the share of index `index` (a compressed G1 point).
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct PartialSignature {
+    pub index: u32,
+    part1: [u8; 32],
+    part2: [u8; 16],
+}
+
+impl PartialSignature {
+    fn from_point(index: u32, point: G1Affine) -> Self {
+        let bytes = point.to_compressed();
+        let part1 = bytes[..32].try_into().expect("Unexpected signature length");
+        let part2 = bytes[32..48].try_into().expect("Unexpected signature length");
+        Self { index, part1, part2 }
+    }
+
+    pub fn flatten(&self) -> [u8; 48] {
+        [&self.part1[..], &self.part2[..]]
+            .concat()
+            .try_into()
+            .expect("Unexpected signature length")
+    }
+
+    fn decompress(&self) -> Result<G1Affine, CryptoError> {
+        Option::from(G1Affine::from_compressed(&self.flatten())).ok_or_else(CryptoError::new)
+    }
+
+    /// Verify the partial signature against the public key of its share.
+    pub fn verify(&self, digest: &Digest, key_set: &ThresholdPublicKeySet) -> Result<(), CryptoError> {
+        let share_key = key_set.share(self.index).ok_or_else(CryptoError::new)?;
+        verify_bls(digest, &self.decompress()?, share_key)
+    }
+}
+
+/// Represents a threshold signature assembled from `threshold` partial signatures (a compressed
+/// G1 point). The default value is not a valid signature.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct ThresholdSignature {
+    part1: [u8; 32],
+    part2: [u8; 16],
+}
+
+impl ThresholdSignature {
+    pub fn flatten(&self) -> [u8; 48] {
+        [&self.part1[..], &self.part2[..]]
+            .concat()
+            .try_into()
+            .expect("Unexpected signature length")
+    }
+
+    fn decompress(&self) -> Result<G1Affine, CryptoError> {
+        Option::from(G1Affine::from_compressed(&self.flatten())).ok_or_else(CryptoError::new)
+    }
+
+    /// Combine `threshold` partial signatures with distinct indices through Lagrange
+    /// interpolation at 0. The partial signatures are not verified.
+    pub fn combine(partials: &[PartialSignature], threshold: usize) -> Result<Self, CryptoError> {
+        let mut seen = HashSet::new();
+        let partials: Vec<_> = partials
+            .iter()
+            .filter(|partial| seen.insert(partial.index))
+            .take(threshold)
+            .collect();
+        if threshold == 0 || partials.len() < threshold {
+            return Err(CryptoError::new());
+        }
+
+        let mut combined = G1Projective::identity();
+        for partial in &partials {
+            let x_i = share_x(partial.index);
+            let mut lagrange = Scalar::one();
+            for other in &partials {
+                if other.index == partial.index {
+                    continue;
+                }
+                let x_j = share_x(other.index);
+                let denominator = Option::<Scalar>::from((x_j - x_i).invert()).ok_or_else(CryptoError::new)?;
+                lagrange *= x_j * denominator;
+            }
+            combined += G1Projective::from(partial.decompress()?) * lagrange;
+        }
+
+        let bytes = G1Affine::from(combined).to_compressed();
+        let part1 = bytes[..32].try_into().expect("Unexpected signature length");
+        let part2 = bytes[32..48].try_into().expect("Unexpected signature length");
+        Ok(Self { part1, part2 })
+    }
+
+    pub fn verify(&self, digest: &Digest, public_key: &ThresholdPublicKey) -> Result<(), CryptoError> {
+        verify_bls(digest, &self.decompress()?, public_key)
+    }
+}
+
+// 组装完整的阈值签名：丢弃无效的部分签名，并用 f+1 个有效份额组装
+pub fn assemble_intact_ts_partial(
+    ts_sig_list: Vec<PartialSignature>,
+    ts_pub: &ThresholdPublicKeySet,
+    msg_hash: &Digest,
+) -> Result<ThresholdSignature, CryptoError> {
+    let valid: Vec<_> = ts_sig_list
+        .into_iter()
+        .filter(|partial| partial.verify(msg_hash, ts_pub).is_ok())
+        .collect();
+    ThresholdSignature::combine(&valid, ts_pub.threshold)
+}
+
+// 验证阈值签名
+pub fn verify_ts_sig(ts_pub: &ThresholdPublicKey, msg_hash: &Digest, intact_sig: &ThresholdSignature) -> bool {
+    intact_sig.verify(msg_hash, ts_pub).is_ok()
+}
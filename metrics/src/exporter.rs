@@ -0,0 +1,82 @@
+use log::{debug, warn};
+use prometheus::{Encoder as _, TextEncoder};
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+use tokio::net::{TcpListener, TcpStream};
+
+#[cfg(test)]
+#[path = "tests/exporter_tests.rs"]
+pub mod exporter_tests;
+
+/// The metrics of the default registry in the Prometheus text format.
+pub fn encode() -> String {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&prometheus::gather(), &mut buffer)
+        .expect("Failed to encode metrics");
+    String::from_utf8(buffer).expect("Metrics are not utf8")
+}
+
+/// Serves the metrics of the process over HTTP, whatever the requested path (e.g. `/metrics`), so
+/// that a Prometheus scraper or the benchmark scripts can read them with a plain GET.
+pub struct MetricsExporter {
+    /// Listener of the metrics address.
+    listener: TcpListener,
+}
+
+impl MetricsExporter {
+    /// Bind the address and serve the metrics on it. Return the bound address, so that the port
+    /// assigned by the OS to an address with port 0 can be read back.
+    pub async fn spawn(address: SocketAddr) -> SocketAddr {
+        let listener = TcpListener::bind(&address)
+            .await
+            .expect("Failed to bind metrics TCP port");
+        let address = listener
+            .local_addr()
+            .expect("Failed to read the metrics address");
+
+        debug!("Serving metrics on {}", address);
+        tokio::spawn(async move {
+            Self { listener }.run().await;
+        });
+        address
+    }
+
+    async fn run(&self) {
+        loop {
+            match self.listener.accept().await {
+                Ok((socket, _)) => {
+                    tokio::spawn(async move {
+                        if let Err(e) = Self::serve(socket).await {
+                            warn!("Failed to serve metrics: {}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("Failed to accept metrics connection: {}", e),
+            }
+        }
+    }
+
+    // Read the request head and answer with the metrics, then close the connection.
+    async fn serve(mut socket: TcpStream) -> std::io::Result<()> {
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            let n = socket.read(&mut buffer).await?;
+            if n == 0 {
+                break;
+            }
+            request.extend_from_slice(&buffer[..n]);
+        }
+
+        let body = encode();
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            TextEncoder::new().format_type(),
+            body.len()
+        );
+        socket.write_all(head.as_bytes()).await?;
+        socket.write_all(body.as_bytes()).await?;
+        socket.shutdown().await
+    }
+}
@@ -0,0 +1,22 @@
+//! The metrics shared by the primary, the consensus, the worker and the client. Every crate defines
+//! its metrics in a `metrics` module, registered in the default registry of the process, and the
+//! node exposes them in the Prometheus text format through `MetricsExporter`.
+mod exporter;
+
+pub use crate::exporter::{encode, MetricsExporter};
+pub use lazy_static::lazy_static;
+pub use prometheus::{
+    register_histogram, register_histogram_vec, register_int_counter, register_int_counter_vec,
+    register_int_gauge, register_int_gauge_vec, Histogram, HistogramVec, IntCounter, IntCounterVec,
+    IntGauge, IntGaugeVec,
+};
+
+/// The buckets of the latency histograms, in ms.
+pub const LATENCY_BUCKETS_MS: &[f64] = &[
+    1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1_000.0, 2_500.0, 5_000.0, 10_000.0,
+];
+
+/// The buckets of the size histograms (bytes or number of txs).
+pub const SIZE_BUCKETS: &[f64] = &[
+    1.0, 10.0, 100.0, 1_000.0, 10_000.0, 100_000.0, 500_000.0, 1_000_000.0, 5_000_000.0,
+];
@@ -0,0 +1,28 @@
+use super::*;
+use crate::{lazy_static, register_int_counter, IntCounter};
+
+lazy_static! {
+    static ref TEST_COUNTER: IntCounter =
+        register_int_counter!("exporter_test_total", "A counter of the exporter tests").unwrap();
+}
+
+#[tokio::test]
+async fn export() {
+    TEST_COUNTER.inc_by(3);
+
+    // Make the exporter.
+    let address = "127.0.0.1:0".parse::<SocketAddr>().unwrap();
+    let address = MetricsExporter::spawn(address).await;
+
+    // Scrape the metrics.
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream
+        .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains("exporter_test_total 3"));
+}
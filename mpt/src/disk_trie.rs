@@ -13,6 +13,7 @@ use crate::{
 	nibbles::Nibbles,
 	node::{empty_children, BranchNode, Node},
 	proof::Proof,
+	retain::{Retained, RetainedCommit},
 };
 
 pub type TrieResult<T> = Result<T, TrieError>;
@@ -27,6 +28,8 @@ pub struct DiskMerklePatriciaTrie {
 	cache: Mutex<HashMap<Vec<u8>, Vec<u8>>>,
 	passing_keys: Mutex<HashSet<Vec<u8>>>,
 	gen_keys: Mutex<HashSet<Vec<u8>>>,
+	// the nodes of the retained root, see `retain`
+	retained: Retained,
 }
 
 impl DiskMerklePatriciaTrie {
@@ -39,6 +42,7 @@ impl DiskMerklePatriciaTrie {
 			cache: Mutex::new(HashMap::new()),
 			passing_keys: Mutex::new(HashSet::new()),
 			gen_keys: Mutex::new(HashSet::new()),
+			retained: Retained::default(),
 
 			db,
 		}
@@ -55,6 +59,7 @@ impl DiskMerklePatriciaTrie {
 					cache: Mutex::new(HashMap::new()),
 					passing_keys: Mutex::new(HashSet::new()),
 					gen_keys: Mutex::new(HashSet::new()),
+					retained: Retained::default(),
 
 					db,
 				};
@@ -467,6 +472,35 @@ impl DiskMerklePatriciaTrie {
 		}
 	}
 
+	/// Keep the nodes of the committed root `root_hash` in the db until another root is retained,
+	/// so that `get_proof_at` serves proofs against it. Return false if its nodes are already
+	/// removed.
+	pub fn retain(&mut self, root_hash: &[u8]) -> bool {
+		match self.retained.retain(root_hash, &self.root_hash) {
+			Some(removed_keys) => {
+				self.db.remove_batch(&removed_keys);
+				true
+			}
+			None => false,
+		}
+	}
+
+	/// Like `get_proof`, against the retained root or the last committed root `root_hash`.
+	pub fn get_proof_at(&self, root_hash: &[u8], key: &[u8]) -> TrieResult<Proof> {
+		if !self.retained.is_retained(root_hash) && root_hash != &self.root_hash[..] {
+			return Err(TrieError::InvalidStateRoot);
+		}
+		let root = self.recover_from_db(root_hash)?;
+		let mut path = self.get_path_at(root.clone(), &Nibbles::from_raw(key.to_vec(), true))?;
+		match root {
+			Node::Empty => {}
+			_ => path.push(root),
+		}
+		Ok(Proof {
+			nodes: path.into_iter().rev().map(|n| self.encode_raw(n)).collect(),
+		})
+	}
+
 	pub fn commit(&mut self) -> TrieResult<Vec<u8>> {
 		let encoded = self.encode_node(self.root.clone());
 		let root_hash = if encoded.len() < LENGTH {
@@ -494,6 +528,13 @@ impl DiskMerklePatriciaTrie {
 			.map(|h| h.to_vec())
 			.collect();
 
+		// the nodes of the retained root stay in the db
+		let generated = self.gen_keys.lock().unwrap().iter().cloned().collect();
+		let removed_keys = self.retained.push(RetainedCommit {
+			root_hash: root_hash.clone(),
+			removed: removed_keys,
+			generated,
+		});
 		self.db.remove_batch(&removed_keys);
 
 		self.root_hash = root_hash.to_vec();
@@ -30,6 +30,7 @@ mod tests;
 mod mpt_store;
 mod mem_trie;
 mod mmpt_store;
+mod retain;
 
 pub use crate::db::MemoryDB;
 pub use crate::diskdb::DiskDB;
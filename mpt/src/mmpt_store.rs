@@ -57,6 +57,13 @@ impl MMPTStore {
                     }
                     let _ = sender.send(Ok(acc_proof));
                   }
+                  StoreCommand::Retain(root, sender) => {
+                    let _ = sender.send(state_trie.retain(&root));
+                  }
+                  StoreCommand::GetProofAt(root, key, sender) => {
+                    let response = state_trie.get_proof_at(&root, &key);
+                    let _ = sender.send(response);
+                  }
                   StoreCommand::RemoveBatch(accs, sender) => {
                     let mut removed_acc_nums = 0;
                     for addr in accs {
@@ -177,5 +184,24 @@ impl MPTStoreTrait for MMPTStore {
     .await
     .expect("Failed to receive reply to Read command from store")
   }
-}
 
+  async fn retain(&mut self, root: RootHash) -> bool {
+    let (sender, receiver) = oneshot::channel();
+    if let Err(e) = self.channel.send(StoreCommand::Retain(root, sender)).await {
+        panic!("Failed to send Retain command to store: {}", e);
+    }
+    receiver
+    .await
+    .expect("Failed to receive reply to Retain command from store")
+  }
+
+  async fn get_proof_at(&mut self, root: RootHash, key: &[u8]) -> TrieResult<Proof> {
+    let (sender, receiver) = oneshot::channel();
+    if let Err(e) = self.channel.send(StoreCommand::GetProofAt(root, key.to_vec(), sender)).await {
+        panic!("Failed to send Read command to store: {}", e);
+    }
+    receiver
+    .await
+    .expect("Failed to receive reply to Read command from store")
+  }
+}
@@ -16,6 +16,8 @@ pub enum StoreCommand {
     Root(oneshot::Sender<TrieResult<RootHash>>),
     GetProof(Key, oneshot::Sender<TrieResult<Proof>>),
     GetProofBatch(Vec<Key>, oneshot::Sender<TrieResult<HashMap<Key, Proof>>>),
+    Retain(RootHash, oneshot::Sender<bool>),
+    GetProofAt(RootHash, Key, oneshot::Sender<TrieResult<Proof>>),
     RemoveBatch(Vec<Key>, oneshot::Sender<TrieResult<i32>>),
     InsertBatch(HashMap<Key, Value>, oneshot::Sender<TrieResult<i32>>),
 }
@@ -69,6 +71,13 @@ impl MPTStore {
                     }
                     let _ = sender.send(Ok(acc_proof));
                   }
+                  StoreCommand::Retain(root, sender) => {
+                    let _ = sender.send(state_trie.retain(&root));
+                  }
+                  StoreCommand::GetProofAt(root, key, sender) => {
+                    let response = state_trie.get_proof_at(&root, &key);
+                    let _ = sender.send(response);
+                  }
                   StoreCommand::RemoveBatch(accs, sender) => {
                     let mut removed_acc_nums = 0;
                     for addr in accs {
@@ -127,6 +136,13 @@ pub trait MPTStoreTrait {
 	async fn get_proof(&mut self, key: &[u8]) -> TrieResult<Proof>;
 
   async fn get_proof_batch(&mut self, accs: Vec<Key>) -> TrieResult<HashMap<Key, Proof>>;
+
+	/// Keeps the nodes of the committed root until another root is retained, so that proofs
+	/// against it are served after later commits. Returns false if its nodes are already removed.
+	async fn retain(&mut self, root: RootHash) -> bool;
+
+	/// Like `get_proof`, against the retained root or the last committed root.
+	async fn get_proof_at(&mut self, root: RootHash, key: &[u8]) -> TrieResult<Proof>;
 }
 
 
@@ -224,5 +240,24 @@ impl MPTStoreTrait for MPTStore {
     .await
     .expect("Failed to receive reply to Read command from store")
   }
-}
 
+  async fn retain(&mut self, root: RootHash) -> bool {
+    let (sender, receiver) = oneshot::channel();
+    if let Err(e) = self.channel.send(StoreCommand::Retain(root, sender)).await {
+        panic!("Failed to send Retain command to store: {}", e);
+    }
+    receiver
+    .await
+    .expect("Failed to receive reply to Retain command from store")
+  }
+
+  async fn get_proof_at(&mut self, root: RootHash, key: &[u8]) -> TrieResult<Proof> {
+    let (sender, receiver) = oneshot::channel();
+    if let Err(e) = self.channel.send(StoreCommand::GetProofAt(root, key.to_vec(), sender)).await {
+        panic!("Failed to send Read command to store: {}", e);
+    }
+    receiver
+    .await
+    .expect("Failed to receive reply to Read command from store")
+  }
+}
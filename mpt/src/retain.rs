@@ -0,0 +1,69 @@
+use std::collections::{HashSet, VecDeque};
+
+/// The commits kept while a root is retained: past this bound the oldest commit is released and
+/// the retained root is given up.
+pub const MAX_RETAINED_COMMITS: usize = 100;
+
+/// A commit of a trie after its retained root.
+#[derive(Debug)]
+pub struct RetainedCommit {
+	pub root_hash: Vec<u8>,
+	/// The nodes the commit removed from the trie, kept in the db while the root is retained.
+	pub removed: Vec<Vec<u8>>,
+	/// The nodes the commit generated: a node removed by an older commit and generated again is
+	/// live, so it is not removed from the db.
+	pub generated: HashSet<Vec<u8>>,
+}
+
+/// Keeps the nodes of a committed root of a trie in its db, so that proofs against that root can
+/// be served after later commits.
+#[derive(Debug, Default)]
+pub struct Retained {
+	root_hash: Option<Vec<u8>>,
+	commits: VecDeque<RetainedCommit>,
+}
+
+impl Retained {
+	pub fn is_retained(&self, root_hash: &[u8]) -> bool {
+		self.root_hash.as_deref() == Some(root_hash)
+	}
+
+	/// Record a commit. Return the nodes to remove from the db now.
+	pub fn push(&mut self, commit: RetainedCommit) -> Vec<Vec<u8>> {
+		self.commits.push_back(commit);
+		if self.commits.len() <= MAX_RETAINED_COMMITS {
+			return Vec::new();
+		}
+		// the nodes of the retained root are removed
+		self.root_hash = None;
+		self.release(1)
+	}
+
+	/// Retain the committed root `root_hash` instead of the previous one, `current` being the last
+	/// committed root. Return the nodes to remove from the db, or None if the nodes of the root are
+	/// already removed.
+	pub fn retain(&mut self, root_hash: &[u8], current: &[u8]) -> Option<Vec<Vec<u8>>> {
+		if let Some(i) = self.commits.iter().rposition(|commit| commit.root_hash == root_hash) {
+			self.root_hash = Some(root_hash.to_vec());
+			return Some(self.release(i + 1));
+		}
+		if self.is_retained(root_hash) || (self.commits.is_empty() && root_hash == current) {
+			self.root_hash = Some(root_hash.to_vec());
+			return Some(Vec::new());
+		}
+		None
+	}
+
+	// forget the first `n` commits: return the nodes they removed that no later commit generated
+	fn release(&mut self, n: usize) -> Vec<Vec<u8>> {
+		let released: Vec<RetainedCommit> = self.commits.drain(..n).collect();
+		let mut generated: HashSet<&Vec<u8>> =
+			self.commits.iter().flat_map(|commit| commit.generated.iter()).collect();
+		let mut removed = Vec::new();
+		for commit in released.iter().rev() {
+			removed.extend(commit.removed.iter().filter(|key| !generated.contains(key)).cloned());
+			generated.extend(commit.generated.iter());
+		}
+		removed
+	}
+}
@@ -685,6 +685,36 @@ mod trie_tests {
 		assert_eq!(value, Some(b"v".to_vec()));
 	}
 
+	#[test]
+	fn test_proof_at_retained_root() {
+		use crate::mem_trie::MemMerklePatriciaTrie;
+		use std::sync::Arc;
+
+		let mut trie = MemMerklePatriciaTrie::new(Arc::new(MemoryDB::new()));
+		for i in 0..100u8 {
+			trie.insert(vec![i; 20], vec![i]).unwrap();
+		}
+		let retained = trie.root().unwrap();
+		assert!(trie.retain(&retained));
+
+		// the later commits keep the nodes of the retained root
+		for i in 0..50u8 {
+			trie.insert(vec![i; 20], vec![i, i]).unwrap();
+			trie.root().unwrap();
+		}
+		let proof = trie.get_proof_at(&retained, &[7; 20]).unwrap();
+		let value = MerklePatriciaTrie::verify_proof(retained.clone(), &[7; 20], proof).unwrap();
+		assert_eq!(value, Some(vec![7]));
+
+		// until a later root is retained
+		let root = trie.root().unwrap();
+		assert!(trie.retain(&root));
+		assert!(trie.get_proof_at(&retained, &[7; 20]).is_err());
+		let proof = trie.get_proof_at(&root, &[7; 20]).unwrap();
+		let value = MerklePatriciaTrie::verify_proof(root, &[7; 20], proof).unwrap();
+		assert_eq!(value, Some(vec![7, 7]));
+	}
+
 	#[test]
 	fn test_ethereum_receipts_proof() {
 		let rlp_proof: Vec<u8> = Vec::from_hex("f9016ef9016bb853f851a009b67a67265063da0dd6a7abad695edb2c439f6b458f2a2ee48a21442fef8a2680808080808080a0a7d4f8b974d21b7244014729b07e9c9f19fdc445da2ceddc089d90cead74be618080808080808080b90113f9011031b9010cf9010901835cdb6eb9010000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000c0").unwrap();
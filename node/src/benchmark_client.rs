@@ -2,6 +2,7 @@ use anyhow::{Context, Result};
 use clap::{crate_name, crate_version, App, AppSettings};
 use config::{Committees, Import};
 use log::info;
+use metrics::MetricsExporter;
 use worker::{Account2ShardType, ExecutorType};
 use env_logger::Env;
 use std::convert::TryFrom;
@@ -24,10 +25,13 @@ async fn main() -> Result<()> {
         .args_from_usage("--workload=<PATH> 'The path where to load workload'")
         .args_from_usage("--acc2shard=<PATH> 'The path where to load acc2shard map'")
         .args_from_usage("--brokers=<PATH> 'The path where to load brokers'")
+        .args_from_usage("--keys_seed=<INT> 'The seed of the keys of the accounts'")
         .args_from_usage("--totaltxs=<INT> 'total txs'")
         .args_from_usage("--client_addr=<ADDR> 'client addr used to listen for messages sent by nodes'")
         .args_from_usage("--committee=<FILE> 'The file containing committee information'")
         .args_from_usage("--epoch=<INT> 'The current epoch'")
+        .args_from_usage("--last_epoch=[INT] 'The last epoch, whose workload is sent after that of the previous epochs'")
+        .args_from_usage("--metrics=[ADDR] 'The address where to serve the Prometheus metrics'")
         .setting(AppSettings::ArgRequiredElseHelp)
         .get_matches();
 
@@ -64,6 +68,11 @@ async fn main() -> Result<()> {
     let brokers_file = matches
         .value_of("brokers")
         .unwrap();      
+    let keys_seed = matches
+        .value_of("keys_seed")
+        .unwrap()
+        .parse::<u64>()
+        .context("The seed of the keys must be a non-negative integer")?;
     let totaltxs = matches
         .value_of("totaltxs")
         .unwrap()
@@ -74,6 +83,13 @@ async fn main() -> Result<()> {
       .unwrap()
       .parse::<usize>()
       .context("The size of transactions must be a non-negative integer")?;
+    // the workload and acc2shard files may contain `{}`, which is replaced by the epoch number
+    let last_epoch = match matches.value_of("last_epoch") {
+      Some(last_epoch) => last_epoch
+        .parse::<usize>()
+        .context("The last epoch must be a non-negative integer")?,
+      None => epoch,
+    };
 
     let client_addr = matches
         .value_of("client_addr")
@@ -97,9 +113,14 @@ async fn main() -> Result<()> {
     let committees = Committees::import(committee_file).context("Failed to load the committees information")?;
     let shard_num = committees.shard_num;
     let shard_size = committees.shard_size;
+    // serve the metrics of the client, if requested
+    if let Some(address) = matches.value_of("metrics") {
+        let address = address.parse::<SocketAddr>().context("Invalid metrics address")?;
+        MetricsExporter::spawn(address).await;
+    }
 
     // 记录重要参数和配置日志
-    info!("Epoch: {:?}, total txs: {:?}", epoch, totaltxs);
+    info!("Epoch: {:?}, last epoch: {:?}, total txs: {:?}", epoch, last_epoch, totaltxs);
     info!("workload file: {:?}", workload_file);
     info!("acc2shard file: {:?}", acc2shard_file);
     info!("brokers file: {:?}", brokers_file);
@@ -126,12 +147,14 @@ async fn main() -> Result<()> {
           rate,
           totaltxs,
           SEND_TX_DURATION_MS,
+          keys_seed,
           client_addr,
           committees,
           epoch,         
+          last_epoch,
         ).await
       }
-      ExecutorType::SharDAG | ExecutorType::Monoxide => { // SharDAG or Monoxide
+      ExecutorType::SharDAG | ExecutorType::Monoxide | ExecutorType::TwoPhaseCommit => { // SharDAG, Monoxide or 2PC
         CommonClientMultiTxSenderPerNode::spawn(
           shard_num,
           shard_size,
@@ -141,7 +164,11 @@ async fn main() -> Result<()> {
           acc_shard_type,
           rate,
           totaltxs,
-          SEND_TX_DURATION_MS
+          SEND_TX_DURATION_MS,
+          keys_seed,
+          committees,
+          epoch,
+          last_epoch,
         ).await
       }
     }
@@ -8,9 +8,12 @@ use config::Export as _;
 use config::Import as _;
 use config::{Committees, KeyPair, Parameters, WorkerId, ShardId};
 use consensus::Consensus;
+use crypto::generate_threshold_keys;
 use env_logger::Env;
 use log::info;
+use metrics::MetricsExporter;
 use primary::{Certificate, Primary};
+use rand::rngs::OsRng;
 use store::Store;
 use tokio::sync::mpsc::{channel, Receiver};
 use worker::Account2Shard;
@@ -19,8 +22,14 @@ use worker::Account2ShardHash;
 use worker::Account2ShardType;
 use worker::AppendType;
 use worker::ExecutorType;
+use worker::{EpochSchedule, NextAcc2Shard, epoch_file};
 use worker::StateStoreType;
 use worker::new_primary_store;
+use worker::{load_key_registry, AccountKeyItem};
+use client::account_keypair;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::sync::Arc;
 use worker::Worker;
 
 // 导入自定义模块
@@ -45,6 +54,21 @@ async fn main() -> Result<()> {
                 .about("Print a fresh key pair to file")
                 .args_from_usage("--filename=<FILE> 'The file where to print the new key pair'"),
         )
+        .subcommand(
+            // 为一个分片的所有节点分发阈值签名密钥份额
+            SubCommand::with_name("deal_threshold_keys")
+                .about("Deal threshold key shares to the key files of a shard")
+                .args_from_usage("--keys=<FILE>... 'The key files of the nodes of the shard'")
+                .args_from_usage("--filename=<FILE> 'The file where to print the threshold public key set'"),
+        )
+        .subcommand(
+            // 为账户生成密钥，公钥写入创世密钥注册表
+            SubCommand::with_name("generate_account_keys")
+                .about("Print the genesis key registry of the accounts, whose keys the clients derive from the seed")
+                .args_from_usage("--accounts=<FILE>... 'The csv files listing the accounts'")
+                .args_from_usage("--seed=<INT> 'The seed of the account keys'")
+                .args_from_usage("--filename=<FILE> 'The file where to print the key registry'"),
+        )
         .subcommand(
             // 启动一个节点，分为 primary 和 worker 两种模式
             SubCommand::with_name("run")
@@ -54,6 +78,7 @@ async fn main() -> Result<()> {
                 .args_from_usage("--parameters=[FILE] 'The file containing the node parameters'")
                 .args_from_usage("--store=<PATH> 'The path where to create the data store'")
                 .args_from_usage("--shardid=<INT> 'The shard id'")
+                .args_from_usage("--metrics=[ADDR] 'The address where to serve the Prometheus metrics'")
                 .subcommand(SubCommand::with_name("primary").about("Run a single primary"))
                 .subcommand(
                     SubCommand::with_name("worker")
@@ -64,11 +89,14 @@ async fn main() -> Result<()> {
                         .args_from_usage("--acc2shard=[FILE] 'The file containing the acc2shard map'")
                         .args_from_usage("--actacc2shard=[FILE] 'The file containing the actacc2shard map'")
                         .args_from_usage("--ftstore=<PATH> 'The path where to create the full t store'")
+                        .args_from_usage("--account_keys=<FILE> 'The file containing the genesis key registry of the accounts'")
                         .args_from_usage("--state_store_type=<INT> 'state_store_type'")
                         .args_from_usage("--executor_type=<INT> 'executor_type'")
                         .args_from_usage("--acc_shard_type=<INT> 'acc_shard_type'")
                         .args_from_usage("--append_type=<INT> 'append_type'")
                         .args_from_usage("--epoch=<INT> 'The current epoch'")
+                        .args_from_usage("--epoch_length=[INT] 'The number of committed heights of an epoch'")
+                        .args_from_usage("--last_epoch=[INT] 'The last epoch (requires --epoch_length)'")
                 )
                 .setting(AppSettings::SubcommandRequiredElseHelp),
         )
@@ -95,6 +123,10 @@ async fn main() -> Result<()> {
         ("generate_keys", Some(sub_matches)) => KeyPair::new()
             .export(sub_matches.value_of("filename").unwrap())
             .context("Failed to generate key pair")?,
+        // 生成阈值密钥，将份额写回各节点的密钥文件
+        ("deal_threshold_keys", Some(sub_matches)) => deal_threshold_keys(sub_matches)?,
+        // 生成账户的创世密钥注册表
+        ("generate_account_keys", Some(sub_matches)) => generate_account_keys(sub_matches)?,
         // 调用 run 函数，启动节点逻辑
         ("run", Some(sub_matches)) => run(sub_matches).await?,
         _ => unreachable!(),
@@ -102,6 +134,63 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+// Deals the threshold key shares of a shard: f+1 of the n shares are needed to sign.
+fn deal_threshold_keys(matches: &ArgMatches<'_>) -> Result<()> {
+    let key_files: Vec<_> = matches.values_of("keys").unwrap().collect();
+    let filename = matches.value_of("filename").unwrap();
+
+    let total = key_files.len();
+    let threshold = (total + 2) / 3;
+    let (key_set, shares) = generate_threshold_keys(threshold, total, &mut OsRng);
+    for (key_file, share) in key_files.iter().zip(shares) {
+        let mut keypair = KeyPair::import(key_file).context("Failed to load the node's keypair")?;
+        keypair.threshold_share = Some(share);
+        keypair
+            .export(key_file)
+            .context("Failed to write the threshold key share")?;
+    }
+    key_set
+        .export(filename)
+        .context("Failed to write the threshold public key set")?;
+    Ok(())
+}
+
+// Prints the public keys of the accounts listed in the csv files (`account` column), derived from
+// the seed like the keys the clients sign with.
+fn generate_account_keys(matches: &ArgMatches<'_>) -> Result<()> {
+    let seed = matches.value_of("seed").unwrap()
+        .parse::<u64>()
+        .context("The seed must be a positive integer")?;
+    let filename = matches.value_of("filename").unwrap();
+
+    let mut accounts = BTreeSet::new();
+    for accounts_file in matches.values_of("accounts").unwrap() {
+        let mut reader = csv::Reader::from_path(accounts_file)
+            .with_context(|| format!("Failed to read accounts from {}", accounts_file))?;
+        for item in reader.deserialize::<AccountItem>() {
+            let item = item.context("Invalid account item")?;
+            let addr = hex::decode(&item.account[2..]).context("Invalid account address")?;
+            accounts.insert(addr);
+        }
+    }
+
+    let mut writer = csv::Writer::from_path(filename).context("Failed to create the key registry")?;
+    for addr in &accounts {
+        let (key, _) = account_keypair(seed, addr);
+        writer.serialize(AccountKeyItem { account: format!("0x{}", hex::encode(addr)), key: key.encode_base64() })
+            .context("Failed to write the key registry")?;
+    }
+    writer.flush().context("Failed to write the key registry")?;
+    info!("Registered the keys of {} accounts", accounts.len());
+    Ok(())
+}
+
+// an account listed in a csv file
+#[derive(Deserialize)]
+struct AccountItem {
+    account: String,
+}
+
 // run 函数细节
 // Runs either a worker or a primary.
 async fn run(matches: &ArgMatches<'_>) -> Result<()> {
@@ -136,6 +225,12 @@ async fn run(matches: &ArgMatches<'_>) -> Result<()> {
         None => Parameters::default(),
     };
 
+    // Serve the metrics of the node, if requested.
+    if let Some(address) = matches.value_of("metrics") {
+        let address = address.parse().context("Invalid metrics address")?;
+        MetricsExporter::spawn(address).await;
+    }
+
     // Make the data store.
     let store = Store::new(store_path).context("Failed to create a store")?;
 
@@ -153,7 +248,7 @@ async fn run(matches: &ArgMatches<'_>) -> Result<()> {
                 keypair,
                 our_committee.clone(),
                 parameters.clone(),
-                store,
+                store.clone(),
                 /* tx_consensus */ tx_new_certificates,
                 /* rx_consensus */ rx_feedback,
             );
@@ -163,6 +258,7 @@ async fn run(matches: &ArgMatches<'_>) -> Result<()> {
                 /* rx_primary */ rx_new_certificates,
                 /* tx_primary */ tx_feedback,
                 tx_output,
+                store,
             );
         }
 
@@ -203,9 +299,15 @@ async fn run(matches: &ArgMatches<'_>) -> Result<()> {
 
             let full_store_path = sub_matches.value_of("ftstore").unwrap();
 
-            let acc2shard_file = sub_matches.value_of("acc2shard").unwrap();// csv
+            // the accounts get their keys from the genesis key registry
+            let keys_file = sub_matches.value_of("account_keys").unwrap();
+            let keys = Arc::new(load_key_registry(keys_file));
+
+            // the files may contain `{}`, which is replaced by the epoch number
+            let acc2shard_pattern = sub_matches.value_of("acc2shard").unwrap();// csv
+            let acc2shard_file = epoch_file(acc2shard_pattern, epoch as u64);
             info!("acc2shard_file: {:?}", acc2shard_file);
-            let actacc2shard_file = sub_matches.value_of("actacc2shard").unwrap();// csv
+            let actacc2shard_file = epoch_file(sub_matches.value_of("actacc2shard").unwrap(), epoch as u64);// csv
             info!("actacc2shard_file: {:?}", actacc2shard_file);
 
             // load test method
@@ -255,14 +357,40 @@ async fn run(matches: &ArgMatches<'_>) -> Result<()> {
               },
               Account2ShardType::GraphPolicy => {
                 acc2shard = Box::new(Account2ShardGraph::new(shard_num, &acc2shard_file));
+              },
+              // the online policy starts from the hash policy and repartitions at each epoch
+              Account2ShardType::OnlinePolicy => {
+                acc2shard = Box::new(Account2ShardHash::new(shard_num));
               }
             }
+            // the acc2shard map of the next epoch is loaded (or computed) every epoch_length heights,
+            // and accounts migrate accordingly
+            let mut epoch_schedule = None;
+            if let Some(epoch_length) = sub_matches.value_of("epoch_length") {
+              let epoch_length = epoch_length
+                  .parse::<u64>()
+                  .context("epoch_length must be a positive integer")?;
+              let last_epoch = sub_matches
+                  .value_of("last_epoch")
+                  .context("--last_epoch is required with --epoch_length")?
+                  .parse::<u64>()
+                  .context("last_epoch must be a positive integer")?;
+              let next_acc2shard = match acc_shard_type {
+                Account2ShardType::HashPolicy => NextAcc2Shard::Unchanged,
+                Account2ShardType::GraphPolicy => NextAcc2Shard::File(acc2shard_pattern.to_string()),
+                Account2ShardType::OnlinePolicy => NextAcc2Shard::Online,
+              };
+              let schedule = EpochSchedule::new(epoch as u64, last_epoch, epoch_length, next_acc2shard);
+              info!("Epoch schedule: {:?}", schedule);
+              epoch_schedule = Some(schedule);
+            }
+
             // initialize local state store
             let primary_store = new_primary_store(
-              shard_id, acc2shard_file, actacc2shard_file, &acc2shard, state_store_type, full_store_path,
+              shard_id, &acc2shard_file, &actacc2shard_file, &acc2shard, state_store_type, full_store_path, &keys,
             ).await;
       
-            Worker::spawn(executor_type, append_type, keypair.name,keypair.secret, id, cs_faults, is_malicious, our_committee, parameters, store, shard_id, committees, primary_store, acc2shard);
+            Worker::spawn(executor_type, append_type, keypair.name, keypair.secret, keypair.threshold_share, id, cs_faults, is_malicious, our_committee, parameters, store, shard_id, committees, primary_store, keys, acc2shard, epoch as u64, epoch_schedule);
         }
         _ => unreachable!(),
     } 
@@ -3,7 +3,8 @@ use config::ShardId;
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 use std::fs::{File, self};
-use worker::{StateStoreType, TStore, MStore, StateStore, Account2Shard, Account2ShardGraph, RawTxOld, RWSet, Frame, StateTransition};
+use worker::{StateStoreType, TStore, MStore, StateStore, Account2Shard, Account2ShardGraph, RawTxOld, RWSet, Frame, StateTransition, KeyRegistry};
+use std::sync::Arc;
 use csv::DeserializeRecordsIter;
 use tokio::time::Instant;
 
@@ -75,20 +76,23 @@ async fn main(){
   // 使用 `Account2ShardGraph` 初始化账户到分片映射。
   let acc2shard: Box<dyn Account2Shard + Send> = Box::new(Account2ShardGraph::new(shard_num, &acc2shard_file));
 
+  // the txs are applied without authorizing their senders, no key is registered
+  let keys = Arc::new(KeyRegistry::new());
+
   // create state store
   let store: Box<dyn StateStore + Send>;
   match state_store_type {
     StateStoreType::MStore => {
       println!("initialize MStore");
-      store = Box::new(MStore::new(shard_id, &acc2shard_file, &acc2shard, full_t_path).await);
+      store = Box::new(MStore::new(shard_id, &acc2shard_file, &acc2shard, full_t_path, &keys).await);
     }
     StateStoreType::TStore => {
       println!("initialize TStore");
-      store = Box::new(TStore::new(shard_id, &acc2shard_file, &actacc2shard_file, &acc2shard, full_t_path).await);
+      store = Box::new(TStore::new(shard_id, &acc2shard_file, &actacc2shard_file, &acc2shard, full_t_path, &keys).await);
     }
   }
   // 创建 StateTransition 对象，用于处理状态转换
-  let mut state_transition = StateTransition::new(store);
+  let mut state_transition = StateTransition::new(store, keys);
 
   // execute txs
   // 读取负载文件，准备执行事务
@@ -124,13 +128,15 @@ async fn main(){
       let mut pass_check = true;
       for rwset in &frame.rwset {
         let acc = latest_states.get_mut(&rwset.addr).unwrap();
-        acc.balance += rwset.value;
-        if rwset.value < 0.0 { // 扣款操作
-          if acc.balance < 0.0 { // the balance is negative after execution
+        match acc.balance.apply_delta(rwset.value) {
+          Ok(balance) => acc.balance = balance,
+          Err(_) => { // the balance is negative or overflows after execution
             pass_check = false;
             break;
           }
-          acc.nonce +=1;          
+        }
+        if rwset.value.is_negative() { // 扣款操作
+          acc.nonce +=1;
         }
       }
       if pass_check { // 如果校验通过，应用新状态
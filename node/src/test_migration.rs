@@ -129,7 +129,7 @@ pub async fn _initial_store_test(
 
   // value
   // 定义账户的初始值
-  let acc = Account { nonce: 0, balance: INIT_BALANCE};
+  let acc = Account { nonce: 0, balance: INIT_BALANCE, key: None };
   let serialized = bincode::serialize(&acc).expect("Failed to serialize account");
 
   // 初始化 MPT 存储
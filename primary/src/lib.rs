@@ -8,6 +8,7 @@ mod garbage_collector;
 mod header_waiter;
 mod helper;
 mod messages;
+mod metrics;
 mod payload_receiver;
 mod primary;
 mod proposer;
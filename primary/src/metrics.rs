@@ -0,0 +1,24 @@
+use ::metrics::{
+    lazy_static, register_histogram, register_int_counter, register_int_gauge, Histogram, IntCounter,
+    IntGauge, SIZE_BUCKETS,
+};
+
+// The metrics of the primary, exported by `metrics::MetricsExporter`.
+lazy_static! {
+    pub static ref HEADERS_CREATED: IntCounter = register_int_counter!(
+        "primary_headers_created_total",
+        "Headers created by the proposer"
+    )
+    .unwrap();
+    pub static ref HEADER_BATCHES: Histogram = register_histogram!(
+        "primary_header_batches",
+        "Batch digests included in the headers created by the proposer",
+        SIZE_BUCKETS.to_vec()
+    )
+    .unwrap();
+    pub static ref PROPOSER_ROUND: IntGauge = register_int_gauge!(
+        "primary_proposer_round",
+        "The round of the last header created by the proposer"
+    )
+    .unwrap();
+}
@@ -1,5 +1,6 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
 use crate::messages::{Certificate, Header};
+use crate::metrics;
 use crate::primary::Round;
 use config::{Committee, WorkerId};
 use crypto::Hash as _;
@@ -92,7 +93,10 @@ impl Proposer {
         )
         .await;
         debug!("Created {:?}", header);
-        
+        metrics::HEADERS_CREATED.inc();
+        metrics::HEADER_BATCHES.observe(header.payload.len() as f64);
+        metrics::PROPOSER_ROUND.set(header.round as i64);
+
         #[cfg(feature = "benchmark")]
         info!("Package {} batches into new header", header.payload.len());
 
@@ -49,6 +49,7 @@ pub fn committee() -> Committee {
                         transactions: format!("127.0.0.1:{}", 400 + i).parse().unwrap(),
                         worker_to_worker: format!("127.0.0.1:{}", 500 + i).parse().unwrap(),
                         cross_shard_worker: format!("127.0.0.1:{}", 600 + i).parse().unwrap(),
+                        query: format!("127.0.0.1:{}", 700 + i).parse().unwrap(),
                     },
                 )]
                 .iter()
@@ -17,6 +17,7 @@ pub enum StoreCommand {
     Write(Key, Value), // 写入键值对
     Read(Key, oneshot::Sender<StoreResult<Option<Value>>>), // 读取键对应的值
     NotifyRead(Key, oneshot::Sender<StoreResult<Value>>), // 通知读取键对应的值
+    Delete(Key), // 删除键值对
 }
 
 #[derive(Clone)]
@@ -40,6 +41,9 @@ impl Store {
                             }
                         }
                     }
+                    StoreCommand::Delete(key) => {
+                        let _ = db.delete(&key);
+                    }
                     StoreCommand::Read(key, sender) => {
                         let response = db.get(&key);
                         let _ = sender.send(response);
@@ -68,6 +72,12 @@ impl Store {
         }
     }
 
+    pub async fn delete(&mut self, key: Key) {
+        if let Err(e) = self.channel.send(StoreCommand::Delete(key)).await {
+            panic!("Failed to send Delete command to store: {}", e);
+        }
+    }
+
     pub async fn read(&mut self, key: Key) -> StoreResult<Option<Value>> {
         let (sender, receiver) = oneshot::channel();
         if let Err(e) = self.channel.send(StoreCommand::Read(key, sender)).await {
@@ -33,6 +33,24 @@ async fn read_write_value() {
     assert_eq!(read_value.unwrap(), value); // 验证读取值和写入值相等
 }
 
+#[tokio::test]
+async fn delete_value() {
+    // Create new store.
+    let path = ".db_test_delete_value";
+    let _ = fs::remove_dir_all(path);
+    let mut store = Store::new(path).unwrap();
+
+    // Write a value, then delete it.
+    let key = vec![0u8, 1u8, 2u8, 3u8];
+    store.write(key.clone(), vec![4u8, 5u8, 6u8, 7u8]).await;
+    store.delete(key.clone()).await;
+
+    // The value is gone.
+    let result = store.read(key).await;
+    assert!(result.is_ok());
+    assert!(result.unwrap().is_none());
+}
+
 #[tokio::test]
 async fn read_unknown_key() {
     // Create new store.
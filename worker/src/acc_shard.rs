@@ -15,6 +15,7 @@ use crate::{Address};
 pub enum Account2ShardType {
   HashPolicy, // 哈希分片策略
   GraphPolicy, // 图分片策略
+  OnlinePolicy, // 在线图划分策略：根据已提交交易构建的账户交互图，每个 epoch 重新划分
 }
 
 // 账户到分片映射的结构定义
@@ -31,10 +32,21 @@ pub struct ActAccToShardItem {
   pub shard: ShardId, // 分片ID
 }
 
+// the key registered for an account at genesis
+#[derive(Default, Clone, Serialize, Deserialize, Debug)]
+pub struct AccountKeyItem {
+  pub account: String, // 账户地址
+  pub key: String, // base64 public key
+}
+
 // 分片策略接口定义
 pub trait Account2Shard {
   fn get_shard_num(&self) -> ShardId; // 获取分片总数
   fn get_shard(&self, addr: &Address) -> ShardId; // 根据账户地址获取分片ID
+  // 显式映射的账户，其余账户按哈希策略分片
+  fn mapped_accounts(&self) -> Vec<Address> {
+    Vec::new()
+  }
 }
 
 
@@ -104,6 +116,27 @@ impl Account2ShardGraph {
         acc2shard
       }
     }
+
+    pub fn from_map(shard_num: ShardId, acc2shard: HashMap<Address, ShardId>) -> Self {
+      Self{
+        shard_num,
+        acc2shard
+      }
+    }
+
+    // the mapping `current` once the given accounts moved to their new shard
+    pub fn with_moves(current: &dyn Account2Shard, moves: impl IntoIterator<Item = (Address, ShardId)>) -> Self {
+      let mut acc2shard: HashMap<Address, ShardId> = current
+        .mapped_accounts()
+        .into_iter()
+        .map(|acc| {
+          let shard = current.get_shard(&acc);
+          (acc, shard)
+        })
+        .collect();
+      acc2shard.extend(moves);
+      Self::from_map(current.get_shard_num(), acc2shard)
+    }
 }
 
 
@@ -124,4 +157,8 @@ impl Account2Shard for Account2ShardGraph {
       }
     }
   }
+
+  fn mapped_accounts(&self) -> Vec<Address> {
+    self.acc2shard.keys().cloned().collect()
+  }
 }
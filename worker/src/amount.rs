@@ -0,0 +1,110 @@
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use thiserror::Error;
+
+#[cfg(test)]
+#[path = "tests/amount_tests.rs"]
+pub mod amount_tests;
+
+/// The reason why a balance update is rejected.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AmountError {
+  #[error("balance overflow")]
+  Overflow,
+
+  #[error("insufficient balance")]
+  Underflow,
+}
+
+/// An amount of wei. The workloads replay Ethereum transfers whose values do not fit exactly in a
+/// f64, and the balances must not depend on the evaluation order, so amounts are integers.
+/// Negative amounts are the deductions of the rwsets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount(i128);
+
+impl Amount {
+  pub const ZERO: Amount = Amount(0);
+
+  pub const fn new(wei: i128) -> Self {
+    Self(wei)
+  }
+
+  pub fn wei(&self) -> i128 {
+    self.0
+  }
+
+  pub fn is_negative(&self) -> bool {
+    self.0 < 0
+  }
+
+  pub fn checked_add(self, other: Amount) -> Option<Amount> {
+    self.0.checked_add(other.0).map(Amount)
+  }
+
+  pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+    self.0.checked_sub(other.0).map(Amount)
+  }
+
+  /// The opposite amount, e.g. the deduction of a credit: `i128::MIN` has none.
+  pub fn checked_neg(self) -> Result<Amount, AmountError> {
+    self.0.checked_neg().map(Amount).ok_or(AmountError::Overflow)
+  }
+
+  /// Apply the delta of a rwset to a balance, which may never become negative.
+  pub fn apply_delta(self, delta: Amount) -> Result<Amount, AmountError> {
+    match self.checked_add(delta) {
+      None if delta.is_negative() => Err(AmountError::Underflow),
+      None => Err(AmountError::Overflow),
+      Some(balance) if balance.is_negative() => Err(AmountError::Underflow),
+      Some(balance) => Ok(balance),
+    }
+  }
+
+  /// The canonical encoding of the amount in the digests.
+  pub fn to_le_bytes(&self) -> [u8; 16] {
+    self.0.to_le_bytes()
+  }
+}
+
+impl fmt::Display for Amount {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+// human-readable formats (the csv workloads, json) carry the decimal string, which may not fit
+// in the integers of the format; binary formats carry the i128
+impl Serialize for Amount {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+      serializer.serialize_str(&self.0.to_string())
+    } else {
+      serializer.serialize_i128(self.0)
+    }
+  }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    struct DecimalVisitor;
+
+    impl<'de> Visitor<'de> for DecimalVisitor {
+      type Value = Amount;
+
+      fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a decimal amount of wei")
+      }
+
+      fn visit_str<E: de::Error>(self, value: &str) -> Result<Amount, E> {
+        value.trim().parse::<i128>().map(Amount).map_err(E::custom)
+      }
+    }
+
+    if deserializer.is_human_readable() {
+      deserializer.deserialize_str(DecimalVisitor)
+    } else {
+      i128::deserialize(deserializer).map(Amount)
+    }
+  }
+}
@@ -1,14 +1,15 @@
 use crate::quorum_waiter::QuorumWaiterMessage;
 use crate::worker::WorkerMessage;
 use crate::messages::GeneralTransaction;
+use crate::metrics;
 use bytes::Bytes;
 // #[cfg(feature = "benchmark")]
 use crypto::Digest;
-use crypto::PublicKey;
+use crypto::{PublicKey, Signature};
 // #[cfg(feature = "benchmark")]
 use ed25519_dalek::{Digest as _, Sha512};
 // #[cfg(feature = "benchmark")]
-use log::info;
+use log::{info, warn};
 use network::ReliableSender;
 // #[cfg(feature = "benchmark")]
 use std::convert::TryInto as _;
@@ -100,6 +101,7 @@ impl BatchMaker {
                     self.current_batch.push(transaction);
 
                     if self.current_batch_size >= self.batch_size {
+                        metrics::BATCHES_SEALED.with_label_values(&["full"]).inc();
                         self.seal().await;
                         timer.as_mut().reset(Instant::now() + Duration::from_millis(self.max_batch_delay));
                     }
@@ -109,6 +111,7 @@ impl BatchMaker {
                 () = &mut timer => {
                     if !self.current_batch.is_empty() {
                         info!("time out, create new batch");
+                        metrics::BATCHES_SEALED.with_label_values(&["timeout"]).inc();
                         self.seal().await;
                     }
                     timer.as_mut().reset(Instant::now() + Duration::from_millis(self.max_batch_delay));
@@ -124,15 +127,27 @@ impl BatchMaker {
     async fn seal(&mut self) {
 
         // Serialize the batch.
-        let packaged_batch_external_txs = self.current_batch_external_txs;
-        let packaged_batch_size = self.current_batch_size;
+        let mut packaged_batch_external_txs = self.current_batch_external_txs;
+        let mut packaged_batch_size = self.current_batch_size;
         self.current_batch_txs = 0;
         self.current_batch_size = 0;
         self.current_batch_external_txs = 0;
 
 
         // assemble batch
-        let batch_tx: Vec<_> = self.current_batch.drain(..).collect();
+        let mut batch_tx: Vec<_> = self.current_batch.drain(..).collect();
+
+        // drop the txs with an invalid client signature
+        let received_txs = batch_tx.len();
+        batch_tx = verify_client_signatures(batch_tx);
+        if batch_tx.len() != received_txs {
+            warn!("Drop {} txs with an invalid signature", received_txs - batch_tx.len());
+            packaged_batch_external_txs = batch_tx.iter().map(|tx| tx.count_packaged_external_tx() as usize).sum();
+            packaged_batch_size = batch_tx.iter().map(|tx| tx.len()).sum();
+            if batch_tx.is_empty() {
+                return;
+            }
+        }
 
         // Look for sample txs (they all start with 0) and gather their txs id (the next 8 bytes)
         // #[cfg(feature = "benchmark")]
@@ -142,6 +157,9 @@ impl BatchMaker {
             .map(|tx| tx.clone())
             .collect();
 
+        metrics::BATCH_SIZE_BYTES.observe(packaged_batch_size as f64);
+        metrics::BATCH_EXTERNAL_TXS.observe(packaged_batch_external_txs as f64);
+
         // Serialize the batch.
         let batch = Batch{external_tx_nums: packaged_batch_external_txs, tx_list: batch_tx};      
 
@@ -188,3 +206,18 @@ impl BatchMaker {
             .expect("Failed to deliver batch");
     }
 }
+
+/// Verify the client signatures of the txs in one batch verification. If it fails, find the
+/// culprits one by one and drop them.
+pub fn verify_client_signatures(txs: GeneralTxList) -> GeneralTxList {
+    let signatures: Vec<_> = txs.iter().filter_map(|tx| tx.client_signature()).collect();
+    if signatures.is_empty() || Signature::verify_batch_digests(&signatures).is_ok() {
+        return txs;
+    }
+    txs.into_iter()
+        .filter(|tx| match tx.client_signature() {
+            Some((digest, public_key, signature)) => signature.verify(&digest, &public_key).is_ok(),
+            None => true,
+        })
+        .collect()
+}
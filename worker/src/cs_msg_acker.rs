@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use config::{ShardId, NodeId};
+use network::{ReliableSender, CancelHandler};
+use crypto::{PublicKey, SignatureService};
+use std::net::SocketAddr;
+use tokio::time::{interval, Duration};
+use log::{info, debug};
+use bytes::Bytes;
+use crate::csmsg_store::CSMsgStore;
+use crate::messages::{CSMsgAck, CrossShardMessage};
+
+
+/// Periodically acknowledges to every node of each source shard the csmsgs executed by our shard,
+/// so that the source shard stops retransmitting them.
+pub struct CSMsgAcker {
+  shard_id: ShardId,
+  shard_num: usize,
+  name: PublicKey,
+  signature_service: SignatureService,
+
+  all_id_pubkey_map: Arc<HashMap<(ShardId, NodeId), (PublicKey, SocketAddr)>>,
+  csmsg_store: CSMsgStore,
+  ack_interval: u64,
+
+  /// A network sender to send the acknowledgements to the other shards.
+  network: ReliableSender,
+  /// The last acknowledged sequence number of each source shard.
+  acked: Vec<u64>,
+  /// Keeps the cancel handlers of the last acknowledgement sent to each source shard; acknowledgements
+  /// are cumulative, so a new one supersedes the previous one.
+  cancel_handlers: HashMap<ShardId, Vec<CancelHandler>>,
+}
+
+
+impl CSMsgAcker {
+  #[allow(clippy::too_many_arguments)]
+  pub fn spawn(
+    shard_id: ShardId,
+    shard_num: usize,
+    name: PublicKey,
+    signature_service: SignatureService,
+    all_id_pubkey_map: Arc<HashMap<(ShardId, NodeId), (PublicKey, SocketAddr)>>,
+    csmsg_store: CSMsgStore,
+    ack_interval: u64,
+  ) {
+      tokio::spawn(async move {
+        Self {
+          shard_id,
+          shard_num,
+          name,
+          signature_service,
+          all_id_pubkey_map,
+          csmsg_store,
+          ack_interval,
+          network: ReliableSender::new(),
+          acked: vec![0; shard_num],
+          cancel_handlers: HashMap::new(),
+        }
+        .run()
+        .await;
+    });
+  }
+
+  /// Main loop acknowledging the csmsgs executed since the last tick.
+  async fn run(&mut self) {
+    info!("CSMsgAcker is running!");
+
+    let mut timer = interval(Duration::from_millis(self.ack_interval));
+    loop {
+      timer.tick().await;
+      for source in 0..self.shard_num {
+        if source == self.shard_id {
+          continue;
+        }
+        let next_seq = self.csmsg_store.low_watermark(source).await.unwrap();
+        if next_seq > self.acked[source] {
+          self.acked[source] = next_seq;
+          self.send_ack(source, next_seq).await;
+        }
+      }
+    }
+  }
+
+  async fn send_ack(&mut self, source: ShardId, next_seq: u64) {
+    let ack = CSMsgAck::new(source, self.shard_id, next_seq, &self.name, &mut self.signature_service).await;
+    let addresses: Vec<SocketAddr> = self.all_id_pubkey_map
+      .iter()
+      .filter(|((shard_id, _), _)| *shard_id == source)
+      .map(|(_, (_, addr))| *addr)
+      .collect();
+
+    debug!("Send {} to: {:?}", ack, addresses);
+    let message = CrossShardMessage::CSMsgAck(ack);
+    let bytes = bincode::serialize(&message).expect("Failed to serialize our ack");
+    let handlers = self.network.broadcast(addresses, Bytes::from(bytes)).await;
+    self.cancel_handlers.insert(source, handlers);
+  }
+}
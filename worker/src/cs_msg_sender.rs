@@ -1,16 +1,96 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
-use config::{ShardId, NodeId};
-use network::{ReliableSender,CancelHandler};
+use config::{Committees, ShardId, NodeId};
+use network::ReliableSender;
 use crypto::{PublicKey, SignatureService};
+use std::convert::TryInto;
 use std::net::SocketAddr;
+use store::Store;
 use tokio::sync::mpsc::Receiver;
-use log::{info, debug};
+use tokio::time::{sleep, Duration, Instant};
+use log::{info, debug, warn};
 use crate::utils::shuffle_node_id_list;
 use bytes::Bytes;
-use crate::messages::Height;
 use crate::worker::SendCSMessage;
-use crate::messages::CSMsg;
+use crate::messages::{CSMsg, CSMsgAck, CrossShardMessage};
+use crate::outstanding::OutstandingMessages;
+
+#[cfg(test)]
+#[path = "tests/cs_msg_sender_tests.rs"]
+pub mod cs_msg_sender_tests;
+
+/// The maximum number of csmsgs retransmitted to a target shard at each timeout.
+const MAX_RETRANSMISSIONS: usize = 1_000;
+
+fn next_seq_key(target_shard: ShardId) -> Vec<u8> {
+  format!("csmsg_next_seq_{}", target_shard).into_bytes()
+}
+
+fn acked_key(target_shard: ShardId) -> Vec<u8> {
+  format!("csmsg_acked_{}", target_shard).into_bytes()
+}
+
+fn sent_key(target_shard: ShardId, seq: u64) -> Vec<u8> {
+  format!("csmsg_sent_{}_{}", target_shard, seq).into_bytes()
+}
+
+
+/// The persisted csmsgs sent to each target shard: the next sequence number, the acknowledged one
+/// and our copies of the csmsgs not acknowledged yet. A restarted node neither reuses the sequence
+/// numbers the target shards already executed nor forgets to retransmit the pending csmsgs.
+#[derive(Clone)]
+pub struct SentCSMsgs {
+  store: Store,
+}
+
+impl SentCSMsgs {
+  pub fn new(store: Store) -> Self {
+    Self { store }
+  }
+
+  async fn read_seq(&mut self, key: Vec<u8>) -> u64 {
+    self.store
+      .read(key)
+      .await
+      .expect("Failed to read csmsg sequence")
+      .map(|bytes| u64::from_le_bytes(bytes[..].try_into().expect("Corrupted csmsg sequence")))
+      .unwrap_or(0)
+  }
+
+  /// The next sequence number, the acknowledged one and the pending csmsgs of a target shard.
+  pub async fn recover(&mut self, target_shard: ShardId) -> (u64, u64, BTreeMap<u64, Bytes>) {
+    let next_seq = self.read_seq(next_seq_key(target_shard)).await;
+    let acked = self.read_seq(acked_key(target_shard)).await;
+    let mut pending = BTreeMap::new();
+    for seq in acked..next_seq {
+      if let Some(bytes) = self.store.read(sent_key(target_shard, seq)).await.expect("Failed to read sent csmsg") {
+        pending.insert(seq, Bytes::from(bytes));
+      }
+    }
+    (next_seq, acked, pending)
+  }
+
+  /// Persist a csmsg before it is sent.
+  pub async fn sent(&mut self, target_shard: ShardId, seq: u64, bytes: &Bytes) {
+    self.store.write(sent_key(target_shard, seq), bytes.to_vec()).await;
+    self.store.write(next_seq_key(target_shard), (seq + 1).to_le_bytes().to_vec()).await;
+  }
+
+  /// Persist an acknowledgement and delete the csmsgs it covers.
+  pub async fn acked(&mut self, target_shard: ShardId, seqs: Vec<u64>, acked: u64) {
+    self.store.write(acked_key(target_shard), acked.to_le_bytes().to_vec()).await;
+    for seq in seqs {
+      self.store.delete(sent_key(target_shard, seq)).await;
+    }
+  }
+}
+
+
+/// Our copy of a csmsg the target shard did not acknowledge yet.
+struct PendingCSMsg {
+  bytes: Bytes,
+  sent_at: Instant,
+}
 
 
 pub struct SendCSMsg {
@@ -23,18 +103,30 @@ pub struct SendCSMsg {
   name: PublicKey,
   signature_service: SignatureService,
 
+  all_committees: Committees,
   all_id_pubkey_map: Arc<HashMap<(ShardId, NodeId), (PublicKey, SocketAddr)>>,
   cs_sender_nums: usize,
   cs_rev_nums: usize, // number of cross-shard receivers 
+  retry_delay: u64,
 
   rx_process_txs: Receiver<SendCSMessage>,
+  rx_ack: Receiver<CSMsgAck>,
   /// A network sender to broadcast the batches to the other workers.
   network: ReliableSender,
-
-  /// Keeps the cancel handlers of the messages we sent.
-  cancel_handlers: HashMap<Height, Vec<CancelHandler>>,
+  sent: SentCSMsgs,
 
   cs_msg_id: Vec<u64>,
+
+  /// The csmsgs of each target shard not acknowledged yet, by sequence number. Every node keeps
+  /// its copy, whether it is a sender or not, to retransmit it after a timeout.
+  pending: Vec<BTreeMap<u64, PendingCSMsg>>,
+  /// The highest acknowledgement received from each node of each target shard.
+  acks: Vec<HashMap<PublicKey, u64>>,
+  /// The sequence number acknowledged by f+1 nodes of each target shard.
+  acked: Vec<u64>,
+  /// The cancel handlers of the csmsgs sent to each target shard, by sequence number. They are
+  /// dropped with the acknowledged csmsgs, which stops their transmissions.
+  outstanding: Vec<OutstandingMessages>,
 }
 
 
@@ -51,15 +143,19 @@ impl SendCSMsg {
     name: PublicKey,
     signature_service: SignatureService,
   
+    all_committees: Committees,
     all_id_pubkey_map: Arc<HashMap<(ShardId, NodeId), (PublicKey, SocketAddr)>>,
     cs_sender_nums: usize,
     cs_rev_nums: usize,
+    retry_delay: u64,
 
     rx_process_txs: Receiver<SendCSMessage>,
+    rx_ack: Receiver<CSMsgAck>,
+    store: Store,
   ) {
       
       tokio::spawn(async move {
-        Self {
+        let mut sender = Self {
           shard_id,
           node_id,
           is_malicious,
@@ -67,20 +163,44 @@ impl SendCSMsg {
           shard_size,
           name,
           signature_service,
+          all_committees,
           all_id_pubkey_map,
           cs_sender_nums,
           cs_rev_nums,
+          retry_delay,
           rx_process_txs,
+          rx_ack,
           network: ReliableSender::new(),
-          cancel_handlers: HashMap::with_capacity(2 * 50 as usize),// TODO 
+          sent: SentCSMsgs::new(store),
           cs_msg_id: vec![0; shard_num as usize],
-        }
-        .run()
-        .await;
+          pending: (0..shard_num).map(|_| BTreeMap::new()).collect(),
+          acks: vec![HashMap::new(); shard_num],
+          acked: vec![0; shard_num],
+          outstanding: (0..shard_num).map(|_| OutstandingMessages::new("csmsg_sender", 0)).collect(),
+        };
+        sender.recover().await;
+        sender.run().await;
     });
 
   }
 
+  // resume the sequence numbers of the target shards, the pending csmsgs are retransmitted at the
+  // first timeout
+  async fn recover(&mut self) {
+    for target_shard in 0..self.cs_msg_id.len() {
+      let (next_seq, acked, pending) = self.sent.recover(target_shard).await;
+      if !pending.is_empty() {
+        info!("recovered {} csmsgs to shard {} not acknowledged before the crash", pending.len(), target_shard);
+      }
+      self.cs_msg_id[target_shard] = next_seq;
+      self.acked[target_shard] = acked;
+      self.pending[target_shard] = pending
+        .into_iter()
+        .map(|(seq, bytes)| (seq, PendingCSMsg { bytes, sent_at: Instant::now() }))
+        .collect();
+    }
+  }
+
   /// Main loop listening to the messages.
   async fn run(&mut self) {
 
@@ -92,12 +212,36 @@ impl SendCSMsg {
     );      
 
     
-    while let Some(SendCSMessage{height, target_shard, tx }) = self.rx_process_txs.recv().await {
-      debug!("receiving csmsg to shard {}: {:?}", target_shard, tx);
+    let timer = sleep(Duration::from_millis(self.retry_delay));
+    tokio::pin!(timer);
+
+    loop {
+      tokio::select! {
+        Some(message) = self.rx_process_txs.recv() => self.send_csmsg(message).await,
+        Some(ack) = self.rx_ack.recv() => {
+          match ack.verify(&self.all_committees) {
+            Ok(()) => self.process_ack(ack).await,
+            Err(e) => warn!("Invalid csmsg ack {}: {}", ack, e),
+          }
+        },
+        () = &mut timer => {
+          self.retransmit().await;
+          for (target_shard, outstanding) in self.outstanding.iter_mut().enumerate() {
+            outstanding.cleanup(self.acked[target_shard]);
+          }
+          timer.as_mut().reset(Instant::now() + Duration::from_millis(self.retry_delay));
+        },
+      }
+    }
+  }
+
+  async fn send_csmsg(&mut self, message: SendCSMessage) {
+      let SendCSMessage{height, target_shard, epoch, tx } = message;
+      debug!("[height: {}] receiving csmsg to shard {}: {:?}", height, target_shard, tx);
 
       // malicious cs node does not process csmsg
       if self.is_malicious {
-        continue;
+        return;
       }
 
       // get csmsg_seq
@@ -106,7 +250,7 @@ impl SendCSMsg {
       *cs_msg_id += 1;
 
       // generate CSMsg
-      let mut csmsg = CSMsg::new(self.shard_id, target_shard,csmsg_seq, tx, &self.name, &mut self.signature_service).await;
+      let mut csmsg = CSMsg::new(self.shard_id, target_shard, csmsg_seq, epoch, tx, &self.name, &mut self.signature_service).await;
       csmsg.set_sig(&mut self.signature_service).await;
       
 
@@ -118,9 +262,11 @@ impl SendCSMsg {
       debug!("sender_ids: {:?}", sender_ids);
       debug!("receiver_ids: {:?}", receiver_ids);
 
+      let digest = csmsg.get_digest();
+      let message = CrossShardMessage::CSMsg(csmsg);
+      let bytes = Bytes::from(bincode::serialize(&message).expect("Failed to serialize our vote"));
+      self.sent.sent(target_shard, csmsg_seq, &bytes).await;
       if sender_ids.contains(&(self.node_id as usize)) { // this node is a sender
-        let bytes = bincode::serialize(&csmsg).expect("Failed to serialize our vote");
-  
         let mut addresses: Vec<SocketAddr> = Vec::new();
         for recv_id in receiver_ids {
           let addr = self.all_id_pubkey_map.get(&(target_shard, *recv_id as u32)).unwrap().1;
@@ -129,14 +275,69 @@ impl SendCSMsg {
   
         debug!(
           "Send cross_shard msg {:?} to: [nodeid: {:?}]{:?}",
-            csmsg.get_digest(), receiver_ids, addresses,
+            digest, receiver_ids, addresses,
         );
-        let handlers = self.network.broadcast(addresses, Bytes::from(bytes.clone())).await;
-        self.cancel_handlers
-          .entry(height)
-          .or_insert_with(Vec::new)
-          .extend(handlers);
+        let handlers = self.network.broadcast(addresses.clone(), bytes.clone()).await;
+        self.outstanding[target_shard].extend(csmsg_seq, addresses, handlers);
       }
+      self.pending[target_shard].insert(csmsg_seq, PendingCSMsg { bytes, sent_at: Instant::now() });
+  }
+
+  // an ack is certified once f+1 nodes of the target shard acknowledged at least as many csmsgs;
+  // the csmsgs it covers are discarded, which cancels their transmissions
+  async fn process_ack(&mut self, ack: CSMsgAck) {
+    if ack.source_shard != self.shard_id || ack.target_shard >= self.acks.len() {
+      return;
+    }
+    let target = ack.target_shard;
+    let next_seq = self.acks[target].entry(ack.author).or_insert(0);
+    *next_seq = (*next_seq).max(ack.next_seq);
+
+    let threshold = match self.all_committees.our_committee(&target) {
+      Ok(committee) => committee.validity_threshold() as usize,
+      Err(_) => return,
+    };
+    let mut next_seqs: Vec<u64> = self.acks[target].values().cloned().collect();
+    if next_seqs.len() < threshold {
+      return;
+    }
+    next_seqs.sort_unstable_by(|a, b| b.cmp(a));
+    let certified = next_seqs[threshold - 1];
+    if certified > self.acked[target] {
+      debug!("shard {} acknowledged csmsgs up to {}", target, certified);
+      self.acked[target] = certified;
+      let pending = self.pending[target].split_off(&certified);
+      let acknowledged = std::mem::replace(&mut self.pending[target], pending);
+      self.sent.acked(target, acknowledged.into_keys().collect(), certified).await;
+      self.outstanding[target].cleanup(certified);
     }
   }
-}
\ No newline at end of file
+
+  // the oldest csmsgs not acknowledged in time are sent by every node to every node of the
+  // target shard, in case the senders or the receivers failed
+  async fn retransmit(&mut self) {
+    let retry_delay = Duration::from_millis(self.retry_delay);
+    for target_shard in 0..self.pending.len() {
+      let addresses: Vec<SocketAddr> = self.all_id_pubkey_map
+        .iter()
+        .filter(|((shard_id, _), _)| *shard_id == target_shard)
+        .map(|(_, (_, addr))| *addr)
+        .collect();
+      let expired: Vec<u64> = self.pending[target_shard]
+        .iter()
+        .filter(|(_, pending)| pending.sent_at.elapsed() >= retry_delay)
+        .map(|(seq, _)| *seq)
+        .take(MAX_RETRANSMISSIONS)
+        .collect();
+      if !expired.is_empty() {
+        warn!("retransmit {} csmsgs to shard {}, the oldest is #{}", expired.len(), target_shard, expired[0]);
+      }
+      for seq in expired {
+        let bytes = self.pending[target_shard][&seq].bytes.clone();
+        let handlers = self.network.broadcast(addresses.clone(), bytes).await;
+        self.outstanding[target_shard].replace(seq, addresses.clone(), handlers);
+        self.pending[target_shard].get_mut(&seq).unwrap().sent_at = Instant::now();
+      }
+    }
+  }
+}
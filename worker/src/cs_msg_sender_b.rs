@@ -1,6 +1,5 @@
-use std::collections::HashMap;
 use config::{ShardId, NodeId};
-use network::{ReliableSender,CancelHandler};
+use network::ReliableSender;
 use crypto::{PublicKey, SignatureService};
 use std::net::SocketAddr;
 use tokio::sync::mpsc::Receiver;
@@ -10,6 +9,7 @@ use bytes::Bytes;
 use crate::messages::Height;
 use crate::worker::SendCSMessage;
 use crate::messages::CSMsg;
+use crate::outstanding::OutstandingMessages;
 
 // 用于处理跨分片消息，并将其发送到 broker 客户端
 pub struct Send2Broker {
@@ -30,8 +30,11 @@ pub struct Send2Broker {
   /// A network sender to broadcast the batches to the other workers.
   network: ReliableSender,
 
-  /// Keeps the cancel handlers of the messages we sent.
-  cancel_handlers: HashMap<Height, Vec<CancelHandler>>,
+  /// Keeps the cancel handlers of the messages we sent, until the client acknowledges them or
+  /// they are `gc_depth` heights old.
+  outstanding: OutstandingMessages,
+  /// The height of the last message we sent.
+  height: Height,
 
   tx1_id: u64, // the id of tx1 sent to broker client
 }
@@ -54,6 +57,7 @@ impl Send2Broker {
     cs_sender_nums: usize,
     cs_rev_nums: usize,
     client: SocketAddr, // addr of broker client
+    gc_depth: u64,
 
     rx_process_txs: Receiver<SendCSMessage>,
   ) {
@@ -72,7 +76,8 @@ impl Send2Broker {
           cs_rev_nums,
           rx_process_txs,
           network: ReliableSender::new(),
-          cancel_handlers: HashMap::with_capacity(2 * 50 as usize),// TODO 
+          outstanding: OutstandingMessages::new("send2broker", gc_depth),
+          height: 0,
           tx1_id: 0,
         }
         .run()
@@ -99,7 +104,7 @@ impl Send2Broker {
 
 
     
-    while let Some(SendCSMessage{height, target_shard, tx }) = self.rx_process_txs.recv().await {
+    while let Some(SendCSMessage{height, target_shard, epoch, tx }) = self.rx_process_txs.recv().await {
       debug!("receiving csmsg to shard {}: {:?}", target_shard, tx);
 
       // malicious cs node does not process csmsg
@@ -108,10 +113,17 @@ impl Send2Broker {
         continue;
       }   
 
+      // the messages of a new height trigger the cleanup of the older ones
+      // 新高度的消息触发旧消息的清理
+      if height > self.height {
+        self.height = height;
+        self.outstanding.cleanup(height);
+      }
+
 
       // generate CSMsg
       // 生成跨分片消息
-      let mut csmsg = CSMsg::new(self.shard_id, target_shard,self.tx1_id, tx, &self.name, &mut self.signature_service).await;
+      let mut csmsg = CSMsg::new(self.shard_id, target_shard, self.tx1_id, epoch, tx, &self.name, &mut self.signature_service).await;
       csmsg.set_sig(&mut self.signature_service).await;
 
 
@@ -139,10 +151,7 @@ impl Send2Broker {
         );
 
         let handler = self.network.send(self.client, Bytes::from(bytes.clone())).await;
-        self.cancel_handlers
-          .entry(height)
-          .or_insert_with(Vec::new)
-          .push(handler);
+        self.outstanding.insert(height, self.client, handler);
       }
     }
   }
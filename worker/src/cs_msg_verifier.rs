@@ -1,12 +1,13 @@
 use std::{collections::HashMap, sync::Arc};
-use config::{Stake, Committees, NodeId, ShardId};
+use config::{Committees, NodeId, ShardId};
 use crypto::{assemble_intact_ts_partial, PublicKey, verify_ts_sig, Digest};
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::time::{self, Instant};
 use crate::csmsg_store::{CSMsgStore, AppendedType};
 use crate::utils::shuffle_node_id_list;
-use crate::messages::{GeneralTransaction, CSMsg};
-use log::{info, debug};
+use crate::messages::{GeneralTransaction, CSMsg, CSMsgId};
+use crate::metrics;
+use log::{info, debug, warn};
 use num_enum::TryFromPrimitive;
 
 pub const TIMER_RESOLUTION: u64 = 2500; // ms
@@ -27,7 +28,6 @@ pub struct CSMsgVerifier {
     tx_batch_maker: Sender<GeneralTransaction>,
     all_committees: Committees,
 
-    vote_threshold: Stake,
     shard_size: usize,
 
     nodeid: NodeId,
@@ -44,7 +44,6 @@ impl CSMsgVerifier {
       rx_cross_shard_msg: Receiver<CSMsg>,
       tx_batch_maker: Sender<GeneralTransaction>,
       all_committees: Committees,
-      vote_threshold: Stake,
       shard_size: usize,
       nodeid: NodeId,
       is_malicious: bool,
@@ -56,7 +55,6 @@ impl CSMsgVerifier {
           rx_cross_shard_msg,
           tx_batch_maker,
           all_committees,
-          vote_threshold,// f+1
           shard_size,
           nodeid,
           is_malicious,
@@ -80,17 +78,18 @@ impl CSMsgVerifier {
           "Receiving CSMsg: {:?}, csmsg_seq: {:?}",
           cs_msg, cs_msg.csmsg_sequence,
         );
+        metrics::CSMSG_QUEUE_DEPTH.set(self.rx_cross_shard_msg.len() as i64);
         // malicious cs node does not process csmsg
         if self.is_malicious {
           continue;
         }
 
-        if cs_msg.verify(&self.all_committees) {
-          self.process_msg(cs_msg).await;
-        } else {
-          debug!(
-            "threshold signature verification failed",
-          );
+        let begin = Instant::now();
+        let verified = cs_msg.verify(&self.all_committees);
+        metrics::CSMSG_VERIFY_LATENCY.observe(begin.elapsed().as_secs_f64() * 1000.0);
+        match verified {
+          Ok(()) => self.process_msg(cs_msg).await,
+          Err(e) => warn!("Invalid csmsg {}: {}", cs_msg, e),
         }
       }
     }
@@ -101,23 +100,32 @@ impl CSMsgVerifier {
       csmsg: CSMsg,
     ) {
         // get the msgId of the csmsg
-        let msg_id = format!("[{}-{}]", csmsg.source_shard, csmsg.csmsg_sequence);
+        let msg_id = csmsg.id();
         debug!("process csmsg: {}", msg_id);
 
+        // get the threshold public key set of the source shard
+        let key_set = match self.all_committees.threshold_keys(&csmsg.source_shard) {
+          Ok(key_set) => key_set,
+          Err(e) => {
+            warn!("{}", e);
+            return;
+          }
+        };
+
         // try to add this csmsg sig to csmsg_store
-        let (is_added, thres_sig_list) = self.csmsg_store.add_csmsg_sig(msg_id.clone(), csmsg.thres_sig).await.unwrap();
+        let (is_added, thres_sig_list) = self.csmsg_store.add_csmsg_sig(msg_id, csmsg.thres_sig).await.unwrap();
         debug!("res of add_csmsg_sig: {:?}, {:?}", is_added, thres_sig_list);
         if is_added {
           if thres_sig_list.len() != 0 { // reach vote_threshold, 
               // assemble intact ts partial and verify ts
               let inner_tx_hash = csmsg.inner_tx_hash;
-              let intact_sig = assemble_intact_ts_partial(thres_sig_list, PublicKey::default(), &inner_tx_hash, self.vote_threshold, self.shard_size);
+              let intact_sig = assemble_intact_ts_partial(thres_sig_list, key_set, &inner_tx_hash).unwrap_or_default();
 
-              if verify_ts_sig(PublicKey::default(), &inner_tx_hash, &intact_sig) {
+              if verify_ts_sig(&key_set.public_key, &inner_tx_hash, &intact_sig) {
                 // pass verification
                 let mut inner_tx = csmsg.tx;
                 // update intact_sig of inner_tx
-                inner_tx.set_thres_sig(intact_sig, csmsg.source_shard);
+                inner_tx.set_thres_sig(intact_sig, msg_id);
 
                 // 追加消息到 DAG
                 let msg_num = self.sampled_csmsg_num;
@@ -126,13 +134,16 @@ impl CSMsgVerifier {
                 let csmsg_store = self.csmsg_store.clone();
                 let nodeid = self.nodeid;
                 let shard_size = self.shard_size;
+                metrics::CSMSG_PENDING_APPENDS.inc();
                 tokio::spawn(async move {
                   let begin = Instant::now();
                   append_msg(
                     tx_batch_maker_pes, csmsg_store, nodeid, shard_size,
-                    msg_id.clone(), inner_tx_hash, inner_tx
+                    msg_id, inner_tx_hash, inner_tx
                   ).await;
                   let append_dur = begin.elapsed().as_millis();
+                  metrics::CSMSG_PENDING_APPENDS.dec();
+                  metrics::CSMSG_APPEND_DELAY.observe(append_dur as f64);
                   if msg_num % SAMPLE_CSMSG_DUR == 0 {
                     info!("{}, csmsg {} append delay: {:?} ms", msg_num, msg_id, append_dur);
                   }                  
@@ -153,7 +164,7 @@ async fn append_msg(
   mut csmsg_store: CSMsgStore,
   nodeid: u32, 
   shard_size: usize,
-  msg_id: String,
+  msg_id: CSMsgId,
   inner_tx_hash: Digest,
   inner_tx: GeneralTransaction,
 ) {
@@ -177,15 +188,14 @@ async fn append_msg(
         .expect("Failed to send cs msg");
     } // check if this csmsg has been appended again. if appended, do not pack it locally. 
   } else { // pes appending, wait timeout
-      let msg_id_clone = msg_id.clone();
       let res = time::timeout(time::Duration::from_millis(TIMER_RESOLUTION), async {
-        let is_appended = csmsg_store.notify_appended(msg_id_clone.clone()).await.unwrap();
+        let is_appended = csmsg_store.notify_appended(msg_id).await.unwrap();
         is_appended 
       });
       match res.await {
           Err(_) => { // pes appending
-            debug!("opt appending for csmsg: [{}] failed", msg_id_clone);
-            let updated = csmsg_store.update_appended(msg_id_clone, AppendedType::Local).await.unwrap();
+            debug!("opt appending for csmsg: {} failed", msg_id);
+            let updated = csmsg_store.update_appended(msg_id, AppendedType::Local).await.unwrap();
             if updated { // update csmsg status from validated to appended successfully, 
               debug!("pes appending!");
               tx_batch_maker
@@ -195,7 +205,7 @@ async fn append_msg(
             } // check if this csmsg has been appended again. if appended, do not pack it locally.
           },
           Ok(_) => { // this csmsg has been appended before timeout
-            debug!(" csmsg: [{}] has been appended before timeout", msg_id_clone);
+            debug!(" csmsg: {} has been appended before timeout", msg_id);
           }
       };
   }
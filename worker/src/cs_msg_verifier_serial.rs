@@ -6,8 +6,9 @@ use tokio::time;
 use crate::cs_msg_verifier::{TIMER_RESOLUTION, SAMPLE_CSMSG_DUR};
 use crate::csmsg_store::{CSMsgStore, AppendedType};
 use crate::utils::shuffle_node_id_list;
-use crate::messages::{GeneralTransaction, CSMsg};
-use log::{info, debug};
+use crate::messages::{GeneralTransaction, CSMsg, CSMsgId};
+use crate::metrics;
+use log::{info, debug, warn};
 use tokio::time::Instant;
 
 
@@ -69,17 +70,18 @@ impl CSMsgVerifierSerial {
           "Receiving CSMsg: {:?}, csmsg_seq: {:?}",
           cs_msg, cs_msg.csmsg_sequence,
         );
+        metrics::CSMSG_QUEUE_DEPTH.set(self.rx_cross_shard_msg.len() as i64);
         // malicious cs node does not process csmsg
         if self.is_malicious {
           continue;
         }
 
-        if cs_msg.verify(&self.all_committees) {
-          self.process_msg(cs_msg).await;
-        } else {
-          debug!(
-            "threshold signature verification failed",
-          );
+        let begin = Instant::now();
+        let verified = cs_msg.verify(&self.all_committees);
+        metrics::CSMSG_VERIFY_LATENCY.observe(begin.elapsed().as_secs_f64() * 1000.0);
+        match verified {
+          Ok(()) => self.process_msg(cs_msg).await,
+          Err(e) => warn!("Invalid csmsg {}: {}", cs_msg, e),
         }
       }
     }
@@ -89,23 +91,32 @@ impl CSMsgVerifierSerial {
       csmsg: CSMsg,
     ) {
         // get the msgId of the csmsg
-        let msg_id = format!("[{}-{}]", csmsg.source_shard, csmsg.csmsg_sequence);
+        let msg_id = csmsg.id();
         debug!("process csmsg: {}", msg_id);
 
+        // get the threshold public key set of the source shard
+        let key_set = match self.all_committees.threshold_keys(&csmsg.source_shard) {
+          Ok(key_set) => key_set,
+          Err(e) => {
+            warn!("{}", e);
+            return;
+          }
+        };
+
         // try to add this csmsg sig to csmsg_store
-        let (is_added, thres_sig_list) = self.csmsg_store.add_csmsg_sig(msg_id.clone(), csmsg.thres_sig).await.unwrap();
+        let (is_added, thres_sig_list) = self.csmsg_store.add_csmsg_sig(msg_id, csmsg.thres_sig).await.unwrap();
         debug!("res of add_csmsg_sig: {:?}, {:?}", is_added, thres_sig_list);
         if is_added {
           if thres_sig_list.len() != 0 { // reach vote_threshold, 
               // assemble intact ts partial and verify ts
               let inner_tx_hash = csmsg.inner_tx_hash;
-              let intact_sig = assemble_intact_ts_partial(thres_sig_list, PublicKey::default(), &inner_tx_hash, self.vote_threshold, self.shard_size);
+              let intact_sig = assemble_intact_ts_partial(thres_sig_list, key_set, &inner_tx_hash).unwrap_or_default();
 
-              if verify_ts_sig(PublicKey::default(), &inner_tx_hash, &intact_sig) {
+              if verify_ts_sig(&key_set.public_key, &inner_tx_hash, &intact_sig) {
                 // pass verification
                 let mut inner_tx = csmsg.tx;
                 // update intact_sig of inner_tx
-                inner_tx.set_thres_sig(intact_sig, csmsg.source_shard);
+                inner_tx.set_thres_sig(intact_sig, msg_id);
 
                 // append csmsg to DAG ledger
                 let msg_num = self.sampled_csmsg_num;
@@ -115,12 +126,15 @@ impl CSMsgVerifierSerial {
                 let nodeid = self.nodeid;
                 let shard_size = self.shard_size;
                 let vote_threshold = self.vote_threshold;
+                metrics::CSMSG_PENDING_APPENDS.inc();
                 tokio::spawn(async move {
                   let begin = Instant::now();
                   append_msg(
                     tx_batch_maker_pes, csmsg_store, nodeid, shard_size, vote_threshold,
-                    msg_id.clone(), inner_tx_hash, inner_tx).await;
+                    msg_id, inner_tx_hash, inner_tx).await;
                   let append_dur = begin.elapsed().as_millis();
+                  metrics::CSMSG_PENDING_APPENDS.dec();
+                  metrics::CSMSG_APPEND_DELAY.observe(append_dur as f64);
                   if msg_num % SAMPLE_CSMSG_DUR == 0 {
                     info!("{}, csmsg {} append delay: {:?} ms", msg_num, msg_id, append_dur);
                   }
@@ -142,7 +156,7 @@ async fn append_msg(
   nodeid: u32, 
   shard_size: usize,
   vote_threshold: Stake,
-  msg_id: String,
+  msg_id: CSMsgId,
   inner_tx_hash: Digest,
   inner_tx: GeneralTransaction,
 ) {
@@ -157,7 +171,7 @@ async fn append_msg(
 
     if nodeid == leader as u32 {
       // update csmsg status
-      let updated = csmsg_store.update_appended(msg_id.clone(), AppendedType::Local).await.unwrap();
+      let updated = csmsg_store.update_appended(msg_id, AppendedType::Local).await.unwrap();
       if updated { // update csmsg status from validated to appended successfully, 
         debug!("the-{} csmsg packager, pack this csmsg!", i);
         // send GeneralTransaction to BatchMaker
@@ -168,17 +182,16 @@ async fn append_msg(
         break;
       } // check if this csmsg has been appended again. if appended, do not pack it locally.
     } else { // waiting timeout
-      let msg_id_clone = msg_id.clone();
       let res = time::timeout(time::Duration::from_millis(TIMER_RESOLUTION), async {
-        let is_appended = csmsg_store.notify_appended(msg_id_clone.clone()).await.unwrap();
+        let is_appended = csmsg_store.notify_appended(msg_id).await.unwrap();
         is_appended 
       });
       match res.await {
           Err(_) => {// timeout
-            debug!("csmsg: {} timeout", msg_id_clone);
+            debug!("csmsg: {} timeout", msg_id);
           },
           Ok(_) => { // this csmsg has been appended before timeout
-            debug!("csmsg: {} has been appended before timeout", msg_id_clone);
+            debug!("csmsg: {} has been appended before timeout", msg_id);
             break;
           }
       };
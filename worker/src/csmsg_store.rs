@@ -1,28 +1,108 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeSet, HashMap, VecDeque};
 use std::fmt;
-use config::Stake;
-use crypto::Signature;
-use log::debug;
-use store::StoreError;
+use config::{ShardId, Stake};
+use crypto::PartialSignature;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use store::{Store, StoreError};
+use crate::messages::CSMsgId;
 use tokio::sync::mpsc::{channel, Sender};
 use tokio::sync::oneshot;
 
+#[cfg(test)]
+#[path = "tests/csmsg_store_tests.rs"]
+pub mod csmsg_store_tests;
+
 
 pub type StoreResult<T> = Result<T, StoreError>;
 
 
-#[derive(Debug)]
+/// The key under which the execution progress of the csmsgs of a source shard is persisted.
+fn progress_key(source: ShardId) -> Vec<u8> {
+  format!("csmsg_executed_{}", source).into_bytes()
+}
+
+/// The execution progress of the csmsgs received from a source shard: every csmsg below the
+/// watermark `low` is executed except the `missing` ones, `executed` holds the executed csmsgs from
+/// `low` on.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceProgress {
+  pub low: u64,
+  pub executed: BTreeSet<u64>,
+  /// The csmsgs pruned below the watermark before being executed: they are still executed when
+  /// the source shard retransmits them.
+  pub missing: BTreeSet<u64>,
+}
+
+impl SourceProgress {
+  pub fn is_executed(&self, seq: u64) -> bool {
+    if seq < self.low {
+      return !self.missing.contains(&seq);
+    }
+    self.executed.contains(&seq)
+  }
+
+  pub fn mark_executed(&mut self, seq: u64) {
+    if seq < self.low {
+      self.missing.remove(&seq);
+      return;
+    }
+    self.executed.insert(seq);
+    // the watermark follows the contiguous prefix of executed csmsgs
+    while self.executed.remove(&self.low) {
+      self.low += 1;
+    }
+  }
+
+  /// Move the watermark up to `gc_depth` csmsgs behind the last executed one, keeping only the
+  /// missing csmsgs below it. Return the new watermark if it moved.
+  pub fn prune(&mut self, gc_depth: u64) -> Option<u64> {
+    let last = *self.executed.iter().next_back()?;
+    let watermark = last.saturating_sub(gc_depth);
+    if watermark <= self.low {
+      return None;
+    }
+    let executed = &self.executed;
+    self.missing.extend((self.low..watermark).filter(|seq| !executed.contains(seq)));
+    self.executed = self.executed.split_off(&watermark);
+    self.low = watermark;
+    while self.executed.remove(&self.low) {
+      self.low += 1;
+    }
+    Some(self.low)
+  }
+
+  /// Every csmsg below the returned sequence number is executed: the acks never skip a missing
+  /// csmsg, so that the source shard keeps retransmitting it.
+  pub fn acked(&self) -> u64 {
+    self.missing.iter().next().copied().unwrap_or(self.low)
+  }
+
+  /// The csmsgs below the last executed csmsg that were not executed yet.
+  pub fn gaps(&self) -> Vec<u64> {
+    let mut gaps: Vec<u64> = self.missing.iter().copied().collect();
+    if let Some(last) = self.executed.iter().next_back() {
+      gaps.extend((self.low..*last).filter(|seq| !self.executed.contains(seq)));
+    }
+    gaps
+  }
+}
+
+
+// executed csmsgs are tracked by the `SourceProgress` of their source shard
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CSMsgStatus {
   Validating,
   Validated,
   Appended,
+  /// Only reported by `CSMsgStore::status`: executed csmsgs leave the status map.
   Executed,
 }
 
 impl fmt::Display for CSMsgStatus {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-      write!(f, "{}", self.to_string())
+      write!(f, "{:?}", self)
   }
 }
 
@@ -33,11 +113,14 @@ pub enum AppendedType {
 }
 
 pub enum CSMsgStoreCommand {
-    AddCSMsgSig(String, Signature, oneshot::Sender<StoreResult<(bool, Vec<Signature>)>>), // called by CSMsgVerification
-    UpdateAppended(String, AppendedType, oneshot::Sender<StoreResult<bool>>),
-    NotifyAppended(String, oneshot::Sender<StoreResult<bool>>), // called by CSMsgVerification
-    CanBeExecuted(String, oneshot::Sender<StoreResult<bool>>),
-    UpdatedExecuted(String), 
+    AddCSMsgSig(CSMsgId, PartialSignature, oneshot::Sender<StoreResult<(bool, Vec<PartialSignature>)>>), // called by CSMsgVerification
+    UpdateAppended(CSMsgId, AppendedType, oneshot::Sender<StoreResult<bool>>),
+    NotifyAppended(CSMsgId, oneshot::Sender<StoreResult<bool>>), // called by CSMsgVerification
+    CanBeExecuted(CSMsgId, oneshot::Sender<StoreResult<bool>>),
+    UpdatedExecuted(CSMsgId), 
+    Gaps(ShardId, oneshot::Sender<StoreResult<Vec<u64>>>),
+    LowWatermark(ShardId, oneshot::Sender<StoreResult<u64>>),
+    Status(CSMsgId, oneshot::Sender<StoreResult<Option<CSMsgStatus>>>),
 }
 
 #[derive(Clone)]
@@ -46,33 +129,62 @@ pub struct CSMsgStore {
 }
 
 impl CSMsgStore {
+    /// An in-memory csmsg store, never pruned.
     pub fn new(vote_threshold: Stake) -> Self {
-        // key: f'{[csmsg.source_shard}-{csmsg.cs_msg_id}]'
+        Self::spawn(vote_threshold, None, 0, u64::MAX)
+    }
 
-        let mut csmsg_status_map: HashMap<String, CSMsgStatus> = HashMap::new();
-        let mut waiting_csmsg_map: HashMap<String, Vec<Signature>> = HashMap::new();
+    /// A csmsg store whose executed marks survive restarts: the execution progress of each of the
+    /// `shard_num` source shards is persisted in `store`. Only the last `gc_depth` csmsgs of each
+    /// source shard are tracked; older csmsgs are pruned and only the missing ones are remembered.
+    pub fn new_with_store(vote_threshold: Stake, store: Store, shard_num: ShardId, gc_depth: u64) -> Self {
+        Self::spawn(vote_threshold, Some(store), shard_num, gc_depth)
+    }
+
+    fn spawn(vote_threshold: Stake, mut store: Option<Store>, shard_num: ShardId, gc_depth: u64) -> Self {
+        let mut csmsg_status_map: HashMap<CSMsgId, CSMsgStatus> = HashMap::new();
+        let mut waiting_csmsg_map: HashMap<CSMsgId, Vec<PartialSignature>> = HashMap::new();
+        let mut progress: HashMap<ShardId, SourceProgress> = HashMap::new();
 
         let mut obligations = HashMap::<_, VecDeque<oneshot::Sender<_>>>::new();
         let (tx, mut rx) = channel(100);
 
         tokio::spawn(async move {
+            // recover the execution progress of every source shard
+            if let Some(store) = store.as_mut() {
+              for source in 0..shard_num {
+                if let Some(bytes) = store.read(progress_key(source)).await.expect("Failed to read csmsg progress") {
+                  let source_progress: SourceProgress = bincode::deserialize(&bytes).expect("Failed to deserialize csmsg progress");
+                  info!("recover csmsgs of shard {}: executed up to {}", source, source_progress.low);
+                  progress.insert(source, source_progress);
+                }
+              }
+            }
+
             while let Some(command) = rx.recv().await {
                 match command {
                     // receive a new csmsg from csmsg_verification, process it, return (is_added, thres_sig_list)
                     CSMsgStoreCommand::AddCSMsgSig(id, sig, sender) => {
+                      if is_executed(&progress, &id) { // this csmsg has been executed or pruned, just ignore it
+                        let _ = sender.send(Ok((false, Vec::default())));
+                        continue;
+                      }
                       debug!("csmsg_status_map (before addcsmsgsig): {:?}", csmsg_status_map);
                       debug!("waiting_csmsg_map (before addcsmsgsig): {:?}", waiting_csmsg_map);
                       // check the csmsg_status first
                       // if this csmsg is received for the first time, insert an entry (id, CSMsgStatus::Validating)
-                      let csmsg_status = csmsg_status_map.entry(id.clone()).or_insert(CSMsgStatus::Validating);
+                      let csmsg_status = csmsg_status_map.entry(id).or_insert(CSMsgStatus::Validating);
                       debug!("csmsg_status (after addcsmsgsig): {:?}", csmsg_status);
                       debug!("waiting_csmsg_map (after addcsmsgsig): {:?}", waiting_csmsg_map);
 
                       match *csmsg_status {
                           CSMsgStatus::Validating => { // this csmsg is under validating, add its sig to waiting_csmsg_map
                             // if this csmsg is received for the first time, insert an entry (id, vec![sig]), else, add the new sig to sig_list
-                            let thres_sigs = waiting_csmsg_map.entry(id.clone()).or_insert(Vec::new());
-                            thres_sigs.push(sig);
+                            let thres_sigs = waiting_csmsg_map.entry(id).or_insert(Vec::new());
+                            // a share only counts once, whoever relays it
+                            if !thres_sigs.iter().any(|s| s.index == sig.index) {
+                              thres_sigs.push(sig);
+                            }
 
 
                             if thres_sigs.len() == vote_threshold {
@@ -87,7 +199,7 @@ impl CSMsgStore {
                             debug!("csmsg_status (after add): {:?}", csmsg_status);
                             debug!("waiting_csmsg_map (after add): {:?}", waiting_csmsg_map);
                           },
-                          _ => { // this csmsg has been validated or appended or executed, just ignore it
+                          _ => { // this csmsg has been validated or appended, just ignore it
                             let _ = sender.send(Ok((false, Vec::default())));
                           }
                       }
@@ -95,12 +207,15 @@ impl CSMsgStore {
                     CSMsgStoreCommand::UpdateAppended(id, appended_type, sender) => {
                       match appended_type {
                           AppendedType::Local => {
-                            let csmsg_status = csmsg_status_map.get_mut(&id).unwrap();
-                            match *csmsg_status {
-                              CSMsgStatus::Appended => {
+                            match csmsg_status_map.get_mut(&id) {
+                              // executed or pruned meanwhile
+                              None => {
                                 let _ = sender.send(Ok(false));
                               },
-                              _ => {
+                              Some(CSMsgStatus::Appended) => {
+                                let _ = sender.send(Ok(false));
+                              },
+                              Some(csmsg_status) => {
                                 *csmsg_status = CSMsgStatus::Appended;
                                 let _ = sender.send(Ok(true));
                               }                  
@@ -117,7 +232,9 @@ impl CSMsgStore {
                               // delete this csmsg from waiting_csmsg_map 
                               waiting_csmsg_map.remove(&id);
                               // override the csmsg_status
-                              csmsg_status_map.insert(id, CSMsgStatus::Appended);
+                              if !is_executed(&progress, &id) {
+                                csmsg_status_map.insert(id, CSMsgStatus::Appended);
+                              }
                               debug!("[after] a csmsg is appended by other node: {:?}", csmsg_status_map);
                               let _ = sender.send(Ok(true));               
                           }
@@ -125,9 +242,9 @@ impl CSMsgStore {
                     }
                     // when this func is called, this csmsg has been validated
                     CSMsgStoreCommand::NotifyAppended(id, sender) => {
-                        let res = csmsg_status_map.get(&id).unwrap();
-                        match res {
-                          CSMsgStatus::Appended => {
+                        match csmsg_status_map.get(&id) {
+                          // appended, or executed and pruned meanwhile
+                          None | Some(CSMsgStatus::Appended) => {
                             let _ = sender.send(Ok(true));
                           },
                           _ => {// new NotifyAppended
@@ -139,28 +256,60 @@ impl CSMsgStore {
                         }
                     }
                     CSMsgStoreCommand::CanBeExecuted(id, sender) => {
-                      let res = csmsg_status_map.get(&id);
-                      match res {
-                        None => {
-                          let _ = sender.send(Ok(true)); 
-                          debug!("this csmsg can be executed");
-                        },
-                        Some(res) => {
-                          match res {
-                            CSMsgStatus::Executed => { // this csmsg has been executed
-                              let _ = sender.send(Ok(false));
-                              debug!("this csmsg has been executed");
-                            },
-                            _ => {
-                              let _ = sender.send(Ok(true));
-                              debug!("this csmsg can be executed");
+                      if is_executed(&progress, &id) { // this csmsg has been executed
+                        let _ = sender.send(Ok(false));
+                        debug!("this csmsg has been executed");
+                      } else {
+                        let _ = sender.send(Ok(true));
+                        debug!("this csmsg can be executed");
+                      }
+                    }
+                    CSMsgStoreCommand::UpdatedExecuted(id) => {
+                      csmsg_status_map.remove(&id);
+                      waiting_csmsg_map.remove(&id);
+                      let source = id.source;
+                      let source_progress = progress.entry(source).or_default();
+                      source_progress.mark_executed(id.seq);
+
+                      // forget the status of the executed csmsgs below the new watermark
+                      if let Some(watermark) = source_progress.prune(gc_depth) {
+                        debug!("prune csmsgs of shard {} below {}", source, watermark);
+                        let source_progress = &*source_progress;
+                        let pruned = |id: &CSMsgId| id.source == source && source_progress.is_executed(id.seq);
+                        csmsg_status_map.retain(|id, _| !pruned(id));
+                        waiting_csmsg_map.retain(|id, _| !pruned(id));
+                        obligations.retain(|id, senders| {
+                          if pruned(id) {
+                            while let Some(s) = senders.pop_front() {
+                              let _ = s.send(Ok(true));
                             }
+                            return false;
                           }
-                        }                        
+                          true
+                        });
+                      }
+
+                      // persist the executed mark before the next command is served
+                      if let Some(store) = store.as_mut() {
+                        let bytes = bincode::serialize(source_progress).expect("Failed to serialize csmsg progress");
+                        store.write(progress_key(source), bytes).await;
                       }
                     }
-                    CSMsgStoreCommand::UpdatedExecuted(id) => {
-                      csmsg_status_map.insert(id, CSMsgStatus::Executed);
+                    CSMsgStoreCommand::Gaps(source, sender) => {
+                      let gaps = progress.get(&source).map(SourceProgress::gaps).unwrap_or_default();
+                      let _ = sender.send(Ok(gaps));
+                    }
+                    CSMsgStoreCommand::LowWatermark(source, sender) => {
+                      let low = progress.get(&source).map_or(0, SourceProgress::acked);
+                      let _ = sender.send(Ok(low));
+                    }
+                    CSMsgStoreCommand::Status(id, sender) => {
+                      let status = if is_executed(&progress, &id) {
+                        Some(CSMsgStatus::Executed)
+                      } else {
+                        csmsg_status_map.get(&id).copied()
+                      };
+                      let _ = sender.send(Ok(status));
                     }
                 }
             }
@@ -168,8 +317,53 @@ impl CSMsgStore {
         Self { channel: tx }
     }
 
+    /// Every csmsg of a source shard below the returned sequence number is executed.
+    pub async fn low_watermark(&mut self, source: ShardId) -> StoreResult<u64> {
+      let (sender, receiver) = oneshot::channel();
+      if let Err(e) = self
+          .channel
+          .send(CSMsgStoreCommand::LowWatermark(source, sender))
+          .await
+      {
+          panic!("Failed to send LowWatermark command to CSMsgStore: {}", e);
+      }
+      receiver
+          .await
+          .expect("Failed to receive reply to LowWatermark command from CSMsgStore")
+    }
+
+    /// The status of a csmsg in our shard, `None` if we have not received it.
+    pub async fn status(&mut self, id: CSMsgId) -> StoreResult<Option<CSMsgStatus>> {
+      let (sender, receiver) = oneshot::channel();
+      if let Err(e) = self
+          .channel
+          .send(CSMsgStoreCommand::Status(id, sender))
+          .await
+      {
+          panic!("Failed to send Status command to CSMsgStore: {}", e);
+      }
+      receiver
+          .await
+          .expect("Failed to receive reply to Status command from CSMsgStore")
+    }
+
+    /// The missing csmsgs of a source shard: not executed although a later csmsg was.
+    pub async fn gaps(&mut self, source: ShardId) -> StoreResult<Vec<u64>> {
+      let (sender, receiver) = oneshot::channel();
+      if let Err(e) = self
+          .channel
+          .send(CSMsgStoreCommand::Gaps(source, sender))
+          .await
+      {
+          panic!("Failed to send Gaps command to CSMsgStore: {}", e);
+      }
+      receiver
+          .await
+          .expect("Failed to receive reply to Gaps command from CSMsgStore")
+    }
 
-    pub async fn update_appended(&mut self, id: String, appended_type: AppendedType) -> StoreResult<bool> {
+
+    pub async fn update_appended(&mut self, id: CSMsgId, appended_type: AppendedType) -> StoreResult<bool> {
       let (sender, receiver) = oneshot::channel();
         if let Err(e) = self.channel.send(CSMsgStoreCommand::UpdateAppended(id, appended_type, sender)).await {
             panic!("Failed to send UpdateAppended command to CSMsgStore: {}", e);
@@ -179,7 +373,7 @@ impl CSMsgStore {
             .expect("Failed to receive reply to AddCSMsgSig command from CSMsgStore")
     }
 
-    pub async fn add_csmsg_sig(&mut self, id: String, sig: Signature) -> StoreResult<(bool, Vec<Signature>)> {
+    pub async fn add_csmsg_sig(&mut self, id: CSMsgId, sig: PartialSignature) -> StoreResult<(bool, Vec<PartialSignature>)> {
         let (sender, receiver) = oneshot::channel();
         if let Err(e) = self.channel.send(CSMsgStoreCommand::AddCSMsgSig(id, sig, sender)).await {
             panic!("Failed to send AddCSMsgSig command to CSMsgStore: {}", e);
@@ -189,7 +383,7 @@ impl CSMsgStore {
             .expect("Failed to receive reply to AddCSMsgSig command from CSMsgStore")
     }
 
-    pub async fn notify_appended(&mut self, id: String) -> StoreResult<bool> {
+    pub async fn notify_appended(&mut self, id: CSMsgId) -> StoreResult<bool> {
         let (sender, receiver) = oneshot::channel();
         if let Err(e) = self
             .channel
@@ -203,7 +397,7 @@ impl CSMsgStore {
             .expect("Failed to receive reply to NotifyAppended command from CSMsgStore")
     }
 
-    pub async fn can_executed(&mut self, id: String) -> StoreResult<bool> {
+    pub async fn can_executed(&mut self, id: CSMsgId) -> StoreResult<bool> {
       let (sender, receiver) = oneshot::channel();
       if let Err(e) = self
           .channel
@@ -217,7 +411,7 @@ impl CSMsgStore {
           .expect("Failed to receive reply to CanBeExecuted command from CSMsgStore")
     }
 
-    pub async fn update_executed(&mut self, id: String) {
+    pub async fn update_executed(&mut self, id: CSMsgId) {
       if let Err(e) = self
           .channel
           .send(CSMsgStoreCommand::UpdatedExecuted(id))
@@ -227,3 +421,8 @@ impl CSMsgStore {
       }
   }
 }
+
+
+fn is_executed(progress: &HashMap<ShardId, SourceProgress>, id: &CSMsgId) -> bool {
+  progress.get(&id.source).map_or(false, |p| p.is_executed(id.seq))
+}
@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+use config::ShardId;
+use log::info;
+use tokio::sync::mpsc::Sender;
+use tokio::task::JoinHandle;
+use crate::{Account2Shard, Account2ShardGraph};
+use crate::messages::{Address, Height};
+use crate::migration::MigrationPlan;
+use crate::messages::Transaction;
+use crate::partitioner::InteractionGraph;
+
+#[cfg(test)]
+#[path = "tests/epoch_tests.rs"]
+pub mod epoch_tests;
+
+/// Return the file of an epoch: every `{}` of the pattern is replaced by the epoch number.
+pub fn epoch_file(pattern: &str, epoch: u64) -> String {
+  pattern.replace("{}", &epoch.to_string())
+}
+
+/// Where the account-to-shard mapping of the next epoch comes from.
+#[derive(Clone, Debug)]
+pub enum NextAcc2Shard {
+  Unchanged, // hash policy
+  File(String), // the pattern of the acc2shard files of the graph policy
+  Online, // partition the txs committed during the epoch
+}
+
+/// Defines the epoch boundaries by committed height: epoch `first_epoch + i` starts at height
+/// `i * epoch_length + 1`, until `last_epoch` which never ends.
+#[derive(Clone, Debug)]
+pub struct EpochSchedule {
+  pub first_epoch: u64,
+  pub last_epoch: u64,
+  pub epoch_length: Height,
+  pub next_acc2shard: NextAcc2Shard,
+}
+
+impl EpochSchedule {
+  pub fn new(
+    first_epoch: u64,
+    last_epoch: u64,
+    epoch_length: Height,
+    next_acc2shard: NextAcc2Shard,
+  ) -> Self {
+    assert!(epoch_length > 0, "The epoch length must be positive");
+    assert!(first_epoch <= last_epoch, "The last epoch must not precede the first one");
+    Self { first_epoch, last_epoch, epoch_length, next_acc2shard }
+  }
+
+  pub fn epoch_at(&self, height: Height) -> u64 {
+    let epoch = self.first_epoch + height.saturating_sub(1) / self.epoch_length;
+    epoch.min(self.last_epoch)
+  }
+
+  pub fn start_height(&self, epoch: u64) -> Height {
+    (epoch - self.first_epoch) * self.epoch_length + 1
+  }
+}
+
+/// Tracks the epoch of the committed blocks on behalf of an executor. At the first height of an
+/// epoch it swaps the account-to-shard mapping and hands the migration plan to the migrator; the
+/// mapping of the next epoch is either loaded in the background beforehand, or computed from the
+/// txs committed during the epoch. In the latter case each shard only moves its own accounts: the
+/// moves of the other shards are applied once their migrations are installed.
+pub struct EpochManager {
+  shard_id: ShardId,
+  shard_num: ShardId,
+  schedule: Option<EpochSchedule>, // None: a single epoch
+
+  epoch: u64,
+  acc2shard: Box<dyn Account2Shard + Send>,
+  next_acc2shard: Option<JoinHandle<Account2ShardGraph>>,
+  graph: InteractionGraph, // the txs committed during the epoch (online policy)
+  moves: BTreeMap<u64, BTreeMap<Address, ShardId>>, // our accounts leaving at each epoch (online policy)
+
+  tx_plan: Sender<MigrationPlan>, // to the Migrator
+}
+
+impl EpochManager {
+  pub fn new(
+    shard_id: ShardId,
+    shard_num: ShardId,
+    epoch: u64,
+    acc2shard: Box<dyn Account2Shard + Send>,
+    schedule: Option<EpochSchedule>,
+    tx_plan: Sender<MigrationPlan>,
+  ) -> Self {
+    let mut manager = Self {
+      shard_id,
+      shard_num,
+      schedule,
+      epoch,
+      acc2shard,
+      next_acc2shard: None,
+      graph: InteractionGraph::new(),
+      moves: BTreeMap::new(),
+      tx_plan,
+    };
+    manager.preload();
+    manager
+  }
+
+  pub fn epoch(&self) -> u64 {
+    self.epoch
+  }
+
+  pub fn acc2shard(&self) -> &dyn Account2Shard {
+    self.acc2shard.as_ref()
+  }
+
+  /// The accounts that left this shard when entering an epoch, with their new shard. None if the
+  /// epoch is not entered yet or its mapping is not computed online.
+  pub fn moves(&self, epoch: u64) -> Option<BTreeMap<Address, ShardId>> {
+    self.moves.get(&epoch).cloned()
+  }
+
+  /// Apply the moves computed by the other shards, carried by their installed migrations.
+  pub fn apply_moves(&mut self, moves: BTreeMap<Address, ShardId>) {
+    if moves.is_empty() {
+      return;
+    }
+    self.acc2shard = Box::new(Account2ShardGraph::with_moves(self.acc2shard.as_ref(), moves));
+  }
+
+  // start loading the mapping of the next epoch, if any
+  fn preload(&mut self) {
+    let schedule = match &self.schedule {
+      Some(schedule) if self.epoch < schedule.last_epoch => schedule,
+      _ => return,
+    };
+    if let NextAcc2Shard::File(pattern) = &schedule.next_acc2shard {
+      let file = epoch_file(pattern, self.epoch + 1);
+      let shard_num = self.shard_num;
+      self.next_acc2shard = Some(tokio::task::spawn_blocking(move || {
+        Account2ShardGraph::new(shard_num, &file)
+      }));
+    }
+  }
+
+  /// Record a tx whose frame was committed by this shard, for the online policy.
+  pub fn observe(&mut self, tx: &Transaction) {
+    if let Some(EpochSchedule{next_acc2shard: NextAcc2Shard::Online, ..}) = self.schedule {
+      self.graph.observe(tx);
+    }
+  }
+
+  /// Called before executing the block at `height`: enter the next epoch at its first height. A
+  /// block may skip several boundaries (e.g. heights of no txs are not executed): the epochs are
+  /// then entered one by one, each with its own migration plan.
+  pub async fn begin_block(&mut self, height: Height) {
+    loop {
+      let online = match &self.schedule {
+        Some(schedule) if schedule.epoch_at(height) > self.epoch => {
+          matches!(schedule.next_acc2shard, NextAcc2Shard::Online)
+        },
+        _ => return,
+      };
+      self.enter_next_epoch(height, online).await;
+    }
+  }
+
+  async fn enter_next_epoch(&mut self, height: Height, online: bool) {
+    let next_epoch = self.epoch + 1;
+    if online {
+      // the graph only depends on the committed txs, so every replica reaches the same mapping
+      let graph = std::mem::take(&mut self.graph);
+      let (next_acc2shard, moves) = graph.partition(self.shard_id, self.acc2shard.as_ref());
+      info!(
+        "[height: {}] online partition of epoch {}: {} accounts leave shard {}",
+        height, next_epoch, moves.len(), self.shard_id
+      );
+      self.moves.insert(next_epoch, moves.clone());
+      let plan = MigrationPlan::online(self.shard_id, self.shard_num, next_epoch, height, moves);
+      self.tx_plan
+        .send(plan)
+        .await
+        .expect("Failed to send migration plan");
+      self.acc2shard = Box::new(next_acc2shard);
+    } else if let Some(handle) = self.next_acc2shard.take() {
+      let next_acc2shard = handle.await.expect("Failed to load the acc2shard map of the next epoch");
+      let plan = MigrationPlan::new(self.shard_id, next_epoch, height, self.acc2shard.as_ref(), &next_acc2shard);
+      info!(
+        "[height: {}] migration plan of epoch {}: {} outgoing and {} incoming accounts",
+        height,
+        next_epoch,
+        plan.outgoing.values().map(|accs| accs.len()).sum::<usize>(),
+        plan.incoming.values().map(|accs| accs.len()).sum::<usize>(),
+      );
+      self.tx_plan
+        .send(plan)
+        .await
+        .expect("Failed to send migration plan");
+      self.acc2shard = Box::new(next_acc2shard);
+    }
+    self.epoch = next_epoch;
+    info!("[height: {}] enter epoch {}", height, self.epoch);
+    self.preload();
+  }
+}
@@ -1,5 +1,7 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
-use crypto::CryptoError;
+use config::{ConfigError, ShardId};
+use crypto::{CryptoError, Digest, PublicKey};
+use crate::messages::CSMsgId;
 use store::StoreError;
 use thiserror::Error;
 
@@ -33,4 +35,46 @@ pub enum ExecutionError {
     #[error("Serialization error: {0}")]
     SerializationError(#[from] Box<bincode::ErrorKind>),
 
+    #[error(transparent)]
+    ConfigError(#[from] ConfigError),
+
+    #[error("Authority {0} is not in shard {1}")]
+    UnknownAuthority(PublicKey, ShardId),
+
+    #[error("Inner tx hash {0} does not match the tx")]
+    InvalidInnerTxHash(Digest),
+
+    #[error("The inner tx of csmsg {0} carries another csmsg id")]
+    CSMsgIdMismatch(CSMsgId),
+
+    #[error("Invalid threshold signature from shard {0}")]
+    InvalidThresholdSignature(ShardId),
+
+    #[error("Cross-shard proof of length {0} does not match step {1}")]
+    MalformedCSProof(usize, usize),
+
+    #[error("Shard {0} is not the executor of hop {1}")]
+    UnexpectedHop(ShardId, usize),
+
+    #[error("Agg tx from shard {0} does not carry a single frame of sorted avatars")]
+    MalformedAggPayload(ShardId),
+
+    #[error("Agg tx from shard {0} declares {1} avatars but carries {2}")]
+    AggPayloadLength(ShardId, usize, usize),
+
+    #[error("Avatars of agg tx from shard {0} do not match its payload hash")]
+    InvalidAggPayloadRoot(ShardId),
+
+    #[error("Avatar deltas of agg tx from shard {0} do not sum to its avatars")]
+    AggDeltasMismatch(ShardId),
+
+    #[error("Invalid migration proof of account {0:?} from shard {1}")]
+    InvalidMigrationProof(Vec<u8>, ShardId),
+
+    #[error("Authority {0} appears in execution certificate more than once")]
+    AuthorityReuse(PublicKey),
+
+    #[error("Execution certificate of height {0} requires f+1 votes")]
+    InsufficientVotes(u64),
+
 }
@@ -0,0 +1,141 @@
+use config::{Committees, ShardId, Stake};
+use crypto::{Digest, Hash, PublicKey, Signature, SignatureService};
+use ed25519_dalek::Digest as _;
+use mpt::RootHash;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use crate::error::ExecutionError;
+use crate::messages::{canonical_hasher, finalize, update_bytes, Height};
+use crate::query::{hex_bytes, hex_digest};
+
+
+/// The outcome of the execution of a block: the state roots once the block is executed and the
+/// root of the receipts of its txs. Every honest replica of a shard computes the same result.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ExecutionResult {
+    pub height: Height,
+    #[serde(with = "hex_bytes")]
+    pub act_root: RootHash,
+    #[serde(with = "hex_bytes")]
+    pub full_root: RootHash,
+    #[serde(with = "hex_digest")]
+    pub tx_receipts_root: Digest,
+}
+
+impl ExecutionResult {
+  pub fn new(height: Height, act_root: RootHash, full_root: RootHash, tx_receipts_root: Digest) -> Self {
+    Self { height, act_root, full_root, tx_receipts_root }
+  }
+}
+
+impl Hash for ExecutionResult {
+  fn digest(&self) -> Digest {
+      let mut hasher = canonical_hasher(b"SHARDAG_EXECUTION_RESULT");
+      hasher.update(self.height.to_le_bytes());
+      update_bytes(&mut hasher, &self.act_root);
+      update_bytes(&mut hasher, &self.full_root);
+      hasher.update(self.tx_receipts_root.to_vec());
+      finalize(hasher)
+  }
+}
+
+impl fmt::Display for ExecutionResult {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      write!(f, "[RESULT][height: {}, digest: {}]", self.height, self.digest())
+  }
+}
+
+/// The root of the receipts of the txs of a block, in execution order.
+pub fn receipts_root(receipts: &[Digest]) -> Digest {
+  let mut hasher = canonical_hasher(b"SHARDAG_RECEIPTS");
+  hasher.update((receipts.len() as u64).to_le_bytes());
+  for receipt in receipts {
+    hasher.update(receipt.to_vec());
+  }
+  finalize(hasher)
+}
+
+// the digest signed by a replica voting for a result
+fn vote_digest(shard_id: ShardId, result: &ExecutionResult, author: &PublicKey) -> Digest {
+  let mut hasher = canonical_hasher(b"SHARDAG_EXECUTION_VOTE");
+  hasher.update((shard_id as u64).to_le_bytes());
+  hasher.update(result.digest().to_vec());
+  hasher.update(author.0);
+  finalize(hasher)
+}
+
+
+/// A replica announces its execution result to the other replicas of its shard.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct ExecutionVote {
+    pub shard_id: ShardId,
+    pub result: ExecutionResult,
+
+    pub author: PublicKey,
+    pub signature: Signature,
+}
+
+impl ExecutionVote {
+  pub async fn new(
+    shard_id: ShardId,
+    result: ExecutionResult,
+    author: &PublicKey,
+    signature_service: &mut SignatureService,
+  ) -> Self {
+    let signature = signature_service.request_signature(vote_digest(shard_id, &result, author)).await;
+    Self { shard_id, result, author: *author, signature }
+  }
+
+  /// Verify that the author is a member of the shard and that its signature is valid.
+  pub fn verify(&self, all_committees: &Committees) -> Result<(), ExecutionError> {
+    let committee = all_committees.our_committee(&self.shard_id)?;
+    ensure!(
+      committee.stake(&self.author) > 0,
+      ExecutionError::UnknownAuthority(self.author, self.shard_id)
+    );
+    self.signature.verify(&vote_digest(self.shard_id, &self.result, &self.author), &self.author)?;
+    Ok(())
+  }
+}
+
+impl fmt::Display for ExecutionVote {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      write!(f, "[VOTE][shard: {}, height: {}, digest: {}]", self.shard_id, self.result.height, self.result.digest())
+  }
+}
+
+
+/// The votes of f+1 replicas of a shard for the same result: at least one honest replica computed it.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct ExecutionCertificate {
+    pub shard_id: ShardId,
+    pub result: ExecutionResult,
+    pub votes: Vec<(PublicKey, Signature)>,
+}
+
+impl ExecutionCertificate {
+  /// Verify that distinct members of the shard holding f+1 stake signed the result.
+  pub fn verify(&self, all_committees: &Committees) -> Result<(), ExecutionError> {
+    let committee = all_committees.our_committee(&self.shard_id)?;
+    let mut used = HashSet::new();
+    let mut weight: Stake = 0;
+    for (name, _) in &self.votes {
+      ensure!(!used.contains(name), ExecutionError::AuthorityReuse(*name));
+      let voting_rights = committee.stake(name);
+      ensure!(voting_rights > 0, ExecutionError::UnknownAuthority(*name, self.shard_id));
+      used.insert(*name);
+      weight += voting_rights;
+    }
+    ensure!(
+      weight >= committee.validity_threshold(),
+      ExecutionError::InsufficientVotes(self.result.height)
+    );
+    let items: Vec<_> = self.votes
+      .iter()
+      .map(|(name, signature)| (vote_digest(self.shard_id, &self.result, name), *name, signature.clone()))
+      .collect();
+    Signature::verify_batch_digests(&items)?;
+    Ok(())
+  }
+}
@@ -0,0 +1,674 @@
+use async_trait::async_trait;
+use config::{Committees, ShardId};
+use crypto::{Digest, Hash as _};
+use std::collections::HashMap;
+use primary::Header;
+use tokio::sync::mpsc::{Receiver, Sender};
+use log::{debug, info, warn};
+use crate::csmsg_store::CSMsgStore;
+use crate::execution_result::{receipts_root, ExecutionResult};
+use crate::metrics;
+use crate::result_checker::ResultChecker;
+use crate::query::{StateQuery, StateRoot};
+use crate::receipt::{Receipt, ReceiptStatus, ReceiptStore};
+use crate::epoch::EpochManager;
+use crate::migration::Migrator;
+use crate::{Address, Frame, RWSet, StateTransition, AmountError};
+use crate::state_store::Account;
+use crate::state_transition::apply_deltas;
+use crate::batch_maker::{verify_client_signatures, Batch, GeneralTxList};
+use crate::worker::{SynchronizationMessage, SendCSMessage};
+use crate::messages::{GeneralTransaction, Height, AggTransaction, CommitMessage, CommitPhase, CSMsgId, Transaction};
+use num_enum::TryFromPrimitive;
+
+#[cfg(test)]
+#[path = "tests/executor_tests.rs"]
+pub mod executor_tests;
+
+
+#[derive(TryFromPrimitive, Debug)]
+#[repr(usize)]
+pub enum ExecutorType {
+  SharDAG,
+  Monoxide,
+  BrokerChain,
+  TwoPhaseCommit,
+}
+
+pub enum ExecutionState {
+  Commit,
+  Abort(AmountError),
+  /// Relayed to the given shard for its next step.
+  Relay(ShardId),
+  RolledBack,
+  /// The outcome depends on the other shards: the executor finishes the tx later.
+  Pending,
+}
+
+impl ExecutionState {
+  // the status written in the receipt of a tx, none while it is pending
+  pub fn receipt_status(&self) -> Option<ReceiptStatus> {
+    match self {
+      ExecutionState::Commit => Some(ReceiptStatus::Committed),
+      ExecutionState::Abort(e) => Some(ReceiptStatus::Aborted(*e)),
+      ExecutionState::Relay(next_shard) => Some(ReceiptStatus::Relayed(*next_shard)),
+      ExecutionState::RolledBack => Some(ReceiptStatus::RolledBack),
+      ExecutionState::Pending => None,
+    }
+  }
+}
+
+
+/// The state of the shard, handed by the driver to the hooks of the executors.
+pub struct ExecutionContext {
+    pub shard_id: ShardId,
+    pub state_transition: StateTransition,
+    pub epoch_manager: EpochManager,
+    pub csmsg_store: CSMsgStore,
+    pub tx_csmsg: Sender<SendCSMessage>,
+
+    receipt_store: ReceiptStore,
+    // the digests of the receipts of the current block
+    receipts: Vec<Digest>,
+    // the compensations whose credit reversal failed, retried at each block
+    pending_compensations: Vec<CommitMessage>,
+
+    // statistical info
+    total_general_txs: u32,
+    total_external_txs: u32,
+    total_cross_shard_txs: u32,
+    total_commit_txs: u32,
+    total_aborted_txs: u32,
+}
+
+impl ExecutionContext {
+  pub fn new(
+    shard_id: ShardId,
+    state_transition: StateTransition,
+    epoch_manager: EpochManager,
+    csmsg_store: CSMsgStore,
+    tx_csmsg: Sender<SendCSMessage>,
+    receipt_store: ReceiptStore,
+  ) -> Self {
+    Self {
+      shard_id,
+      state_transition,
+      epoch_manager,
+      csmsg_store,
+      tx_csmsg,
+      receipt_store,
+      receipts: Vec::new(),
+      pending_compensations: Vec::new(),
+
+      total_general_txs: 0,
+      total_external_txs: 0,
+      total_commit_txs: 0,
+      total_cross_shard_txs: 0,
+      total_aborted_txs: 0,
+    }
+  }
+
+  // the new states of the accounts of a frame: a deduction may not make a balance negative
+  async fn new_states(&mut self, frame: &Frame) -> Result<HashMap<Address, Account>, AmountError> {
+    let latest_states = self.state_transition.get_latest_states(&frame.rwset).await;
+    debug!{"latest states: {:?}", latest_states};
+    // the frame of an independent tx is already executed against these states
+    if let Some(outcome) = self.state_transition.speculated(frame, &latest_states) {
+      return outcome;
+    }
+    apply_deltas(&frame.rwset, latest_states)
+  }
+
+  // apply a frame, unless its balance check fails
+  pub async fn apply_frame(&mut self, frame: &Frame) -> Result<(), AmountError> {
+    let new_states = self.new_states(frame).await?;
+    self.state_transition.apply_new_states(new_states).await;
+    Ok(())
+  }
+
+  // the balance check of a frame, without applying it
+  pub async fn check_frame(&mut self, frame: &Frame) -> Result<(), AmountError> {
+    self.new_states(frame).await.map(|_| ())
+  }
+
+  // persist the receipt of a tx, its digest goes in the receipts root of the block
+  pub async fn write_receipt(&mut self, receipt: Receipt) {
+    debug!("{}", receipt);
+    let digest = self.receipt_store.write(&receipt).await;
+    self.receipts.push(digest);
+  }
+
+  // write the receipt of a transfer tx executed by our shard and account for its outcome
+  pub async fn finish_tx(&mut self, height: Height, transfer_tx: &Transaction, exec_state: &ExecutionState) {
+    if let Some(status) = exec_state.receipt_status() {
+      let receipt = Receipt::new(
+        transfer_tx.client_digest(), status, height,
+        self.shard_id, transfer_tx.shard_path(), Some(transfer_tx.timestamp),
+      );
+      self.write_receipt(receipt).await;
+    }
+
+    match exec_state {
+      ExecutionState::Commit => {
+        debug!(
+          "[height: {}] commit tx: {:?}",
+          height, transfer_tx.get_digest()
+        );
+        self.total_external_txs += 1;
+        self.total_cross_shard_txs += transfer_tx.count_cs_tx();
+        self.total_commit_txs += 1;
+        self.epoch_manager.observe(transfer_tx);
+        // output sample info
+        if transfer_tx.sample == 0 {// sample tx
+          info!(
+            "Successfully execute sample tx {} in batch",
+              transfer_tx.counter,
+          );
+        }
+      },
+      ExecutionState::Abort(_) | ExecutionState::RolledBack => {
+        self.total_external_txs += 1;
+        self.total_cross_shard_txs += transfer_tx.count_cs_tx();
+        self.total_aborted_txs += 1;
+        warn!(
+          "[height: {}] {}, fail to execute tx: {:?}",
+          height, exec_state.receipt_status().unwrap(), transfer_tx
+        );
+      },
+      // our frame is applied, the next shard executes the next one
+      ExecutionState::Relay(_) => self.epoch_manager.observe(transfer_tx),
+      ExecutionState::Pending => {},
+    }
+  }
+
+  // a client tx dropped before it is authorized keeps its nonce, the receipt tells the client to
+  // resubmit it. A replayed tx is dropped too, the receipt of its execution is kept
+  pub async fn reject_tx(&mut self, height: Height, tx: &GeneralTransaction) {
+    let transfer_tx = match tx {
+      GeneralTransaction::TransferTx(transfer_tx) if transfer_tx.get_csmsg_id().is_none() => transfer_tx,
+      _ => return,
+    };
+    let tx_digest = transfer_tx.client_digest();
+    if self.receipt_store.read(&tx_digest).await.expect("Failed to read receipt").is_some() {
+      return;
+    }
+    let receipt = Receipt::new(
+      tx_digest, ReceiptStatus::Rejected, height,
+      self.shard_id, transfer_tx.shard_path(), Some(transfer_tx.timestamp),
+    );
+    self.write_receipt(receipt).await;
+  }
+
+  // a relayed tx aborted in our shard: the shards of its earlier frames undo them, otherwise the
+  // deductions they applied would be lost
+  pub async fn send_compensations(&mut self, height: Height, transfer_tx: &Transaction) {
+    for step in 0..transfer_tx.step {
+      let msg = CommitMessage::new(self.shard_id, CommitPhase::Compensate, transfer_tx.clone(), step);
+      if msg.target_shard() == self.shard_id {
+        self.compensate(height, &msg).await;
+      } else {
+        debug!("[height: {}] send compensation {}", height, msg);
+        self.send_csmsg(height, msg.target_shard(), GeneralTransaction::CommitMsg(msg)).await;
+      }
+    }
+  }
+
+  // undo a frame applied by our shard. A compensation is executed once, like the csmsg of the
+  // aborted tx; a redelivered one is also recognized by the mark of its frame
+  pub async fn compensate(&mut self, height: Height, msg: &CommitMessage) {
+    match msg.frame() {
+      Some(frame) if frame.shardid == self.shard_id => {},
+      _ => return,
+    }
+    let compensated = self.receipt_store.compensated(&msg.tx.client_digest(), msg.tx.step).await
+      .expect("Failed to read compensation");
+    let pending = self.pending_compensations.iter()
+      .any(|p| p.tx.client_digest() == msg.tx.client_digest() && p.tx.step == msg.tx.step);
+    if compensated || pending {
+      debug!("[height: {}] frame {} of tx {} already compensated", height, msg.tx.step, msg.tx.counter);
+      return;
+    }
+    if let Err(e) = self.apply_compensation(height, msg).await {
+      // the credit is taken back once the account is funded again, see `retry_compensations`
+      warn!("[height: {}] fail to compensate frame {} of tx {}, retry later: {}", height, msg.tx.step, msg.tx.counter, e);
+      self.pending_compensations.push(msg.clone());
+      self.receipt_store.write_pending_compensations(&self.pending_compensations).await;
+    }
+  }
+
+  // returning a deduction cannot fail, taking back a credit fails if it is already spent
+  async fn apply_compensation(&mut self, height: Height, msg: &CommitMessage) -> Result<(), AmountError> {
+    let frame = msg.frame().unwrap();
+    let rwset = frame.rwset.iter()
+      .map(|rw| rw.value.checked_neg().map(|value| RWSet { addr: rw.addr.clone(), value }))
+      .collect::<Result<Vec<RWSet>, AmountError>>()?;
+    self.apply_frame(&Frame { shardid: frame.shardid, rwset }).await?;
+    self.receipt_store.write_compensated(&msg.tx.client_digest(), msg.tx.step).await;
+    let receipt = Receipt::new(
+      msg.tx.client_digest(), ReceiptStatus::RolledBack, height,
+      self.shard_id, msg.tx.shard_path(), Some(msg.tx.timestamp),
+    );
+    self.write_receipt(receipt).await;
+    Ok(())
+  }
+
+  /// Load the compensations waiting for a retry before a restart.
+  pub async fn recover(&mut self) {
+    self.pending_compensations = self.receipt_store.read_pending_compensations().await
+      .expect("Failed to read pending compensations");
+    if !self.pending_compensations.is_empty() {
+      info!("recovered {} pending compensations", self.pending_compensations.len());
+    }
+  }
+
+  // retry the pending compensations in the order they were received, at the end of each block
+  pub async fn retry_compensations(&mut self, height: Height) {
+    if self.pending_compensations.is_empty() {
+      return;
+    }
+    let mut pending = Vec::new();
+    for msg in std::mem::take(&mut self.pending_compensations) {
+      if self.apply_compensation(height, &msg).await.is_err() {
+        pending.push(msg);
+      }
+    }
+    self.pending_compensations = pending;
+    self.receipt_store.write_pending_compensations(&self.pending_compensations).await;
+  }
+
+  // send a csmsg to the target shard, or to the brokers (`ShardId::MAX`) in BrokerChain
+  pub async fn send_csmsg(&mut self, height: Height, target_shard: ShardId, tx: GeneralTransaction) {
+    let message = SendCSMessage{height, target_shard, epoch: self.epoch_manager.epoch(), tx};
+    self.tx_csmsg
+        .send(message)
+        .await
+        .expect("Failed to send new block to ADSynchronizer");
+  }
+}
+
+
+/// A cross-shard protocol. The `ExecutionDriver` verifies the txs of each height, executes them
+/// and commits the state roots of the height; the protocol decides how a tx moves between the
+/// shards through these hooks.
+#[async_trait]
+pub trait Executor: Send {
+  /// The name of the executor in the logs.
+  fn name(&self) -> &'static str;
+
+  /// Check a tx once its csmsg proof is verified: return false to ignore it.
+  async fn pre_verify(&mut self, _ctx: &mut ExecutionContext, _height: Height, _tx: &GeneralTransaction) -> bool {
+    true
+  }
+
+  /// Execute the current frame of a transfer tx: by default the frame is applied, then the tx
+  /// commits here or is relayed (see `relay`).
+  async fn execute_frame(&mut self, ctx: &mut ExecutionContext, height: Height, tx: &Transaction) -> ExecutionState {
+    if let Err(e) = ctx.apply_frame(tx.payload.get(tx.step).unwrap()).await {
+      return ExecutionState::Abort(e);
+    }
+    match self.relay(ctx, height, tx).await {
+      Some(next_shard) => ExecutionState::Relay(next_shard),
+      None => ExecutionState::Commit,
+    }
+  }
+
+  /// Once the current frame of a tx is applied, the shard the tx is relayed to for its next
+  /// step, or None if it commits here.
+  async fn relay(&mut self, ctx: &mut ExecutionContext, height: Height, tx: &Transaction) -> Option<ShardId>;
+
+  /// Update a relayed tx before it is sent as a csmsg: by default it moves to its next step.
+  fn prepare_relay(&mut self, shard_id: ShardId, tx: &mut Transaction) {
+    tx.update_relay_info(shard_id);
+  }
+
+  /// Execute an agg tx and return its outcome, or None if the protocol ignores them.
+  async fn execute_agg_tx(
+    &mut self,
+    _ctx: &mut ExecutionContext,
+    _height: Height,
+    _agg_tx: &AggTransaction,
+  ) -> Option<ExecutionState> {
+    None
+  }
+
+  /// Execute a phase of TwoPhaseCommit. By default they are ignored; the compensations are
+  /// executed by the driver.
+  async fn execute_commit_msg(&mut self, _ctx: &mut ExecutionContext, _height: Height, _msg: &CommitMessage) {}
+
+  /// Called once the txs of a height are executed, before its state roots are committed.
+  async fn end_block(&mut self, _ctx: &mut ExecutionContext, _height: Height) {}
+}
+
+
+/// The txs of a batch to execute. Only our own batches are checked before they are sealed, so the
+/// client txs of any batch are checked again and those with an invalid signature are dropped by
+/// every replica alike.
+fn verified_txs(height: Height, batch: Batch) -> GeneralTxList {
+  let received = batch.tx_list.len();
+  let txs = verify_client_signatures(batch.tx_list);
+  if txs.len() != received {
+    warn!("[height: {}] drop {} txs with an invalid signature", height, received - txs.len());
+  }
+  txs
+}
+
+/// The accounts our shard reads when it executes a tx: the frame of its current step, the sender
+/// of a client tx (its nonce), the first frame of an agg tx.
+fn read_accounts(tx: &GeneralTransaction) -> Vec<Address> {
+  let mut accs: Vec<Address> = match tx {
+    GeneralTransaction::TransferTx(tx) => tx.payload.get(tx.step),
+    GeneralTransaction::AggTx(tx) => tx.payload.first(),
+    GeneralTransaction::CommitMsg(msg) => msg.frame(),
+    GeneralTransaction::MigrationTx(_) => None,
+  }
+  .map(|frame| frame.rwset.iter().map(|rw| rw.addr.clone()).collect())
+  .unwrap_or_default();
+  if let GeneralTransaction::TransferTx(tx) = tx {
+    if tx.get_csmsg_id().is_none() {
+      accs.push(tx.sender.clone());
+    }
+  }
+  accs
+}
+
+
+/// Runs an executor: the txs of each height are verified, executed through the hooks of the
+/// executor and their receipts written, then the state roots of the height are checked against
+/// the other replicas (see `ResultChecker`). The state queries are answered between two heights.
+pub struct ExecutionDriver {
+    executor: Box<dyn Executor>,
+    ctx: ExecutionContext,
+    all_committees: Committees,
+
+    // channel
+    rx_process_txs: Receiver<SynchronizationMessage>,
+    rx_state_query: Receiver<StateQuery>,
+
+    migrator: Migrator,
+    result_checker: ResultChecker,
+    // the last executed height
+    height: Height,
+    // the cold accounts are evicted from the active tier of the state store every `evict_interval`
+    // heights (never if zero)
+    evict_interval: Height,
+    evict_idle: Height,
+}
+
+
+impl ExecutionDriver {
+    #[allow(clippy::too_many_arguments)]
+    pub fn spawn(
+      executor: Box<dyn Executor>,
+
+      // node config
+      shard_id: ShardId,
+      all_committees: Committees,
+
+      rx_process_txs: Receiver<SynchronizationMessage>,
+      tx_csmsg: Sender<SendCSMessage>,
+
+      // state store
+      state_transition: StateTransition,
+      epoch_manager: EpochManager,
+      csmsg_store: CSMsgStore,
+      migrator: Migrator,
+      result_checker: ResultChecker,
+      receipt_store: ReceiptStore,
+      rx_state_query: Receiver<StateQuery>,
+      evict_interval: Height,
+      evict_idle: Height,
+    ) {
+        tokio::spawn(async move {
+          Self {
+            executor,
+            ctx: ExecutionContext::new(shard_id, state_transition, epoch_manager, csmsg_store, tx_csmsg, receipt_store),
+            all_committees,
+            rx_process_txs,
+            rx_state_query,
+            migrator,
+            result_checker,
+            height: 0,
+            evict_interval,
+            evict_idle,
+          }
+          .run()
+          .await;
+      });
+    }
+
+    /// Main loop listening to the messages.
+    async fn run(&mut self) {
+
+      info!("{} is running!", self.executor.name());
+      self.ctx.recover().await;
+
+      // the queries are answered between two blocks
+      loop {
+        tokio::select! {
+          Some(SynchronizationMessage{height, header, batch_list}) = self.rx_process_txs.recv() => {
+            debug!(
+              "[height: {}][header: {}] Receiving TxBlock msg",
+              height, header
+            );
+            self.process_tx_block(height, header, batch_list).await;
+            self.height = height;
+          },
+          Some(query) = self.rx_state_query.recv() => {
+            self.ctx.state_transition.answer(query, self.ctx.shard_id, self.height, &self.ctx.epoch_manager).await;
+          },
+          else => break,
+        }
+      }
+    }
+
+
+    // verify tx before execution, ignore invalid tx or redundant csmsg tx
+    // return (is_csmsg, is_valid)
+    async fn verify_tx(&mut self, tx: &GeneralTransaction) -> (Option<CSMsgId>, bool) {
+      match tx.get_csmsg_id() {
+        None => {
+          (None, true) // TODO: we assume that intra-shard tx is identical
+        }, // not a csmsg
+        Some(csmsg_id) => {
+          // verify the threshold sig and get tx_hash
+          match tx.verify_cs_proof(&self.all_committees) {
+            Err(e) => { // invalid csmsg
+              warn!("invalid csmsg {}: {}", csmsg_id, e);
+              (Some(csmsg_id), false)
+            },
+            Ok(_) => {// valid csmsg, may be redundant
+              let can_executed = self.ctx.csmsg_store.can_executed(csmsg_id).await.unwrap();
+              (Some(csmsg_id), can_executed)
+            }
+          }
+        }
+      }
+    }
+
+    // verify and execute a tx, return false if it is ignored
+    async fn process_tx(&mut self, height: Height, tx: GeneralTransaction) -> bool {
+      // a migration is installed before the next block, it is not executed
+      if let GeneralTransaction::MigrationTx(migration) = &tx {
+        self.migrator.deliver(height, migration, &self.all_committees);
+        return false;
+      }
+
+      // verify tx before execution, ignore invalid tx or redundant csmsg tx
+      let (csmsg_id, is_valid) = self.verify_tx(&tx).await;
+      if !is_valid {
+        info!("redundant csmsg tx");
+        return false;
+      }
+      if !self.executor.pre_verify(&mut self.ctx, height, &tx).await {
+        self.ctx.reject_tx(height, &tx).await;
+        return false;
+      }
+
+      // txs touching accounts in flight wait for the migration, those of migrated accounts are dropped
+      if self.migrator.hold(height, &tx) {
+        if self.migrator.migrated_out(&tx) {
+          self.ctx.reject_tx(height, &tx).await;
+        }
+        return false;
+      }
+
+      // client txs are executed only with the next nonce and the registered key of their sender
+      if let GeneralTransaction::TransferTx(transfer_tx) = &tx {
+        if transfer_tx.get_csmsg_id().is_none()
+          && !self.ctx.state_transition.authorize(&transfer_tx.sender, &transfer_tx.sender_key, transfer_tx.nonce).await {
+          warn!(
+            "[height: {}] drop tx {} with stale nonce {} or unregistered key",
+            height, transfer_tx.counter, transfer_tx.nonce
+          );
+          return false;
+        }
+      }
+
+      self.ctx.total_general_txs += 1;
+
+      match tx {
+        GeneralTransaction::TransferTx(transfer_tx) => {
+          debug!(
+            "[height: {}] process transfer tx: {:?}",
+            height, transfer_tx
+          );
+          self.exec_transfer_tx(height, transfer_tx, csmsg_id).await;
+        },
+        GeneralTransaction::AggTx(agg_tx) => {
+          debug!(
+            "[height: {}] process agg tx: {:?}", height, agg_tx
+          );
+          if let Some(exec_state) = self.executor.execute_agg_tx(&mut self.ctx, height, &agg_tx).await {
+            if let (ExecutionState::Commit, Some(csmsg_id)) = (&exec_state, csmsg_id) {
+              self.ctx.csmsg_store.update_executed(csmsg_id).await;
+            }
+            let shard_id = self.ctx.shard_id;
+            if let Some(status) = exec_state.receipt_status() {
+              let receipt = Receipt::new(
+                agg_tx.digest(), status, height,
+                shard_id, vec![agg_tx.source_shard, shard_id], None,
+              );
+              self.ctx.write_receipt(receipt).await;
+            }
+            if !matches!(exec_state, ExecutionState::Commit) {
+              warn!("Account aggregation error!")
+            }
+          }
+        },
+        GeneralTransaction::CommitMsg(msg) => {
+          debug!(
+            "[height: {}] process 2pc msg: {}", height, msg
+          );
+          match msg.phase {
+            CommitPhase::Compensate => self.ctx.compensate(height, &msg).await,
+            _ => self.executor.execute_commit_msg(&mut self.ctx, height, &msg).await,
+          }
+          // the phases are delivered once, whatever their outcome
+          if let Some(csmsg_id) = csmsg_id {
+            self.ctx.csmsg_store.update_executed(csmsg_id).await;
+          }
+        }
+      }
+      true
+    }
+
+    // execute the current frame of a transfer tx, then commit it or relay it to its next shard
+    async fn exec_transfer_tx(&mut self, height: Height, mut transfer_tx: Transaction, csmsg_id: Option<CSMsgId>) {
+      let exec_state = self.executor.execute_frame(&mut self.ctx, height, &transfer_tx).await;
+      // mark the csmsg has been executed, whatever the outcome: an aborted relayed tx is compensated
+      // below, it must not be executed again
+      if let Some(csmsg_id) = csmsg_id {
+        self.ctx.csmsg_store.update_executed(csmsg_id).await;
+      }
+      self.ctx.finish_tx(height, &transfer_tx, &exec_state).await;
+
+      match exec_state {
+        ExecutionState::Relay(next_shard) => {
+          // send this transfer tx to next step shard for execution
+          self.executor.prepare_relay(self.ctx.shard_id, &mut transfer_tx);
+          debug!(
+            "[height: {}] relay tx to shard {}: {:?}",
+            height, next_shard, transfer_tx
+          );
+          self.ctx.send_csmsg(height, next_shard, GeneralTransaction::TransferTx(transfer_tx)).await;
+        },
+        ExecutionState::Abort(_) | ExecutionState::RolledBack if transfer_tx.step > 0 => {
+          self.ctx.send_compensations(height, &transfer_tx).await;
+        },
+        _ => {},
+      }
+    }
+
+    async fn process_tx_block(&mut self, height: Height, header: Header, batch_list: Vec<Batch>) {
+
+        let mut cur_general_txs = 0;
+
+        // switch to the mapping of a new epoch, then replay the txs paused until their accounts were installed
+        self.ctx.epoch_manager.begin_block(height).await;
+        let mut txs = self.migrator.begin_block(height, &mut self.ctx.state_transition.store).await;
+        self.ctx.epoch_manager.apply_moves(self.migrator.take_moves());
+
+        let mut digest_iterator = header.payload.iter();
+        for batch in batch_list {
+
+          let digest = digest_iterator.next().unwrap().0;
+          debug!(
+            "[height: {}][header: {}] process batch: {:?}",
+            height, header, digest
+          );
+          txs.extend(verified_txs(height, batch));
+        }
+
+        // the accounts of the block are read at once and its independent txs executed concurrently,
+        // then its txs are executed in order, applying the outcomes of the independent ones
+        let accs: Vec<Address> = txs.iter().flat_map(read_accounts).collect();
+        metrics::PREFETCHED_ACCOUNTS.observe(accs.len() as f64);
+        self.ctx.state_transition.prefetch(accs).await;
+        let speculated = self.ctx.state_transition.speculate(self.ctx.shard_id, &txs).await;
+        metrics::SPECULATED_TXS.observe(speculated as f64);
+        for tx in txs {
+          if self.process_tx(height, tx).await {
+            cur_general_txs += 1;
+          }
+        }// end of for
+        self.ctx.retry_compensations(height).await;
+        self.ctx.state_transition.write_back().await;
+
+        self.executor.end_block(&mut self.ctx, height).await;
+
+        if self.evict_interval != 0 && height % self.evict_interval == 0 {
+          let evicted = self.ctx.state_transition.store.evict(height, self.evict_idle).await;
+          debug!("[height: {}] evicted {} cold accounts", height, evicted);
+        }
+
+        // commit updated states and check the result of the block against the other replicas
+        let (act_root, full_root) = self.ctx.state_transition.store.root().await;
+        let result = ExecutionResult::new(
+          height, act_root.unwrap_or_default(), full_root.unwrap_or_default(), receipts_root(&self.ctx.receipts),
+        );
+        self.ctx.receipts.clear();
+        let (diverged, certified) = self.result_checker.submit(result).await;
+        if let Some(diverged) = diverged {
+          panic!("State diverged at height {}: killing node.", diverged);
+        }
+        // the proofs of the query service are served against the last certified roots
+        if let Some(certified) = certified {
+          let root = StateRoot {
+            shard_id: self.ctx.shard_id,
+            height: certified.height,
+            act_root: certified.act_root,
+            full_root: certified.full_root,
+          };
+          self.ctx.state_transition.certify(root).await;
+        }
+        metrics::EXECUTED_HEIGHT.set(height as i64);
+
+        if cur_general_txs != 0 {
+          info!(
+            "[height: {}] total_general_txs: {}, total_external_txs: {}, total_cross_shard_txs: {}, total_commit_txs: {}, total_aborted_txs: {}",
+              height, self.ctx.total_general_txs, self.ctx.total_external_txs, self.ctx.total_cross_shard_txs,
+              self.ctx.total_commit_txs, self.ctx.total_aborted_txs
+          );
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use async_trait::async_trait;
+use config::ShardId;
+use crypto::Digest;
+use log::{debug, info, warn};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use crate::executor::{ExecutionContext, ExecutionState, Executor};
+use crate::receipt::{Receipt, ReceiptStatus};
+use crate::RWSet;
+use crate::messages::{CommitMessage, CommitPhase, GeneralTransaction, Height, Transaction};
+
+#[cfg(test)]
+#[path = "tests/executor_2pc_tests.rs"]
+pub mod executor_2pc_tests;
+
+
+// a tx coordinated by our shard, waiting for the votes of its participants
+struct PendingTx {
+    tx: Transaction,
+    // the frames executed by our shard, locked until the decision
+    local: Vec<usize>,
+    // the frames of the participants
+    remote: Vec<usize>,
+    // the frames of the participants which did not vote yet
+    waiting: BTreeSet<usize>,
+    // the tx is aborted if a vote is missing at this height
+    deadline: Height,
+}
+
+/// TwoPhaseCommit: the lock-based atomic commit baseline. The coordinator of a tx, the shard of
+/// its first frame, locks and checks its own frames, then asks the shards of the other frames to
+/// lock and prepare them. Once all of them voted yes, the frames are applied and the locks
+/// released; a no vote, or a vote missing after `timeout` heights, rolls the tx back. Txs touching
+/// a locked account are rolled back rather than waiting, so there are no deadlocks.
+pub struct TwoPCExecutor {
+    timeout: Height,
+    // the txs coordinated by our shard, by client digest
+    pending: HashMap<Digest, PendingTx>,
+    // the frames prepared by our shard as a participant, waiting for the decision
+    prepared: HashSet<(Digest, usize)>,
+    // the frames aborted before their prepare arrived (the csmsgs of the phases may be reordered),
+    // with the height of the abort
+    aborted: HashMap<(Digest, usize), Height>,
+}
+
+impl TwoPCExecutor {
+  pub fn new(timeout: Height) -> Self {
+    info!(
+      "commit_timeout: {}",
+      timeout,
+    );
+    Self { timeout, pending: HashMap::new(), prepared: HashSet::new(), aborted: HashMap::new() }
+  }
+
+  async fn send(&mut self, ctx: &mut ExecutionContext, height: Height, phase: CommitPhase, tx: &Transaction, step: usize) {
+    let msg = CommitMessage::new(ctx.shard_id, phase, tx.clone(), step);
+    debug!("[height: {}] send 2pc msg {}", height, msg);
+    ctx.send_csmsg(height, msg.target_shard(), GeneralTransaction::CommitMsg(msg)).await;
+  }
+
+  // all the votes are yes: apply our frames and tell the participants to apply theirs
+  async fn commit(&mut self, ctx: &mut ExecutionContext, height: Height, owner: &Digest) {
+    let pending = self.pending.remove(owner).unwrap();
+    for &step in &pending.local {
+      ctx.apply_frame(&pending.tx.payload[step]).await.expect("A prepared frame cannot fail");
+    }
+    ctx.state_transition.unlock(&rwsets(&pending.tx, &pending.local), owner);
+    for &step in &pending.remote {
+      self.send(ctx, height, CommitPhase::Commit, &pending.tx, step).await;
+    }
+    ctx.finish_tx(height, &pending.tx, &ExecutionState::Commit).await;
+  }
+
+  // a participant voted no (it holds no lock) or the votes timed out: release our locks and tell
+  // the other participants to release theirs
+  async fn abort(&mut self, ctx: &mut ExecutionContext, height: Height, owner: &Digest, voted_no: Option<usize>) {
+    let pending = self.pending.remove(owner).unwrap();
+    ctx.state_transition.unlock(&rwsets(&pending.tx, &pending.local), owner);
+    for &step in pending.remote.iter().filter(|step| Some(**step) != voted_no) {
+      self.send(ctx, height, CommitPhase::Abort, &pending.tx, step).await;
+    }
+    ctx.finish_tx(height, &pending.tx, &ExecutionState::RolledBack).await;
+  }
+
+  // the receipt of a frame executed by our shard as a participant
+  async fn write_receipt(&mut self, ctx: &mut ExecutionContext, height: Height, msg: &CommitMessage, status: ReceiptStatus) {
+    let receipt = Receipt::new(
+      msg.tx.client_digest(), status, height,
+      ctx.shard_id, vec![msg.source_shard, ctx.shard_id], Some(msg.tx.timestamp),
+    );
+    ctx.write_receipt(receipt).await;
+  }
+}
+
+#[async_trait]
+impl Executor for TwoPCExecutor {
+  fn name(&self) -> &'static str {
+    "TwoPCExecutor"
+  }
+
+  // the prepare phase, run by the coordinator of a client tx
+  async fn execute_frame(&mut self, ctx: &mut ExecutionContext, height: Height, tx: &Transaction) -> ExecutionState {
+    if tx.payload.first().map(|frame| frame.shardid) != Some(ctx.shard_id) {
+      warn!("[height: {}] tx {} is not coordinated by our shard", height, tx.counter);
+      return ExecutionState::RolledBack;
+    }
+    let owner = tx.client_digest();
+    let (local, remote): (Vec<usize>, Vec<usize>) =
+      (0..tx.payload.len()).partition(|step| tx.payload[*step].shardid == ctx.shard_id);
+
+    let locked = rwsets(tx, &local);
+    if !ctx.state_transition.try_lock(&locked, &owner) {
+      debug!("[height: {}] lock conflict on tx {}", height, tx.counter);
+      return ExecutionState::RolledBack;
+    }
+    for &step in &local {
+      if let Err(e) = ctx.check_frame(&tx.payload[step]).await {
+        ctx.state_transition.unlock(&locked, &owner);
+        return ExecutionState::Abort(e);
+      }
+    }
+
+    // an intra-shard tx commits at once
+    if remote.is_empty() {
+      for &step in &local {
+        ctx.apply_frame(&tx.payload[step]).await.expect("A checked frame cannot fail");
+      }
+      ctx.state_transition.unlock(&locked, &owner);
+      return ExecutionState::Commit;
+    }
+
+    for &step in &remote {
+      self.send(ctx, height, CommitPhase::Prepare, tx, step).await;
+    }
+    let waiting = remote.iter().cloned().collect();
+    let deadline = height + self.timeout;
+    self.pending.insert(owner, PendingTx { tx: tx.clone(), local, remote, waiting, deadline });
+    ExecutionState::Pending
+  }
+
+  // the txs are never relayed: the coordinator talks to each participant
+  async fn relay(&mut self, _ctx: &mut ExecutionContext, _height: Height, _tx: &Transaction) -> Option<ShardId> {
+    None
+  }
+
+  async fn execute_commit_msg(&mut self, ctx: &mut ExecutionContext, height: Height, msg: &CommitMessage) {
+    let owner = msg.tx.client_digest();
+    let step = msg.tx.step;
+    let frame = match msg.frame() {
+      Some(frame) => frame,
+      None => return,
+    };
+
+    match msg.phase {
+      // participant: lock and check our frame, then vote
+      CommitPhase::Prepare => {
+        if self.aborted.remove(&(owner.clone(), step)).is_some() {
+          return;
+        }
+        let vote = if ctx.state_transition.try_lock(&frame.rwset, &owner) {
+          match ctx.check_frame(frame).await {
+            Ok(()) => true,
+            Err(_) => {
+              ctx.state_transition.unlock(&frame.rwset, &owner);
+              false
+            }
+          }
+        } else {
+          false
+        };
+        if vote {
+          self.prepared.insert((owner, step));
+        }
+        self.send(ctx, height, CommitPhase::Vote(vote), &msg.tx, step).await;
+      },
+
+      // coordinator: commit once all the participants voted yes, abort at the first no
+      CommitPhase::Vote(vote) => {
+        let all_voted = match self.pending.get_mut(&owner) {
+          // the tx was aborted before this yes vote arrived: the participant still holds its locks
+          None => {
+            if vote {
+              self.send(ctx, height, CommitPhase::Abort, &msg.tx, step).await;
+            }
+            return;
+          },
+          Some(pending) => {
+            pending.waiting.remove(&step);
+            pending.waiting.is_empty()
+          },
+        };
+        if !vote {
+          self.abort(ctx, height, &owner, Some(step)).await;
+        } else if all_voted {
+          self.commit(ctx, height, &owner).await;
+        }
+      },
+
+      // participant: apply our frame and release its locks
+      CommitPhase::Commit => {
+        if self.prepared.remove(&(owner.clone(), step)) {
+          ctx.apply_frame(frame).await.expect("A prepared frame cannot fail");
+          ctx.state_transition.unlock(&frame.rwset, &owner);
+          self.write_receipt(ctx, height, msg, ReceiptStatus::Committed).await;
+        }
+      },
+
+      // participant: release the locks of our frame
+      CommitPhase::Abort => {
+        if self.prepared.remove(&(owner.clone(), step)) {
+          ctx.state_transition.unlock(&frame.rwset, &owner);
+          self.write_receipt(ctx, height, msg, ReceiptStatus::RolledBack).await;
+        } else {
+          self.aborted.insert((owner, step), height);
+        }
+      },
+
+      // the txs are never relayed, so there is nothing to compensate
+      CommitPhase::Compensate => {},
+    }
+  }
+
+  // abort the txs whose votes timed out, in the same order on every replica
+  async fn end_block(&mut self, ctx: &mut ExecutionContext, height: Height) {
+    let mut expired: Vec<Digest> = self.pending
+      .iter()
+      .filter(|(_, pending)| pending.deadline <= height)
+      .map(|(owner, _)| owner.clone())
+      .collect();
+    expired.sort();
+    for owner in expired {
+      warn!("[height: {}] votes of tx {} timed out", height, self.pending[&owner].tx.counter);
+      self.abort(ctx, height, &owner, None).await;
+    }
+
+    // a prepare arriving after this lock its frame, then its yes vote is answered by an abort
+    let timeout = self.timeout;
+    self.aborted.retain(|_, aborted_at| *aborted_at + timeout > height);
+  }
+}
+
+
+// the rwsets of some frames of a tx
+fn rwsets(tx: &Transaction, steps: &[usize]) -> Vec<RWSet> {
+  steps.iter().flat_map(|step| tx.payload[*step].rwset.iter().cloned()).collect()
+}
@@ -1,344 +1,146 @@
-use config::ShardId;
-use primary::Header;
-use tokio::sync::mpsc::{Receiver, Sender};
-use log::{debug, info, warn};
-use crate::csmsg_store::CSMsgStore;
+use async_trait::async_trait;
+use config::{Parameters, ShardId};
+use log::{debug, info};
+use crate::executor::{ExecutionContext, ExecutionState, Executor};
+use crate::metrics;
 use crate::state_store::AvatartStore;
-use crate::{Frame, Account2Shard, StateTransition};
-use crate::batch_maker::Batch;
-use crate::worker::{SynchronizationMessage, SendCSMessage};
-use crate::messages::{GeneralTransaction, Height, RWSet, AggTransaction};
+use crate::Frame;
+use crate::messages::{GeneralTransaction, Height, AggTransaction, Transaction};
 use crate::utils::AllocatedSize;
-use num_enum::TryFromPrimitive;
-extern crate csv;
-extern crate serde_derive;
 
+#[cfg(test)]
+#[path = "tests/executor_s_tests.rs"]
+pub mod executor_s_tests;
 
-#[derive(TryFromPrimitive, Debug)]
-#[repr(usize)]
-pub enum ExecutorType {
-  SharDAG,
-  Monoxide,
-  BrokerChain,
-}
-
-pub enum ExecutionState {
-  Commit,
-  Abort,
-  Relay,
-}
 
+/// SharDAG: a tx is relayed only to the shard of a deduction. The additions of the remaining
+/// frames are cached as avatar accounts, and aggregated into an agg tx to each shard when a
+/// trigger fires (see `due_shards`). The target shard checks the avatars against the root
+/// committed by the agg tx, and against the avatars cached at each height if they are included.
 pub struct SExecutor {
-    // node config
-    shard_id: ShardId,
-
-    // channel
-    rx_process_txs: Receiver<SynchronizationMessage>,
-    tx_csmsg: Sender<SendCSMessage>,
-
-    // state store
-    state_transition: StateTransition,
     avatar_store: AvatartStore,
-    _acc2shard: Box<dyn Account2Shard + Send>,
-    csmsg_store: CSMsgStore,
 
-    // parameters 控制账户聚合的参数
-    agg_interval: usize,
-
-    // pending_tx_list: Vec<PendingTx>,
-
-    // statistical info
-    total_general_txs: u32,
-    total_external_txs: u32,
-    total_cross_shard_txs: u32,
-    total_commit_txs: u32,
-    total_aborted_txs: u32,
+    // parameters 控制账户聚合的参数, zero disables a trigger
+    agg_interval: u64,
+    agg_max_bytes: usize,
+    agg_max_accounts: usize,
+    agg_max_age: u64,
 }
 
-
 impl SExecutor {
-    #[allow(clippy::too_many_arguments)]
-    pub fn spawn(
-
-      // node config
-      shard_id: ShardId,
-
-      rx_process_txs: Receiver<SynchronizationMessage>,
-      tx_csmsg: Sender<SendCSMessage>,
-
-      // state store
-      state_transition: StateTransition,
-      _acc2shard: Box<dyn Account2Shard + Send>,
-      csmsg_store: CSMsgStore,
-    ) {
-        
-        tokio::spawn(async move {
-          Self {
-            shard_id,
-            state_transition,
-            avatar_store: AvatartStore::new(),
-            _acc2shard,
-            csmsg_store,
-            rx_process_txs,
-            tx_csmsg,
-            agg_interval: 100,
-            
-            total_general_txs: 0,
-            total_external_txs: 0,
-            total_commit_txs: 0,
-            total_cross_shard_txs: 0,
-            total_aborted_txs: 0,
-          }
-          .run()
-          .await;
-      });
+  pub fn new(parameters: &Parameters) -> Self {
+    info!(
+      "agg_interval: {}, agg_max_bytes: {}, agg_max_accounts: {}, agg_max_age: {}, agg_deltas: {}",
+      parameters.agg_interval, parameters.agg_max_bytes, parameters.agg_max_accounts, parameters.agg_max_age,
+      parameters.agg_deltas,
+    );
+    Self {
+      avatar_store: AvatartStore::new(parameters.agg_deltas),
+      agg_interval: parameters.agg_interval,
+      agg_max_bytes: parameters.agg_max_bytes,
+      agg_max_accounts: parameters.agg_max_accounts,
+      agg_max_age: parameters.agg_max_age,
     }
+  }
+
+  // the target shards whose avatars are aggregated at this height, with the trigger that fired:
+  // every shard every `agg_interval` heights or once the avatars take `agg_max_bytes`, otherwise
+  // the shards with `agg_max_accounts` avatars or whose oldest avatar is `agg_max_age` heights old
+  fn due_shards(&self, height: Height) -> Vec<(ShardId, &'static str)> {
+    let all = if self.agg_interval != 0 && height % self.agg_interval == 0 {
+      Some("height")
+    } else if self.agg_max_bytes != 0 && self.avatar_store.bytes() >= self.agg_max_bytes {
+      Some("memory")
+    } else {
+      None
+    };
+    let mut shards: Vec<(ShardId, &'static str)> = self.avatar_store.avatars
+      .iter()
+      .filter(|(_, avatars)| !avatars.is_empty())
+      .filter_map(|(shard, avatars)| {
+        let trigger = all
+          .or_else(|| {
+            Some("accounts").filter(|_| self.agg_max_accounts != 0 && avatars.len() >= self.agg_max_accounts)
+          })
+          .or_else(|| {
+            let since = self.avatar_store.since(*shard)?;
+            Some("age").filter(|_| self.agg_max_age != 0 && height >= since + self.agg_max_age)
+          })?;
+        Some((*shard, trigger))
+      })
+      .collect();
+    // the agg txs are sent in the same order by every replica
+    shards.sort();
+    shards
+  }
+}
 
-    /// Main loop listening to the messages.
-    async fn run(&mut self) {
-
-      info!("SExecutor is running!");
- 
-      info!(
-        "agg_interval: {}",
-        self.agg_interval,
-      );
-      
-      while let Some(SynchronizationMessage{height, header, batch_list}) = self.rx_process_txs.recv().await {
-        debug!(
-          "[height: {}][header: {}] Receiving TxBlock msg",
-          height, header
-        );    
-        self.process_tx_block(height, header, batch_list).await;        
+#[async_trait]
+impl Executor for SExecutor {
+  fn name(&self) -> &'static str {
+    "SExecutor"
+  }
+
+  async fn relay(&mut self, _ctx: &mut ExecutionContext, height: Height, tx: &Transaction) -> Option<ShardId> {
+    // check if the transaction needs to be relayed
+    if _is_need_relay(&tx.payload, tx.step+1) {
+      Some(tx.next_shard())
+    } else {
+      // caches avatar accounts and commit this transfer tx
+      for frame in &tx.payload[tx.step+1..] {
+        // caches avatar accounts for this frame
+        self.avatar_store.insert(height, frame);
       }
+      debug!("update avatar store: {:?}", self.avatar_store);
+      None
     }
-
-
-    // verify tx before execution, ignore invalid tx or redundant csmsg tx
-    // return (is_csmsg, is_valid)
-    async fn verify_tx(&mut self, tx: &GeneralTransaction) -> (Option<String>, bool) {
-      match tx.get_csmsg_id() {
-        None => {
-          (None, true) // TODO: we assume that intra-shard tx is identical
-        }, // not a csmsg
-        Some(csmsg_id) => {
-          // verify the threshold sig and get tx_hash
-          let tx_hash = tx.verify_cs_proof();
-          match tx_hash {
-            None => { // invalid csmsg
-              (Some(csmsg_id), false)
-            },
-            Some(_) => {// valid csmsg
-              let can_executed = self.csmsg_store.can_executed(csmsg_id.clone()).await.unwrap();
-              if can_executed {
-                (Some(csmsg_id), true)
-              } else {
-                (Some(csmsg_id), false)
-              }
-            }                            
-          }    
-        }                        
-      }  
-    }
-
-    async fn process_tx_block(&mut self, height: Height, header: Header, batch_list: Vec<Batch>) {
-
-        let mut cur_general_txs = 0;
-
-        let mut digest_iterator = header.payload.iter();
-        for batch in batch_list {
-
-          let digest = digest_iterator.next().unwrap().0;
-          debug!(
-            "[height: {}][header: {}] process batch: {:?}",
-            height, header, digest
-          ); 
-
-          for tx in batch.tx_list {
-            // verify tx before execution, ignore invalid tx or redundant csmsg tx
-            let (csmsg_id, is_valid) = self.verify_tx(&tx).await;
-            if !is_valid {
-              info!("redundant csmsg tx");
-              continue;
-            }
-
-            self.total_general_txs += 1;
-            cur_general_txs += 1;
-
-            match tx {
-              GeneralTransaction::TransferTx(mut transfer_tx) => {
-                debug!(
-                  "[height: {}] process transfer tx: {:?}",
-                  height, transfer_tx 
-                );
-
-                let exec_state = self.exec_tx(&transfer_tx.payload, transfer_tx.step, csmsg_id).await;                
-
-                match exec_state {
-                  ExecutionState::Commit => {
-                    debug!(
-                      "[height: {}] commit tx: {:?}",
-                      height, transfer_tx.get_digest() 
-                    );  
-                    self.total_external_txs += 1;
-                    self.total_cross_shard_txs += transfer_tx.count_cs_tx();
-                    self.total_commit_txs += 1;
-                    // output sample info
-                    if transfer_tx.sample == 0 {// sample tx
-                      info!(
-                        "Successfully execute sample tx {} in batch",
-                          transfer_tx.counter,
-                      );
-                    }
-                  },
-                  ExecutionState::Relay => {
-                    // send this transfer tx to next step shard for execution
-                    let next_shard = transfer_tx.update_relay_info(self.shard_id);
-                    debug!(
-                      "[height: {}] relay tx: {:?}",
-                      height, transfer_tx 
-                    );
-                    // send csmsg to target shard
-                    let message = SendCSMessage{height, target_shard: next_shard, tx: GeneralTransaction::TransferTx(transfer_tx)};
-                    self.tx_csmsg
-                        .send(message)
-                        .await
-                        .expect("Failed to send new block to ADSynchronizer");
-                  },
-                  ExecutionState::Abort => {
-                      self.total_external_txs += 1;
-                      self.total_cross_shard_txs += transfer_tx.count_cs_tx();                      
-                      self.total_aborted_txs += 1;
-                      warn!(
-                        "[height: {}]insufficient balance, fail to execute tx: {:?}",
-                        height, transfer_tx
-                      );
-                  }           
-                }
-              },
-              GeneralTransaction::AggTx(agg_tx) => {
-                debug!(
-                  "[height: {}] process agg tx: {:?}", height, agg_tx
-                );
-                let exec_state = self.exec_tx(&agg_tx.payload, 0, csmsg_id).await;
-                match exec_state {
-                  ExecutionState::Commit => {},
-                  _ => {
-                    warn!("Account aggregation error!")
-                  }                    
-                }
-              }
-            }
-          }// end of for 
-        }
-
-        // commit updated states
-        let _ = self.state_transition.store.root().await;    
-        
-        if cur_general_txs != 0 {
-          info!(
-            "[height: {}] total_general_txs: {}, total_external_txs: {}, total_cross_shard_txs: {}, total_commit_txs: {}, total_aborted_txs: {}",
-              height, self.total_general_txs, self.total_external_txs, self.total_cross_shard_txs, self.total_commit_txs, self.total_aborted_txs
-          );
-        }
-
-        // account aggregation  
-        if height % self.agg_interval as u64 == 0 {
-            info!(
-              "[height: {}] Storage cost of acc caching: {} KB",
-                height, self.avatar_store.avatars.allocated_size() / 1024
-            );
-
-            for (shardid, avatars) in self.avatar_store.avatars.drain(){
-                debug!("Generate agg tx containing {} avatar accs for shard {}", avatars.len(), shardid);
-                let mut rwset: Vec<RWSet> = avatars
-                    .into_iter()
-                    .map(|(addr, value)| RWSet { addr, value })
-                    .collect();
-                rwset.sort_by(|a, b| a.addr.cmp(&b.addr));
-
-                let payload_len = rwset.len();
-                let payload = vec![Frame{shardid, rwset}];
-                let agg_tx = AggTransaction::new(self.shard_id, payload, payload_len);
-
-                // send csmsg to target shard
-                let message = SendCSMessage{height, target_shard: shardid, tx: GeneralTransaction::AggTx(agg_tx)};
-                self.tx_csmsg
-                    .send(message)
-                    .await
-                    .expect("Failed to send new block to ADSynchronizer");
-            }
-            // info!("after acc agg, the avatar store: {:?}", self.state_store.avatar_states);
-        }
+  }
+
+  async fn execute_agg_tx(
+    &mut self,
+    ctx: &mut ExecutionContext,
+    _height: Height,
+    agg_tx: &AggTransaction,
+  ) -> Option<ExecutionState> {
+    let exec_state = match ctx.apply_frame(agg_tx.payload.first().unwrap()).await {
+      Ok(()) => ExecutionState::Commit,
+      Err(e) => ExecutionState::Abort(e),
+    };
+    Some(exec_state)
+  }
+
+  async fn end_block(&mut self, ctx: &mut ExecutionContext, height: Height) {
+    // account aggregation
+    let due_shards = self.due_shards(height);
+    if !due_shards.is_empty() {
+        info!(
+          "[height: {}] Storage cost of acc caching: {} KB",
+            height, self.avatar_store.avatars.allocated_size() / 1024
+        );
     }
 
-    
-    // execute the step-th frame in payload
-    async fn exec_tx (&mut self, payload: &Vec<Frame>, step: usize, csmsg_id: Option<String>) -> ExecutionState {
-      // get the latest states of the involved accs
-      let frame = payload.get(step).unwrap();
-      let mut latest_states = self.state_transition.get_latest_states(&frame.rwset).await;
-      debug!{"latest states: {:?}", latest_states};
+    for (shardid, trigger) in due_shards {
+        let (rwset, deltas) = self.avatar_store.take(shardid);
+        debug!("Generate agg tx containing {} avatar accs for shard {} ({})", rwset.len(), shardid, trigger);
 
-      // balance check
-      let mut pass_check = true;
-      for rwset in &frame.rwset {
-        let acc = latest_states.get_mut(&rwset.addr).unwrap();
-        acc.balance += rwset.value;
+        let payload_len = rwset.len();
+        let payload = vec![Frame{shardid, rwset}];
+        let agg_tx = AggTransaction::new(ctx.shard_id, payload, payload_len, deltas);
+        metrics::AGG_TXS.with_label_values(&[trigger]).inc();
+        metrics::AGG_TX_ACCOUNTS.observe(payload_len as f64);
+        metrics::AGG_TX_SIZE_BYTES.observe(agg_tx.len() as f64);
 
-        if rwset.value < 0.0 { // deduction
-          if acc.balance < 0.0 { // the balance is negative after execution
-            pass_check = false;
-            break;
-          }
-          acc.nonce +=1;          
-        }
-      }
-
-      if !pass_check {
-        return ExecutionState::Abort;
-      } 
-      // pass check
-      self.state_transition.apply_new_states(latest_states).await;
-
-      // this tx is executed successfully, mark the csmsg has been executed
-      match csmsg_id {
-        None => {},
-        Some(csmsg_id) => {
-          self.csmsg_store.update_executed(csmsg_id).await;
-        }
-      }
-
-      // check if the transaction needs to be relayed
-      if self._is_need_relay(&payload, step+1) {
-        ExecutionState::Relay
-      } else {
-        // caches avatar accounts and commit this transfer tx
-        for frame in &payload[step+1..] {
-          // caches avatar accounts for this frame
-          self.avatar_store.insert(frame);
-        }
-        debug!("update avatar store: {:?}", self.avatar_store);
-        ExecutionState::Commit   
-      }
+        // send csmsg to target shard
+        ctx.send_csmsg(height, shardid, GeneralTransaction::AggTx(agg_tx)).await;
     }
+    metrics::AVATAR_STORE_BYTES.set(self.avatar_store.bytes() as i64);
+  }
+}
 
-
-    // check if the step-th frame in payload contains a deduction operation
-    fn _is_need_relay(&self, payload: &Vec<Frame>, step: usize) -> bool{
-      let frame = payload.get(step);
-      match frame {
-        None => { // end of payload
-          false
-        },
-        Some(frame) => {
-          for rwset in &frame.rwset{
-            if rwset.value < 0.0 {
-              return true
-            }
-          }
-          return false
-        }          
-      }      
-    }
-}
\ No newline at end of file
+// check if the step-th frame in payload contains a deduction operation
+fn _is_need_relay(payload: &[Frame], step: usize) -> bool {
+  match payload.get(step) {
+    None => false, // end of payload
+    Some(frame) => frame.rwset.iter().any(|rwset| rwset.value.is_negative()),
+  }
+}
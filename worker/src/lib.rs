@@ -1,4 +1,6 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
+#[macro_use]
+mod error;
 mod batch_maker;
 mod helper;
 mod primary_connector;
@@ -6,22 +8,37 @@ mod processor;
 mod quorum_waiter;
 mod synchronizer;
 mod worker;
-mod error;
 mod messages;
+mod amount;
 mod cs_msg_verifier;
 mod cs_msg_verifier_serial;
 mod tx_convertor;
+mod executor;
 mod executor_s;
 mod executor_m;
 mod executor_b;
+mod executor_2pc;
 mod cs_msg_sender;
 mod cs_msg_sender_b;
+mod cs_msg_acker;
 mod utils;
 mod acc_shard;
 mod csmsg_store;
 mod batch_fetcher;
 mod state_store;
 mod state_transition;
+mod scheduler;
+mod epoch;
+mod partitioner;
+mod migration;
+mod migration_sender;
+mod migration_verifier;
+mod execution_result;
+mod result_checker;
+mod receipt;
+mod query;
+mod metrics;
+mod outstanding;
 
 // #[cfg(test)]
 // #[path = "tests/common.rs"]
@@ -29,13 +46,22 @@ mod state_transition;
 
 
 pub use crate::worker::Worker;
+pub use crate::error::ExecutionError;
 pub use crate::messages::GeneralTransaction;
-pub use crate::messages::{Transaction, Frame, Amount};
-pub use crate::messages::{Address, RWSet, RawTxOld, CoreTx, CSMsg};
-pub use crate::acc_shard::{Account2ShardHash, Account2ShardType, Account2Shard, Account2ShardGraph, AccToShardItem};
-pub use crate::executor_s::ExecutorType;
+pub use crate::messages::{Transaction, Frame};
+pub use crate::amount::{Amount, AmountError};
+pub use crate::messages::{Address, RWSet, RawTxOld, CoreTx, CSMsg, CSMsgId, CommitMessage, CommitPhase};
+pub use crate::acc_shard::{Account2ShardHash, Account2ShardType, Account2Shard, Account2ShardGraph, AccToShardItem, AccountKeyItem};
+pub use crate::executor::{ExecutorType, Executor, ExecutionContext, ExecutionState};
 pub use crate::cs_msg_verifier::{CSMsgVerifier, AppendType};
 pub use crate::csmsg_store::{CSMsgStore, AppendedType};
 pub use crate::utils::random_select_brokers;
-pub use state_store::{StateStoreType, StateStore, TStore, MStore, INIT_BALANCE, new_primary_store, BrokerItem, Account, RawState};
-pub use crate::state_transition::StateTransition;
\ No newline at end of file
+pub use state_store::{StateStoreType, StateStore, TStore, MStore, INIT_BALANCE, new_primary_store, BrokerItem, Account, RawState, AccountProof, KeyRegistry, load_key_registry};
+pub use crate::state_transition::StateTransition;
+pub use crate::migration::MigrationPlan;
+pub use crate::epoch::{EpochSchedule, EpochManager, NextAcc2Shard, epoch_file};
+pub use crate::partitioner::InteractionGraph;
+pub use crate::execution_result::{ExecutionResult, ExecutionVote, ExecutionCertificate, receipts_root};
+pub use crate::result_checker::{ResultChecker, result_key, certificate_key, CERTIFIED_HEIGHT_KEY};
+pub use crate::receipt::{Receipt, ReceiptStatus, ReceiptStore};
+pub use crate::query::{QueryService, StateQuery, StateRoot, StateProof, RpcRequest, RpcResponse, RpcError};
\ No newline at end of file
@@ -1,18 +1,120 @@
 use bytes::BytesMut;
 use config::Committees;
 use config::Export;
-use crypto::{Digest, Hash, PublicKey, Signature, SignatureService};
+use crypto::{verify_ts_sig, Digest, Hash, PartialSignature, PublicKey, SecretKey, Signature, SignatureService, ThresholdSignature};
 use config::ShardId;
 use ed25519_dalek::Digest as _;
 use ed25519_dalek::Sha512;
 use serde::{Deserialize, Serialize};
+use mpt::{Proof, RootHash};
+use std::collections::{BTreeMap, HashMap};
 use std::convert::TryInto;
 use std::fmt;
+use crate::amount::Amount;
+use crate::execution_result::ExecutionVote;
+use crate::error::{ExecutionError, ExecutionResult};
 
 
+#[cfg(test)]
+#[path = "tests/messages_tests.rs"]
+pub mod messages_tests;
+
 pub type Height = u64;
 pub type Address = Vec<u8>;
-pub type Amount = f64;
+
+/// The version of the canonical digest scheme of `Transaction`, `AggTransaction`, `CommitMessage`,
+/// `CSMsg`, `CSMsgAck`, `Migration`, `CertifiedMigration`, `ExecutionResult`, `ExecutionVote` and
+/// `Receipt`. Bump it whenever the set or the encoding of the hashed fields changes.
+pub const DIGEST_VERSION: u8 = 10;
+
+// 规范化编码：每个摘要以类型标签和版本号开头，整数统一为小端 u64，变长字段带长度前缀
+pub(crate) fn canonical_hasher(domain: &[u8]) -> Sha512 {
+  let mut hasher = Sha512::new();
+  hasher.update(domain);
+  hasher.update([DIGEST_VERSION]);
+  hasher
+}
+
+pub(crate) fn update_bytes(hasher: &mut Sha512, bytes: &[u8]) {
+  hasher.update((bytes.len() as u64).to_le_bytes());
+  hasher.update(bytes);
+}
+
+fn update_address_option(hasher: &mut Sha512, addr: &Option<Address>) {
+  match addr {
+    None => hasher.update([0u8]),
+    Some(addr) => {
+      hasher.update([1u8]);
+      update_bytes(hasher, addr);
+    }
+  }
+}
+
+fn update_payload(hasher: &mut Sha512, payload: &[Frame]) {
+  hasher.update((payload.len() as u64).to_le_bytes());
+  for frame in payload {
+    hasher.update((frame.shardid as u64).to_le_bytes());
+    hasher.update((frame.rwset.len() as u64).to_le_bytes());
+    for rwset in frame.rwset.iter() {
+      update_bytes(hasher, &rwset.addr);
+      hasher.update(rwset.value.to_le_bytes());
+    }
+  }
+}
+
+// addresses are hashed in ascending order, whatever the order of the map they come from
+fn update_addresses<'a>(hasher: &mut Sha512, addrs: impl Iterator<Item = &'a Address>) {
+  let mut addrs: Vec<_> = addrs.collect();
+  addrs.sort();
+  hasher.update((addrs.len() as u64).to_le_bytes());
+  for addr in addrs {
+    update_bytes(hasher, addr);
+  }
+}
+
+pub(crate) fn finalize(hasher: Sha512) -> Digest {
+  Digest(hasher.finalize().as_slice()[..32].try_into().unwrap())
+}
+
+fn update_csmsg_id(hasher: &mut Sha512, id: &CSMsgId) {
+  hasher.update((id.source as u64).to_le_bytes());
+  hasher.update((id.target as u64).to_le_bytes());
+  hasher.update(id.epoch.to_le_bytes());
+  hasher.update(id.seq.to_le_bytes());
+}
+
+
+/// The identifier of a cross-shard message. Sequence numbers are assigned per (source, target)
+/// pair and the epoch never decreases along them, so ids are ordered by sequence within a pair.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct CSMsgId {
+  pub source: ShardId,
+  pub target: ShardId,
+  pub epoch: u64,
+  pub seq: u64,
+}
+
+impl CSMsgId {
+  pub fn new(source: ShardId, target: ShardId, epoch: u64, seq: u64) -> Self {
+    Self { source, target, epoch, seq }
+  }
+}
+
+impl fmt::Display for CSMsgId {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      write!(f, "[{}->{}, epoch: {}, seq: {}]", self.source, self.target, self.epoch, self.seq)
+  }
+}
+
+
+/// The messages received on the `cross_shard_worker` address.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum CrossShardMessage {
+    CSMsg(CSMsg),
+    CSMsgAck(CSMsgAck),
+    Migration(Migration),
+    ExecutionVote(ExecutionVote),
+}
 
 
 /// The message exchanged between workers from different shards.
@@ -20,6 +122,8 @@ pub type Amount = f64;
 pub enum GeneralTransaction {
     TransferTx(Transaction), // original_tx or relayed_tx 原始交易或转发交易
     AggTx(AggTransaction), // 聚合交易
+    CommitMsg(CommitMessage), // two-phase commit
+    MigrationTx(CertifiedMigration), // incoming accounts, installed after the block appending them
 }
 
 impl GeneralTransaction {
@@ -31,6 +135,8 @@ impl GeneralTransaction {
       GeneralTransaction::TransferTx(tx) => {
         tx.counter
       }
+      GeneralTransaction::CommitMsg(msg) => msg.tx.counter,
+      GeneralTransaction::MigrationTx(_) => 0,
     }
   }
 
@@ -42,26 +148,41 @@ impl GeneralTransaction {
       }
   }
   // 设置门限签名
-  pub fn set_thres_sig(&mut self, thres_sig: Signature, source_shard: ShardId) {
+  pub fn set_thres_sig(&mut self, thres_sig: ThresholdSignature, csmsg_id: CSMsgId) {
     match self {
-      GeneralTransaction::TransferTx(tx) => tx.set_thres_sig(thres_sig, source_shard),
+      GeneralTransaction::TransferTx(tx) => tx.set_thres_sig(thres_sig, csmsg_id),
       GeneralTransaction::AggTx(tx) => tx.set_thres_sig(thres_sig),
+      GeneralTransaction::CommitMsg(msg) => msg.set_thres_sig(thres_sig),
+      GeneralTransaction::MigrationTx(_) => {},
     }
   }
 
   // return csmsg_id if the tx is csmsg
-  pub fn get_csmsg_id(&self) -> Option<String> {
+  pub fn get_csmsg_id(&self) -> Option<CSMsgId> {
     match self {
       GeneralTransaction::TransferTx(tx) => tx.get_csmsg_id(),
       GeneralTransaction::AggTx(tx) => tx.get_csmsg_id(),
+      GeneralTransaction::CommitMsg(msg) => msg.get_csmsg_id(),
+      GeneralTransaction::MigrationTx(_) => None,
     }
   }
 
-  // 设置csmsg的序列号
-  pub fn set_csmsg_seq(&mut self, csmsg_seq: u64) {
+  // 设置csmsg的序列号和 epoch
+  pub fn set_csmsg_header(&mut self, csmsg_seq: u64, epoch: u64) {
     match self {
-      GeneralTransaction::TransferTx(tx) => tx.set_csmsg_sequence(csmsg_seq),
-      GeneralTransaction::AggTx(tx) => tx.set_csmsg_sequence(csmsg_seq),
+      GeneralTransaction::TransferTx(tx) => {
+        tx.set_csmsg_sequence(csmsg_seq);
+        tx.set_csmsg_epoch(epoch);
+      },
+      GeneralTransaction::AggTx(tx) => {
+        tx.set_csmsg_sequence(csmsg_seq);
+        tx.set_csmsg_epoch(epoch);
+      },
+      GeneralTransaction::CommitMsg(msg) => {
+        msg.set_csmsg_sequence(csmsg_seq);
+        msg.set_csmsg_epoch(epoch);
+      },
+      GeneralTransaction::MigrationTx(_) => {},
     }
   }
 
@@ -71,6 +192,8 @@ impl GeneralTransaction {
     match self {
       GeneralTransaction::TransferTx(tx) => tx.payload_hash.clone(),
       GeneralTransaction::AggTx(tx) => tx.payload_hash.clone(),
+      GeneralTransaction::CommitMsg(msg) => msg.tx.payload_hash.clone(),
+      GeneralTransaction::MigrationTx(migration) => migration.migration.header_digest(),
     }
   }
 
@@ -78,7 +201,7 @@ impl GeneralTransaction {
   pub fn count_packaged_external_tx(&self) -> u32 {
     match self {
       GeneralTransaction::TransferTx(tx) => tx.count_packaged_external_tx(),
-      GeneralTransaction::AggTx(_) => 0,
+      GeneralTransaction::AggTx(_) | GeneralTransaction::CommitMsg(_) | GeneralTransaction::MigrationTx(_) => 0,
     }
   }
 
@@ -86,7 +209,7 @@ impl GeneralTransaction {
   pub fn count_external_tx(&self) -> u32 {
     match self {
       GeneralTransaction::TransferTx(_) => 1,
-      GeneralTransaction::AggTx(_) => 0,
+      GeneralTransaction::AggTx(_) | GeneralTransaction::CommitMsg(_) | GeneralTransaction::MigrationTx(_) => 0,
     }
   }
 
@@ -95,7 +218,7 @@ impl GeneralTransaction {
       GeneralTransaction::TransferTx(tx) => {
         tx.count_cs_tx()
       },
-      GeneralTransaction::AggTx(_) => 0,
+      GeneralTransaction::AggTx(_) | GeneralTransaction::CommitMsg(_) | GeneralTransaction::MigrationTx(_) => 0,
     }
   }
 
@@ -103,6 +226,8 @@ impl GeneralTransaction {
     match self {
       GeneralTransaction::TransferTx(tx) => tx.len(),
       GeneralTransaction::AggTx(tx) => tx.len(),
+      GeneralTransaction::CommitMsg(msg) => msg.len(),
+      GeneralTransaction::MigrationTx(migration) => migration.migration.len(),
     }
   }
 
@@ -110,13 +235,17 @@ impl GeneralTransaction {
     match self {
       GeneralTransaction::TransferTx(tx) => tx.digest(),
       GeneralTransaction::AggTx(agg_tx) => agg_tx.digest(),
+      GeneralTransaction::CommitMsg(msg) => msg.digest(),
+      GeneralTransaction::MigrationTx(migration) => migration.digest(),
     }
   }
 
   // TODO
-  pub fn get_thres_sig(&self) -> Signature {
+  pub fn get_thres_sig(&self) -> ThresholdSignature {
     match self {
       GeneralTransaction::AggTx(agg_tx) => agg_tx.thres_sig.clone(),
+      GeneralTransaction::CommitMsg(msg) => msg.thres_sig.clone(),
+      GeneralTransaction::MigrationTx(migration) => migration.cert.clone(),
       GeneralTransaction::TransferTx(tx) => tx.cs_proof.last().unwrap().1.clone(),
     }
   }
@@ -128,20 +257,56 @@ impl GeneralTransaction {
     }
   }
 
-  // TODO
-  pub fn verify(&self, _all_committees: &Committees) -> bool {
+  // the client signature to check before packaging the tx: only the txs submitted by the clients
+  // carry one, relayed txs and agg_txs are certified by the threshold signature of their source shard
+  pub fn client_signature(&self) -> Option<(Digest, PublicKey, Signature)> {
     match self {
-      _ => {return true}
+      GeneralTransaction::TransferTx(tx) if tx.get_csmsg_id().is_none() => Some(tx.client_signature()),
+      _ => None,
     }
   }
 
-  // TODO
-  // verify the thresold sig of csmsg (relayed transfer tx or agg_tx)
-  // return Some(tx_hash) if pass verification; else return None
-  pub fn verify_cs_proof(&self) -> Option<Digest> {
-    let tx_hash = self.get_digest();
-    let _thres_sig = self.get_thres_sig();
-    Some(tx_hash)
+  // verify the cs_proof of csmsg or the certificate of a migration; intra-shard txs have no proof
+  // to check
+  pub fn verify(&self, all_committees: &Committees) -> ExecutionResult<()> {
+    match self {
+      GeneralTransaction::MigrationTx(migration) => migration.verify(all_committees),
+      _ => match self.get_csmsg_id() {
+        None => Ok(()),
+        Some(_) => self.verify_cs_proof(all_committees).map(|_| ()),
+      },
+    }
+  }
+
+  // verify the thresold sig of csmsg (relayed transfer tx, agg_tx or 2pc msg)
+  // return the tx_hash if pass verification
+  pub fn verify_cs_proof(&self, all_committees: &Committees) -> ExecutionResult<Digest> {
+    match self {
+      GeneralTransaction::AggTx(agg_tx) => {
+        let tx_hash = agg_tx.digest();
+        let key_set = all_committees.threshold_keys(&agg_tx.source_shard)?;
+        ensure!(
+          verify_ts_sig(&key_set.public_key, &tx_hash, &agg_tx.thres_sig),
+          ExecutionError::InvalidThresholdSignature(agg_tx.source_shard)
+        );
+        agg_tx.verify_payload()?;
+        Ok(tx_hash)
+      },
+      GeneralTransaction::CommitMsg(msg) => {
+        let tx_hash = msg.digest();
+        let key_set = all_committees.threshold_keys(&msg.source_shard)?;
+        ensure!(
+          verify_ts_sig(&key_set.public_key, &tx_hash, &msg.thres_sig),
+          ExecutionError::InvalidThresholdSignature(msg.source_shard)
+        );
+        Ok(tx_hash)
+      },
+      GeneralTransaction::TransferTx(tx) => tx.verify_cs_proof(all_committees),
+      GeneralTransaction::MigrationTx(migration) => {
+        migration.verify(all_committees)?;
+        Ok(migration.digest())
+      },
+    }
   }
 }
 
@@ -195,7 +360,7 @@ impl Hash for CoreTx {
       let mut hasher = Sha512::new();
       hasher.update(&self.sender);
       hasher.update(&self.receiver);
-      hasher.update(self.amount.to_be_bytes());
+      hasher.update(self.amount.to_le_bytes());
       for rwset in self.payload.iter(){
         hasher.update(&rwset.addr);
         hasher.update(rwset.value.to_le_bytes());
@@ -218,15 +383,20 @@ pub struct Frame {
 pub struct AggTransaction {
   pub digest: Digest,
   pub source_shard: ShardId,
-  pub thres_sig: Signature,
+  pub thres_sig: ThresholdSignature,
   pub csmsg_sequence: u64,
+  pub csmsg_epoch: u64,
 
+  /// The root of the avatars of the payload (see `avatars_root`).
   pub payload_hash: Digest,
   pub payload_length: usize, // # of avatars
   pub payload: Vec<Frame>,
+  /// The avatars cached by the source at each height since the last aggregation, if the source
+  /// includes them (`agg_deltas`): they sum to the avatars of the payload.
+  pub deltas: Vec<AvatarDelta>,
 
     // [source_shard] are updated in AggTransaction::new() by executor
-    // [csmsg_sequence] is updated in CSMsg::new() by send_cs_msg 
+    // [csmsg_sequence] and [csmsg_epoch] are updated in CSMsg::new() by send_cs_msg 
     // [thres_sig] is updated in tx.set_thres_sig() by cs_msg_verifier
 }
 
@@ -243,11 +413,13 @@ impl AggTransaction {
       self.digest()
     }
 
-    pub fn get_csmsg_id(&self) -> Option<String> {
-      Some(format!("[{}-{}]", self.source_shard, self.csmsg_sequence))
+    // the avatars are aggregated into the target shard, executing the only frame
+    pub fn get_csmsg_id(&self) -> Option<CSMsgId> {
+      let target = self.payload.first().map_or(ShardId::MAX, |frame| frame.shardid);
+      Some(CSMsgId::new(self.source_shard, target, self.csmsg_epoch, self.csmsg_sequence))
     }
 
-    pub fn set_thres_sig(&mut self, thres_sig: Signature) {
+    pub fn set_thres_sig(&mut self, thres_sig: ThresholdSignature) {
       self.thres_sig = thres_sig;
     }
 
@@ -255,42 +427,132 @@ impl AggTransaction {
       self.csmsg_sequence = csmsg_seq;
     }
 
+    pub fn set_csmsg_epoch(&mut self, epoch: u64) {
+      self.csmsg_epoch = epoch;
+    }
+
     pub fn new(
       source_shard: ShardId,  
       payload: Vec<Frame>,
       payload_len: usize,
+      deltas: Vec<AvatarDelta>,
     ) -> Self {
-      let mut tx = 
       Self {
         digest: Digest::default(),
         source_shard,
-        thres_sig: Signature::default(),
+        thres_sig: ThresholdSignature::default(),
         csmsg_sequence: 0,
-        payload_hash: Digest::default(),
+        csmsg_epoch: 0,
+        payload_hash: avatars_root(&payload),
         payload_length: payload_len,
         payload, 
-      };
+        deltas,
+      }
+    }
 
-      // calculate the agg tx payload hash
-      let mut hasher = Sha512::new();
-      let frame = tx.payload.get(0).unwrap();
-      for rwset in frame.rwset.iter(){
-        hasher.update(&rwset.addr);
-        hasher.update(rwset.value.to_le_bytes());
+    /// Check the payload against its commitment: a single frame of avatars in ascending order of
+    /// address, as many as `payload_length`, with `payload_hash` as root, and summing the deltas
+    /// if any. The signature of the source only proves that it sent the agg tx.
+    pub fn verify_payload(&self) -> ExecutionResult<()> {
+      ensure!(
+        self.payload.len() == 1,
+        ExecutionError::MalformedAggPayload(self.source_shard)
+      );
+      let rwset = &self.payload[0].rwset;
+      ensure!(
+        rwset.windows(2).all(|pair| pair[0].addr < pair[1].addr),
+        ExecutionError::MalformedAggPayload(self.source_shard)
+      );
+      ensure!(
+        self.payload_length == rwset.len(),
+        ExecutionError::AggPayloadLength(self.source_shard, self.payload_length, rwset.len())
+      );
+      ensure!(
+        self.payload_hash == avatars_root(&self.payload),
+        ExecutionError::InvalidAggPayloadRoot(self.source_shard)
+      );
+
+      if !self.deltas.is_empty() {
+        ensure!(
+          self.deltas.windows(2).all(|pair| pair[0].height < pair[1].height),
+          ExecutionError::AggDeltasMismatch(self.source_shard)
+        );
+        let mut sums: HashMap<&Address, Amount> = HashMap::new();
+        for rw in self.deltas.iter().flat_map(|delta| delta.rwset.iter()) {
+          let sum = sums.entry(&rw.addr).or_insert(Amount::ZERO);
+          *sum = sum.checked_add(rw.value).ok_or(ExecutionError::AggDeltasMismatch(self.source_shard))?;
+        }
+        ensure!(
+          sums.len() == rwset.len() && rwset.iter().all(|rw| sums.get(&rw.addr) == Some(&rw.value)),
+          ExecutionError::AggDeltasMismatch(self.source_shard)
+        );
       }
-      tx.payload_hash = Digest(hasher.finalize().as_slice()[..32].try_into().unwrap());
-      tx
+      Ok(())
     }
 }
 
+/// The avatars cached by the source of an agg tx at a height, summed per account and in
+/// ascending order of address.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AvatarDelta {
+  pub height: Height,
+  pub rwset: Vec<RWSet>,
+}
+
+/// The Merkle root of the avatars of an agg tx, in the order of the payload. The leaves commit to
+/// the target shard, the address and the value of each avatar; an odd node is carried up.
+pub fn avatars_root(payload: &[Frame]) -> Digest {
+  let mut level: Vec<Digest> = payload
+    .iter()
+    .flat_map(|frame| frame.rwset.iter().map(move |rw| {
+      let mut hasher = canonical_hasher(b"SHARDAG_AVATAR");
+      hasher.update((frame.shardid as u64).to_le_bytes());
+      update_bytes(&mut hasher, &rw.addr);
+      hasher.update(rw.value.to_le_bytes());
+      finalize(hasher)
+    }))
+    .collect();
+  if level.is_empty() {
+    return finalize(canonical_hasher(b"SHARDAG_AVATARS_EMPTY"));
+  }
+  while level.len() > 1 {
+    level = level
+      .chunks(2)
+      .map(|pair| match pair {
+        [left, right] => {
+          let mut hasher = canonical_hasher(b"SHARDAG_AVATAR_NODE");
+          hasher.update(left.to_vec());
+          hasher.update(right.to_vec());
+          finalize(hasher)
+        },
+        [single] => single.clone(),
+        _ => unreachable!(),
+      })
+      .collect();
+  }
+  level.pop().unwrap()
+}
+
+// [digest] is never set and [thres_sig] certifies the digest, so both are left out
 impl Hash for AggTransaction {
   fn digest(&self) -> Digest {
-      let mut hasher = Sha512::new();
-      hasher.update(self.source_shard.to_le_bytes());
+      let mut hasher = canonical_hasher(b"SHARDAG_AGG_TX");
+      hasher.update((self.source_shard as u64).to_le_bytes());
       hasher.update(self.csmsg_sequence.to_le_bytes());
+      hasher.update(self.csmsg_epoch.to_le_bytes());
       hasher.update(self.payload_hash.to_vec());
-      hasher.update(self.payload_length.to_be_bytes());
-      Digest(hasher.finalize().as_slice()[..32].try_into().unwrap())
+      hasher.update((self.payload_length as u64).to_le_bytes());
+      update_payload(&mut hasher, &self.payload);
+      hasher.update((self.deltas.len() as u64).to_le_bytes());
+      for delta in &self.deltas {
+        hasher.update(delta.height.to_le_bytes());
+        hasher.update((delta.rwset.len() as u64).to_le_bytes());
+        for rw in &delta.rwset {
+          update_bytes(&mut hasher, &rw.addr);
+          hasher.update(rw.value.to_le_bytes());
+        }
+      }
+      finalize(hasher)
   }
 }
 
@@ -303,6 +565,119 @@ impl fmt::Display for AggTransaction {
 }
 
 
+/// The phases of a tx in TwoPhaseCommit. The coordinator of a tx, the shard of its first frame,
+/// asks the shards of its other frames to lock and prepare them, then commits or aborts the tx
+/// once they voted (or the votes timed out).
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum CommitPhase {
+  Prepare,
+  /// The vote of a participant: true if its frame is locked and can be applied.
+  Vote(bool),
+  Commit,
+  Abort,
+  /// Undo the step-th frame, applied by the target shard before the relayed tx aborted later on its
+  /// path (SharDAG and Monoxide).
+  Compensate,
+}
+
+/// A phase of TwoPhaseCommit for the step-th frame of a client tx, exchanged as a csmsg between
+/// the coordinator of the tx and the participant executing the frame, or the compensation of the
+/// frame sent by the shard where a relayed tx aborted.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CommitMessage {
+  pub source_shard: ShardId,
+  pub thres_sig: ThresholdSignature,
+  pub csmsg_sequence: u64,
+  pub csmsg_epoch: u64,
+
+  pub phase: CommitPhase,
+  pub tx: Transaction,
+
+    // [tx.step] is the frame of the participant
+    // [csmsg_sequence] and [csmsg_epoch] are updated in CSMsg::new() by send_cs_msg
+    // [thres_sig] is updated in msg.set_thres_sig() by cs_msg_verifier
+}
+
+impl CommitMessage {
+    pub fn new(source_shard: ShardId, phase: CommitPhase, mut tx: Transaction, step: usize) -> Self {
+      tx.step = step;
+      Self {
+        source_shard,
+        thres_sig: ThresholdSignature::default(),
+        csmsg_sequence: 0,
+        csmsg_epoch: 0,
+        phase,
+        tx,
+      }
+    }
+
+    pub fn len(&self) -> usize {
+      let bytes = bincode::serialize(&self)
+      .expect("Failed to serialize our own vote");
+      bytes.len()
+    }
+
+    // the votes go to the coordinator, the other phases to the participant
+    pub fn target_shard(&self) -> ShardId {
+      let step = match self.phase {
+        CommitPhase::Vote(_) => 0,
+        _ => self.tx.step,
+      };
+      self.tx.payload.get(step).map_or(ShardId::MAX, |frame| frame.shardid)
+    }
+
+    // the frame of the participant
+    pub fn frame(&self) -> Option<&Frame> {
+      self.tx.payload.get(self.tx.step)
+    }
+
+    pub fn get_csmsg_id(&self) -> Option<CSMsgId> {
+      Some(CSMsgId::new(self.source_shard, self.target_shard(), self.csmsg_epoch, self.csmsg_sequence))
+    }
+
+    pub fn set_thres_sig(&mut self, thres_sig: ThresholdSignature) {
+      self.thres_sig = thres_sig;
+    }
+
+    pub fn set_csmsg_sequence(&mut self, csmsg_seq: u64) {
+      self.csmsg_sequence = csmsg_seq;
+    }
+
+    pub fn set_csmsg_epoch(&mut self, epoch: u64) {
+      self.csmsg_epoch = epoch;
+    }
+}
+
+// [thres_sig] certifies the digest, and the tx is identified by its client digest and frame
+impl Hash for CommitMessage {
+  fn digest(&self) -> Digest {
+      let mut hasher = canonical_hasher(b"SHARDAG_COMMIT_MSG");
+      hasher.update((self.source_shard as u64).to_le_bytes());
+      hasher.update(self.csmsg_sequence.to_le_bytes());
+      hasher.update(self.csmsg_epoch.to_le_bytes());
+      match self.phase {
+        CommitPhase::Prepare => hasher.update([0u8]),
+        CommitPhase::Vote(false) => hasher.update([1u8]),
+        CommitPhase::Vote(true) => hasher.update([2u8]),
+        CommitPhase::Commit => hasher.update([3u8]),
+        CommitPhase::Abort => hasher.update([4u8]),
+        CommitPhase::Compensate => hasher.update([5u8]),
+      }
+      hasher.update(self.tx.client_digest().to_vec());
+      hasher.update((self.tx.step as u64).to_le_bytes());
+      finalize(hasher)
+  }
+}
+
+impl fmt::Display for CommitMessage {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      write!(f, "[2PC][{:?} of tx {}, frame: {}, source shard: {}]",
+         self.phase, self.tx.counter, self.tx.step, self.source_shard
+        )
+  }
+}
+
+
 // 交易数据
 #[derive(Default, Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub struct Transaction {
@@ -312,10 +687,11 @@ pub struct Transaction {
     pub signature: Signature,  
 
     pub sender: Address, //20B
+    pub sender_key: PublicKey, // the key of the sender, registered at genesis
     pub receiver: Address, 
     pub amount: Amount,
     pub timestamp: u128,
-    pub nonce: i32,
+    pub nonce: i64,
 
     // payload
     pub payload_hash: Digest,
@@ -329,12 +705,13 @@ pub struct Transaction {
     // cross-shard info for relayed cross-shard tx
     pub involved_shard_num: usize, // involved shard num in a completely sharding archi
     pub step: usize, // the step-th frame
-    pub cs_proof: Vec<(ShardId,Signature)>,
+    pub cs_proof: Vec<(CSMsgId, ThresholdSignature)>, // (csmsg_id, thres_sig) of each hop
     pub source_shard: ShardId,
     pub csmsg_sequence: u64, 
+    pub csmsg_epoch: u64,
 
     // [step] and [source_shard] are updated in tx.update_relay_info() by executor
-    // [csmsg_sequence] is updated in CSMsg::new() by send_cs_msg 
+    // [csmsg_sequence] and [csmsg_epoch] are updated in CSMsg::new() by send_cs_msg 
     // [cs_proof] is updated in tx.set_thres_sig() by cs_msg_verifier
 
     pub padding: Vec<u8>,
@@ -353,11 +730,13 @@ impl Transaction {
       self.sample == 0
     }
 
-    pub fn get_csmsg_id(&self) -> Option<String> {
+    // the target of a relayed tx is the executor of the step-th frame
+    pub fn get_csmsg_id(&self) -> Option<CSMsgId> {
       if self.source_shard == ShardId::MAX {// not a csmsg
         None
       } else {
-        Some(format!("[{}-{}]", self.source_shard, self.csmsg_sequence))
+        let target = self.payload.get(self.step).map_or(ShardId::MAX, |frame| frame.shardid);
+        Some(CSMsgId::new(self.source_shard, target, self.csmsg_epoch, self.csmsg_sequence))
       }
     }
 
@@ -385,18 +764,120 @@ impl Transaction {
       }
     }
 
-    pub fn set_thres_sig(&mut self, thres_sig: Signature, source_shard: ShardId) {
-      self.cs_proof.push((source_shard, thres_sig));      
+    pub fn set_thres_sig(&mut self, thres_sig: ThresholdSignature, csmsg_id: CSMsgId) {
+      self.cs_proof.push((csmsg_id, thres_sig));
     }
 
     pub fn set_csmsg_sequence(&mut self, csmsg_seq: u64) {
       self.csmsg_sequence = csmsg_seq;
     }
 
+    pub fn set_csmsg_epoch(&mut self, epoch: u64) {
+      self.csmsg_epoch = epoch;
+    }
+
     pub fn get_digest(&self) -> Digest {
       self.digest()
     }
 
+    // the digest signed by the sender: the tx as submitted by the client, without relay info
+    pub fn client_digest(&self) -> Digest {
+      self.canonical_digest(0, ShardId::MAX, 0, 0, &[])
+    }
+
+    // sign the tx with the key of the sender; the nonce must be set beforehand
+    pub fn sign(&mut self, public_key: &PublicKey, secret: &SecretKey) {
+      self.sender_key = *public_key;
+      self.tx_hash = self.client_digest();
+      self.signature = Signature::new(&self.tx_hash, secret);
+    }
+
+    // the (digest, key, signature) checked for the tx; the executor checks that the key is the
+    // one registered for the sender, see StateTransition::authorize()
+    pub fn client_signature(&self) -> (Digest, PublicKey, Signature) {
+      (self.client_digest(), self.sender_key, self.signature.clone())
+    }
+
+    pub fn verify_signature(&self) -> ExecutionResult<()> {
+      let (digest, public_key, signature) = self.client_signature();
+      signature.verify(&digest, &public_key)?;
+      Ok(())
+    }
+
+    // the canonical digest of the tx with the given relay info: [tx_hash] is a cache,
+    // [signature] signs the digest and [padding] carries no semantics, so they are left out
+    fn canonical_digest(
+      &self,
+      step: usize,
+      source_shard: ShardId,
+      csmsg_sequence: u64,
+      csmsg_epoch: u64,
+      cs_proof: &[(CSMsgId, ThresholdSignature)],
+    ) -> Digest {
+      let mut hasher = canonical_hasher(b"SHARDAG_TX");
+      hasher.update([self.sample]);
+      hasher.update(self.counter.to_le_bytes());
+      update_bytes(&mut hasher, &self.sender);
+      hasher.update(self.sender_key.0);
+      update_bytes(&mut hasher, &self.receiver);
+      hasher.update(self.amount.to_le_bytes());
+      hasher.update(self.timestamp.to_le_bytes());
+      hasher.update(self.nonce.to_le_bytes());
+
+      hasher.update(self.payload_hash.to_vec());
+      hasher.update((self.rwset_num as u64).to_le_bytes());
+      update_payload(&mut hasher, &self.payload);
+
+      update_address_option(&mut hasher, &self.original_sender);
+      update_address_option(&mut hasher, &self.final_receiver);
+
+      hasher.update((self.involved_shard_num as u64).to_le_bytes());
+      hasher.update((step as u64).to_le_bytes());
+      hasher.update((source_shard as u64).to_le_bytes());
+      hasher.update(csmsg_sequence.to_le_bytes());
+      hasher.update(csmsg_epoch.to_le_bytes());
+      hasher.update((cs_proof.len() as u64).to_le_bytes());
+      for (csmsg_id, thres_sig) in cs_proof {
+        update_csmsg_id(&mut hasher, csmsg_id);
+        hasher.update(thres_sig.flatten());
+      }
+      finalize(hasher)
+    }
+
+    // the digest certified by the `hop`-th entry of cs_proof, i.e. the digest of the tx when
+    // it was relayed to the (hop+1)-th frame
+    fn relay_digest(&self, hop: usize) -> Digest {
+      let (csmsg_id, _) = &self.cs_proof[hop];
+      self.canonical_digest(hop + 1, csmsg_id.source, csmsg_id.seq, csmsg_id.epoch, &self.cs_proof[..hop])
+    }
+
+    // the i-th entry of cs_proof is the certificate of the shard executing the i-th frame;
+    // return the digest certified by the last hop (the inner_tx_hash of the last csmsg)
+    pub fn verify_cs_proof(&self, all_committees: &Committees) -> ExecutionResult<Digest> {
+      ensure!(
+        !self.cs_proof.is_empty() && self.cs_proof.len() == self.step,
+        ExecutionError::MalformedCSProof(self.cs_proof.len(), self.step)
+      );
+      let (last_id, _) = self.cs_proof.last().unwrap();
+      ensure!(
+        Some(*last_id) == self.get_csmsg_id(),
+        ExecutionError::UnexpectedHop(last_id.source, self.step - 1)
+      );
+      for (hop, (csmsg_id, thres_sig)) in self.cs_proof.iter().enumerate() {
+        ensure!(
+          self.payload.get(hop).map(|frame| frame.shardid) == Some(csmsg_id.source)
+            && self.payload.get(hop + 1).map(|frame| frame.shardid) == Some(csmsg_id.target),
+          ExecutionError::UnexpectedHop(csmsg_id.source, hop)
+        );
+        let key_set = all_committees.threshold_keys(&csmsg_id.source)?;
+        ensure!(
+          verify_ts_sig(&key_set.public_key, &self.relay_digest(hop), thres_sig),
+          ExecutionError::InvalidThresholdSignature(csmsg_id.source)
+        );
+      }
+      Ok(self.relay_digest(self.step - 1))
+    }
+
     // return the next shardid
     pub fn update_relay_info(&mut self, source_shard: ShardId) -> ShardId {
       self.step += 1;
@@ -405,6 +886,16 @@ impl Transaction {
       next_shard
     }
 
+    // the shard executing the next step, ShardId::MAX at the last step
+    pub fn next_shard(&self) -> ShardId {
+      self.payload.get(self.step + 1).map_or(ShardId::MAX, |frame| frame.shardid)
+    }
+
+    // the shards that executed the tx up to the current step
+    pub fn shard_path(&self) -> Vec<ShardId> {
+      self.payload.iter().take(self.step + 1).map(|frame| frame.shardid).collect()
+    }
+
     pub fn new(
       sender: Address,
       recv: Address,
@@ -424,7 +915,7 @@ impl Transaction {
       Self {
         sample: (tx_sample), counter: (tx_counter), 
         tx_hash: Digest::default(), signature: Signature::default(),
-        sender: (sender), receiver: (recv), amount: (amount), 
+        sender: (sender), sender_key: PublicKey::default(), receiver: (recv), amount: (amount), 
         timestamp, nonce: 0, 
         
         payload,
@@ -439,6 +930,7 @@ impl Transaction {
         cs_proof: Vec::default(),
         source_shard: ShardId::MAX,
         csmsg_sequence: 0,
+        csmsg_epoch: 0,
         padding: Vec::default()
       };
       // fullfill the padding
@@ -465,19 +957,7 @@ impl Transaction {
 
 impl Hash for Transaction {
   fn digest(&self) -> Digest {
-      let mut hasher = Sha512::new();
-      hasher.update(self.sample.to_le_bytes());
-      hasher.update(self.counter.to_le_bytes());
-      hasher.update(&self.sender);
-      hasher.update(&self.receiver);
-      hasher.update(self.amount.to_le_bytes());
-      hasher.update(self.nonce.to_le_bytes());
-
-      hasher.update(self.payload_hash.to_vec());
-      hasher.update(self.rwset_num.to_be_bytes());
-      hasher.update(self.step.to_be_bytes());
-
-      Digest(hasher.finalize().as_slice()[..32].try_into().unwrap())
+      self.canonical_digest(self.step, self.source_shard, self.csmsg_sequence, self.csmsg_epoch, &self.cs_proof)
   }
 }
 
@@ -496,10 +976,11 @@ pub struct CSMsg {
     pub source_shard: ShardId,
     pub target_shard: ShardId,
     pub csmsg_sequence :u64, 
+    pub epoch: u64, // epoch of the source shard when the tx was executed
 
     pub tx: GeneralTransaction, // original_tx or agg_tx   
     pub inner_tx_hash: Digest, // serve as randomness to choose csmsg sender, reciver, and packagers.
-    pub thres_sig: Signature,
+    pub thres_sig: PartialSignature, // partial signature of the author over inner_tx_hash
  
     pub author: PublicKey,
     pub signature: Signature,
@@ -510,20 +991,22 @@ impl CSMsg {
     source_shard: ShardId,
     target_shard: ShardId,
     csmsg_seq :u64,
+    epoch: u64,
     mut tx: GeneralTransaction, // original_tx or agg_tx
 
     author: &PublicKey, // author of the vote
     signature_service: &mut SignatureService, // used to set thres_sig
   ) -> Self {
 
-    tx.set_csmsg_seq(csmsg_seq);
+    tx.set_csmsg_header(csmsg_seq, epoch);
     let inner_tx_hash = tx.get_digest();
-    let thres_sig = signature_service.request_signature(inner_tx_hash.clone()).await;
+    let thres_sig = signature_service.request_partial_signature(inner_tx_hash.clone()).await;
     
     let cs_msg = Self {
       source_shard, 
       target_shard, 
       csmsg_sequence: csmsg_seq,
+      epoch,
       tx,
       inner_tx_hash,
       thres_sig,
@@ -539,6 +1022,10 @@ impl CSMsg {
       self.tx.get_counter()
   }
 
+  pub fn id(&self) -> CSMsgId {
+    CSMsgId::new(self.source_shard, self.target_shard, self.epoch, self.csmsg_sequence)
+  }
+
 
   // set normal sig
   pub async fn set_sig(
@@ -559,27 +1046,278 @@ impl CSMsg {
     self.digest()
   }
 
-  /// TODO verify thres_sig
-  pub fn verify(&self, _all_committees: &Committees) -> bool {
-    return true;
+  /// Verify that the author is a member of the source shard, that the inner tx hash matches
+  /// the tx, and that both the signature and the partial signature of the author are valid.
+  pub fn verify(&self, all_committees: &Committees) -> ExecutionResult<()> {
+    let committee = all_committees.our_committee(&self.source_shard)?;
+    ensure!(
+      committee.stake(&self.author) > 0,
+      ExecutionError::UnknownAuthority(self.author, self.source_shard)
+    );
+    ensure!(
+      self.tx.get_digest() == self.inner_tx_hash,
+      ExecutionError::InvalidInnerTxHash(self.inner_tx_hash.clone())
+    );
+    // the inner tx carries the id under which it is executed (txs relayed to a broker carry none)
+    ensure!(
+      self.tx.get_csmsg_id().map_or(true, |id| id == self.id()),
+      ExecutionError::CSMsgIdMismatch(self.id())
+    );
+    self.signature.verify(&self.digest(), &self.author)?;
+
+    let key_set = all_committees.threshold_keys(&self.source_shard)?;
+    self.thres_sig.verify(&self.inner_tx_hash, key_set)?;
+    Ok(())
   }
 
 }
 
+// [signature] signs the digest, every other field is bound
 impl Hash for CSMsg {
   fn digest(&self) -> Digest {
-      let mut hasher = Sha512::new();
-      hasher.update(self.source_shard.to_le_bytes());
-      hasher.update(self.target_shard.to_le_bytes());
+      let mut hasher = canonical_hasher(b"SHARDAG_CSMSG");
+      hasher.update((self.source_shard as u64).to_le_bytes());
+      hasher.update((self.target_shard as u64).to_le_bytes());
       hasher.update(self.csmsg_sequence.to_le_bytes());
+      hasher.update(self.epoch.to_le_bytes());
 
-      Digest(hasher.finalize().as_slice()[..32].try_into().unwrap())
+      match &self.tx {
+        GeneralTransaction::TransferTx(_) => hasher.update([0u8]),
+        GeneralTransaction::AggTx(_) => hasher.update([1u8]),
+      }
+      hasher.update(self.tx.get_digest().to_vec());
+      hasher.update(self.inner_tx_hash.to_vec());
+      hasher.update(self.thres_sig.index.to_le_bytes());
+      hasher.update(self.thres_sig.flatten());
+      hasher.update(self.author.0);
+      finalize(hasher)
   }
 }
 
 impl fmt::Display for CSMsg {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-      write!(f, "[AD][source_shard: {}, target_shard: {}, csmsg_sequence: {}]",
-         self.source_shard, self.target_shard, self.csmsg_sequence)
+      write!(f, "[AD][source_shard: {}, target_shard: {}, csmsg_sequence: {}, epoch: {}]",
+         self.source_shard, self.target_shard, self.csmsg_sequence, self.epoch)
+  }
+}
+
+/// A node of the target shard acknowledges that its shard executed every csmsg sent by the source
+/// shard below `next_seq`. The source shard trusts the acknowledgement once f+1 nodes sent it.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct CSMsgAck {
+    pub source_shard: ShardId,
+    pub target_shard: ShardId,
+    pub next_seq: u64,
+
+    pub author: PublicKey,
+    pub signature: Signature,
+}
+
+impl CSMsgAck {
+  pub async fn new(
+    source_shard: ShardId,
+    target_shard: ShardId,
+    next_seq: u64,
+    author: &PublicKey,
+    signature_service: &mut SignatureService,
+  ) -> Self {
+    let mut ack = Self {
+      source_shard,
+      target_shard,
+      next_seq,
+      author: *author,
+      signature: Signature::default(),
+    };
+    ack.signature = signature_service.request_signature(ack.digest()).await;
+    ack
+  }
+
+  /// Verify that the author is a member of the target shard and that its signature is valid.
+  pub fn verify(&self, all_committees: &Committees) -> ExecutionResult<()> {
+    let committee = all_committees.our_committee(&self.target_shard)?;
+    ensure!(
+      committee.stake(&self.author) > 0,
+      ExecutionError::UnknownAuthority(self.author, self.target_shard)
+    );
+    self.signature.verify(&self.digest(), &self.author)?;
+    Ok(())
+  }
+}
+
+// [signature] signs the digest, every other field is bound
+impl Hash for CSMsgAck {
+  fn digest(&self) -> Digest {
+      let mut hasher = canonical_hasher(b"SHARDAG_CSMSG_ACK");
+      hasher.update((self.source_shard as u64).to_le_bytes());
+      hasher.update((self.target_shard as u64).to_le_bytes());
+      hasher.update(self.next_seq.to_le_bytes());
+      hasher.update(self.author.0);
+      finalize(hasher)
+  }
+}
+
+impl fmt::Display for CSMsgAck {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      write!(f, "[ACK][source_shard: {}, target_shard: {}, next_seq: {}]",
+         self.source_shard, self.target_shard, self.next_seq)
   }
-}
\ No newline at end of file
+}
+
+/// The accounts a shard hands over to another shard at an epoch boundary, with their MPT proofs
+/// against the roots of the source shard at the migration height.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Migration {
+    // Migration header
+    pub source_shard_id: ShardId,
+    pub target_shard_id: ShardId,
+    pub epoch: u64,
+    pub act_root: RootHash, // root of act_t (empty for MStore)
+    pub full_root: RootHash, // root of full_t
+
+    pub active_acc_proof_map : HashMap<Address, Proof>, // proven against act_root
+    pub dormant_acc_proof_map : HashMap<Address, Proof>, // proven against full_root
+    // online policy: every account leaving the source shard at this epoch, with its target shard,
+    // so that every shard learns the moves computed by the others
+    pub moves: BTreeMap<Address, ShardId>,
+    pub thres_sig: PartialSignature, // partial signature of the author over the header digest
+
+    pub author: PublicKey,
+    pub signature: Signature,
+}
+
+impl Migration {
+  pub fn new(
+    source_shard_id: ShardId, target_shard_id: ShardId, epoch: u64,
+    act_root: RootHash, full_root: RootHash,
+    active_acc_proof_map: HashMap<Address, Proof>, dormant_acc_proof_map : HashMap<Address, Proof>
+  ) -> Self {
+    Self { 
+      source_shard_id, 
+      target_shard_id, 
+      epoch, 
+      act_root,
+      full_root,
+      active_acc_proof_map,
+      dormant_acc_proof_map,
+      moves: BTreeMap::new(),
+      thres_sig: PartialSignature::default(),
+      author: PublicKey::default(),
+      signature: Signature::default(),
+    }
+  }
+
+  // the digest certified by the source shard: the roots, the migrated accounts and the moves, but
+  // not the proofs (they are checked against the roots) nor the author
+  pub fn header_digest(&self) -> Digest {
+    let mut hasher = canonical_hasher(b"SHARDAG_MIGRATION");
+    hasher.update((self.source_shard_id as u64).to_le_bytes());
+    hasher.update((self.target_shard_id as u64).to_le_bytes());
+    hasher.update(self.epoch.to_le_bytes());
+    update_bytes(&mut hasher, &self.act_root);
+    update_bytes(&mut hasher, &self.full_root);
+    update_addresses(&mut hasher, self.active_acc_proof_map.keys());
+    update_addresses(&mut hasher, self.dormant_acc_proof_map.keys());
+    hasher.update((self.moves.len() as u64).to_le_bytes());
+    for (addr, shard_id) in &self.moves {
+      update_bytes(&mut hasher, addr);
+      hasher.update((*shard_id as u64).to_le_bytes());
+    }
+    finalize(hasher)
+  }
+
+  // set the partial signature over the header, then sign the whole message
+  pub async fn sign(
+    &mut self,
+    author: &PublicKey,
+    signature_service: &mut SignatureService,
+  ) {
+    self.thres_sig = signature_service.request_partial_signature(self.header_digest()).await;
+    self.author = *author;
+    self.signature = signature_service.request_signature(self.digest()).await;
+  }
+
+  pub fn len(&self) -> usize {
+    let bytes = bincode::serialize(&self)
+    .expect("Failed to serialize our own migration");
+    bytes.len()
+  }
+
+  /// Verify that the author is a member of the source shard and that both the signature and the
+  /// partial signature of the author are valid. The proofs are not checked.
+  pub fn verify(&self, all_committees: &Committees) -> ExecutionResult<()> {
+    let committee = all_committees.our_committee(&self.source_shard_id)?;
+    ensure!(
+      committee.stake(&self.author) > 0,
+      ExecutionError::UnknownAuthority(self.author, self.source_shard_id)
+    );
+    self.signature.verify(&self.digest(), &self.author)?;
+
+    let key_set = all_committees.threshold_keys(&self.source_shard_id)?;
+    self.thres_sig.verify(&self.header_digest(), key_set)?;
+    Ok(())
+  }
+}
+
+// [signature] signs the digest; the proofs are bound through the header
+impl Hash for Migration {
+  fn digest(&self) -> Digest {
+      let mut hasher = canonical_hasher(b"SHARDAG_MIGRATION_MSG");
+      hasher.update(self.header_digest().to_vec());
+      hasher.update(self.thres_sig.index.to_le_bytes());
+      hasher.update(self.thres_sig.flatten());
+      hasher.update(self.author.0);
+      finalize(hasher)
+  }
+}
+
+impl fmt::Display for Migration {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      write!(f, "[MIGRATION][source_shard: {}, target_shard: {}, epoch: {}, active accs: {}, dormant accs: {}, moves: {}]",
+         self.source_shard_id, self.target_shard_id, self.epoch,
+         self.active_acc_proof_map.len(), self.dormant_acc_proof_map.len(), self.moves.len())
+  }
+}
+
+
+/// A migration whose header is certified by the threshold signature of its source shard. It is
+/// appended to the DAG of the target shard, so that every node installs its accounts after the
+/// same block.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct CertifiedMigration {
+    pub migration: Migration,
+    pub cert: ThresholdSignature, // over the header digest of the migration
+}
+
+impl CertifiedMigration {
+  pub fn new(migration: Migration, cert: ThresholdSignature) -> Self {
+    Self { migration, cert }
+  }
+
+  /// Verify the certificate over the header. The proofs are checked when the accounts are
+  /// installed.
+  pub fn verify(&self, all_committees: &Committees) -> ExecutionResult<()> {
+    let source_shard_id = self.migration.source_shard_id;
+    let key_set = all_committees.threshold_keys(&source_shard_id)?;
+    ensure!(
+      verify_ts_sig(&key_set.public_key, &self.migration.header_digest(), &self.cert),
+      ExecutionError::InvalidThresholdSignature(source_shard_id)
+    );
+    Ok(())
+  }
+}
+
+// the copies appended by several nodes share this digest, whoever signed them
+impl Hash for CertifiedMigration {
+  fn digest(&self) -> Digest {
+      let mut hasher = canonical_hasher(b"SHARDAG_CERTIFIED_MIGRATION");
+      hasher.update(self.migration.header_digest().to_vec());
+      hasher.update(self.cert.flatten());
+      finalize(hasher)
+  }
+}
+
+impl fmt::Display for CertifiedMigration {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      write!(f, "[CERTIFIED]{}", self.migration)
+  }
+}
@@ -0,0 +1,84 @@
+use ::metrics::{
+  lazy_static, register_histogram, register_int_counter, register_int_counter_vec, register_int_gauge,
+  register_int_gauge_vec, Histogram, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, LATENCY_BUCKETS_MS,
+  SIZE_BUCKETS,
+};
+
+// The metrics of the worker, exported by `metrics::MetricsExporter`. They replace the numbers the
+// benchmark scripts parse from the logs.
+lazy_static! {
+  /// The batches sealed by the `BatchMaker`, by reason (`full` or `timeout`).
+  pub static ref BATCHES_SEALED: IntCounterVec = register_int_counter_vec!(
+    "worker_batches_sealed_total", "Batches sealed by the batch maker, by seal reason", &["reason"]
+  ).unwrap();
+  pub static ref BATCH_SIZE_BYTES: Histogram = register_histogram!(
+    "worker_batch_size_bytes", "Size of the sealed batches in bytes", SIZE_BUCKETS.to_vec()
+  ).unwrap();
+  pub static ref BATCH_EXTERNAL_TXS: Histogram = register_histogram!(
+    "worker_batch_external_txs", "External txs packaged in the sealed batches", SIZE_BUCKETS.to_vec()
+  ).unwrap();
+
+  /// The txs executed by our shard, by receipt status (`committed`, `aborted` or `relayed`).
+  pub static ref EXECUTED_TXS: IntCounterVec = register_int_counter_vec!(
+    "worker_executed_txs_total", "Txs executed by the executor, by outcome", &["status"]
+  ).unwrap();
+  /// The accounts of a block read at once before its txs are executed.
+  pub static ref PREFETCHED_ACCOUNTS: Histogram = register_histogram!(
+    "worker_prefetched_accounts", "Accounts read at once before the txs of a block", SIZE_BUCKETS.to_vec()
+  ).unwrap();
+  /// The txs of a block executed concurrently, as they conflict with no earlier tx of the block.
+  pub static ref SPECULATED_TXS: Histogram = register_histogram!(
+    "worker_speculated_txs", "Independent txs of a block executed concurrently", SIZE_BUCKETS.to_vec()
+  ).unwrap();
+  pub static ref EXECUTED_HEIGHT: IntGauge = register_int_gauge!(
+    "worker_executed_height", "The last height executed by the executor"
+  ).unwrap();
+
+  /// The csmsgs waiting in the channel of the `CSMsgVerifier`.
+  pub static ref CSMSG_QUEUE_DEPTH: IntGauge = register_int_gauge!(
+    "worker_csmsg_queue_depth", "CSMsgs received but not yet verified"
+  ).unwrap();
+  /// The csmsgs verified but not yet appended to our DAG.
+  pub static ref CSMSG_PENDING_APPENDS: IntGauge = register_int_gauge!(
+    "worker_csmsg_pending_appends", "Verified csmsgs not yet appended"
+  ).unwrap();
+  pub static ref CSMSG_VERIFY_LATENCY: Histogram = register_histogram!(
+    "worker_csmsg_verify_latency_ms", "Verification time of a csmsg in ms", LATENCY_BUCKETS_MS.to_vec()
+  ).unwrap();
+  /// The delay between the verification of a csmsg and its append to the DAG, by us or by another
+  /// node of the shard.
+  pub static ref CSMSG_APPEND_DELAY: Histogram = register_histogram!(
+    "worker_csmsg_append_delay_ms", "Append delay of a verified csmsg in ms", LATENCY_BUCKETS_MS.to_vec()
+  ).unwrap();
+
+  /// The agg txs sent by the SharDAG executor, by trigger (`height`, `memory`, `accounts` or
+  /// `age`).
+  pub static ref AGG_TXS: IntCounterVec = register_int_counter_vec!(
+    "worker_agg_txs_total", "Agg txs sent by the executor, by aggregation trigger", &["trigger"]
+  ).unwrap();
+  pub static ref AGG_TX_ACCOUNTS: Histogram = register_histogram!(
+    "worker_agg_tx_accounts", "Avatar accounts in the sent agg txs", SIZE_BUCKETS.to_vec()
+  ).unwrap();
+  pub static ref AGG_TX_SIZE_BYTES: Histogram = register_histogram!(
+    "worker_agg_tx_size_bytes", "Size of the sent agg txs in bytes", SIZE_BUCKETS.to_vec()
+  ).unwrap();
+  /// The avatar accounts cached by the SharDAG executor, in bytes of agg tx payload.
+  pub static ref AVATAR_STORE_BYTES: IntGauge = register_int_gauge!(
+    "worker_avatar_store_bytes", "Avatar accounts waiting for aggregation, in bytes"
+  ).unwrap();
+
+  /// The reads of the `TStore`, by tier (`active` for a hit in act_t, `full` otherwise).
+  pub static ref TSTORE_READS: IntCounterVec = register_int_counter_vec!(
+    "worker_tstore_reads_total", "Account reads of the TStore, by the tier serving them", &["tier"]
+  ).unwrap();
+  /// The cold accounts moved from act_t to full_t by the `TStore`.
+  pub static ref TSTORE_EVICTIONS: IntCounter = register_int_counter!(
+    "worker_tstore_evictions_total", "Cold accounts evicted from the active trie of the TStore"
+  ).unwrap();
+
+  /// The messages sent through a `ReliableSender` and neither acknowledged nor garbage collected,
+  /// by sender and peer (see `OutstandingMessages`).
+  pub static ref OUTSTANDING_MESSAGES: IntGaugeVec = register_int_gauge_vec!(
+    "worker_outstanding_messages", "Messages not acknowledged yet, by sender and peer", &["component", "peer"]
+  ).unwrap();
+}
@@ -0,0 +1,338 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use config::{Committees, ShardId};
+use log::{debug, info, warn};
+use mpt::{Key, Value};
+use tokio::sync::mpsc::{Receiver, Sender};
+use crate::{Account2Shard, StateStore};
+use crate::messages::{Address, CertifiedMigration, CommitPhase, Frame, GeneralTransaction, Height, Migration};
+use crate::migration_verifier::verify_proofs;
+
+#[cfg(test)]
+#[path = "tests/migration_tests.rs"]
+pub mod migration_tests;
+
+
+/// The accounts moving in and out of a shard when switching to the mapping of the next epoch.
+/// Every node of the shard derives the same plan from the two mappings.
+#[derive(Clone, Debug, Default)]
+pub struct MigrationPlan {
+  pub epoch: u64,
+  pub height: Height, // outgoing accounts are exported and incoming ones paused from this height
+  pub outgoing: BTreeMap<ShardId, BTreeSet<Address>>, // target shard -> accounts
+  pub incoming: BTreeMap<ShardId, BTreeSet<Address>>, // source shard -> accounts
+  // the incoming accounts are not known in advance: every certified account of the sources is installed
+  pub accept_any: bool,
+}
+
+impl MigrationPlan {
+  pub fn new(
+    shard_id: ShardId,
+    epoch: u64,
+    height: Height,
+    current: &dyn Account2Shard,
+    next: &dyn Account2Shard,
+  ) -> Self {
+    let mut outgoing: BTreeMap<ShardId, BTreeSet<Address>> = BTreeMap::new();
+    let mut incoming: BTreeMap<ShardId, BTreeSet<Address>> = BTreeMap::new();
+
+    // accounts mapped by neither policy fall back to the same hash policy and never move
+    let accs: BTreeSet<Address> = current
+      .mapped_accounts()
+      .into_iter()
+      .chain(next.mapped_accounts())
+      .collect();
+    for addr in accs {
+      let from = current.get_shard(&addr);
+      let to = next.get_shard(&addr);
+      if from == to {
+        continue;
+      }
+      if from == shard_id {
+        outgoing.entry(to).or_default().insert(addr);
+      } else if to == shard_id {
+        incoming.entry(from).or_default().insert(addr);
+      }
+    }
+
+    Self {
+      epoch,
+      height,
+      outgoing,
+      incoming,
+      accept_any: false,
+    }
+  }
+
+  /// The plan of the online policy, where each shard only decides which of its own accounts leave.
+  /// A migration, possibly empty, is exchanged with every other shard, so that every node of the
+  /// target shard waits for the same migrations before installing them.
+  pub fn online(
+    shard_id: ShardId,
+    shard_num: usize,
+    epoch: u64,
+    height: Height,
+    moves: BTreeMap<Address, ShardId>,
+  ) -> Self {
+    let others = (0..shard_num).filter(|shard| *shard != shard_id);
+    let mut outgoing: BTreeMap<ShardId, BTreeSet<Address>> = others.clone().map(|shard| (shard, BTreeSet::new())).collect();
+    let incoming = others.map(|shard| (shard, BTreeSet::new())).collect();
+    for (addr, to) in moves {
+      outgoing.entry(to).or_default().insert(addr);
+    }
+
+    Self {
+      epoch,
+      height,
+      outgoing,
+      incoming,
+      accept_any: true,
+    }
+  }
+}
+
+/// The accounts of a certified migration whose proofs have been checked.
+#[derive(Debug)]
+pub struct VerifiedMigration {
+  pub source_shard_id: ShardId,
+  pub epoch: u64,
+  pub active_accs: HashMap<Key, Value>,
+  pub dormant_accs: HashMap<Key, Value>,
+  pub moves: BTreeMap<Address, ShardId>,
+}
+
+/// Drives the migration plans on behalf of an executor: exports the outgoing accounts, installs
+/// the incoming ones once their certified migration is committed in our DAG, and holds back the
+/// txs touching accounts in flight. The other txs are executed meanwhile.
+pub struct Migrator {
+  shard_id: ShardId,
+
+  // channel
+  rx_plan: Receiver<MigrationPlan>,
+  tx_migration: Sender<Migration>, // to the MigrationSender
+
+  plans: VecDeque<MigrationPlan>,
+  exported: bool, // whether the front plan has been exported
+  // the committed migrations, by (epoch, source shard), until their plan is exported
+  verified: HashMap<(u64, ShardId), VerifiedMigration>,
+  // the migrations committed so far: the later copies are ignored
+  delivered: HashSet<(u64, ShardId)>,
+
+  migrated_out: HashSet<Address>,
+  in_flight: HashSet<Address>,
+  paused_txs: Vec<GeneralTransaction>,
+  // the moves of the other shards carried by the installed migrations (online policy)
+  moves: BTreeMap<Address, ShardId>,
+}
+
+impl Migrator {
+  pub fn new(
+    shard_id: ShardId,
+    rx_plan: Receiver<MigrationPlan>,
+    tx_migration: Sender<Migration>,
+  ) -> Self {
+    Self {
+      shard_id,
+      rx_plan,
+      tx_migration,
+      plans: VecDeque::new(),
+      exported: false,
+      verified: HashMap::new(),
+      delivered: HashSet::new(),
+      migrated_out: HashSet::new(),
+      in_flight: HashSet::new(),
+      paused_txs: Vec::new(),
+      moves: BTreeMap::new(),
+    }
+  }
+
+  /// Called before executing the block at `height`. Return the paused txs that can be executed
+  /// again, in the order they were paused.
+  pub async fn begin_block(
+    &mut self,
+    height: Height,
+    store: &mut Box<dyn StateStore + Send>,
+  ) -> Vec<GeneralTransaction> {
+    while let Ok(plan) = self.rx_plan.try_recv() {
+      self.plans.push_back(plan);
+    }
+
+    let mut installed = false;
+    while let Some(plan) = self.plans.front() {
+      if !self.exported {
+        if plan.height > height {
+          break;
+        }
+        let plan = plan.clone();
+        self.export(&plan, store).await;
+        self.exported = true;
+      }
+
+      // the next plan is exported once every migration of this one is installed
+      let mut plan = self.plans.pop_front().unwrap();
+      installed |= self.install(height, &mut plan, store).await;
+      if !plan.incoming.is_empty() {
+        self.plans.push_front(plan);
+        break;
+      }
+      self.exported = false;
+    }
+
+    if !installed {
+      return Vec::new();
+    }
+    let in_flight = &self.in_flight;
+    let (resumed, paused): (Vec<_>, Vec<_>) = self
+      .paused_txs
+      .drain(..)
+      .partition(|tx| !Self::touches(tx, in_flight));
+    self.paused_txs = paused;
+    resumed
+  }
+
+  /// Take the moves of the other shards installed so far, for the account-to-shard mapping of the
+  /// epoch manager. They are applied at the same height by every node of the shard.
+  pub fn take_moves(&mut self) -> BTreeMap<Address, ShardId> {
+    std::mem::take(&mut self.moves)
+  }
+
+  /// Take a certified migration committed in the block at `height`. Its accounts are installed
+  /// before the next block, once the plan of its epoch is exported. Every node verifies it, as any
+  /// node of the shard may have appended it.
+  pub fn deliver(&mut self, height: Height, migration: &CertifiedMigration, all_committees: &Committees) {
+    let key = (migration.migration.epoch, migration.migration.source_shard_id);
+    if migration.migration.target_shard_id != self.shard_id || self.delivered.contains(&key) {
+      return;
+    }
+    let verified = migration
+      .verify(all_committees)
+      .and_then(|_| verify_proofs(&migration.migration));
+    match verified {
+      Ok(verified) => {
+        debug!("[height: {}] deliver {}", height, migration);
+        self.delivered.insert(key);
+        self.verified.insert(key, verified);
+      },
+      Err(e) => warn!("[height: {}] invalid migration {}: {}", height, migration, e),
+    }
+  }
+
+  /// Return true if the tx must not be executed now: it is either paused until its accounts are
+  /// installed, or dropped because its accounts now live in another shard.
+  pub fn hold(&mut self, height: Height, tx: &GeneralTransaction) -> bool {
+    if self.migrated_out(tx) {
+      warn!("[height: {}] drop tx touching migrated-out accounts: {:?}", height, tx.get_digest());
+      return true;
+    }
+    if Self::touches(tx, &self.in_flight) {
+      debug!("[height: {}] pause tx touching in-flight accounts: {:?}", height, tx.get_digest());
+      self.paused_txs.push(tx.clone());
+      return true;
+    }
+    false
+  }
+
+  /// Whether the tx touches accounts which now live in another shard.
+  pub fn migrated_out(&self, tx: &GeneralTransaction) -> bool {
+    Self::touches(tx, &self.migrated_out)
+  }
+
+  // whether the frame executed by this shard touches one of the accounts
+  fn touches(tx: &GeneralTransaction, accs: &HashSet<Address>) -> bool {
+    if accs.is_empty() {
+      return false;
+    }
+    let frame: Option<&Frame> = match tx {
+      GeneralTransaction::TransferTx(tx) => tx.payload.get(tx.step),
+      GeneralTransaction::AggTx(tx) => tx.payload.first(),
+      GeneralTransaction::CommitMsg(msg) => match msg.phase {
+        CommitPhase::Vote(_) => None,
+        _ => msg.frame(),
+      },
+      GeneralTransaction::MigrationTx(_) => None,
+    };
+    match frame {
+      Some(frame) => frame.rwset.iter().any(|rw| accs.contains(&rw.addr)),
+      None => false,
+    }
+  }
+
+  async fn export(&mut self, plan: &MigrationPlan, store: &mut Box<dyn StateStore + Send>) {
+    // accounts coming back are paused rather than dropped
+    for addr in plan.incoming.values().flatten() {
+      self.migrated_out.remove(addr);
+      self.in_flight.insert(addr.clone());
+    }
+    if plan.outgoing.is_empty() {
+      return;
+    }
+
+    // all migrations of this epoch are proven against the same roots. With the online policy they
+    // also carry all our moves, as the other shards only know the moves they computed
+    let accs: Vec<Address> = plan.outgoing.values().flatten().cloned().collect();
+    let moves: BTreeMap<Address, ShardId> = match plan.accept_any {
+      true => plan.outgoing
+        .iter()
+        .flat_map(|(target_shard_id, accs)| accs.iter().map(move |addr| (addr.clone(), *target_shard_id)))
+        .collect(),
+      false => BTreeMap::new(),
+    };
+    self.migrated_out.extend(accs.iter().cloned());
+    let (act_root, full_root, mut act_acc_proof, mut dor_acc_proof) = store.export_accounts(accs).await;
+
+    for (target_shard_id, accs) in &plan.outgoing {
+      let mut active_acc_proof_map = HashMap::new();
+      let mut dormant_acc_proof_map = HashMap::new();
+      for addr in accs {
+        if let Some(proof) = act_acc_proof.remove(addr) {
+          active_acc_proof_map.insert(addr.clone(), proof);
+        } else if let Some(proof) = dor_acc_proof.remove(addr) {
+          dormant_acc_proof_map.insert(addr.clone(), proof);
+        }
+      }
+      let mut migration = Migration::new(
+        self.shard_id, *target_shard_id, plan.epoch, act_root.clone(), full_root.clone(),
+        active_acc_proof_map, dormant_acc_proof_map,
+      );
+      migration.moves = moves.clone();
+      info!("[height: {}] export migration {}", plan.height, migration);
+      self.tx_migration
+        .send(migration)
+        .await
+        .expect("Failed to send migration");
+    }
+  }
+
+  // install the committed migrations of the sources of an exported plan, and remove them from the
+  // plan. Return true if some accounts were installed
+  async fn install(&mut self, height: Height, plan: &mut MigrationPlan, store: &mut Box<dyn StateStore + Send>) -> bool {
+    let ready: Vec<ShardId> = plan.incoming
+      .keys()
+      .filter(|source| self.verified.contains_key(&(plan.epoch, **source)))
+      .cloned()
+      .collect();
+    for source_shard_id in &ready {
+      let accs = plan.incoming.remove(source_shard_id).unwrap();
+      let VerifiedMigration{mut active_accs, mut dormant_accs, moves, ..} = self.verified.remove(&(plan.epoch, *source_shard_id)).unwrap();
+
+      // only install the accounts the next mapping assigns to us
+      if !plan.accept_any {
+        active_accs.retain(|addr, _| accs.contains(addr));
+        dormant_accs.retain(|addr, _| accs.contains(addr));
+      }
+      for addr in active_accs.keys().chain(dormant_accs.keys()) {
+        self.migrated_out.remove(addr);
+      }
+      info!(
+        "[height: {}] install {} active and {} dormant accs from shard {} (epoch {})",
+        height, active_accs.len(), dormant_accs.len(), source_shard_id, plan.epoch
+      );
+      store.import_accounts(active_accs, dormant_accs).await;
+      self.moves.extend(moves);
+
+      for addr in &accs {
+        self.in_flight.remove(addr);
+      }
+    }
+    !ready.is_empty()
+  }
+}
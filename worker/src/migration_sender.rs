@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use config::{ShardId, NodeId};
+use network::ReliableSender;
+use crypto::{PublicKey, SignatureService};
+use std::net::SocketAddr;
+use tokio::sync::mpsc::Receiver;
+use log::{info, debug};
+use bytes::Bytes;
+use crate::messages::{CrossShardMessage, Migration};
+use crate::outstanding::OutstandingMessages;
+
+/// The number of past epochs whose migrations are still retransmitted: a shard installs the
+/// migrations of an epoch before it starts the next one.
+const MIGRATION_GC_EPOCHS: u64 = 1;
+
+/// Signs the migrations exported by the executor and sends them to every node of the target shard,
+/// which needs f+1 matching copies to certify the migration.
+pub struct MigrationSender {
+  name: PublicKey,
+  signature_service: SignatureService,
+
+  all_id_pubkey_map: Arc<HashMap<(ShardId, NodeId), (PublicKey, SocketAddr)>>,
+
+  rx_migration: Receiver<Migration>,
+  /// A network sender to send the migrations to the other shards.
+  network: ReliableSender,
+
+  /// Keeps the cancel handlers of the messages we sent, by epoch, until the target shards
+  /// acknowledge them or their epoch is over.
+  outstanding: OutstandingMessages,
+}
+
+
+impl MigrationSender {
+  pub fn spawn(
+    name: PublicKey,
+    signature_service: SignatureService,
+    all_id_pubkey_map: Arc<HashMap<(ShardId, NodeId), (PublicKey, SocketAddr)>>,
+    rx_migration: Receiver<Migration>,
+  ) {
+      tokio::spawn(async move {
+        Self {
+          name,
+          signature_service,
+          all_id_pubkey_map,
+          rx_migration,
+          network: ReliableSender::new(),
+          outstanding: OutstandingMessages::new("migration_sender", MIGRATION_GC_EPOCHS),
+        }
+        .run()
+        .await;
+    });
+  }
+
+  /// Main loop listening to the messages.
+  async fn run(&mut self) {
+    info!("MigrationSender is running!");
+
+    while let Some(mut migration) = self.rx_migration.recv().await {
+      migration.sign(&self.name, &mut self.signature_service).await;
+
+      let target_shard = migration.target_shard_id;
+      let addresses: Vec<SocketAddr> = self.all_id_pubkey_map
+        .iter()
+        .filter(|((shard_id, _), _)| *shard_id == target_shard)
+        .map(|(_, (_, addr))| *addr)
+        .collect();
+
+      debug!(
+        "Send migration {:?} ({} B) to: {:?}",
+          migration.header_digest(), migration.len(), addresses,
+      );
+      let epoch = migration.epoch;
+      let message = CrossShardMessage::Migration(migration);
+      let bytes = bincode::serialize(&message).expect("Failed to serialize our migration");
+      let handlers = self.network.broadcast(addresses.clone(), Bytes::from(bytes)).await;
+      self.outstanding.extend(epoch, addresses, handlers);
+      self.outstanding.cleanup(epoch);
+    }
+  }
+}
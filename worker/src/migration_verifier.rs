@@ -0,0 +1,154 @@
+use std::collections::{HashMap, HashSet};
+use config::{Committees, NodeId, ShardId};
+use crypto::{assemble_intact_ts_partial, verify_ts_sig, Digest, PartialSignature};
+use log::{info, debug, warn};
+use mpt::{Key, MerklePatriciaTrie, Proof, RootHash, Trie, Value};
+use tokio::sync::mpsc::{Receiver, Sender};
+use crate::error::{ExecutionError, ExecutionResult};
+use crate::messages::{Address, CertifiedMigration, GeneralTransaction, Migration};
+use crate::migration::VerifiedMigration;
+use crate::utils::shuffle_node_id_list;
+
+
+// the copies of a migration received with the same header
+#[derive(Default)]
+struct Candidate {
+  migration: Option<Migration>, // the first copy whose proofs are valid
+  partials: Vec<PartialSignature>,
+}
+
+/// Collects the copies of the migrations sent to this shard. A migration is certified once f+1
+/// nodes of the source shard signed the same header; f+1 nodes of our shard, drawn from the
+/// header, then append it to the DAG, and the executor installs it once it is committed.
+pub struct MigrationVerifier {
+  shard_id: ShardId,
+  nodeid: NodeId,
+  all_committees: Committees,
+
+  rx_migration: Receiver<Migration>,
+  tx_batch_maker: Sender<GeneralTransaction>,
+
+  candidates: HashMap<(u64, ShardId), HashMap<Digest, Candidate>>,
+  delivered: HashSet<(u64, ShardId)>,
+}
+
+impl MigrationVerifier {
+  pub fn spawn(
+    shard_id: ShardId,
+    nodeid: NodeId,
+    all_committees: Committees,
+    rx_migration: Receiver<Migration>,
+    tx_batch_maker: Sender<GeneralTransaction>,
+  ) {
+    tokio::spawn(async move {
+      Self {
+        shard_id,
+        nodeid,
+        all_committees,
+        rx_migration,
+        tx_batch_maker,
+        candidates: HashMap::new(),
+        delivered: HashSet::new(),
+      }
+      .run()
+      .await;
+    });
+  }
+
+  /// Main loop listening to the messages.
+  async fn run(&mut self) {
+    info!("MigrationVerifier is running!");
+
+    while let Some(migration) = self.rx_migration.recv().await {
+      debug!("Receiving migration {} from {}", migration, migration.author);
+      if let Err(e) = self.process_migration(migration).await {
+        warn!("Invalid migration: {}", e);
+      }
+    }
+  }
+
+  async fn process_migration(&mut self, migration: Migration) -> ExecutionResult<()> {
+    let key = (migration.epoch, migration.source_shard_id);
+    if migration.target_shard_id != self.shard_id || self.delivered.contains(&key) {
+      return Ok(());
+    }
+    migration.verify(&self.all_committees)?;
+
+    let digest = migration.header_digest();
+    let candidate = self.candidates
+      .entry(key)
+      .or_insert_with(HashMap::new)
+      .entry(digest.clone())
+      .or_insert_with(Candidate::default);
+    if candidate.partials.iter().any(|x| x.index == migration.thres_sig.index) {
+      return Ok(());
+    }
+    candidate.partials.push(migration.thres_sig.clone());
+
+    // the proofs are only checked once per header
+    if candidate.migration.is_none() {
+      verify_proofs(&migration)?;
+      candidate.migration = Some(migration);
+    }
+
+    let key_set = self.all_committees.threshold_keys(&key.1)?;
+    if candidate.partials.len() < key_set.threshold {
+      return Ok(());
+    }
+    let thres_sig = assemble_intact_ts_partial(candidate.partials.clone(), key_set, &digest)?;
+    ensure!(
+      verify_ts_sig(&key_set.public_key, &digest, &thres_sig),
+      ExecutionError::InvalidThresholdSignature(key.1)
+    );
+
+    let migration = CertifiedMigration::new(candidate.migration.take().unwrap(), thres_sig);
+    self.candidates.remove(&key);
+    self.delivered.insert(key);
+    info!("Certified migration {}", migration.migration);
+
+    // f+1 appenders, so that at least one honest node appends it; the executor ignores the copies
+    let appenders = self.all_committees.validity_threshold() as usize;
+    let shard_size = self.all_committees.our_committee(&self.shard_id)?.size();
+    if shuffle_node_id_list(shard_size, &digest)[..appenders].contains(&(self.nodeid as usize)) {
+      self.tx_batch_maker
+        .send(GeneralTransaction::MigrationTx(migration))
+        .await
+        .expect("Failed to send certified migration");
+    }
+    Ok(())
+  }
+}
+
+/// Verify the proof of every account of a migration against its roots and extract the states of
+/// the accounts.
+pub fn verify_proofs(migration: &Migration) -> ExecutionResult<VerifiedMigration> {
+  let source_shard_id = migration.source_shard_id;
+  let active_accs = verify_proof_map(&migration.act_root, &migration.active_acc_proof_map, source_shard_id)?;
+  let dormant_accs = verify_proof_map(&migration.full_root, &migration.dormant_acc_proof_map, source_shard_id)?;
+  Ok(VerifiedMigration {
+    source_shard_id,
+    epoch: migration.epoch,
+    active_accs,
+    dormant_accs,
+    moves: migration.moves.clone(),
+  })
+}
+
+fn verify_proof_map(
+  root: &RootHash,
+  proof_map: &HashMap<Address, Proof>,
+  source_shard_id: ShardId,
+) -> ExecutionResult<HashMap<Key, Value>> {
+  let mut accs = HashMap::new();
+  for (addr, proof) in proof_map {
+    match MerklePatriciaTrie::verify_proof(root.clone(), addr, proof.clone()) {
+      Ok(Some(value)) => {
+        accs.insert(addr.clone(), value);
+      },
+      _ => {
+        bail!(ExecutionError::InvalidMigrationProof(addr.clone(), source_shard_id));
+      },
+    }
+  }
+  Ok(accs)
+}
@@ -0,0 +1,75 @@
+use crate::metrics;
+use network::CancelHandler;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::SocketAddr;
+use tokio::sync::oneshot::error::TryRecvError;
+
+#[cfg(test)]
+#[path = "tests/outstanding_tests.rs"]
+pub mod outstanding_tests;
+
+
+/// The cancel handlers of the messages a component sent through a `ReliableSender`, by height (or
+/// by epoch). Dropping a handler stops the retransmissions of its message, so the handlers are
+/// dropped once the peer acknowledged the message, or once it is `gc_depth` heights old, like the
+/// rounds below the gc depth in `primary::Core`.
+pub struct OutstandingMessages {
+  /// The sender owning the handlers, which labels the metrics.
+  component: &'static str,
+  gc_depth: u64,
+  handlers: BTreeMap<u64, Vec<(SocketAddr, CancelHandler)>>,
+  /// The peers we reported in the metrics, so that their gauge drops to 0 once they caught up.
+  peers: HashSet<SocketAddr>,
+}
+
+impl OutstandingMessages {
+  pub fn new(component: &'static str, gc_depth: u64) -> Self {
+    Self { component, gc_depth, handlers: BTreeMap::new(), peers: HashSet::new() }
+  }
+
+  pub fn insert(&mut self, height: u64, peer: SocketAddr, handler: CancelHandler) {
+    self.handlers.entry(height).or_default().push((peer, handler));
+  }
+
+  /// Keep the handlers of a broadcast, given in the order of the addresses.
+  pub fn extend(&mut self, height: u64, peers: Vec<SocketAddr>, handlers: Vec<CancelHandler>) {
+    self.handlers.entry(height).or_default().extend(peers.into_iter().zip(handlers));
+  }
+
+  /// Keep the handlers of a new broadcast of the messages of a height, dropping the previous ones.
+  pub fn replace(&mut self, height: u64, peers: Vec<SocketAddr>, handlers: Vec<CancelHandler>) {
+    self.handlers.insert(height, peers.into_iter().zip(handlers).collect());
+  }
+
+  /// Drop the handlers of the acknowledged messages and of the messages older than `gc_depth`
+  /// heights behind `height`, then report the messages still outstanding for each peer.
+  pub fn cleanup(&mut self, height: u64) {
+    let gc_height = height.saturating_sub(self.gc_depth);
+    self.handlers = self.handlers.split_off(&gc_height);
+    for handlers in self.handlers.values_mut() {
+      handlers.retain_mut(|(_, handler)| matches!(handler.try_recv(), Err(TryRecvError::Empty)));
+    }
+    self.handlers.retain(|_, handlers| !handlers.is_empty());
+
+    let mut outstanding: HashMap<SocketAddr, usize> = HashMap::new();
+    for (peer, _) in self.handlers.values().flatten() {
+      *outstanding.entry(*peer).or_insert(0) += 1;
+    }
+    self.peers.extend(outstanding.keys());
+    for peer in &self.peers {
+      let count = outstanding.get(peer).cloned().unwrap_or(0);
+      metrics::OUTSTANDING_MESSAGES
+        .with_label_values(&[self.component, &peer.to_string()])
+        .set(count as i64);
+    }
+  }
+
+  /// The number of messages still outstanding.
+  pub fn len(&self) -> usize {
+    self.handlers.values().map(|handlers| handlers.len()).sum()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.handlers.is_empty()
+  }
+}
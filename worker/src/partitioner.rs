@@ -0,0 +1,116 @@
+use std::collections::{BTreeMap, BTreeSet};
+use config::ShardId;
+use log::info;
+use crate::{Account2Shard, Account2ShardGraph};
+use crate::messages::{Address, Transaction};
+
+#[cfg(test)]
+#[path = "tests/partitioner_tests.rs"]
+pub mod partitioner_tests;
+
+/// The tolerated load imbalance: no shard may carry more than (1 + IMBALANCE) times the average load.
+pub const IMBALANCE: f64 = 0.1;
+/// The maximum number of label propagation rounds per epoch.
+pub const MAX_ROUNDS: usize = 10;
+
+/// The account-interaction graph built from the txs committed by a shard during an epoch. Every
+/// tx touching one of our accounts is executed by us, so the neighborhood of our accounts is
+/// complete; only the accounts of the frame we execute are ours.
+/// BTree collections keep the iteration order, hence the partition, identical on every replica.
+#[derive(Default, Debug)]
+pub struct InteractionGraph {
+  weights: BTreeMap<Address, u64>, // number of txs touching the account
+  edges: BTreeMap<Address, BTreeMap<Address, u64>>, // number of txs touching both accounts
+  local: BTreeSet<Address>,
+}
+
+impl InteractionGraph {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Record a committed tx; `step` is the index of the frame executed by this shard.
+  pub fn observe(&mut self, tx: &Transaction) {
+    let accs: BTreeSet<&Address> = tx.payload
+      .iter()
+      .flat_map(|frame| frame.rwset.iter().map(|rw| &rw.addr))
+      .collect();
+    for acc in &accs {
+      *self.weights.entry((*acc).clone()).or_insert(0) += 1;
+      for other in &accs {
+        if acc != other {
+          *self.edges
+            .entry((*acc).clone())
+            .or_default()
+            .entry((*other).clone())
+            .or_insert(0) += 1;
+        }
+      }
+    }
+    if let Some(frame) = tx.payload.get(tx.step) {
+      self.local.extend(frame.rwset.iter().map(|rw| rw.addr.clone()));
+    }
+  }
+
+  /// Run label propagation over our accounts, starting from the current mapping: each account
+  /// moves to the shard holding most of its interactions, as long as the target stays within the
+  /// load-balance constraint. Only our accounts are moved, since the owner of an account is the
+  /// only shard seeing all its txs; the loads are those of the accounts of the graph.
+  /// Return the mapping of the next epoch and the accounts leaving us with their target shard.
+  pub fn partition(
+    &self,
+    shard_id: ShardId,
+    current: &dyn Account2Shard,
+  ) -> (Account2ShardGraph, BTreeMap<Address, ShardId>) {
+    let shard_num = current.get_shard_num();
+    let mut labels: BTreeMap<&Address, ShardId> = self.weights
+      .keys()
+      .map(|acc| {
+        let shard = if self.local.contains(acc) { shard_id } else { current.get_shard(acc) };
+        (acc, shard)
+      })
+      .collect();
+
+    let mut loads = vec![0u64; shard_num];
+    for (acc, shard) in &labels {
+      loads[*shard] += self.weights[*acc];
+    }
+    let total: u64 = loads.iter().sum();
+    let capacity = ((1.0 + IMBALANCE) * total as f64 / shard_num as f64).ceil() as u64;
+
+    for round in 0..MAX_ROUNDS {
+      let mut moved = 0;
+      for acc in &self.local {
+        let label = labels[acc];
+        let mut gains = vec![0u64; shard_num];
+        if let Some(neighbors) = self.edges.get(acc) {
+          for (neighbor, weight) in neighbors {
+            gains[labels[neighbor]] += weight;
+          }
+        }
+        // ties keep the current shard, then go to the lowest shard id
+        let best = (0..shard_num).fold(label, |best, shard| {
+          if gains[shard] > gains[best] { shard } else { best }
+        });
+        let weight = self.weights[acc];
+        if best != label && loads[best] + weight <= capacity {
+          loads[label] -= weight;
+          loads[best] += weight;
+          labels.insert(acc, best);
+          moved += 1;
+        }
+      }
+      if moved == 0 {
+        info!("label propagation converged after {} rounds", round);
+        break;
+      }
+    }
+
+    let moves: BTreeMap<Address, ShardId> = self.local
+      .iter()
+      .map(|acc| (acc.clone(), labels[acc]))
+      .filter(|(_, shard)| *shard != shard_id)
+      .collect();
+    (Account2ShardGraph::with_moves(current, moves.clone()), moves)
+  }
+}
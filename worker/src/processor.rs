@@ -3,13 +3,14 @@ use crate::csmsg_store::CSMsgStore;
 use crate::utils::shuffle_node_id_list;
 use crate::worker::SerializedBatchDigestMessage;
 use crate::worker::WorkerMessage;
+use config::Committees;
 use config::NodeId;
 use config::WorkerId;
 use crypto::Digest;
 use ed25519_dalek::Digest as _;
 use ed25519_dalek::Sha512;
 use log::debug;
-use log::info;
+use log::warn;
 use primary::WorkerPrimaryMessage;
 use std::convert::TryInto;
 use store::Store;
@@ -38,6 +39,7 @@ pub struct Processor {
   own_digest: bool,
   csmsg_store: CSMsgStore,
   cs_rev_nums: usize, // number of cross-shard receivers 
+  all_committees: Committees,
 }
 
 impl Processor {
@@ -57,6 +59,8 @@ impl Processor {
         own_digest: bool,
         csmsg_store: CSMsgStore,
         cs_rev_nums: usize,
+        // The shards information, used to verify the cs_proof of appended csmsgs.
+        all_committees: Committees,
     ) {
 
       tokio::spawn(async move {
@@ -71,6 +75,7 @@ impl Processor {
             own_digest,
             csmsg_store,
             cs_rev_nums,
+            all_committees,
         }
         .run()
         .await;
@@ -121,12 +126,11 @@ impl Processor {
                       None => {}, // not a csmsg
                       Some(csmsg_id) => {
                         // verify the threshold sig and get tx_hash
-                        let tx_hash = tx.verify_cs_proof();
-                        match tx_hash {
-                          None => {
-                            info!("an invalid csmsg is appended!");
+                        match tx.verify_cs_proof(&self.all_committees) {
+                          Err(e) => {
+                            warn!("an invalid csmsg is appended: {}", e);
                           },
-                          Some(tx_hash) => {// valid csmsg
+                          Ok(tx_hash) => {// valid csmsg
                             // mark this csmsg has been appended regardless of whether the node is a receiver or not
                             // let _ = self.csmsg_store.update_appended(csmsg_id, AppendedType::Remote).await;
 
@@ -0,0 +1,322 @@
+use config::ShardId;
+use crypto::Digest;
+use mpt::RootHash;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use store::Store;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
+use crate::csmsg_store::CSMsgStore;
+use crate::execution_result::ExecutionCertificate;
+use crate::messages::{Address, CSMsgId, Height};
+use crate::receipt::ReceiptStore;
+use crate::result_checker::{certificate_key, CERTIFIED_HEIGHT_KEY};
+use crate::state_store::{Account, AccountProof};
+
+#[cfg(test)]
+#[path = "tests/query_tests.rs"]
+pub mod query_tests;
+
+
+pub const JSONRPC_VERSION: &str = "2.0";
+
+// the error codes of the JSON-RPC 2.0 specification
+pub const PARSE_ERROR: i64 = -32700;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+
+/// The queries answered by the executor, which owns the state. It answers them between two blocks,
+/// so the answers match the execution result of the last executed height.
+#[derive(Debug)]
+pub enum StateQuery {
+  Account(Address, oneshot::Sender<Option<Account>>),
+  StateRoot(oneshot::Sender<StateRoot>),
+  /// The proof of an account at a height, served only for the last certified height.
+  Proof(Address, Height, oneshot::Sender<Option<StateProof>>),
+  /// The last executed height and the current epoch.
+  Progress(oneshot::Sender<(Height, u64)>),
+  /// Our accounts that left at an epoch of the online policy, with their new shard.
+  Moves(u64, oneshot::Sender<Option<BTreeMap<Address, ShardId>>>),
+}
+
+/// The roots of the state once a height is executed.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct StateRoot {
+    pub shard_id: ShardId,
+    pub height: Height,
+    #[serde(with = "hex_bytes")]
+    pub act_root: RootHash,
+    #[serde(with = "hex_bytes")]
+    pub full_root: RootHash,
+}
+
+/// The proof of an account against the roots of a height.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct StateProof {
+    pub root: StateRoot,
+    pub proof: AccountProof,
+}
+
+
+/// A JSON-RPC 2.0 request. The params are positional or named.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RpcRequest {
+    pub jsonrpc: String,
+    pub id: Value,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+impl RpcRequest {
+  pub fn new(id: u64, method: &str, params: Value) -> Self {
+    Self { jsonrpc: JSONRPC_VERSION.to_string(), id: Value::from(id), method: method.to_string(), params }
+  }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+  pub fn new(code: i64, message: String) -> Self {
+    Self { code, message }
+  }
+}
+
+impl fmt::Display for RpcError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      write!(f, "rpc error {}: {}", self.code, self.message)
+  }
+}
+
+/// A JSON-RPC 2.0 response: either a result or an error.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl RpcResponse {
+  fn new(id: Value, outcome: Result<Value, RpcError>) -> Self {
+    let (result, error) = match outcome {
+      Ok(result) => (Some(result), None),
+      Err(error) => (None, Some(error)),
+    };
+    Self { jsonrpc: JSONRPC_VERSION.to_string(), id, result, error }
+  }
+
+  /// The result of the call, deserialized.
+  pub fn into_result<T: DeserializeOwned>(self) -> Result<T, RpcError> {
+    match (self.error, self.result) {
+      (Some(error), _) => Err(error),
+      (None, result) => serde_json::from_value(result.unwrap_or(Value::Null))
+        .map_err(|e| RpcError::new(PARSE_ERROR, e.to_string())),
+    }
+  }
+}
+
+
+/// Answers the read-only JSON-RPC queries received on the `query` address of the worker:
+/// `get_account(addr)`, `get_state_root()`, `get_proof(addr, height)`, `get_csmsg_status(id)`,
+/// `get_receipt(digest)`, `get_height()`, `get_epoch()`, `get_moves(epoch)`,
+/// `get_certified_height()` and `get_certificate(height)` (see `ResultChecker`). Addresses,
+/// digests, roots and proof nodes are hex strings. An account that was never touched is null: it
+/// holds `INIT_BALANCE`.
+#[derive(Clone)]
+pub struct QueryService {
+  tx_state_query: Sender<StateQuery>,
+  store: Store,
+  receipt_store: ReceiptStore,
+  csmsg_store: CSMsgStore,
+}
+
+impl QueryService {
+  pub fn new(tx_state_query: Sender<StateQuery>, store: Store, csmsg_store: CSMsgStore) -> Self {
+    let receipt_store = ReceiptStore::new(store.clone());
+    Self { tx_state_query, store, receipt_store, csmsg_store }
+  }
+
+  /// Answer a serialized request. Batches of requests are not supported.
+  pub async fn handle(&self, request: &[u8]) -> RpcResponse {
+    match serde_json::from_slice::<RpcRequest>(request) {
+      Err(e) => RpcResponse::new(Value::Null, Err(RpcError::new(PARSE_ERROR, e.to_string()))),
+      Ok(request) => {
+        let outcome = self.call(&request.method, &request.params).await;
+        RpcResponse::new(request.id, outcome)
+      }
+    }
+  }
+
+  async fn call(&self, method: &str, params: &Value) -> Result<Value, RpcError> {
+    match method {
+      "get_account" => {
+        let addr = hex_param(params, 0, "addr")?;
+        to_value(self.query(|reply| StateQuery::Account(addr, reply)).await)
+      },
+      "get_state_root" => to_value(self.query(StateQuery::StateRoot).await),
+      "get_proof" => {
+        let addr = hex_param(params, 0, "addr")?;
+        let height: Height = param(params, 1, "height")?;
+        to_value(self.query(|reply| StateQuery::Proof(addr, height, reply)).await)
+      },
+      "get_csmsg_status" => {
+        let id: CSMsgId = param(params, 0, "id")?;
+        let status = self.csmsg_store.clone().status(id).await
+          .map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?;
+        to_value(status)
+      },
+      "get_receipt" => {
+        let digest = Digest::try_from(&hex_param(params, 0, "digest")?[..])
+          .map_err(|_| RpcError::new(INVALID_PARAMS, "a digest has 32 bytes".to_string()))?;
+        let receipt = self.receipt_store.clone().read(&digest).await
+          .map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?;
+        to_value(receipt)
+      },
+      "get_height" => to_value(self.query(StateQuery::Progress).await.0),
+      "get_epoch" => to_value(self.query(StateQuery::Progress).await.1),
+      "get_moves" => {
+        let epoch: u64 = param(params, 0, "epoch")?;
+        let moves = self.query(|reply| StateQuery::Moves(epoch, reply)).await.map(|moves| {
+          moves.into_iter().map(|(addr, shard_id)| (hex::encode(addr), shard_id)).collect::<BTreeMap<_, _>>()
+        });
+        to_value(moves)
+      },
+      "get_certified_height" => {
+        let height = self.read(CERTIFIED_HEIGHT_KEY.to_vec()).await?
+          .map(|bytes| Height::from_le_bytes(bytes[..].try_into().expect("Corrupted certified height")));
+        to_value(height)
+      },
+      "get_certificate" => {
+        let height: Height = param(params, 0, "height")?;
+        let certificate = self.read(certificate_key(height)).await?.map(|bytes| {
+          bincode::deserialize::<ExecutionCertificate>(&bytes).expect("Failed to deserialize execution certificate")
+        });
+        to_value(certificate)
+      },
+      _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method {}", method))),
+    }
+  }
+
+  async fn read(&self, key: Vec<u8>) -> Result<Option<Vec<u8>>, RpcError> {
+    self.store.clone().read(key).await.map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))
+  }
+
+  async fn query<T>(&self, command: impl FnOnce(oneshot::Sender<T>) -> StateQuery) -> T {
+    let (sender, receiver) = oneshot::channel();
+    if let Err(e) = self.tx_state_query.send(command(sender)).await {
+      panic!("Failed to send state query to the executor: {}", e);
+    }
+    receiver
+      .await
+      .expect("Failed to receive reply to state query from the executor")
+  }
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, RpcError> {
+  serde_json::to_value(value).map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))
+}
+
+// the index-th positional param, or the named one
+fn param<T: DeserializeOwned>(params: &Value, index: usize, name: &str) -> Result<T, RpcError> {
+  let value = match params {
+    Value::Array(values) => values.get(index),
+    Value::Object(values) => values.get(name),
+    _ => None,
+  };
+  let value = value.ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("missing param {}", name)))?;
+  serde_json::from_value(value.clone())
+    .map_err(|e| RpcError::new(INVALID_PARAMS, format!("invalid param {}: {}", name, e)))
+}
+
+fn hex_param(params: &Value, index: usize, name: &str) -> Result<Vec<u8>, RpcError> {
+  let value: String = param(params, index, name)?;
+  hex::decode(value.trim_start_matches("0x"))
+    .map_err(|e| RpcError::new(INVALID_PARAMS, format!("invalid param {}: {}", name, e)))
+}
+
+
+/// Serde helpers writing bytes as hex strings in human-readable formats (the JSON-RPC answers)
+/// and as bytes otherwise, like `Amount`.
+pub mod hex_bytes {
+  use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+  pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+      serializer.serialize_str(&hex::encode(bytes))
+    } else {
+      bytes.serialize(serializer)
+    }
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    if deserializer.is_human_readable() {
+      let value = String::deserialize(deserializer)?;
+      hex::decode(value.trim_start_matches("0x")).map_err(de::Error::custom)
+    } else {
+      Vec::<u8>::deserialize(deserializer)
+    }
+  }
+}
+
+pub mod hex_digest {
+  use crypto::Digest;
+  use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+  use std::convert::TryFrom;
+
+  pub fn serialize<S: Serializer>(digest: &Digest, serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+      serializer.serialize_str(&hex::encode(digest.0))
+    } else {
+      digest.serialize(serializer)
+    }
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Digest, D::Error> {
+    if deserializer.is_human_readable() {
+      let bytes = super::hex_bytes::deserialize(deserializer)?;
+      Digest::try_from(&bytes[..]).map_err(de::Error::custom)
+    } else {
+      Digest::deserialize(deserializer)
+    }
+  }
+}
+
+pub mod hex_proof {
+  use mpt::Proof;
+  use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+  pub fn serialize<S: Serializer>(proof: &Proof, serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+      let nodes: Vec<String> = proof.nodes.iter().map(hex::encode).collect();
+      nodes.serialize(serializer)
+    } else {
+      proof.serialize(serializer)
+    }
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Proof, D::Error> {
+    if deserializer.is_human_readable() {
+      let nodes = Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(hex::decode)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(de::Error::custom)?;
+      Ok(Proof::from(nodes))
+    } else {
+      Proof::deserialize(deserializer)
+    }
+  }
+}
@@ -0,0 +1,195 @@
+use config::ShardId;
+use crypto::{Digest, Hash};
+use ed25519_dalek::Digest as _;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use store::{Store, StoreError};
+use crate::amount::AmountError;
+use crate::messages::{canonical_hasher, finalize, CommitMessage, Height};
+use crate::metrics;
+use crate::query::hex_digest;
+
+#[cfg(test)]
+#[path = "tests/receipt_tests.rs"]
+pub mod receipt_tests;
+
+
+/// The outcome of a tx in a shard.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum ReceiptStatus {
+  Committed,
+  Aborted(AmountError),
+  /// The tx is executed here and relayed to the given shard for its next step, or to the
+  /// brokers (`ShardId::MAX`) in BrokerChain.
+  Relayed(ShardId),
+  /// The tx is rolled back by the protocol, e.g. in TwoPhaseCommit when a participant voted no
+  /// or the votes timed out.
+  RolledBack,
+  /// The client tx is dropped before its execution, e.g. because its sender migrated to another
+  /// shard. Its nonce is not consumed: the client resubmits it with the same nonce.
+  Rejected,
+}
+
+impl ReceiptStatus {
+  /// The label of the status in the metrics.
+  pub fn label(&self) -> &'static str {
+    match self {
+      ReceiptStatus::Committed => "committed",
+      ReceiptStatus::Aborted(_) | ReceiptStatus::RolledBack => "aborted",
+      ReceiptStatus::Relayed(_) => "relayed",
+      ReceiptStatus::Rejected => "rejected",
+    }
+  }
+}
+
+impl fmt::Display for ReceiptStatus {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ReceiptStatus::Committed => write!(f, "committed"),
+      ReceiptStatus::Aborted(e) => write!(f, "aborted ({})", e),
+      ReceiptStatus::Relayed(shard) => write!(f, "relayed to shard {}", shard),
+      ReceiptStatus::RolledBack => write!(f, "rolled back"),
+      ReceiptStatus::Rejected => write!(f, "rejected"),
+    }
+  }
+}
+
+
+/// The receipt written by a shard when it executes a tx. The receipts of a block are committed in
+/// the `tx_receipts_root` of its execution result, except the latency observed by the replica.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Receipt {
+    /// The client digest of the tx (see `Transaction::client_digest`), or the digest of the agg tx.
+    #[serde(with = "hex_digest")]
+    pub tx_digest: Digest,
+    pub status: ReceiptStatus,
+    pub height: Height,
+    pub shard_id: ShardId,
+    /// The shards that executed the tx so far, ending with this one.
+    pub shard_path: Vec<ShardId>,
+    /// The time between the submission of the tx by the client and its execution here, in ms.
+    pub latency_ms: Option<u64>,
+}
+
+impl Receipt {
+  /// `timestamp` is the submission time of the tx in µs since the unix epoch, if known.
+  pub fn new(
+    tx_digest: Digest,
+    status: ReceiptStatus,
+    height: Height,
+    shard_id: ShardId,
+    shard_path: Vec<ShardId>,
+    timestamp: Option<u128>,
+  ) -> Self {
+    let latency_ms = timestamp.map(|timestamp| {
+      let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_micros();
+      (now.saturating_sub(timestamp) / 1000) as u64
+    });
+    Self { tx_digest, status, height, shard_id, shard_path, latency_ms }
+  }
+}
+
+impl Hash for Receipt {
+  fn digest(&self) -> Digest {
+    let mut hasher = canonical_hasher(b"SHARDAG_RECEIPT");
+    hasher.update(self.tx_digest.to_vec());
+    match self.status {
+      ReceiptStatus::Committed => hasher.update([0u8]),
+      ReceiptStatus::Relayed(shard) => {
+        hasher.update([1u8]);
+        hasher.update((shard as u64).to_le_bytes());
+      },
+      ReceiptStatus::Aborted(AmountError::Overflow) => hasher.update([2u8]),
+      ReceiptStatus::Aborted(AmountError::Underflow) => hasher.update([3u8]),
+      ReceiptStatus::RolledBack => hasher.update([4u8]),
+      ReceiptStatus::Rejected => hasher.update([5u8]),
+    }
+    hasher.update(self.height.to_le_bytes());
+    hasher.update((self.shard_id as u64).to_le_bytes());
+    hasher.update((self.shard_path.len() as u64).to_le_bytes());
+    for shard in &self.shard_path {
+      hasher.update((*shard as u64).to_le_bytes());
+    }
+    finalize(hasher)
+  }
+}
+
+impl fmt::Display for Receipt {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      write!(
+        f, "[RECEIPT][tx: {}, {}, height: {}, shard path: {:?}]",
+        self.tx_digest, self.status, self.height, self.shard_path
+      )
+  }
+}
+
+
+/// The key under which the receipt of a tx is persisted.
+fn receipt_key(tx_digest: &Digest) -> Vec<u8> {
+  let mut key = b"receipt_".to_vec();
+  key.extend_from_slice(&tx_digest.0);
+  key
+}
+
+/// The key under which the compensation of the step-th frame of a tx is marked: a tx may have
+/// several frames in our shard, each one is compensated once.
+fn compensated_key(tx_digest: &Digest, step: usize) -> Vec<u8> {
+  let mut key = b"compensated_".to_vec();
+  key.extend_from_slice(&tx_digest.0);
+  key.extend_from_slice(&(step as u64).to_le_bytes());
+  key
+}
+
+/// The key under which the compensations waiting for a retry are persisted.
+const PENDING_COMPENSATIONS_KEY: &[u8] = b"pending_compensations";
+
+/// Persists the receipts written by our executor so that clients can look them up by tx digest.
+/// A later receipt of the same tx in our shard replaces the earlier one.
+#[derive(Clone)]
+pub struct ReceiptStore {
+  store: Store,
+}
+
+impl ReceiptStore {
+  pub fn new(store: Store) -> Self {
+    Self { store }
+  }
+
+  /// Persist a receipt and return its digest. Every tx executed by our shard gets a receipt, so
+  /// the executed txs are counted here for all the executors.
+  pub async fn write(&mut self, receipt: &Receipt) -> Digest {
+    metrics::EXECUTED_TXS.with_label_values(&[receipt.status.label()]).inc();
+    let bytes = bincode::serialize(receipt).expect("Failed to serialize receipt");
+    self.store.write(receipt_key(&receipt.tx_digest), bytes).await;
+    receipt.digest()
+  }
+
+  pub async fn read(&mut self, tx_digest: &Digest) -> Result<Option<Receipt>, StoreError> {
+    let receipt = self.store.read(receipt_key(tx_digest)).await?.map(|bytes| {
+      bincode::deserialize(&bytes).expect("Failed to deserialize receipt")
+    });
+    Ok(receipt)
+  }
+
+  pub async fn compensated(&mut self, tx_digest: &Digest, step: usize) -> Result<bool, StoreError> {
+    Ok(self.store.read(compensated_key(tx_digest, step)).await?.is_some())
+  }
+
+  pub async fn write_compensated(&mut self, tx_digest: &Digest, step: usize) {
+    self.store.write(compensated_key(tx_digest, step), Vec::new()).await;
+  }
+
+  /// The compensations which could not be applied yet, in the order they were received.
+  pub async fn read_pending_compensations(&mut self) -> Result<Vec<CommitMessage>, StoreError> {
+    let pending = self.store.read(PENDING_COMPENSATIONS_KEY.to_vec()).await?.map(|bytes| {
+      bincode::deserialize(&bytes).expect("Failed to deserialize pending compensations")
+    });
+    Ok(pending.unwrap_or_default())
+  }
+
+  pub async fn write_pending_compensations(&mut self, pending: &[CommitMessage]) {
+    let bytes = bincode::serialize(pending).expect("Failed to serialize pending compensations");
+    self.store.write(PENDING_COMPENSATIONS_KEY.to_vec(), bytes).await;
+  }
+}
@@ -0,0 +1,250 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use bytes::Bytes;
+use config::{Committee, Committees, NodeId, ShardId, Stake};
+use crypto::{PublicKey, Signature, SignatureService};
+use log::{debug, error, info, warn};
+use network::{CancelHandler, ReliableSender};
+use store::Store;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::oneshot;
+use crate::execution_result::{ExecutionCertificate, ExecutionResult, ExecutionVote};
+use crate::messages::{CrossShardMessage, Height};
+
+#[cfg(test)]
+#[path = "tests/result_checker_tests.rs"]
+pub mod result_checker_tests;
+
+
+/// The key under which our execution result of a height is persisted.
+pub fn result_key(height: Height) -> Vec<u8> {
+  format!("execution_result_{}", height).into_bytes()
+}
+
+/// The key under which the execution certificate of a height is persisted.
+pub fn certificate_key(height: Height) -> Vec<u8> {
+  format!("execution_certificate_{}", height).into_bytes()
+}
+
+/// The key under which the last certified height is persisted.
+pub const CERTIFIED_HEIGHT_KEY: &[u8] = b"execution_certified_height";
+
+
+/// Counts the votes of the replicas of our shard until f+1 of them agree on the result of a height.
+/// The roots cover the whole state, so a height certifies the heights below it as well: once a
+/// height is certified, the votes for it and for the heights below it are dropped.
+pub struct ResultTally {
+  committee: Committee,
+  votes: BTreeMap<Height, HashMap<PublicKey, (ExecutionResult, Signature)>>,
+  settled: Option<Height>,
+}
+
+impl ResultTally {
+  pub fn new(committee: Committee) -> Self {
+    Self { committee, votes: BTreeMap::new(), settled: None }
+  }
+
+  /// Record a (verified) vote and return the certificate of its result if it is the f+1-th vote
+  /// for it. Only the first vote of each replica for a height counts.
+  pub fn add_vote(&mut self, vote: ExecutionVote) -> Option<ExecutionCertificate> {
+    let height = vote.result.height;
+    if self.settled.map_or(false, |settled| height <= settled) {
+      return None;
+    }
+    let votes = self.votes.entry(height).or_default();
+    if votes.contains_key(&vote.author) {
+      return None;
+    }
+    votes.insert(vote.author, (vote.result.clone(), vote.signature));
+
+    let matching: Vec<(PublicKey, Signature)> = votes
+      .iter()
+      .filter(|(_, (result, _))| *result == vote.result)
+      .map(|(name, (_, signature))| (*name, signature.clone()))
+      .collect();
+    let weight: Stake = matching.iter().map(|(name, _)| self.committee.stake(name)).sum();
+    if weight < self.committee.validity_threshold() {
+      return None;
+    }
+
+    self.votes = self.votes.split_off(&(height + 1));
+    self.settled = Some(height);
+    Some(ExecutionCertificate { shard_id: vote.shard_id, result: vote.result, votes: matching })
+  }
+}
+
+
+type SubmitCommand = (ExecutionResult, oneshot::Sender<(Option<Height>, Option<ExecutionResult>)>);
+
+/// Exchanges the execution result of every height with the other replicas of our shard and checks
+/// ours against the result certified by f+1 of them. A replica whose result differs has diverged:
+/// it raises an alarm and its executor halts.
+#[derive(Clone)]
+pub struct ResultChecker {
+  channel: Sender<SubmitCommand>,
+}
+
+impl ResultChecker {
+  #[allow(clippy::too_many_arguments)]
+  pub fn spawn(
+    shard_id: ShardId,
+    name: PublicKey,
+    all_committees: Committees,
+    signature_service: SignatureService,
+    all_id_pubkey_map: Arc<HashMap<(ShardId, NodeId), (PublicKey, SocketAddr)>>,
+    store: Store,
+    rx_vote: Receiver<ExecutionVote>,
+  ) -> Self {
+    let committee = all_committees.our_committee(&shard_id).expect("Our shard is not in the committees");
+    let peers = all_id_pubkey_map
+      .iter()
+      .filter(|((shard, _), (key, _))| *shard == shard_id && *key != name)
+      .map(|(_, (_, addr))| *addr)
+      .collect();
+
+    let (tx, rx_submit) = channel(100);
+    tokio::spawn(async move {
+      Core {
+        shard_id,
+        name,
+        all_committees,
+        signature_service,
+        peers,
+        store,
+        rx_submit,
+        rx_vote,
+        tally: ResultTally::new(committee),
+        local: BTreeMap::new(),
+        pending: BTreeMap::new(),
+        diverged: None,
+        certified: None,
+        network: ReliableSender::new(),
+        cancel_handlers: BTreeMap::new(),
+      }
+      .run()
+      .await;
+    });
+    Self { channel: tx }
+  }
+
+  /// Submit our result of a height. Return the first height at which our state diverged from the
+  /// state certified by our shard, if any, and the last certified result that matched ours.
+  pub async fn submit(&self, result: ExecutionResult) -> (Option<Height>, Option<ExecutionResult>) {
+    let (sender, receiver) = oneshot::channel();
+    if let Err(e) = self.channel.send((result, sender)).await {
+      panic!("Failed to submit execution result to ResultChecker: {}", e);
+    }
+    receiver
+      .await
+      .expect("Failed to receive reply to execution result from ResultChecker")
+  }
+}
+
+
+struct Core {
+  shard_id: ShardId,
+  name: PublicKey,
+  all_committees: Committees,
+  signature_service: SignatureService,
+  /// The addresses of the other replicas of our shard.
+  peers: Vec<SocketAddr>,
+  store: Store,
+
+  rx_submit: Receiver<SubmitCommand>,
+  rx_vote: Receiver<ExecutionVote>,
+
+  tally: ResultTally,
+  /// Our results not checked yet.
+  local: BTreeMap<Height, ExecutionResult>,
+  /// The certificates of the heights we did not execute yet.
+  pending: BTreeMap<Height, ExecutionCertificate>,
+  diverged: Option<Height>,
+  /// The last certified result that matched ours.
+  certified: Option<ExecutionResult>,
+
+  network: ReliableSender,
+  /// The cancel handlers of our votes, kept until their height is checked.
+  cancel_handlers: BTreeMap<Height, Vec<CancelHandler>>,
+}
+
+impl Core {
+  async fn run(&mut self) {
+    info!("ResultChecker is running!");
+
+    loop {
+      tokio::select! {
+        Some((result, reply)) = self.rx_submit.recv() => {
+          self.process_result(result).await;
+          let _ = reply.send((self.diverged, self.certified.clone()));
+        },
+        Some(vote) = self.rx_vote.recv() => {
+          if vote.shard_id != self.shard_id {
+            continue;
+          }
+          match vote.verify(&self.all_committees) {
+            Err(e) => warn!("invalid execution vote {}: {}", vote, e),
+            Ok(()) => {
+              if let Some(certificate) = self.tally.add_vote(vote) {
+                self.process_certificate(certificate).await;
+              }
+            }
+          }
+        },
+      }
+    }
+  }
+
+  async fn process_result(&mut self, result: ExecutionResult) {
+    let height = result.height;
+    let bytes = bincode::serialize(&result).expect("Failed to serialize execution result");
+    self.store.write(result_key(height), bytes).await;
+    self.local.insert(height, result.clone());
+
+    let vote = ExecutionVote::new(self.shard_id, result, &self.name, &mut self.signature_service).await;
+    debug!("Send {} to: {:?}", vote, self.peers);
+    let message = CrossShardMessage::ExecutionVote(vote.clone());
+    let bytes = bincode::serialize(&message).expect("Failed to serialize our vote");
+    let handlers = self.network.broadcast(self.peers.clone(), Bytes::from(bytes)).await;
+    self.cancel_handlers.insert(height, handlers);
+
+    if let Some(certificate) = self.tally.add_vote(vote) {
+      self.process_certificate(certificate).await;
+    }
+    if let Some(certificate) = self.pending.remove(&height) {
+      self.check(certificate);
+    }
+  }
+
+  async fn process_certificate(&mut self, certificate: ExecutionCertificate) {
+    let height = certificate.result.height;
+    let bytes = bincode::serialize(&certificate).expect("Failed to serialize execution certificate");
+    self.store.write(certificate_key(height), bytes).await;
+    self.store.write(CERTIFIED_HEIGHT_KEY.to_vec(), height.to_le_bytes().to_vec()).await;
+
+    if self.local.contains_key(&height) {
+      self.check(certificate);
+    } else {
+      self.pending.insert(height, certificate);
+    }
+  }
+
+  // compare our result with the certified one, then forget the heights up to it
+  fn check(&mut self, certificate: ExecutionCertificate) {
+    let height = certificate.result.height;
+    let local = &self.local[&height];
+    if *local != certificate.result {
+      error!(
+        "State diverged at height {}: our result {} differs from the result {} certified by our shard",
+        height, local, certificate.result
+      );
+      self.diverged.get_or_insert(height);
+    } else {
+      debug!("Execution result of height {} is certified", height);
+      self.certified = Some(certificate.result);
+    }
+    self.local = self.local.split_off(&(height + 1));
+    self.pending = self.pending.split_off(&(height + 1));
+    self.cancel_handlers = self.cancel_handlers.split_off(&(height + 1));
+  }
+}
@@ -0,0 +1,134 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use config::ShardId;
+use futures::future::join_all;
+use crate::{Address, AmountError, Frame};
+use crate::messages::GeneralTransaction;
+use crate::state_store::{Account, KeyRegistry};
+use crate::state_transition::{apply_deltas, new_account};
+
+#[cfg(test)]
+#[path = "tests/scheduler_tests.rs"]
+pub mod scheduler_tests;
+
+
+// the fewest txs worth a task of their own
+const MIN_TASK_TXS: usize = 16;
+
+// the accounts a tx may touch in any shard, as declared by the rwsets of its frames, with the
+// sender of a client tx whose nonce is consumed. Every declared account may be written, e.g. the
+// accounts of the earlier frames of an aborted tx by their compensation
+fn declared_accounts(tx: &GeneralTransaction) -> Vec<&Address> {
+  let mut accs: Vec<&Address> = match tx {
+    GeneralTransaction::TransferTx(tx) => tx.payload.iter(),
+    GeneralTransaction::AggTx(tx) => tx.payload.iter(),
+    GeneralTransaction::CommitMsg(msg) => msg.tx.payload.iter(),
+    GeneralTransaction::MigrationTx(_) => [].iter(),
+  }
+  .flat_map(|frame| frame.rwset.iter().map(|rw| &rw.addr))
+  .collect();
+  if let GeneralTransaction::TransferTx(tx) = tx {
+    if tx.get_csmsg_id().is_none() {
+      accs.push(&tx.sender);
+    }
+  }
+  accs
+}
+
+/// The txs of a block declaring no account of an earlier tx of the block: they read the accounts
+/// as prefetched before the block, whatever the outcome of the earlier txs. A tx conflicting with
+/// an earlier one is executed once the earlier one is.
+pub fn independent_txs(txs: &[GeneralTransaction]) -> Vec<usize> {
+  let mut declared: HashSet<&Address> = HashSet::new();
+  let mut independent = Vec::new();
+  for (i, tx) in txs.iter().enumerate() {
+    let accs = declared_accounts(tx);
+    if accs.iter().all(|acc| !declared.contains(acc)) {
+      independent.push(i);
+    }
+    declared.extend(accs);
+  }
+  independent
+}
+
+
+/// A frame executed ahead of its turn against the accounts prefetched before its block. Its
+/// outcome is applied when the frame is executed in block order, provided its accounts hold the
+/// same states then (see `StateTransition::speculated`).
+pub struct Speculation {
+  pub frame: Frame,
+  // the states of the accounts of the frame before it is applied
+  pub inputs: HashMap<Address, Account>,
+  pub outcome: Result<HashMap<Address, Account>, AmountError>,
+}
+
+// execute the frame our shard executes for a transfer tx or an agg tx, once a client tx consumed
+// the nonce of its sender; None for the other txs and the client txs which are not authorized
+fn speculate(
+  shard_id: ShardId,
+  tx: &GeneralTransaction,
+  accounts: &HashMap<Address, Option<Account>>,
+  keys: &KeyRegistry,
+) -> Option<Speculation> {
+  let (frame, sender) = match tx {
+    GeneralTransaction::TransferTx(tx) => {
+      let sender = Some((&tx.sender, &tx.sender_key, tx.nonce)).filter(|_| tx.get_csmsg_id().is_none());
+      (tx.payload.get(tx.step)?, sender)
+    },
+    GeneralTransaction::AggTx(tx) => (tx.payload.first()?, None),
+    _ => return None,
+  };
+  if frame.shardid != shard_id {
+    return None;
+  }
+
+  let state = |addr: &Address| accounts.get(addr).cloned().flatten().unwrap_or_else(|| new_account(keys, addr));
+  let mut authorized = HashMap::new();
+  if let Some((sender, key, nonce)) = sender {
+    let mut acc = state(sender);
+    if acc.nonce != nonce || acc.key != Some(*key) {
+      return None;
+    }
+    acc.nonce += 1;
+    authorized.insert(sender.clone(), acc);
+  }
+  let inputs: HashMap<Address, Account> = frame.rwset
+    .iter()
+    .map(|rw| (rw.addr.clone(), authorized.get(&rw.addr).cloned().unwrap_or_else(|| state(&rw.addr))))
+    .collect();
+  let outcome = apply_deltas(&frame.rwset, inputs.clone());
+  Some(Speculation { frame: frame.clone(), inputs, outcome })
+}
+
+/// Execute the independent txs of a block concurrently against its prefetched accounts: each task
+/// executes a share of the txs with a copy of their accounts.
+pub async fn speculate_block(
+  shard_id: ShardId,
+  txs: &[GeneralTransaction],
+  accounts: &HashMap<Address, Option<Account>>,
+  keys: &Arc<KeyRegistry>,
+) -> Vec<Speculation> {
+  let independent = independent_txs(txs);
+  let tasks = std::thread::available_parallelism().map_or(1, |n| n.get());
+  let task_txs = ((independent.len() + tasks - 1) / tasks).max(MIN_TASK_TXS);
+  let handles: Vec<_> = independent
+    .chunks(task_txs)
+    .map(|share| {
+      let txs: Vec<GeneralTransaction> = share.iter().map(|i| txs[*i].clone()).collect();
+      let accounts: HashMap<Address, Option<Account>> = txs
+        .iter()
+        .flat_map(declared_accounts)
+        .filter_map(|addr| accounts.get(addr).map(|acc| (addr.clone(), acc.clone())))
+        .collect();
+      let keys = keys.clone();
+      tokio::task::spawn_blocking(move || {
+        txs.iter().filter_map(|tx| speculate(shard_id, tx, &accounts, &keys)).collect::<Vec<_>>()
+      })
+    })
+    .collect();
+  join_all(handles)
+    .await
+    .into_iter()
+    .flat_map(|speculations| speculations.expect("Failed to execute independent txs"))
+    .collect()
+}
@@ -1,7 +1,10 @@
-use std::{collections::HashMap, fs::File};
-use crate::{messages::{Address, Amount}, RWSet, Frame, Account2Shard, acc_shard::{AccToShardItem, ActAccToShardItem}};
+use std::{collections::{BTreeMap, HashMap, HashSet}, fs::File};
+use crate::{amount::Amount, messages::{Address, AvatarDelta, Height, Migration}, RWSet, Frame, Account2Shard, acc_shard::{AccToShardItem, ActAccToShardItem, AccountKeyItem}};
+use crate::query::hex_proof;
+use crate::metrics;
 use async_trait::async_trait;
 use config::ShardId;
+use crypto::PublicKey;
 use csv::DeserializeRecordsIter;
 use hex::FromHex;
 use log::info;
@@ -10,12 +13,20 @@ use serde::{Deserialize, Serialize};
 use tokio::{time::Instant, sync::{oneshot, mpsc}};
 use std::thread;
 use futures::executor::block_on;
+use futures::future::join_all;
 use std::sync::Arc;
+use store::Store;
+use std::convert::TryInto;
 use num_enum::TryFromPrimitive;
 use tokio::sync::mpsc::{channel, Sender, Receiver};
 
+#[cfg(test)]
+#[path = "tests/state_store_tests.rs"]
+pub mod state_store_tests;
 
-pub const INIT_BALANCE: f64 = 100000000000000000000000000000000000000000000.0;
+
+// 1e36 wei, far above the ether supply
+pub const INIT_BALANCE: Amount = Amount::new(1_000_000_000_000_000_000_000_000_000_000_000_000);
 
 #[derive(TryFromPrimitive, Debug, Clone)]
 #[repr(usize)]
@@ -38,31 +49,131 @@ pub struct BrokerItem {
   pub freq: u64,
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub struct Account {
     pub nonce: i64, 
     pub balance: Amount,
+    pub key: Option<PublicKey>, // the key registered for the account when it is created, see KeyRegistry
+}
+
+
+/// The keys of the accounts, fixed at genesis: an account gets the key of its address when it is
+/// created, either loaded at genesis or on its first write in a shard. The txs of an account
+/// without a key are rejected.
+pub type KeyRegistry = HashMap<Address, PublicKey>;
+
+// load the genesis key registry csv
+pub fn load_key_registry(keys_file: &str) -> KeyRegistry {
+  let mut reader = csv::Reader::from_path(keys_file).unwrap();
+  let mut registry = KeyRegistry::new();
+  let mut key_iter: DeserializeRecordsIter<File, AccountKeyItem> = reader.deserialize().into_iter();
+  while let Some(Ok(item)) = key_iter.next() {
+    let addr = Vec::from_hex(&item.account[2..]).unwrap();
+    let key = PublicKey::decode_base64(&item.key).expect("Invalid account key");
+    registry.insert(addr, key);
+  }
+  info!("Loaded the keys of {} accounts from {}", registry.len(), keys_file);
+  registry
+}
+
+
+/// The proof of an account, or of its absence, against the roots of a state store.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum AccountProof {
+  /// TStore: the account is active, proven against the root of act_t.
+  Active(#[serde(with = "hex_proof")] Proof),
+  /// TStore: the proof of absence from act_t, then the proof of the account (or of its absence)
+  /// against the root of full_t.
+  Dormant(#[serde(with = "hex_proof")] Proof, #[serde(with = "hex_proof")] Proof),
+  /// MStore: every account is in full_t.
+  Full(#[serde(with = "hex_proof")] Proof),
 }
 
 
+/// The avatar accounts cached by SharDAG, by target shard, until they are aggregated.
 #[derive(Debug)]
 pub struct AvatartStore {
-  pub avatars: HashMap<ShardId, HashMap<Address, Amount>>
+  pub avatars: HashMap<ShardId, HashMap<Address, Amount>>,
+  // the height at which the oldest avatar of each target shard was cached
+  since: HashMap<ShardId, Height>,
+  // the size of the avatars in the payloads of the agg txs. Unlike the allocated size, it only
+  // depends on the cached avatars, so every replica aggregates at the same heights
+  bytes: usize,
+  // the avatars cached at each height, by target shard, if they are included in the agg txs
+  deltas: Option<HashMap<ShardId, BTreeMap<Height, HashMap<Address, Amount>>>>,
 }
 
 impl AvatartStore {
-  pub fn new() -> Self {
+  pub fn new(record_deltas: bool) -> Self {
     Self{
-      avatars: HashMap::default()
+      avatars: HashMap::default(),
+      since: HashMap::default(),
+      bytes: 0,
+      deltas: if record_deltas { Some(HashMap::default()) } else { None },
     }
   }
 
-  pub fn insert(&mut self, frame: &Frame) {
+  // the avatars of an account add up until they are aggregated
+  pub fn insert(&mut self, height: Height, frame: &Frame) {
     let avatars = self.avatars.entry(frame.shardid).or_insert(HashMap::new());
+    self.since.entry(frame.shardid).or_insert(height);
     for rwset in &frame.rwset {
-      avatars.insert(rwset.addr.clone(), rwset.value);
+      match avatars.get_mut(&rwset.addr) {
+        Some(value) => *value = value.checked_add(rwset.value).expect("Avatar balance overflow"),
+        None => {
+          avatars.insert(rwset.addr.clone(), rwset.value);
+          self.bytes += avatar_bytes(&rwset.addr);
+        },
+      }
+    }
+
+    if let Some(deltas) = self.deltas.as_mut() {
+      let delta = deltas.entry(frame.shardid).or_default().entry(height).or_default();
+      for rwset in &frame.rwset {
+        let value = delta.entry(rwset.addr.clone()).or_insert(Amount::ZERO);
+        *value = value.checked_add(rwset.value).expect("Avatar balance overflow");
+      }
     }
   }
+
+  /// Remove the avatars of a target shard to aggregate them, in ascending order of address, with
+  /// the avatars cached at each height if they are recorded.
+  pub fn take(&mut self, shard: ShardId) -> (Vec<RWSet>, Vec<AvatarDelta>) {
+    self.since.remove(&shard);
+    let avatars = self.avatars.remove(&shard).unwrap_or_default();
+    self.bytes -= avatars.keys().map(avatar_bytes).sum::<usize>();
+
+    let deltas = self.deltas
+      .as_mut()
+      .and_then(|deltas| deltas.remove(&shard))
+      .unwrap_or_default()
+      .into_iter()
+      .map(|(height, avatars)| AvatarDelta { height, rwset: sorted_rwset(avatars) })
+      .collect();
+    (sorted_rwset(avatars), deltas)
+  }
+
+  pub fn bytes(&self) -> usize {
+    self.bytes
+  }
+
+  /// The height at which the oldest avatar of a target shard was cached.
+  pub fn since(&self, shard: ShardId) -> Option<Height> {
+    self.since.get(&shard).cloned()
+  }
+}
+
+fn sorted_rwset(avatars: HashMap<Address, Amount>) -> Vec<RWSet> {
+  let mut rwset: Vec<RWSet> = avatars
+    .into_iter()
+    .map(|(addr, value)| RWSet { addr, value })
+    .collect();
+  rwset.sort_by(|a, b| a.addr.cmp(&b.addr));
+  rwset
+}
+
+fn avatar_bytes(addr: &Address) -> usize {
+  addr.len() + std::mem::size_of::<Amount>()
 }
 
 
@@ -71,6 +182,41 @@ pub trait StateStore {
   async fn insert(&mut self, key: Vec<u8>, value: Vec<u8>);
   async fn get(&mut self, key: &[u8]) -> Option<Vec<u8>>;
   async fn root(&mut self) -> (Option<RootHash>, Option<RootHash>);
+
+  /// Read an account like `get`, but leave the store untouched: a dormant account is not
+  /// activated, so the roots do not change.
+  async fn peek(&mut self, key: &[u8]) -> Option<Vec<u8>>;
+
+  /// `peek` several accounts, with concurrent reads.
+  async fn peek_batch(&mut self, keys: Vec<Key>) -> Vec<Option<Vec<u8>>>;
+
+  /// `insert` several accounts at once.
+  async fn insert_batch(&mut self, accs: HashMap<Key, Value>);
+
+  /// Prove an account, or its absence, against the current roots.
+  async fn get_proof(&mut self, key: &[u8]) -> AccountProof;
+
+  /// Keep the tries of the given roots until the next call, so that `get_proof_at` serves proofs
+  /// against them after later commits. Return false if their nodes are already pruned.
+  async fn retain(&mut self, act_root: RootHash, full_root: RootHash) -> bool;
+
+  /// Prove an account, or its absence, against the retained (or the current) roots.
+  async fn get_proof_at(&mut self, act_root: RootHash, full_root: RootHash, key: &[u8]) -> Option<AccountProof>;
+
+  /// Remove the outgoing accounts from the store. Return the (active, full) roots before the
+  /// removal and the proofs of the active and of the dormant accounts against them.
+  async fn export_accounts(
+    &mut self,
+    accs: Vec<Address>,
+  ) -> (RootHash, RootHash, HashMap<Address, Proof>, HashMap<Address, Proof>);
+
+  /// Install the incoming (already verified) active and dormant accounts.
+  async fn import_accounts(&mut self, active_accs: HashMap<Key, Value>, dormant_accs: HashMap<Key, Value>);
+
+  /// Move the active accounts not accessed during the last `idle` heights to the dormant tier.
+  /// Called at the same heights on every replica, so that their roots stay the same. Return the
+  /// number of evicted accounts.
+  async fn evict(&mut self, height: Height, idle: Height) -> usize;
   
   async fn test_migration(
     &mut self, 
@@ -99,16 +245,17 @@ pub async fn new_primary_store(
   acc2shard: &Box<dyn Account2Shard + Send>,
   state_store_type: StateStoreType, 
   full_t_path: &str, 
+  keys: &KeyRegistry,
 ) -> Box<dyn StateStore + Send> {
   let store: Box<dyn StateStore + Send>;
   match state_store_type {
     StateStoreType::MStore => {
       println!("initialize MStore");
-      store = Box::new(MStore::new(shard_id, &acc2shard_file, &acc2shard, full_t_path).await);
+      store = Box::new(MStore::new(shard_id, &acc2shard_file, &acc2shard, full_t_path, keys).await);
     }
     StateStoreType::TStore => {
       println!("initialize TStore");
-      store = Box::new(TStore::new(shard_id, &acc2shard_file, &actacc2shard_file, &acc2shard, full_t_path).await);
+      store = Box::new(TStore::new(shard_id, &acc2shard_file, &actacc2shard_file, &acc2shard, full_t_path, keys).await);
     }
   }
   store
@@ -120,6 +267,7 @@ pub async fn load_accs(
   acc2shard_file: &str, 
   acc2shard: &Box<dyn Account2Shard + Send>,
   full_t_path: &str,
+  keys: &KeyRegistry,
 ) -> MPTStore{
 
   let mut full_t = MPTStore::new(full_t_path);
@@ -138,7 +286,7 @@ pub async fn load_accs(
     // get account's shardid according to acc2shard policy
     let _shard_id = acc2shard.get_shard(&addr);
     if _shard_id == shard_id { // local account
-      let acc = Account { nonce: 0, balance: INIT_BALANCE};
+      let acc = Account { nonce: 0, balance: INIT_BALANCE, key: keys.get(&addr).copied() };
       let serialized = bincode::serialize(&acc).expect("Failed to serialize account");
       if full_t.insert(addr, serialized).await.unwrap(){
         loaded_local_accs+=1;
@@ -159,14 +307,16 @@ pub async fn load_accs(
 }
 
 
-// load initial act account state csv
+// load initial act account state csv, with the loaded accounts
 pub async fn load_act_accs(
   shard_id: ShardId,
   actacc2shard_file: &str, 
   acc2shard: &Box<dyn Account2Shard + Send>, // get account's shardid according to acc2shard policy
-) -> MMPTStore{
+  keys: &KeyRegistry,
+) -> (MMPTStore, HashSet<Key>){
 
   let mut act_t = MMPTStore::new();
+  let mut local_accs = HashSet::new();
   info!("Begin loading act account...");
   println!("Begin loading act account...");
   let mut loaded_local_accs = 0;
@@ -180,11 +330,12 @@ pub async fn load_act_accs(
     let addr = Vec::from_hex(&acc_shard.act_account[2..]).unwrap();
     let _shard_id = acc2shard.get_shard(&addr);
     if _shard_id == shard_id { // local account
-      let acc = Account { nonce: 0, balance: INIT_BALANCE};
+      let acc = Account { nonce: 0, balance: INIT_BALANCE, key: keys.get(&addr).copied() };
       let serialized = bincode::serialize(&acc).expect("Failed to serialize account");
-      if act_t.insert(addr, serialized).await.unwrap(){
+      if act_t.insert(addr.clone(), serialized).await.unwrap(){
         loaded_local_accs+=1;
       }
+      local_accs.insert(addr);
     }
   }
   let dur = before_load.elapsed().as_millis();
@@ -197,7 +348,63 @@ pub async fn load_act_accs(
     loaded_accs, loaded_local_accs, dur
   );
   let _ = act_t.root().await;   
-  act_t
+  (act_t, local_accs)
+}
+
+
+// the key of the accounts stamped by the eviction of a height
+fn accessed_key(height: Height) -> Vec<u8> {
+  format!("tstore_accessed_{}", height).into_bytes()
+}
+
+const EVICTED_HEIGHT_KEY: &[u8] = b"tstore_evicted_height";
+
+
+/// The persisted access heights of the active accounts: the accounts stamped by each of the last
+/// evictions. A restarted replica rebuilds the same access heights, so it evicts the same accounts
+/// as the others and reaches the same roots.
+pub struct AccessLog {
+  store: Store,
+  // the heights of the persisted stamps
+  heights: BTreeSet<Height>,
+}
+
+impl AccessLog {
+  pub fn new(store: Store) -> Self {
+    Self { store, heights: BTreeSet::new() }
+  }
+
+  /// Persist the accounts stamped by the eviction of a height, and delete the stamps which can no
+  /// longer keep an account active.
+  pub async fn stamped(&mut self, height: Height, idle: Height, keys: &[Key]) {
+    let bytes = bincode::serialize(keys).expect("Failed to serialize accessed accounts");
+    self.store.write(accessed_key(height), bytes).await;
+    self.store.write(EVICTED_HEIGHT_KEY.to_vec(), height.to_le_bytes().to_vec()).await;
+    self.heights.insert(height);
+    let live = self.heights.split_off(&(height + 1).saturating_sub(idle));
+    for stale in std::mem::replace(&mut self.heights, live) {
+      self.store.delete(accessed_key(stale)).await;
+    }
+  }
+
+  /// The last access height of the accounts stamped by the evictions which did not evict them yet.
+  pub async fn recover(&mut self, idle: Height) -> HashMap<Key, Height> {
+    let mut last_access = HashMap::new();
+    let evicted = match self.store.read(EVICTED_HEIGHT_KEY.to_vec()).await.expect("Failed to read evicted height") {
+      Some(bytes) => Height::from_le_bytes(bytes[..].try_into().expect("Corrupted evicted height")),
+      None => return last_access,
+    };
+    for height in (evicted + 1).saturating_sub(idle)..=evicted {
+      if let Some(bytes) = self.store.read(accessed_key(height)).await.expect("Failed to read accessed accounts") {
+        let keys: Vec<Key> = bincode::deserialize(&bytes).expect("Corrupted accessed accounts");
+        for key in keys {
+          last_access.insert(key, height);
+        }
+        self.heights.insert(height);
+      }
+    }
+    last_access
+  }
 }
 
 
@@ -208,6 +415,13 @@ pub struct TStore {
   pub insert_dur: Vec<u128>,
   pub get_act_dur: Vec<u128>,
   pub get_full_dur: Vec<u128>,
+  // the active accounts, with the height of the last eviction which found them accessed
+  last_access: HashMap<Key, Height>,
+  // the accounts accessed since the last eviction
+  touched: HashSet<Key>,
+  // the persisted access heights, loaded by the first eviction after a restart
+  access_log: AccessLog,
+  recovered: bool,
 }
 
 
@@ -218,11 +432,13 @@ impl TStore {
     actacc2shard_file: &str,
     acc2shard: &Box<dyn Account2Shard + Send>,
     full_t_path: &str, 
+    keys: &KeyRegistry,
   ) -> Self {
 
     info!("Initialize TStore!");
-    let full_t = load_accs(shard_id, acc2shard_file, acc2shard, full_t_path).await;
-    let act_t = load_act_accs(shard_id, actacc2shard_file, acc2shard).await;
+    let full_t = load_accs(shard_id, acc2shard_file, acc2shard, full_t_path, keys).await;
+    let (act_t, touched) = load_act_accs(shard_id, actacc2shard_file, acc2shard, keys).await;
+    let access_store = Store::new(&format!("{}_access", full_t_path)).expect("Failed to create access store");
     Self { 
       shard_id,
       act_t,
@@ -230,6 +446,17 @@ impl TStore {
       insert_dur: Vec::default(),
       get_act_dur: Vec::default(),
       get_full_dur: Vec::default(),
+      last_access: HashMap::default(),
+      touched,
+      access_log: AccessLog::new(access_store),
+      recovered: false,
+    }
+  }
+
+  // the accesses only depend on the executed txs and the migrations, never on the queries
+  fn touch(&mut self, key: &[u8]) {
+    if !self.touched.contains(key) {
+      self.touched.insert(key.to_vec());
     }
   }
 }
@@ -241,6 +468,7 @@ impl StateStore for TStore {
   async fn insert(&mut self, key: Vec<u8>, value: Vec<u8>) {
     let begin_insert = Instant::now();
     self.act_t.insert(key.clone(), value.clone()).await.unwrap();
+    self.touch(&key);
     self.insert_dur.push(begin_insert.elapsed().as_micros());
   }
 
@@ -249,14 +477,18 @@ impl StateStore for TStore {
     //get acc from active T first
     let res = self.act_t.get(key).await.unwrap();
     if res.is_some() {
+      self.touch(key);
       self.get_act_dur.push(begin_get.elapsed().as_micros());
+      metrics::TSTORE_READS.with_label_values(&["active"]).inc();
       return res;
     }
     // get acc from full_t
+    metrics::TSTORE_READS.with_label_values(&["full"]).inc();
     let mut res_full_t = self.full_t.get(key).await.unwrap();
     match res_full_t {
       Some(value) => {
         let _ = self.act_t.insert(key.to_vec(), value.clone()).await.unwrap();
+        self.touch(key);
         res_full_t =  Some(value);
       },
       None => {
@@ -273,6 +505,150 @@ impl StateStore for TStore {
     (Some(root_hash_act), Some(root_hash_full))
   }
 
+  async fn peek(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+    match self.act_t.get(key).await.unwrap() {
+      Some(value) => Some(value),
+      None => self.full_t.get(key).await.unwrap(),
+    }
+  }
+
+  async fn peek_batch(&mut self, keys: Vec<Key>) -> Vec<Option<Vec<u8>>> {
+    let act_t = &self.act_t;
+    let active = join_all(keys.iter().map(|key| async move {
+      act_t.clone().get(key).await.unwrap()
+    })).await;
+    let full_t = &self.full_t;
+    join_all(keys.iter().zip(active).map(|(key, value)| async move {
+      match value {
+        Some(value) => Some(value),
+        None => full_t.clone().get(key).await.unwrap(),
+      }
+    })).await
+  }
+
+  // the accounts written back are active
+  async fn insert_batch(&mut self, accs: HashMap<Key, Value>) {
+    let begin_insert = Instant::now();
+    for key in accs.keys() {
+      self.touch(key);
+    }
+    self.act_t.insert_batch(accs).await.unwrap();
+    self.insert_dur.push(begin_insert.elapsed().as_micros());
+  }
+
+  async fn get_proof(&mut self, key: &[u8]) -> AccountProof {
+    let act_proof = self.act_t.get_proof(key).await.unwrap();
+    if self.act_t.contains(key).await.unwrap() {
+      return AccountProof::Active(act_proof);
+    }
+    let full_proof = self.full_t.get_proof(key).await.unwrap();
+    AccountProof::Dormant(act_proof, full_proof)
+  }
+
+  async fn retain(&mut self, act_root: RootHash, full_root: RootHash) -> bool {
+    let act = self.act_t.retain(act_root).await;
+    let full = self.full_t.retain(full_root).await;
+    act && full
+  }
+
+  async fn get_proof_at(&mut self, act_root: RootHash, full_root: RootHash, key: &[u8]) -> Option<AccountProof> {
+    let act_proof = self.act_t.get_proof_at(act_root.clone(), key).await.ok()?;
+    if MerklePatriciaTrie::verify_proof(act_root, key, act_proof.clone()).ok()?.is_some() {
+      return Some(AccountProof::Active(act_proof));
+    }
+    let full_proof = self.full_t.get_proof_at(full_root, key).await.ok()?;
+    Some(AccountProof::Dormant(act_proof, full_proof))
+  }
+
+  async fn export_accounts(
+    &mut self,
+    accs: Vec<Address>,
+  ) -> (RootHash, RootHash, HashMap<Address, Proof>, HashMap<Address, Proof>) {
+    let act_root = self.act_t.root().await.unwrap();
+    let full_root = self.full_t.root().await.unwrap();
+
+    // active accs are proven against act_t, dormant ones against full_t
+    let mut out_act_accs = Vec::new();
+    let mut out_dor_accs = Vec::new();
+    for addr in accs {
+      if self.act_t.contains(&addr).await.unwrap() {
+        out_act_accs.push(addr);
+      } else if self.full_t.contains(&addr).await.unwrap() {
+        out_dor_accs.push(addr);
+      }
+    }
+    let act_acc_proof = self.act_t.get_proof_batch(out_act_accs.clone()).await.unwrap();
+    let dor_acc_proof = self.full_t.get_proof_batch(out_dor_accs.clone()).await.unwrap();
+
+    // delete act accs, dor accs and old snapshot of act accs from full_t
+    for addr in &out_act_accs {
+      self.last_access.remove(addr);
+      self.touched.remove(addr);
+    }
+    let _ = self.act_t.remove_batch(out_act_accs.clone()).await.unwrap();
+    let _ = self.full_t.remove_batch(out_act_accs).await.unwrap();
+    let _ = self.full_t.remove_batch(out_dor_accs).await.unwrap();
+    let _ = self.root().await;
+
+    (act_root, full_root, act_acc_proof, dor_acc_proof)
+  }
+
+  async fn import_accounts(&mut self, active_accs: HashMap<Key, Value>, dormant_accs: HashMap<Key, Value>) {
+    for key in active_accs.keys() {
+      self.touch(key);
+    }
+    let _ = self.act_t.insert_batch(active_accs).await.unwrap();
+    let _ = self.full_t.insert_batch(dormant_accs).await.unwrap();
+    let _ = self.root().await;
+  }
+
+  // the accounts accessed since the last eviction are stamped with its height, then the cold ones
+  // overwrite their (stale) snapshot in full_t and leave act_t. The tries do not depend on the
+  // order of the updates, so neither do the roots. The stamps are persisted before the eviction
+  async fn evict(&mut self, height: Height, idle: Height) -> usize {
+    if !self.recovered {
+      // the accounts exported since their last stamp are no longer ours
+      for (key, accessed) in self.access_log.recover(idle).await {
+        if self.act_t.contains(&key).await.unwrap() {
+          self.last_access.insert(key, accessed);
+        }
+      }
+      self.recovered = true;
+    }
+    let stamped: Vec<Key> = self.touched.drain().collect();
+    self.access_log.stamped(height, idle, &stamped).await;
+    for key in stamped {
+      self.last_access.insert(key, height);
+    }
+    let cold: Vec<Key> = self.last_access
+      .iter()
+      .filter(|(_, accessed)| accessed.saturating_add(idle) <= height)
+      .map(|(key, _)| key.clone())
+      .collect();
+    if cold.is_empty() {
+      return 0;
+    }
+    for key in &cold {
+      self.last_access.remove(key);
+    }
+
+    let act_t = &self.act_t;
+    let values = join_all(cold.iter().map(|key| async move {
+      act_t.clone().get(key).await.unwrap()
+    })).await;
+    let accs: HashMap<Key, Value> = cold
+      .iter()
+      .cloned()
+      .zip(values)
+      .filter_map(|(key, value)| value.map(|value| (key, value)))
+      .collect();
+    let evicted = accs.len();
+    let _ = self.full_t.insert_batch(accs).await.unwrap();
+    let _ = self.act_t.remove_batch(cold).await.unwrap();
+    metrics::TSTORE_EVICTIONS.inc_by(evicted as u64);
+    evicted
+  }
+
   async fn test_migration(
     &mut self, 
     out_act_accs: Vec<Vec<u8>>,
@@ -396,7 +772,9 @@ impl StateStore for TStore {
 
     // serialize 
     let before_s = Instant::now();
-    let migration_msg = Migration::new(shard_id, target_shard_id, epoch, act_acc_proof, dor_acc_proof);
+    let migration_msg = Migration::new(
+      shard_id, target_shard_id, epoch, act_root_hash.clone(), dor_root_hash.clone(), act_acc_proof, dor_acc_proof,
+    );
     let serialized = bincode::serialize(&migration_msg).expect("Failed to serialize account");
     let migration_data_size = serialized.len();
     println!("[TStore_test_migration] migration_data_size: {} B", migration_data_size);
@@ -531,10 +909,11 @@ impl MStore {
     acc2shard_file: &str, 
     acc2shard: &Box<dyn Account2Shard + Send>,
     full_t_path: &str, 
+    keys: &KeyRegistry,
   ) -> Self {
 
     info!("Initialize MStore!");
-    let full_t = load_accs(shard_id, acc2shard_file, acc2shard, full_t_path).await;
+    let full_t = load_accs(shard_id, acc2shard_file, acc2shard, full_t_path, keys).await;
 
     Self { 
       shard_id,
@@ -566,6 +945,67 @@ impl StateStore for MStore {
     (Some(Vec::default()), Some(root_hash_full))
   }
 
+  async fn peek(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+    self.full_t.get(key).await.unwrap()
+  }
+
+  async fn peek_batch(&mut self, keys: Vec<Key>) -> Vec<Option<Vec<u8>>> {
+    let full_t = &self.full_t;
+    join_all(keys.iter().map(|key| async move {
+      full_t.clone().get(key).await.unwrap()
+    })).await
+  }
+
+  async fn insert_batch(&mut self, accs: HashMap<Key, Value>) {
+    let begin_insert = Instant::now();
+    self.full_t.insert_batch(accs).await.unwrap();
+    self.insert_dur.push(begin_insert.elapsed().as_micros());
+  }
+
+  async fn get_proof(&mut self, key: &[u8]) -> AccountProof {
+    AccountProof::Full(self.full_t.get_proof(key).await.unwrap())
+  }
+
+  // MStore has no act_t
+  async fn retain(&mut self, _act_root: RootHash, full_root: RootHash) -> bool {
+    self.full_t.retain(full_root).await
+  }
+
+  async fn get_proof_at(&mut self, _act_root: RootHash, full_root: RootHash, key: &[u8]) -> Option<AccountProof> {
+    self.full_t.get_proof_at(full_root, key).await.ok().map(AccountProof::Full)
+  }
+
+  // MStore keeps every account in full_t, so all of them are exported as dormant
+  async fn export_accounts(
+    &mut self,
+    accs: Vec<Address>,
+  ) -> (RootHash, RootHash, HashMap<Address, Proof>, HashMap<Address, Proof>) {
+    let full_root = self.full_t.root().await.unwrap();
+
+    let mut out_accs = Vec::new();
+    for addr in accs {
+      if self.full_t.contains(&addr).await.unwrap() {
+        out_accs.push(addr);
+      }
+    }
+    let acc_proof = self.full_t.get_proof_batch(out_accs.clone()).await.unwrap();
+    let _ = self.full_t.remove_batch(out_accs).await.unwrap();
+    let _ = self.full_t.root().await;
+
+    (Vec::default(), full_root, HashMap::default(), acc_proof)
+  }
+
+  async fn import_accounts(&mut self, active_accs: HashMap<Key, Value>, dormant_accs: HashMap<Key, Value>) {
+    let _ = self.full_t.insert_batch(active_accs).await.unwrap();
+    let _ = self.full_t.insert_batch(dormant_accs).await.unwrap();
+    let _ = self.full_t.root().await;
+  }
+
+  // a single tier: nothing to evict
+  async fn evict(&mut self, _height: Height, _idle: Height) -> usize {
+    0
+  }
+
   async fn test_migration(
     &mut self, 
     out_act_accs: Vec<Vec<u8>>,
@@ -605,7 +1045,9 @@ impl StateStore for MStore {
 
     // 3. serialize 
     let before_3 = Instant::now();
-    let migration_msg = Migration::new(shard_id, target_shard_id, epoch, act_acc_proof, dor_acc_proof);
+    let migration_msg = Migration::new(
+      shard_id, target_shard_id, epoch, Vec::default(), full_root_hash.clone(), act_acc_proof, dor_acc_proof,
+    );
     let serialized = bincode::serialize(&migration_msg).expect("Failed to serialize account");
 
     let migration_data_size = serialized.len();
@@ -699,30 +1141,3 @@ pub fn _assemle_rwset(avatars: HashMap<Address, Amount>) -> Vec<RWSet> {
   }
   rwset    
 }
-
-
-
-
-#[derive(Clone, Serialize, Deserialize, Debug, Default)]
-pub struct Migration {
-    pub source_shard_id: ShardId,
-    pub target_shard_id: ShardId,
-    pub epoch: u64,
-    pub active_acc_proof_map : HashMap<Address, Proof>,
-    pub dormant_acc_proof_map : HashMap<Address, Proof>,
-}
-
-impl Migration {
-  pub fn new(
-    source_shard_id: ShardId, target_shard_id: ShardId, epoch: u64, 
-    active_acc_proof_map: HashMap<Address, Proof>, dormant_acc_proof_map : HashMap<Address, Proof>
-  ) -> Self {
-    Self { 
-      source_shard_id, 
-      target_shard_id, 
-      epoch, 
-      active_acc_proof_map,
-      dormant_acc_proof_map
-    }
-  }
-}
\ No newline at end of file
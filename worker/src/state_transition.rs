@@ -1,44 +1,264 @@
-use std::collections::HashMap;
-use log::debug;
-use crate::{StateStore, Address, state_store::Account, RWSet, INIT_BALANCE};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use config::ShardId;
+use crypto::{Digest, PublicKey};
+use log::{debug, warn};
+use crate::{StateStore, Address, state_store::{Account, KeyRegistry}, AmountError, Frame, RWSet, INIT_BALANCE};
+use crate::messages::{GeneralTransaction, Height};
+use crate::scheduler::{speculate_block, Speculation};
+use crate::epoch::EpochManager;
+use crate::query::{StateQuery, StateRoot, StateProof};
 
 pub struct StateTransition{
   pub store: Box<dyn StateStore + Send>,
+  // the accounts locked by the txs prepared in TwoPhaseCommit, with the tx holding each lock; the
+  // locks are not part of the state roots, all the replicas take and release them at the same txs
+  locks: HashMap<Address, Digest>,
+  // the accounts of the block being executed
+  prefetched: Option<Prefetched>,
+  // the roots of the last certified height, whose tries the store keeps to serve the proofs
+  certified: Option<StateRoot>,
+  // the genesis keys of the accounts created by the txs
+  keys: Arc<KeyRegistry>,
+}
+
+// the accounts of a block are read from the store before its txs and written back after them:
+// an account read by a tx is written back even if unchanged, as TStore activates the accounts it
+// reads, so the roots are those of reading and writing each account through the store
+struct Prefetched {
+  accounts: HashMap<Address, Option<Account>>,
+  written: HashSet<Address>,
+  // the frames of the independent txs executed ahead, by their first account: the accounts of
+  // independent txs are disjoint
+  speculations: HashMap<Address, Speculation>,
+}
+
+// an account appearing for the first time in our shard, with its genesis key
+pub fn new_account(keys: &KeyRegistry, addr: &Address) -> Account {
+  Account { nonce: 0, balance: INIT_BALANCE, key: keys.get(addr).copied() }
+}
+
+// the new states of the accounts of a frame: a deduction may not make a balance negative
+pub fn apply_deltas(
+  rwset: &[RWSet],
+  mut states: HashMap<Address, Account>,
+) -> Result<HashMap<Address, Account>, AmountError> {
+  for rw in rwset {
+    let acc = states.get_mut(&rw.addr).unwrap();
+    acc.balance = acc.balance.apply_delta(rw.value)?;
+  }
+  Ok(states)
 }
 
 impl StateTransition {
   pub fn new(
     store: Box<dyn StateStore + Send>,
+    keys: Arc<KeyRegistry>,
   ) -> Self {
-    Self { store }
+    Self { store, locks: HashMap::new(), prefetched: None, certified: None, keys }
+  }
+
+  /// Read the accounts of the txs of a block at once, they are then served from memory.
+  pub async fn prefetch(&mut self, mut accs: Vec<Address>) {
+    accs.sort();
+    accs.dedup();
+    let values = self.store.peek_batch(accs.clone()).await;
+    let accounts = accs
+      .into_iter()
+      .zip(values)
+      .map(|(addr, value)| (addr, value.map(|value| bincode::deserialize(&value).unwrap())))
+      .collect();
+    self.prefetched = Some(Prefetched { accounts, written: HashSet::new(), speculations: HashMap::new() });
+  }
+
+  /// Execute the independent txs of the prefetched block concurrently (see `independent_txs`),
+  /// their outcomes are applied as the txs are executed in block order. Return how many frames
+  /// were executed ahead.
+  pub async fn speculate(&mut self, shard_id: ShardId, txs: &[GeneralTransaction]) -> usize {
+    let prefetched = match self.prefetched.as_mut() {
+      Some(prefetched) => prefetched,
+      None => return 0,
+    };
+    let speculations = speculate_block(shard_id, txs, &prefetched.accounts, &self.keys).await;
+    let speculated = speculations.len();
+    prefetched.speculations = speculations
+      .into_iter()
+      .filter_map(|speculation| Some((speculation.frame.rwset.first()?.addr.clone(), speculation)))
+      .collect();
+    speculated
+  }
+
+  /// The outcome of a frame executed ahead, if its accounts still hold the states it was executed
+  /// against: it is then the outcome of executing it now.
+  pub fn speculated(
+    &self,
+    frame: &Frame,
+    states: &HashMap<Address, Account>,
+  ) -> Option<Result<HashMap<Address, Account>, AmountError>> {
+    let speculation = self.prefetched.as_ref()?.speculations.get(&frame.rwset.first()?.addr)?;
+    if speculation.frame != *frame || speculation.inputs != *states {
+      return None;
+    }
+    Some(speculation.outcome.clone())
+  }
+
+  /// Write back the accounts read or written by the txs of the block at once, and drop the frames
+  /// executed ahead.
+  pub async fn write_back(&mut self) {
+    if let Some(mut prefetched) = self.prefetched.take() {
+      let accs = prefetched.written
+        .into_iter()
+        .map(|addr| {
+          let acc = prefetched.accounts.remove(&addr).flatten().unwrap();
+          let serialized = bincode::serialize(&acc).expect("Failed to serialize account");
+          (addr, serialized)
+        })
+        .collect();
+      self.store.insert_batch(accs).await;
+    }
+  }
+
+  async fn read(&mut self, addr: &Address) -> Option<Account> {
+    match self.prefetched.as_mut() {
+      Some(prefetched) => {
+        if !prefetched.accounts.contains_key(addr) {
+          let value = self.store.peek(addr).await;
+          prefetched.accounts.insert(addr.clone(), value.map(|value| bincode::deserialize(&value).unwrap()));
+        }
+        let acc = prefetched.accounts[addr].clone();
+        if acc.is_some() {
+          prefetched.written.insert(addr.clone());
+        }
+        acc
+      },
+      None => self.store.get(addr).await.map(|value| bincode::deserialize(&value).unwrap()),
+    }
+  }
+
+  async fn write(&mut self, addr: Address, acc: Account) {
+    match self.prefetched.as_mut() {
+      Some(prefetched) => {
+        prefetched.written.insert(addr.clone());
+        prefetched.accounts.insert(addr, Some(acc));
+      },
+      None => {
+        let serialized = bincode::serialize(&acc).expect("Failed to serialize account");
+        self.store.insert(addr, serialized).await;
+      },
+    }
   }
 
   pub async fn get_latest_states(&mut self, rwset: &Vec<RWSet>) -> HashMap<Address,Account> {
     let mut temp_states:HashMap<Address,Account> = HashMap::new();
     for rw in rwset {
-      let value = self.store.get(&rw.addr).await;
-      match value {
-          Some(value) => {
-            let acc: Account = bincode::deserialize(&value).unwrap();
+      match self.read(&rw.addr).await {
+          Some(acc) => {
             temp_states.insert(rw.addr.clone(), acc);
           },
           None => {  // crate a new account
             debug!("This account appears for the first time");
-            temp_states.insert(rw.addr.clone(), Account{nonce: 0, balance: INIT_BALANCE});
+            temp_states.insert(rw.addr.clone(), new_account(&self.keys, &rw.addr));
           }
       }
     }
     temp_states
   }
   
+  // a tx is executed only with the next nonce of its sender, which is then consumed whatever the
+  // outcome of the tx, so that replayed and duplicated txs are rejected. The tx must be signed with
+  // the key registered for the sender when its account was created, an account without a key
+  // cannot send txs. A tx dropped before it is authorized leaves the nonce to its resubmission
+  pub async fn authorize(&mut self, sender: &Address, key: &PublicKey, nonce: i64) -> bool {
+    let mut acc = match self.read(sender).await {
+      Some(acc) => acc,
+      None => new_account(&self.keys, sender),
+    };
+    if acc.nonce != nonce || acc.key != Some(*key) {
+      return false;
+    }
+    acc.nonce += 1;
+    self.write(sender.clone(), acc).await;
+    true
+  }
+
+  // answer a query of the query service between two blocks, once `height` is executed
+  pub async fn answer(&mut self, query: StateQuery, shard_id: ShardId, height: Height, epoch_manager: &EpochManager) {
+    match query {
+      StateQuery::Account(addr, reply) => {
+        let account = self.store.peek(&addr).await.map(|value| bincode::deserialize(&value).unwrap());
+        let _ = reply.send(account);
+      },
+      StateQuery::StateRoot(reply) => {
+        let _ = reply.send(self.state_root(shard_id, height).await);
+      },
+      StateQuery::Proof(addr, proven_height, reply) => {
+        let proof = match self.certified.clone() {
+          Some(root) if root.height == proven_height => self.store
+            .get_proof_at(root.act_root.clone(), root.full_root.clone(), &addr)
+            .await
+            .map(|proof| StateProof { root, proof }),
+          _ => None,
+        };
+        let _ = reply.send(proof);
+      },
+      StateQuery::Progress(reply) => {
+        let _ = reply.send((height, epoch_manager.epoch()));
+      },
+      StateQuery::Moves(epoch, reply) => {
+        let _ = reply.send(epoch_manager.moves(epoch));
+      },
+    }
+  }
+
+  /// Serve the proofs against the roots of a certified height, until a later height is certified.
+  pub async fn certify(&mut self, root: StateRoot) {
+    if self.certified.as_ref().map_or(false, |certified| certified.height >= root.height) {
+      return;
+    }
+    if self.store.retain(root.act_root.clone(), root.full_root.clone()).await {
+      self.certified = Some(root);
+    } else {
+      warn!("The tries of the certified height {} are already pruned", root.height);
+      self.certified = None;
+    }
+  }
+
+  async fn state_root(&mut self, shard_id: ShardId, height: Height) -> StateRoot {
+    let (act_root, full_root) = self.store.root().await;
+    StateRoot { shard_id, height, act_root: act_root.unwrap_or_default(), full_root: full_root.unwrap_or_default() }
+  }
+
   pub async fn apply_new_states(&mut self, new_states: HashMap<Address,Account>) {
     for (addr, account) in new_states {
-      let serialized = bincode::serialize(&account).expect("Failed to serialize account");
-      self.store.insert(addr, serialized).await;
+      self.write(addr, account).await;
     }
   }
-}
-
 
+  // lock the accounts of a frame for a tx, all or none: false if one of them is locked by another tx
+  pub fn try_lock(&mut self, rwset: &[RWSet], owner: &Digest) -> bool {
+    if self.is_locked(rwset, Some(owner)) {
+      return false;
+    }
+    for rw in rwset {
+      self.locks.insert(rw.addr.clone(), owner.clone());
+    }
+    true
+  }
 
+  // whether one of the accounts is locked, by another tx than `owner` if given
+  pub fn is_locked(&self, rwset: &[RWSet], owner: Option<&Digest>) -> bool {
+    rwset.iter().any(|rw| match self.locks.get(&rw.addr) {
+      Some(holder) => Some(holder) != owner,
+      None => false,
+    })
+  }
 
+  // release the locks held by a tx on the accounts of a frame
+  pub fn unlock(&mut self, rwset: &[RWSet], owner: &Digest) {
+    for rw in rwset {
+      if self.locks.get(&rw.addr) == Some(owner) {
+        self.locks.remove(&rw.addr);
+      }
+    }
+  }
+}
@@ -0,0 +1,46 @@
+use super::*;
+use crate::messages::RawTxOld;
+
+#[test]
+fn apply_delta() {
+    let balance = Amount::new(10);
+    assert_eq!(balance.apply_delta(Amount::new(5)), Ok(Amount::new(15)));
+    assert_eq!(balance.apply_delta(Amount::new(-10)), Ok(Amount::ZERO));
+    assert_eq!(balance.apply_delta(Amount::new(-11)), Err(AmountError::Underflow));
+    assert_eq!(
+        Amount::new(i128::MAX).apply_delta(Amount::new(1)),
+        Err(AmountError::Overflow)
+    );
+}
+
+#[test]
+fn checked_neg() {
+    assert_eq!(Amount::new(10).checked_neg(), Ok(Amount::new(-10)));
+    assert_eq!(Amount::new(-10).checked_neg(), Ok(Amount::new(10)));
+    assert_eq!(Amount::new(i128::MIN).checked_neg(), Err(AmountError::Overflow));
+}
+
+#[test]
+fn parse_workload_amounts_exactly() {
+    // 4629941000000000000 is not representable as a f64
+    let data = "sender,receiver,amount\n0x01,0x02,4629941000000000000\n0x01,0x02,1234567890123456789012\n";
+    let amounts: Vec<Amount> = csv::Reader::from_reader(data.as_bytes())
+        .deserialize::<RawTxOld>()
+        .map(|raw_tx| raw_tx.unwrap().amount)
+        .collect();
+    assert_eq!(
+        amounts,
+        vec![Amount::new(4_629_941_000_000_000_000), Amount::new(1_234_567_890_123_456_789_012)]
+    );
+}
+
+#[test]
+fn serialization_roundtrip() {
+    let amount = Amount::new(-1_234_567_890_123_456_789_012);
+    let bytes = bincode::serialize(&amount).unwrap();
+    assert_eq!(bincode::deserialize::<Amount>(&bytes).unwrap(), amount);
+
+    let json = serde_json::to_string(&amount).unwrap();
+    assert_eq!(json, "\"-1234567890123456789012\"");
+    assert_eq!(serde_json::from_str::<Amount>(&json).unwrap(), amount);
+}
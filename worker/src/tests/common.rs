@@ -45,6 +45,7 @@ pub fn committee(shardid: ShardId) -> Committee {
                         transactions: format!("127.0.0.1:{}", 400 + i).parse().unwrap(),
                         worker_to_worker: format!("127.0.0.1:{}", 500 + i).parse().unwrap(),
                         cross_shard_worker: format!("127.0.0.1:{}", 600 + i).parse().unwrap(),
+                        query: format!("127.0.0.1:{}", 700 + i).parse().unwrap(),
                     },
                 )]
                 .iter()
@@ -87,6 +88,9 @@ pub fn committee_with_base_port(base_port: u16, shardid: ShardId) -> Committee {
 
             let port = worker.cross_shard_worker.port();
             worker.cross_shard_worker.set_port(base_port + port);
+
+            let port = worker.query.port();
+            worker.query.set_port(base_port + port);
         }
     }
     committee
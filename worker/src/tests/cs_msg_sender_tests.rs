@@ -0,0 +1,29 @@
+use super::*;
+use std::fs;
+
+// Fixture: the sent csmsgs persisted in a new store.
+fn sent_csmsgs(path: &str) -> SentCSMsgs {
+    let _ = fs::remove_dir_all(path);
+    SentCSMsgs::new(Store::new(path).unwrap())
+}
+
+#[tokio::test]
+async fn recover_sent_csmsgs() {
+    let mut sent = sent_csmsgs(".db_test_cs_msg_sender_recover");
+    for seq in 0..5 {
+        sent.sent(1, seq, &Bytes::from(vec![seq as u8])).await;
+    }
+    sent.sent(2, 0, &Bytes::from(vec![9])).await;
+    sent.acked(1, vec![0, 1], 2).await;
+
+    // a restarted node resumes the sequence numbers and keeps the unacknowledged csmsgs
+    let (next_seq, acked, pending) = sent.recover(1).await;
+    assert_eq!((next_seq, acked), (5, 2));
+    assert_eq!(pending.keys().cloned().collect::<Vec<_>>(), vec![2, 3, 4]);
+    assert_eq!(pending[&3], Bytes::from(vec![3]));
+
+    let (next_seq, acked, pending) = sent.recover(2).await;
+    assert_eq!((next_seq, acked, pending.len()), (1, 0, 1));
+    let (next_seq, acked, pending) = sent.recover(0).await;
+    assert_eq!((next_seq, acked, pending.len()), (0, 0, 0));
+}
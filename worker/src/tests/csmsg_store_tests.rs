@@ -0,0 +1,75 @@
+use super::*;
+use std::fs;
+
+// Fixture: the seq-th csmsg from a source shard to shard 0.
+fn id(source: ShardId, seq: u64) -> CSMsgId {
+    CSMsgId::new(source, 0, 0, seq)
+}
+
+#[test]
+fn progress_tracks_gaps() {
+    let mut progress = SourceProgress::default();
+    for seq in &[0, 1, 3, 6] {
+        progress.mark_executed(*seq);
+    }
+    assert_eq!(progress.low, 2);
+    assert_eq!(progress.gaps(), vec![2, 4, 5]);
+    assert!(progress.is_executed(1));
+    assert!(!progress.is_executed(2));
+
+    // the gap is filled: the watermark follows the contiguous prefix
+    progress.mark_executed(2);
+    assert_eq!(progress.low, 4);
+    assert_eq!(progress.gaps(), vec![4, 5]);
+}
+
+#[test]
+fn progress_prunes_old_gaps() {
+    let mut progress = SourceProgress::default();
+    for seq in &[0, 2, 3, 5, 9] {
+        progress.mark_executed(*seq);
+    }
+    assert_eq!(progress.prune(10), None);
+    // only the last 5 csmsgs are tracked, but the older gap is kept and bounds the acks
+    assert_eq!(progress.prune(5), Some(4));
+    assert!(progress.is_executed(2));
+    assert!(!progress.is_executed(1));
+    assert_eq!(progress.gaps(), vec![1, 4, 6, 7, 8]);
+    assert_eq!(progress.acked(), 1);
+
+    // the retransmitted csmsg is executed at last
+    progress.mark_executed(1);
+    assert!(progress.is_executed(1));
+    assert_eq!(progress.acked(), 4);
+}
+
+#[tokio::test]
+async fn executed_marks_survive_restart() {
+    let path = ".db_test_csmsg_store";
+    let _ = fs::remove_dir_all(path);
+    let store = Store::new(path).unwrap();
+
+    let mut csmsg_store = CSMsgStore::new_with_store(2, store.clone(), 2, 100);
+    csmsg_store.update_executed(id(1, 0)).await;
+    csmsg_store.update_executed(id(1, 2)).await;
+    assert_eq!(csmsg_store.gaps(1).await.unwrap(), vec![1]);
+
+    // a new csmsg store on the same storage refuses to execute the csmsgs again
+    let mut csmsg_store = CSMsgStore::new_with_store(2, store, 2, 100);
+    assert!(!csmsg_store.can_executed(id(1, 0)).await.unwrap());
+    assert!(csmsg_store.can_executed(id(1, 1)).await.unwrap());
+    assert!(!csmsg_store.can_executed(id(1, 2)).await.unwrap());
+    assert!(csmsg_store.can_executed(id(0, 0)).await.unwrap());
+}
+
+#[tokio::test]
+async fn report_csmsg_status() {
+    let mut csmsg_store = CSMsgStore::new(2);
+    assert_eq!(csmsg_store.status(id(1, 0)).await.unwrap(), None);
+
+    csmsg_store.update_appended(id(1, 0), AppendedType::Remote).await.unwrap();
+    assert_eq!(csmsg_store.status(id(1, 0)).await.unwrap(), Some(CSMsgStatus::Appended));
+
+    csmsg_store.update_executed(id(1, 0)).await;
+    assert_eq!(csmsg_store.status(id(1, 0)).await.unwrap(), Some(CSMsgStatus::Executed));
+}
@@ -0,0 +1,44 @@
+use super::*;
+use crate::acc_shard::Account2ShardHash;
+use tokio::sync::mpsc::channel;
+
+#[tokio::test]
+async fn enter_skipped_epochs() {
+    // epochs of 10 heights, from epoch 0 up to epoch 3
+    let schedule = EpochSchedule::new(0, 3, 10, NextAcc2Shard::Online);
+    let (tx_plan, mut rx_plan) = channel(10);
+    let mut manager = EpochManager::new(0, 2, 0, Box::new(Account2ShardHash::new(2)), Some(schedule), tx_plan);
+
+    manager.begin_block(10).await;
+    assert_eq!(manager.epoch(), 0);
+    assert!(rx_plan.try_recv().is_err());
+
+    // the first block after a gap enters every epoch it skipped, one plan each
+    manager.begin_block(25).await;
+    assert_eq!(manager.epoch(), 2);
+    assert_eq!(rx_plan.try_recv().unwrap().epoch, 1);
+    assert_eq!(rx_plan.try_recv().unwrap().epoch, 2);
+    assert!(rx_plan.try_recv().is_err());
+
+    // the last epoch never ends
+    manager.begin_block(100).await;
+    assert_eq!(manager.epoch(), 3);
+    assert_eq!(rx_plan.try_recv().unwrap().epoch, 3);
+    assert!(rx_plan.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn apply_moves_of_other_shards() {
+    let schedule = EpochSchedule::new(0, 1, 10, NextAcc2Shard::Online);
+    let (tx_plan, _rx_plan) = channel(10);
+    let mut manager = EpochManager::new(0, 2, 0, Box::new(Account2ShardHash::new(2)), Some(schedule), tx_plan);
+    manager.begin_block(11).await;
+    assert_eq!(manager.moves(1), Some(BTreeMap::new()));
+    assert_eq!(manager.moves(2), None);
+
+    // shard 1 moved its account 1 to us
+    assert_eq!(manager.acc2shard().get_shard(&vec![1; 20]), 1);
+    manager.apply_moves(vec![(vec![1; 20], 0)].into_iter().collect());
+    assert_eq!(manager.acc2shard().get_shard(&vec![1; 20]), 0);
+    assert_eq!(manager.acc2shard().get_shard(&vec![3; 20]), 1);
+}
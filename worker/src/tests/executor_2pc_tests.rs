@@ -0,0 +1,112 @@
+use super::*;
+use crate::amount::{Amount, AmountError};
+use crate::executor::executor_tests::{balance, context, next_msg};
+use crate::state_store::INIT_BALANCE;
+use crate::Frame;
+
+// Fixture: a transfer from an account of shard 0 to an account of shard 1.
+fn transfer(counter: u64, sender: u8, receiver: u8, amount: i128) -> Transaction {
+    let payload = vec![
+        Frame { shardid: 0, rwset: vec![RWSet { addr: vec![sender; 20], value: Amount::new(-amount) }] },
+        Frame { shardid: 1, rwset: vec![RWSet { addr: vec![receiver; 20], value: Amount::new(amount) }] },
+    ];
+    Transaction::new(vec![sender; 20], vec![receiver; 20], Amount::new(amount), payload, 2, 2, 1, counter, 0, None, None)
+}
+
+#[tokio::test]
+async fn commit_cross_shard_tx() {
+    let (mut ctx_0, mut rx_0) = context(0, ".db_test_2pc_commit_0");
+    let (mut ctx_1, mut rx_1) = context(1, ".db_test_2pc_commit_1");
+    let mut coordinator = TwoPCExecutor::new(10);
+    let mut participant = TwoPCExecutor::new(10);
+    let tx = transfer(1, 0xa, 0xb, 100);
+
+    assert!(matches!(coordinator.execute_frame(&mut ctx_0, 1, &tx).await, ExecutionState::Pending));
+    let prepare = next_msg(&mut rx_0).await;
+    assert_eq!((prepare.phase, prepare.target_shard()), (CommitPhase::Prepare, 1));
+
+    // the accounts stay locked until the decision
+    let conflicting = transfer(2, 0xa, 0xc, 1);
+    assert!(matches!(coordinator.execute_frame(&mut ctx_0, 1, &conflicting).await, ExecutionState::RolledBack));
+
+    participant.execute_commit_msg(&mut ctx_1, 2, &prepare).await;
+    let vote = next_msg(&mut rx_1).await;
+    assert_eq!((vote.phase, vote.target_shard()), (CommitPhase::Vote(true), 0));
+
+    coordinator.execute_commit_msg(&mut ctx_0, 3, &vote).await;
+    let commit = next_msg(&mut rx_0).await;
+    assert_eq!(commit.phase, CommitPhase::Commit);
+    participant.execute_commit_msg(&mut ctx_1, 4, &commit).await;
+
+    assert_eq!(balance(&mut ctx_0, 0xa).await, INIT_BALANCE.checked_sub(Amount::new(100)).unwrap());
+    assert_eq!(balance(&mut ctx_1, 0xb).await, INIT_BALANCE.checked_add(Amount::new(100)).unwrap());
+    assert!(!ctx_0.state_transition.is_locked(&tx.payload[0].rwset, None));
+    assert!(!ctx_1.state_transition.is_locked(&tx.payload[1].rwset, None));
+}
+
+#[tokio::test]
+async fn abort_on_no_vote() {
+    let (mut ctx_0, mut rx_0) = context(0, ".db_test_2pc_no_vote_0");
+    let (mut ctx_1, mut rx_1) = context(1, ".db_test_2pc_no_vote_1");
+    let mut coordinator = TwoPCExecutor::new(10);
+    let mut participant = TwoPCExecutor::new(10);
+
+    // the coordinator checks its own frame before the prepare
+    let tx = transfer(1, 0xa, 0xb, i128::MAX);
+    assert!(matches!(coordinator.execute_frame(&mut ctx_0, 1, &tx).await, ExecutionState::Abort(AmountError::Underflow)));
+    assert!(rx_0.try_recv().is_err());
+
+    // the frame of the participant would overflow the balance of the receiver
+    let mut tx = transfer(2, 0xa, 0xb, INIT_BALANCE.wei());
+    tx.payload[1].rwset[0].value = Amount::new(i128::MAX);
+    assert!(matches!(coordinator.execute_frame(&mut ctx_0, 1, &tx).await, ExecutionState::Pending));
+
+    let prepare = next_msg(&mut rx_0).await;
+    participant.execute_commit_msg(&mut ctx_1, 2, &prepare).await;
+    let vote = next_msg(&mut rx_1).await;
+    assert_eq!(vote.phase, CommitPhase::Vote(false));
+    assert!(!ctx_1.state_transition.is_locked(&tx.payload[1].rwset, None));
+
+    // the participant holds no lock: the coordinator only releases its own
+    coordinator.execute_commit_msg(&mut ctx_0, 3, &vote).await;
+    assert!(rx_0.try_recv().is_err());
+    assert!(!ctx_0.state_transition.is_locked(&tx.payload[0].rwset, None));
+    assert_eq!(balance(&mut ctx_0, 0xa).await, INIT_BALANCE);
+}
+
+#[tokio::test]
+async fn abort_on_timeout() {
+    let (mut ctx_0, mut rx_0) = context(0, ".db_test_2pc_timeout_0");
+    let (mut ctx_1, mut rx_1) = context(1, ".db_test_2pc_timeout_1");
+    let mut coordinator = TwoPCExecutor::new(10);
+    let mut participant = TwoPCExecutor::new(10);
+    let tx = transfer(1, 0xa, 0xb, 100);
+
+    assert!(matches!(coordinator.execute_frame(&mut ctx_0, 1, &tx).await, ExecutionState::Pending));
+    let prepare = next_msg(&mut rx_0).await;
+    coordinator.end_block(&mut ctx_0, 10).await;
+    assert!(rx_0.try_recv().is_err());
+    coordinator.end_block(&mut ctx_0, 11).await;
+    let abort = next_msg(&mut rx_0).await;
+    assert_eq!(abort.phase, CommitPhase::Abort);
+    assert!(!ctx_0.state_transition.is_locked(&tx.payload[0].rwset, None));
+
+    // the abort overtakes the prepare, which is then ignored
+    participant.execute_commit_msg(&mut ctx_1, 12, &abort).await;
+    participant.execute_commit_msg(&mut ctx_1, 13, &prepare).await;
+    assert!(rx_1.try_recv().is_err());
+
+    // a prepare arriving once the abort is forgotten is rolled back by the coordinator
+    let (mut ctx_1, mut rx_1) = context(1, ".db_test_2pc_timeout_2");
+    let mut participant = TwoPCExecutor::new(10);
+    participant.execute_commit_msg(&mut ctx_1, 12, &abort).await;
+    participant.end_block(&mut ctx_1, 22).await;
+    participant.execute_commit_msg(&mut ctx_1, 23, &prepare).await;
+    let vote = next_msg(&mut rx_1).await;
+    assert!(ctx_1.state_transition.is_locked(&tx.payload[1].rwset, None));
+    coordinator.execute_commit_msg(&mut ctx_0, 24, &vote).await;
+    let abort = next_msg(&mut rx_0).await;
+    participant.execute_commit_msg(&mut ctx_1, 25, &abort).await;
+    assert!(!ctx_1.state_transition.is_locked(&tx.payload[1].rwset, None));
+    assert_eq!(balance(&mut ctx_1, 0xb).await, INIT_BALANCE);
+}
@@ -0,0 +1,117 @@
+use super::*;
+use crate::amount::Amount;
+use crate::RWSet;
+use crate::executor::executor_tests::context;
+use crate::worker::SendCSMessage;
+use tokio::sync::mpsc::Receiver;
+
+// Fixture: a SharDAG executor with the given triggers.
+fn executor(agg_interval: u64, agg_max_bytes: usize, agg_max_accounts: usize, agg_max_age: u64) -> SExecutor {
+    let parameters = Parameters {
+        agg_interval,
+        agg_max_bytes,
+        agg_max_accounts,
+        agg_max_age,
+        agg_deltas: true,
+        ..Parameters::default()
+    };
+    SExecutor::new(&parameters)
+}
+
+// Fixture: a tx of shard 0 crediting an account in each of the given shards.
+fn transfer(sender: u8, receivers: &[(ShardId, u8)]) -> Transaction {
+    let total = receivers.len() as i128;
+    let mut payload = vec![Frame { shardid: 0, rwset: vec![RWSet { addr: vec![sender; 20], value: Amount::new(-total) }] }];
+    for (shardid, receiver) in receivers {
+        payload.push(Frame { shardid: *shardid, rwset: vec![RWSet { addr: vec![*receiver; 20], value: Amount::new(1) }] });
+    }
+    let len = payload.len();
+    Transaction::new(vec![sender; 20], vec![receivers[0].1; 20], Amount::new(total), payload, len, len, 1, 0, 0, None, None)
+}
+
+// the target shard and the accounts of the next agg tx, which must be valid
+async fn next_agg_tx(rx_csmsg: &mut Receiver<SendCSMessage>) -> (ShardId, Vec<u8>) {
+    match rx_csmsg.recv().await.unwrap() {
+        SendCSMessage { tx: GeneralTransaction::AggTx(agg_tx), target_shard, .. } => {
+            assert!(agg_tx.verify_payload().is_ok());
+            let accs = agg_tx.payload[0].rwset.iter().map(|rw| rw.addr[0]).collect();
+            (target_shard, accs)
+        }
+        message => panic!("Unexpected csmsg {:?}", message.tx),
+    }
+}
+
+#[tokio::test]
+async fn aggregate_on_accounts_and_age() {
+    let (mut ctx, mut rx_csmsg) = context(0, ".db_test_agg_accounts");
+    let mut executor = executor(0, 0, 2, 5);
+
+    assert_eq!(executor.relay(&mut ctx, 1, &transfer(0xa, &[(1, 0xb)])).await, None);
+    executor.end_block(&mut ctx, 2).await;
+    assert!(rx_csmsg.try_recv().is_err());
+
+    // the second distinct account of shard 1
+    executor.relay(&mut ctx, 3, &transfer(0xa, &[(1, 0xb)])).await;
+    executor.end_block(&mut ctx, 3).await;
+    assert!(rx_csmsg.try_recv().is_err());
+    executor.relay(&mut ctx, 3, &transfer(0xa, &[(1, 0xc)])).await;
+    executor.end_block(&mut ctx, 3).await;
+    assert_eq!(next_agg_tx(&mut rx_csmsg).await, (1, vec![0xb, 0xc]));
+    assert_eq!(executor.avatar_store.bytes(), 0);
+
+    // the age counts from the first avatar cached after the aggregation
+    executor.relay(&mut ctx, 4, &transfer(0xa, &[(1, 0xd)])).await;
+    executor.relay(&mut ctx, 6, &transfer(0xa, &[(1, 0xd)])).await;
+    executor.end_block(&mut ctx, 8).await;
+    assert!(rx_csmsg.try_recv().is_err());
+    executor.end_block(&mut ctx, 9).await;
+    assert_eq!(next_agg_tx(&mut rx_csmsg).await, (1, vec![0xd]));
+}
+
+#[tokio::test]
+async fn aggregate_all_on_height_and_memory() {
+    let (mut ctx, mut rx_csmsg) = context(0, ".db_test_agg_memory");
+    let avatar_bytes = 20 + std::mem::size_of::<Amount>();
+    let mut executor = executor(10, 3 * avatar_bytes, 0, 0);
+
+    executor.relay(&mut ctx, 1, &transfer(0xa, &[(2, 0xb), (1, 0xc)])).await;
+    executor.end_block(&mut ctx, 1).await;
+    assert!(rx_csmsg.try_recv().is_err());
+    assert_eq!(executor.avatar_store.bytes(), 2 * avatar_bytes);
+
+    // every shard is aggregated, in the order of the shards
+    executor.relay(&mut ctx, 2, &transfer(0xa, &[(2, 0xd)])).await;
+    executor.end_block(&mut ctx, 2).await;
+    assert_eq!(next_agg_tx(&mut rx_csmsg).await, (1, vec![0xc]));
+    assert_eq!(next_agg_tx(&mut rx_csmsg).await, (2, vec![0xb, 0xd]));
+
+    executor.relay(&mut ctx, 3, &transfer(0xa, &[(1, 0xe)])).await;
+    executor.end_block(&mut ctx, 9).await;
+    assert!(rx_csmsg.try_recv().is_err());
+    executor.end_block(&mut ctx, 10).await;
+    assert_eq!(next_agg_tx(&mut rx_csmsg).await, (1, vec![0xe]));
+    assert!(rx_csmsg.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn aggregate_avatar_deltas() {
+    let (mut ctx, mut rx_csmsg) = context(0, ".db_test_agg_deltas");
+    let mut executor = executor(10, 0, 0, 0);
+
+    executor.relay(&mut ctx, 3, &transfer(0xa, &[(1, 0xb), (1, 0xc)])).await;
+    executor.relay(&mut ctx, 3, &transfer(0xa, &[(1, 0xb)])).await;
+    executor.relay(&mut ctx, 5, &transfer(0xa, &[(1, 0xc)])).await;
+    executor.end_block(&mut ctx, 10).await;
+
+    match rx_csmsg.recv().await.unwrap().tx {
+        GeneralTransaction::AggTx(agg_tx) => {
+            assert!(agg_tx.verify_payload().is_ok());
+            // the credits of an account add up
+            let avatars: Vec<(u8, Amount)> = agg_tx.payload[0].rwset.iter().map(|rw| (rw.addr[0], rw.value)).collect();
+            assert_eq!(avatars, vec![(0xb, Amount::new(2)), (0xc, Amount::new(2))]);
+            let heights: Vec<(Height, usize)> = agg_tx.deltas.iter().map(|delta| (delta.height, delta.rwset.len())).collect();
+            assert_eq!(heights, vec![(3, 2), (5, 1)]);
+        }
+        tx => panic!("Unexpected csmsg {:?}", tx),
+    }
+}
@@ -0,0 +1,320 @@
+use super::*;
+use crate::acc_shard::Account2ShardHash;
+use crate::amount::Amount;
+use crate::executor_m::MExecutor;
+use crate::state_store::{MStore, INIT_BALANCE};
+use crypto::{generate_keypair, PublicKey};
+use mpt::MPTStore;
+use rand::rngs::StdRng;
+use rand::SeedableRng as _;
+use std::fs;
+use std::sync::Arc;
+use store::Store;
+use tokio::sync::mpsc::{channel, Receiver};
+
+// Fixture: the genesis key of the account `[addr; 20]`, the accounts from 0xf0 have none.
+pub fn registered_key(addr: u8) -> PublicKey {
+    PublicKey([addr; 32])
+}
+
+// Fixture: the executor of a shard (out of 2), with the csmsgs it sends.
+pub fn context(shard_id: ShardId, path: &str) -> (ExecutionContext, Receiver<SendCSMessage>) {
+    let (state_path, receipts_path) = (format!("{}_state", path), format!("{}_receipts", path));
+    let _ = fs::remove_dir_all(&state_path);
+    let _ = fs::remove_dir_all(&receipts_path);
+    let state_store = MStore {
+        shard_id,
+        full_t: MPTStore::new(&state_path),
+        insert_dur: Vec::new(),
+        get_dur: Vec::new(),
+    };
+    let (tx_plan, _) = channel(1);
+    let epoch_manager = EpochManager::new(shard_id, 2, 0, Box::new(Account2ShardHash::new(2)), None, tx_plan);
+    let receipt_store = ReceiptStore::new(Store::new(&receipts_path).unwrap());
+    let (tx_csmsg, rx_csmsg) = channel(10);
+    let ctx = ExecutionContext::new(
+        shard_id,
+        StateTransition::new(
+            Box::new(state_store),
+            Arc::new((0..0xf0).map(|addr| (vec![addr; 20], registered_key(addr))).collect()),
+        ),
+        epoch_manager,
+        CSMsgStore::new(2),
+        tx_csmsg,
+        receipt_store,
+    );
+    (ctx, rx_csmsg)
+}
+
+pub async fn next_msg(rx_csmsg: &mut Receiver<SendCSMessage>) -> CommitMessage {
+    match rx_csmsg.recv().await.unwrap() {
+        SendCSMessage { tx: GeneralTransaction::CommitMsg(msg), target_shard, .. } => {
+            assert_eq!(target_shard, msg.target_shard());
+            msg
+        }
+        message => panic!("Unexpected csmsg {:?}", message.tx),
+    }
+}
+
+pub async fn balance(ctx: &mut ExecutionContext, addr: u8) -> Amount {
+    let rwset = vec![RWSet { addr: vec![addr; 20], value: Amount::ZERO }];
+    ctx.state_transition.get_latest_states(&rwset).await[&vec![addr; 20]].balance
+}
+
+// the balances of the accounts of a tx over the shards
+async fn total_balance(ctxs: &mut [&mut ExecutionContext], tx: &Transaction) -> i128 {
+    let mut total = 0;
+    for frame in &tx.payload {
+        let ctx = ctxs.iter_mut().find(|ctx| ctx.shard_id == frame.shardid).unwrap();
+        for rwset in &frame.rwset {
+            total += balance(ctx, rwset.addr[0]).await.wei();
+        }
+    }
+    total
+}
+
+#[tokio::test]
+async fn compensate_aborted_relayed_tx() {
+    let (mut ctx_0, mut rx_0) = context(0, ".db_test_compensate_0");
+    let (mut ctx_1, mut rx_1) = context(1, ".db_test_compensate_1");
+    let mut executor = MExecutor;
+
+    // three senders, the last one cannot pay
+    let frame = |shardid, addr, value| Frame { shardid, rwset: vec![RWSet { addr: vec![addr; 20], value: Amount::new(value) }] };
+    let payload = vec![
+        frame(0, 0xa, -100),
+        frame(1, 0xb, -100),
+        frame(0, 0xc, -INIT_BALANCE.wei() - 100),
+        frame(1, 0xd, INIT_BALANCE.wei() + 300),
+    ];
+    let mut tx = Transaction::new(vec![0xa; 20], vec![0xd; 20], Amount::new(300), payload, 4, 2, 1, 1, 0, None, None);
+    let total = total_balance(&mut [&mut ctx_0, &mut ctx_1], &tx).await;
+
+    assert!(matches!(executor.execute_frame(&mut ctx_0, 1, &tx).await, ExecutionState::Relay(1)));
+    executor.prepare_relay(0, &mut tx);
+    assert!(matches!(executor.execute_frame(&mut ctx_1, 2, &tx).await, ExecutionState::Relay(0)));
+    executor.prepare_relay(1, &mut tx);
+    assert!(matches!(executor.execute_frame(&mut ctx_0, 3, &tx).await, ExecutionState::Abort(AmountError::Underflow)));
+    assert_ne!(total_balance(&mut [&mut ctx_0, &mut ctx_1], &tx).await, total);
+
+    // the first frame is undone at once, the second one by shard 1
+    ctx_0.send_compensations(3, &tx).await;
+    assert_eq!(balance(&mut ctx_0, 0xa).await, INIT_BALANCE);
+    let compensation = next_msg(&mut rx_0).await;
+    assert_eq!((compensation.phase, compensation.target_shard()), (CommitPhase::Compensate, 1));
+    assert!(rx_0.try_recv().is_err());
+
+    ctx_1.compensate(4, &compensation).await;
+    assert_eq!(balance(&mut ctx_1, 0xb).await, INIT_BALANCE);
+    assert_eq!(total_balance(&mut [&mut ctx_0, &mut ctx_1], &tx).await, total);
+    let receipt = ctx_1.receipt_store.read(&tx.client_digest()).await.unwrap().unwrap();
+    assert_eq!((receipt.status, receipt.shard_path), (ReceiptStatus::RolledBack, vec![0, 1]));
+
+    // a redelivered compensation is ignored
+    ctx_1.compensate(5, &compensation).await;
+    assert_eq!(total_balance(&mut [&mut ctx_0, &mut ctx_1], &tx).await, total);
+    assert!(rx_1.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn compensate_frames_of_same_shard() {
+    let (mut ctx_0, mut rx_0) = context(0, ".db_test_compensate_same_shard_0");
+    let (mut ctx_1, mut rx_1) = context(1, ".db_test_compensate_same_shard_1");
+    let mut executor = MExecutor;
+
+    // shard 0 executes the first and the third frames, the last sender cannot pay
+    let frame = |shardid, addr, value| Frame { shardid, rwset: vec![RWSet { addr: vec![addr; 20], value: Amount::new(value) }] };
+    let payload = vec![
+        frame(0, 0xa, -100),
+        frame(1, 0xb, -100),
+        frame(0, 0xc, -100),
+        frame(1, 0xd, -INIT_BALANCE.wei() - 100),
+    ];
+    let mut tx = Transaction::new(vec![0xa; 20], vec![0xd; 20], Amount::new(300), payload, 4, 2, 1, 1, 0, None, None);
+    let total = total_balance(&mut [&mut ctx_0, &mut ctx_1], &tx).await;
+
+    assert!(matches!(executor.execute_frame(&mut ctx_0, 1, &tx).await, ExecutionState::Relay(1)));
+    executor.prepare_relay(0, &mut tx);
+    assert!(matches!(executor.execute_frame(&mut ctx_1, 2, &tx).await, ExecutionState::Relay(0)));
+    executor.prepare_relay(1, &mut tx);
+    assert!(matches!(executor.execute_frame(&mut ctx_0, 3, &tx).await, ExecutionState::Relay(1)));
+    executor.prepare_relay(0, &mut tx);
+    assert!(matches!(executor.execute_frame(&mut ctx_1, 4, &tx).await, ExecutionState::Abort(AmountError::Underflow)));
+
+    // both frames of shard 0 are undone, each one once
+    ctx_1.send_compensations(4, &tx).await;
+    assert!(rx_1.try_recv().is_ok() && rx_1.try_recv().is_ok() && rx_1.try_recv().is_err());
+    for step in [0, 2] {
+        let compensation = CommitMessage::new(1, CommitPhase::Compensate, tx.clone(), step);
+        ctx_0.compensate(5, &compensation).await;
+        ctx_0.compensate(6, &compensation).await;
+    }
+    assert_eq!(balance(&mut ctx_0, 0xa).await, INIT_BALANCE);
+    assert_eq!(balance(&mut ctx_0, 0xc).await, INIT_BALANCE);
+    assert_eq!(total_balance(&mut [&mut ctx_0, &mut ctx_1], &tx).await, total);
+    assert!(rx_0.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn retry_failed_compensation() {
+    let (mut ctx_0, _rx_0) = context(0, ".db_test_compensate_retry");
+    let credit = |value| Frame { shardid: 0, rwset: vec![RWSet { addr: vec![0xc; 20], value: Amount::new(value) }] };
+    let payload = vec![
+        Frame { shardid: 1, rwset: vec![RWSet { addr: vec![0xb; 20], value: Amount::new(-100) }] },
+        credit(100),
+    ];
+    let tx = Transaction::new(vec![0xb; 20], vec![0xc; 20], Amount::new(100), payload, 2, 2, 1, 1, 0, None, None);
+
+    // the credit is spent before its compensation arrives
+    ctx_0.apply_frame(&credit(100)).await.unwrap();
+    ctx_0.apply_frame(&credit(-INIT_BALANCE.wei() - 100)).await.unwrap();
+    let compensation = CommitMessage::new(1, CommitPhase::Compensate, tx.clone(), 1);
+    ctx_0.compensate(1, &compensation).await;
+    assert_eq!(balance(&mut ctx_0, 0xc).await, Amount::ZERO);
+    assert_eq!(ctx_0.receipt_store.read_pending_compensations().await.unwrap(), vec![compensation.clone()]);
+
+    // it is taken back once the account is funded again, also after a restart
+    ctx_0.retry_compensations(2).await;
+    ctx_0.apply_frame(&credit(300)).await.unwrap();
+    ctx_0.pending_compensations.clear();
+    ctx_0.recover().await;
+    ctx_0.retry_compensations(3).await;
+    assert_eq!(balance(&mut ctx_0, 0xc).await, Amount::new(200));
+    assert!(ctx_0.receipt_store.read_pending_compensations().await.unwrap().is_empty());
+    let receipt = ctx_0.receipt_store.read(&tx.client_digest()).await.unwrap().unwrap();
+    assert_eq!(receipt.status, ReceiptStatus::RolledBack);
+
+    // and never again
+    ctx_0.compensate(4, &compensation).await;
+    ctx_0.retry_compensations(4).await;
+    assert_eq!(balance(&mut ctx_0, 0xc).await, Amount::new(200));
+}
+
+#[tokio::test]
+async fn compensate_only_own_frame() {
+    let (mut ctx_1, _rx_1) = context(1, ".db_test_compensate_other");
+    let payload = vec![
+        Frame { shardid: 0, rwset: vec![RWSet { addr: vec![0xa; 20], value: Amount::new(-100) }] },
+        Frame { shardid: 1, rwset: vec![RWSet { addr: vec![0xb; 20], value: Amount::new(100) }] },
+    ];
+    let tx = Transaction::new(vec![0xa; 20], vec![0xb; 20], Amount::new(100), payload, 2, 2, 1, 1, 0, None, None);
+
+    // the frame belongs to shard 0
+    let compensation = CommitMessage::new(0, CommitPhase::Compensate, tx.clone(), 0);
+    ctx_1.compensate(1, &compensation).await;
+    assert_eq!(balance(&mut ctx_1, 0xa).await, INIT_BALANCE);
+    assert_eq!(ctx_1.receipt_store.read(&tx.client_digest()).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn concurrent_execution_matches_serial() {
+    let (mut serial, _rx_serial) = context(0, ".db_test_concurrent_serial");
+    let (mut concurrent, _rx_concurrent) = context(0, ".db_test_concurrent_concurrent");
+    let mut executor = MExecutor;
+    let transfer = |sender: u8, receiver: u8, amount: i128, nonce: i64| {
+        let rwset = vec![
+            RWSet { addr: vec![sender; 20], value: Amount::new(-amount) },
+            RWSet { addr: vec![receiver; 20], value: Amount::new(amount) },
+        ];
+        let payload = vec![Frame { shardid: 0, rwset }];
+        let mut tx = Transaction::new(vec![sender; 20], vec![receiver; 20], Amount::new(amount), payload, 1, 1, 1, 0, 0, None, None);
+        tx.sender_key = registered_key(sender);
+        tx.nonce = nonce;
+        tx
+    };
+    // the third tx spends what the first one received, the fourth one cannot pay, the fifth one
+    // has a stale nonce, the others are independent
+    let mut txs = vec![
+        transfer(0xa, 0xb, 100, 0),
+        transfer(0xc, 0xd, 10, 0),
+        transfer(0xb, 0xe, INIT_BALANCE.wei() + 50, 0),
+        transfer(0xf, 0xa, INIT_BALANCE.wei() + 1, 0),
+        transfer(0x80, 0x81, 10, 1),
+    ];
+    txs.extend((0x20..0x70).step_by(2).map(|addr| transfer(addr, addr + 1, 10, 0)));
+
+    for tx in &txs {
+        if serial.state_transition.authorize(&tx.sender, &tx.sender_key, tx.nonce).await {
+            executor.execute_frame(&mut serial, 1, tx).await;
+        }
+    }
+
+    let block: Vec<GeneralTransaction> = txs.iter().cloned().map(GeneralTransaction::TransferTx).collect();
+    concurrent.state_transition.prefetch(block.iter().flat_map(read_accounts).collect()).await;
+    assert_eq!(concurrent.state_transition.speculate(0, &block).await, 2 + 40);
+    for tx in &txs {
+        if concurrent.state_transition.authorize(&tx.sender, &tx.sender_key, tx.nonce).await {
+            executor.execute_frame(&mut concurrent, 1, tx).await;
+        }
+    }
+    concurrent.state_transition.write_back().await;
+
+    assert_eq!(balance(&mut concurrent, 0xe).await, INIT_BALANCE.checked_add(Amount::new(INIT_BALANCE.wei() + 50)).unwrap());
+    assert_eq!(balance(&mut concurrent, 0xa).await, INIT_BALANCE.checked_sub(Amount::new(100)).unwrap());
+    assert_eq!(balance(&mut concurrent, 0x21).await, INIT_BALANCE.checked_add(Amount::new(10)).unwrap());
+    // the act_root and the full_root of the block
+    assert_eq!(concurrent.state_transition.store.root().await, serial.state_transition.store.root().await);
+}
+
+#[tokio::test]
+async fn authorize_registered_key() {
+    let (mut ctx, _) = context(0, ".db_test_executor_sender_key");
+    let sender = vec![0xa; 20];
+
+    // the first tx of an account cannot register another key than its genesis one
+    assert!(!ctx.state_transition.authorize(&sender, &registered_key(0xb), 0).await);
+    assert!(ctx.state_transition.authorize(&sender, &registered_key(0xa), 0).await);
+    assert!(!ctx.state_transition.authorize(&sender, &registered_key(0xa), 0).await);
+    assert!(ctx.state_transition.authorize(&sender, &registered_key(0xa), 1).await);
+
+    // an account without a genesis key cannot send txs
+    assert!(!ctx.state_transition.authorize(&vec![0xf0; 20], &registered_key(0xf0), 0).await);
+}
+
+#[tokio::test]
+async fn reject_tx_keeps_nonce() {
+    let (mut ctx, _) = context(0, ".db_test_executor_reject");
+    let payload = vec![Frame { shardid: 0, rwset: vec![RWSet { addr: vec![0xa; 20], value: Amount::new(-10) }] }];
+    let mut tx = Transaction::new(vec![0xa; 20], vec![0xb; 20], Amount::new(10), payload, 1, 1, 1, 0, 0, None, None);
+    tx.sender_key = registered_key(0xa);
+
+    // e.g. the sender migrated out: the tx is rejected without consuming its nonce
+    ctx.reject_tx(1, &GeneralTransaction::TransferTx(tx.clone())).await;
+    let receipt = ctx.receipt_store.read(&tx.client_digest()).await.unwrap().unwrap();
+    assert_eq!(receipt.status, ReceiptStatus::Rejected);
+    assert!(ctx.state_transition.authorize(&tx.sender, &tx.sender_key, 0).await);
+
+    // the resubmission was executed, rejecting a replay of it keeps its receipt
+    let executed = Receipt::new(tx.client_digest(), ReceiptStatus::Committed, 2, 0, tx.shard_path(), None);
+    ctx.write_receipt(executed.clone()).await;
+    ctx.reject_tx(3, &GeneralTransaction::TransferTx(tx.clone())).await;
+    assert_eq!(ctx.receipt_store.read(&tx.client_digest()).await.unwrap(), Some(executed));
+}
+
+#[test]
+fn drop_peer_txs_with_invalid_signature() {
+    let (name, secret) = generate_keypair(&mut StdRng::from_seed([0; 32]));
+    let transfer = |nonce, amount| {
+        let rwset = vec![RWSet { addr: vec![0xa; 20], value: Amount::new(-amount) }];
+        let payload = vec![Frame { shardid: 0, rwset }];
+        let mut tx = Transaction::new(vec![0xa; 20], vec![0xb; 20], Amount::new(amount), payload, 1, 1, 1, 0, 0, None, None);
+        tx.nonce = nonce;
+        tx.sign(&name, &secret);
+        tx
+    };
+
+    // a peer packaged a tx whose amount was changed after it was signed
+    let mut tampered = transfer(1, 10);
+    tampered.amount = Amount::new(1000);
+    let mut relayed = transfer(2, 10);
+    relayed.update_relay_info(0);
+    let tx_list = vec![transfer(0, 10), tampered, relayed];
+    let batch = Batch { external_tx_nums: 3, tx_list: tx_list.into_iter().map(GeneralTransaction::TransferTx).collect() };
+
+    let txs = verified_txs(1, batch);
+    let nonces: Vec<_> = txs.iter().map(|tx| match tx {
+        GeneralTransaction::TransferTx(tx) => tx.nonce,
+        tx => panic!("Unexpected tx {:?}", tx),
+    }).collect();
+    assert_eq!(nonces, vec![0, 2]);
+}
@@ -0,0 +1,421 @@
+use super::*;
+use config::{Authority, Committee, PrimaryAddresses};
+use crypto::{
+    assemble_intact_ts_partial, generate_keypair, generate_threshold_keys, Hash as _, SecretKey,
+    ThresholdPublicKeySet, ThresholdSecretShare,
+};
+use rand::rngs::StdRng;
+use rand::SeedableRng as _;
+use mpt::Proof;
+use std::collections::HashMap;
+
+// Fixture
+fn keys() -> Vec<(PublicKey, SecretKey)> {
+    let mut rng = StdRng::from_seed([0; 32]);
+    (0..4).map(|_| generate_keypair(&mut rng)).collect()
+}
+
+// Fixture
+fn threshold_keys() -> (ThresholdPublicKeySet, Vec<ThresholdSecretShare>) {
+    let mut rng = StdRng::from_seed([1; 32]);
+    generate_threshold_keys(2, 4, &mut rng)
+}
+
+// Fixture: two shards with the same authorities.
+pub fn committees() -> Committees {
+    let committee = Committee {
+        authorities: keys()
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, _))| {
+                let primary = PrimaryAddresses {
+                    primary_to_primary: format!("127.0.0.1:{}", 100 + i).parse().unwrap(),
+                    worker_to_primary: format!("127.0.0.1:{}", 200 + i).parse().unwrap(),
+                };
+                let authority = Authority {
+                    stake: 1,
+                    primary,
+                    workers: HashMap::new(),
+                };
+                (name, authority)
+            })
+            .collect(),
+    };
+    let (key_set, _) = threshold_keys();
+    Committees {
+        shards: (0..2).map(|shardid| (shardid, committee.clone())).collect(),
+        client: "127.0.0.1:0".parse().unwrap(),
+        shard_num: 2,
+        shard_size: 4,
+        threshold_keys: (0..2).map(|shardid| (shardid, key_set.clone())).collect(),
+    }
+}
+
+// Fixture
+fn payload() -> Vec<Frame> {
+    vec![
+        Frame {
+            shardid: 0,
+            rwset: vec![RWSet { addr: vec![1; 20], value: Amount::new(-10) }],
+        },
+        Frame {
+            shardid: 1,
+            rwset: vec![RWSet { addr: vec![2; 20], value: Amount::new(10) }],
+        },
+    ]
+}
+
+// Fixture: the avatars of two accounts of shard 1, cached at two heights.
+fn agg_transaction() -> AggTransaction {
+    let avatar = |addr: u8, value: i128| RWSet { addr: vec![addr; 20], value: Amount::new(value) };
+    let payload = vec![Frame { shardid: 1, rwset: vec![avatar(1, 10), avatar(2, 30)] }];
+    let deltas = vec![
+        AvatarDelta { height: 4, rwset: vec![avatar(1, 10), avatar(2, 10)] },
+        AvatarDelta { height: 7, rwset: vec![avatar(2, 20)] },
+    ];
+    AggTransaction::new(0, payload, 2, deltas)
+}
+
+// Fixture
+fn transaction() -> Transaction {
+    Transaction::new(
+        vec![1; 20],
+        vec![2; 20],
+        Amount::new(10),
+        payload(),
+        2,
+        2,
+        1,
+        7,
+        1_000,
+        Some(vec![3; 20]),
+        Some(vec![4; 20]),
+    )
+}
+
+// Assemble the threshold signature of shard 0 over a digest.
+pub fn certify(digest: &Digest) -> ThresholdSignature {
+    let (key_set, shares) = threshold_keys();
+    let partials = shares.iter().map(|share| share.sign(digest)).collect();
+    assemble_intact_ts_partial(partials, &key_set, digest).unwrap()
+}
+
+// Fixture: a tx executed by shard 0 and relayed to shard 1.
+fn relayed_transaction() -> (Transaction, Digest) {
+    let mut tx = transaction();
+    tx.update_relay_info(0);
+    tx.set_csmsg_sequence(5);
+    tx.set_csmsg_epoch(2);
+    let digest = tx.digest();
+    let csmsg_id = tx.get_csmsg_id().unwrap();
+    tx.set_thres_sig(certify(&digest), csmsg_id);
+    (tx, digest)
+}
+
+#[test]
+fn tampered_transaction_invalidates_signature() {
+    let (name, secret) = keys().pop().unwrap();
+    let (tx, _) = relayed_transaction();
+    let signature = Signature::new(&tx.digest(), &secret);
+    assert!(signature.verify(&tx.digest(), &name).is_ok());
+
+    let tamperings: Vec<fn(&mut Transaction)> = vec![
+        |tx| tx.sample += 1,
+        |tx| tx.counter += 1,
+        |tx| tx.sender[0] ^= 1,
+        |tx| tx.receiver[0] ^= 1,
+        |tx| tx.amount = Amount::new(11),
+        |tx| tx.timestamp += 1,
+        |tx| tx.nonce += 1,
+        |tx| tx.payload_hash = Digest::default(),
+        |tx| tx.rwset_num += 1,
+        |tx| tx.payload[1].shardid += 1,
+        |tx| tx.payload[1].rwset[0].addr[0] ^= 1,
+        |tx| tx.payload[1].rwset[0].value = Amount::new(11),
+        |tx| tx.original_sender = None,
+        |tx| tx.final_receiver = Some(vec![5; 20]),
+        |tx| tx.involved_shard_num += 1,
+        |tx| tx.step += 1,
+        |tx| tx.source_shard += 1,
+        |tx| tx.csmsg_sequence += 1,
+        |tx| tx.csmsg_epoch += 1,
+        |tx| tx.cs_proof[0].0.seq += 1,
+        |tx| tx.cs_proof[0].0.epoch += 1,
+        |tx| tx.cs_proof[0].1 = ThresholdSignature::default(),
+        |tx| tx.cs_proof.clear(),
+    ];
+    for tamper in tamperings {
+        let mut tampered = tx.clone();
+        tamper(&mut tampered);
+        assert!(signature.verify(&tampered.digest(), &name).is_err());
+    }
+}
+
+#[test]
+fn tampered_agg_transaction_invalidates_signature() {
+    let (name, secret) = keys().pop().unwrap();
+    let mut agg_tx = AggTransaction::new(0, payload(), 2, Vec::new());
+    agg_tx.set_csmsg_sequence(3);
+    let signature = Signature::new(&agg_tx.digest(), &secret);
+    assert!(signature.verify(&agg_tx.digest(), &name).is_ok());
+
+    let tamperings: Vec<fn(&mut AggTransaction)> = vec![
+        |tx| tx.source_shard += 1,
+        |tx| tx.csmsg_sequence += 1,
+        |tx| tx.csmsg_epoch += 1,
+        |tx| tx.payload_hash = Digest::default(),
+        |tx| tx.payload_length += 1,
+        |tx| tx.payload[1].shardid += 1,
+        |tx| tx.payload[1].rwset[0].addr[0] ^= 1,
+        |tx| tx.payload[1].rwset[0].value = Amount::new(11),
+        |tx| {
+            tx.payload.pop();
+        },
+        |tx| tx.deltas.push(AvatarDelta { height: 1, rwset: Vec::new() }),
+    ];
+    for tamper in tamperings {
+        let mut tampered = agg_tx.clone();
+        tamper(&mut tampered);
+        assert!(signature.verify(&tampered.digest(), &name).is_err());
+    }
+}
+
+#[tokio::test]
+async fn tampered_csmsg_invalidates_signature() {
+    let (name, secret) = keys().pop().unwrap();
+    let share = threshold_keys().1.pop();
+    let mut signature_service = SignatureService::new_with_threshold_share(secret, share);
+    let tx = GeneralTransaction::TransferTx(transaction());
+    let mut csmsg = CSMsg::new(0, 1, 5, 2, tx, &name, &mut signature_service).await;
+    csmsg.set_sig(&mut signature_service).await;
+    assert!(csmsg.verify(&committees()).is_ok());
+
+    let tamperings: Vec<fn(&mut CSMsg)> = vec![
+        |csmsg| csmsg.source_shard = 1,
+        |csmsg| csmsg.target_shard = 0,
+        |csmsg| csmsg.csmsg_sequence += 1,
+        |csmsg| csmsg.epoch += 1,
+        |csmsg| csmsg.inner_tx_hash = Digest::default(),
+        |csmsg| csmsg.thres_sig = PartialSignature::default(),
+        |csmsg| csmsg.author = PublicKey::default(),
+        |csmsg| match &mut csmsg.tx {
+            GeneralTransaction::TransferTx(tx) => tx.amount = Amount::new(11),
+            _ => unreachable!(),
+        },
+        |csmsg| csmsg.tx = GeneralTransaction::AggTx(AggTransaction::new(0, payload(), 2, Vec::new())),
+    ];
+    for tamper in tamperings {
+        let mut tampered = csmsg.clone();
+        tamper(&mut tampered);
+        assert!(tampered.signature.verify(&tampered.digest(), &name).is_err());
+        assert!(tampered.verify(&committees()).is_err());
+    }
+}
+
+#[tokio::test]
+async fn tampered_csmsg_ack_invalidates_signature() {
+    let (name, secret) = keys().pop().unwrap();
+    let mut signature_service = SignatureService::new(secret);
+    let ack = CSMsgAck::new(0, 1, 42, &name, &mut signature_service).await;
+    assert!(ack.verify(&committees()).is_ok());
+
+    let tamperings: Vec<fn(&mut CSMsgAck)> = vec![
+        |ack| ack.source_shard = 1,
+        |ack| ack.target_shard = 0,
+        |ack| ack.next_seq += 1,
+        |ack| ack.author = PublicKey::default(),
+    ];
+    for tamper in tamperings {
+        let mut tampered = ack.clone();
+        tamper(&mut tampered);
+        assert!(tampered.verify(&committees()).is_err());
+    }
+}
+
+#[test]
+fn verify_valid_cs_proof() {
+    let (tx, digest) = relayed_transaction();
+    let tx = GeneralTransaction::TransferTx(tx);
+    assert_eq!(tx.verify_cs_proof(&committees()).unwrap(), digest);
+
+    let mut agg_tx = agg_transaction();
+    agg_tx.set_csmsg_sequence(3);
+    let digest = agg_tx.digest();
+    agg_tx.set_thres_sig(certify(&digest));
+    let agg_tx = GeneralTransaction::AggTx(agg_tx);
+    assert_eq!(agg_tx.verify_cs_proof(&committees()).unwrap(), digest);
+
+    let mut msg = CommitMessage::new(0, CommitPhase::Prepare, transaction(), 1);
+    msg.set_csmsg_sequence(3);
+    let digest = msg.digest();
+    msg.set_thres_sig(certify(&digest));
+    let mut msg = GeneralTransaction::CommitMsg(msg);
+    assert_eq!(msg.verify_cs_proof(&committees()).unwrap(), digest);
+
+    // the signature covers the phase
+    if let GeneralTransaction::CommitMsg(msg) = &mut msg {
+        msg.phase = CommitPhase::Abort;
+    }
+    assert!(msg.verify_cs_proof(&committees()).is_err());
+}
+
+#[test]
+fn verify_agg_payload() {
+    let agg_tx = agg_transaction();
+    assert!(agg_tx.verify_payload().is_ok());
+    assert!(AggTransaction::new(0, agg_tx.payload.clone(), 2, Vec::new()).verify_payload().is_ok());
+
+    // a faulty source signs whatever it sends, the payload must match its own commitments
+    let tamperings: Vec<fn(&mut AggTransaction)> = vec![
+        |tx| tx.payload.push(Frame { shardid: 0, rwset: Vec::new() }),
+        |tx| tx.payload[0].rwset.swap(0, 1),
+        |tx| tx.payload[0].rwset[1].value = Amount::new(31),
+        |tx| tx.payload_length = 3,
+        |tx| tx.payload_hash = Digest::default(),
+        |tx| tx.deltas[1].rwset[0].value = Amount::new(21),
+        |tx| tx.deltas.swap(0, 1),
+        |tx| {
+            tx.deltas.pop();
+        },
+    ];
+    for tamper in tamperings {
+        let mut tampered = agg_tx.clone();
+        tamper(&mut tampered);
+        assert!(tampered.verify_payload().is_err());
+    }
+
+    // the changed avatars are committed again, but do not match the deltas
+    let mut tampered = agg_tx.clone();
+    tampered.payload[0].rwset[1].value = Amount::new(31);
+    tampered.payload_hash = avatars_root(&tampered.payload);
+    assert!(matches!(tampered.verify_payload(), Err(ExecutionError::AggDeltasMismatch(0))));
+
+    // the receiver checks the payload of a certified agg tx
+    tampered.set_thres_sig(certify(&tampered.digest()));
+    assert!(GeneralTransaction::AggTx(tampered).verify_cs_proof(&committees()).is_err());
+}
+
+#[test]
+fn verify_tampered_cs_proof() {
+    let (mut tx, _) = relayed_transaction();
+    tx.amount = Amount::new(11);
+    let tx = GeneralTransaction::TransferTx(tx);
+    assert!(tx.verify_cs_proof(&committees()).is_err());
+
+    let (mut tx, _) = relayed_transaction();
+    tx.cs_proof[0].1 = ThresholdSignature::default();
+    let tx = GeneralTransaction::TransferTx(tx);
+    assert!(tx.verify_cs_proof(&committees()).is_err());
+
+    // a certificate for another target shard
+    let (mut tx, _) = relayed_transaction();
+    tx.cs_proof[0].0.target = 0;
+    let tx = GeneralTransaction::TransferTx(tx);
+    assert!(tx.verify_cs_proof(&committees()).is_err());
+}
+
+#[test]
+fn csmsg_id_of_relayed_transaction() {
+    let (tx, _) = relayed_transaction();
+    assert_eq!(tx.get_csmsg_id(), Some(CSMsgId::new(0, 1, 2, 5)));
+    assert_eq!(transaction().get_csmsg_id(), None);
+}
+
+#[test]
+fn verify_client_signature() {
+    let (name, secret) = keys().pop().unwrap();
+    let mut tx = transaction();
+    tx.nonce = 3;
+    tx.sign(&name, &secret);
+    assert!(tx.verify_signature().is_ok());
+
+    // the signature survives the relay of the tx, but relayed txs are not checked against it
+    let mut relayed = tx.clone();
+    relayed.update_relay_info(0);
+    assert!(relayed.verify_signature().is_ok());
+    assert!(GeneralTransaction::TransferTx(relayed).client_signature().is_none());
+    assert!(GeneralTransaction::TransferTx(tx.clone()).client_signature().is_some());
+
+    let tamperings: Vec<fn(&mut Transaction)> = vec![
+        |tx| tx.nonce += 1,
+        |tx| tx.amount = Amount::new(11),
+        |tx| tx.receiver[0] ^= 1,
+        |tx| tx.sender[0] ^= 1,
+        |tx| tx.sender_key = PublicKey::default(),
+    ];
+    for tamper in tamperings {
+        let mut tampered = tx.clone();
+        tamper(&mut tampered);
+        assert!(tampered.verify_signature().is_err());
+    }
+
+    // signed with another key than the carried one
+    let (_, secret) = keys().remove(0);
+    let mut forged = transaction();
+    forged.sign(&name, &secret);
+    assert!(forged.verify_signature().is_err());
+}
+
+#[tokio::test]
+async fn csmsg_carries_the_id_of_its_tx() {
+    let (name, secret) = keys().pop().unwrap();
+    let share = threshold_keys().1.pop();
+    let mut signature_service = SignatureService::new_with_threshold_share(secret, share);
+    let mut tx = transaction();
+    tx.update_relay_info(0);
+    let tx = GeneralTransaction::TransferTx(tx);
+
+    let mut csmsg = CSMsg::new(0, 1, 5, 2, tx.clone(), &name, &mut signature_service).await;
+    csmsg.set_sig(&mut signature_service).await;
+    assert_eq!(csmsg.tx.get_csmsg_id(), Some(csmsg.id()));
+    assert!(csmsg.verify(&committees()).is_ok());
+
+    // a csmsg addressed to a shard other than the executor of the next frame
+    let mut csmsg = CSMsg::new(0, 0, 5, 2, tx, &name, &mut signature_service).await;
+    csmsg.set_sig(&mut signature_service).await;
+    assert!(csmsg.verify(&committees()).is_err());
+}
+
+#[tokio::test]
+async fn tampered_migration_invalidates_signature() {
+    let (name, secret) = keys().pop().unwrap();
+    let share = threshold_keys().1.pop();
+    let mut signature_service = SignatureService::new_with_threshold_share(secret, share);
+    let proofs = |addr: u8| {
+        (0..2)
+            .map(|i| (vec![addr + i; 20], Proof { nodes: vec![vec![i; 32]] }))
+            .collect::<HashMap<_, _>>()
+    };
+    let mut migration = Migration::new(0, 1, 3, vec![1; 32], vec![2; 32], proofs(1), proofs(5));
+    migration.moves = vec![(vec![1; 20], 1), (vec![9; 20], 2)].into_iter().collect();
+    migration.sign(&name, &mut signature_service).await;
+    assert!(migration.verify(&committees()).is_ok());
+
+    let tamperings: Vec<fn(&mut Migration)> = vec![
+        |migration| migration.source_shard_id = 1,
+        |migration| migration.target_shard_id = 0,
+        |migration| migration.epoch += 1,
+        |migration| migration.act_root[0] ^= 1,
+        |migration| migration.full_root[0] ^= 1,
+        |migration| {
+            migration.active_acc_proof_map.remove(&vec![1; 20]);
+        },
+        |migration| {
+            let proof = migration.dormant_acc_proof_map.remove(&vec![5; 20]).unwrap();
+            migration.active_acc_proof_map.insert(vec![5; 20], proof);
+        },
+        |migration| {
+            migration.moves.insert(vec![9; 20], 1);
+        },
+        |migration| {
+            migration.moves.remove(&vec![9; 20]);
+        },
+        |migration| migration.thres_sig = PartialSignature::default(),
+        |migration| migration.author = PublicKey::default(),
+    ];
+    for tamper in tamperings {
+        let mut tampered = migration.clone();
+        tamper(&mut tampered);
+        assert!(tampered.verify(&committees()).is_err());
+    }
+}
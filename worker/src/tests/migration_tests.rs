@@ -0,0 +1,99 @@
+use super::*;
+use crate::amount::Amount;
+use crate::messages::messages_tests::{certify, committees};
+use crate::messages::{RWSet, Transaction};
+use crate::state_store::{Account, MStore};
+use crypto::Hash as _;
+use mpt::MPTStore;
+use std::fs;
+use tokio::sync::mpsc::channel;
+
+// Fixture: a MStore of a shard with the accounts 1 and 2.
+async fn store(shard_id: ShardId, path: &str) -> Box<dyn StateStore + Send> {
+    let _ = fs::remove_dir_all(path);
+    let mut store = MStore {
+        shard_id,
+        full_t: MPTStore::new(path),
+        insert_dur: Vec::new(),
+        get_dur: Vec::new(),
+    };
+    for addr in 1..=2u8 {
+        let account = Account { nonce: 0, balance: Amount::new(addr as i128), key: None };
+        store.insert(vec![addr; 20], bincode::serialize(&account).unwrap()).await;
+    }
+    Box::new(store)
+}
+
+// Fixture: the account 1 of shard 0, certified for shard 1 at epoch 1.
+async fn certified_migration(path: &str) -> CertifiedMigration {
+    let mut source = store(0, path).await;
+    let (act_root, full_root, act_proofs, dor_proofs) = source.export_accounts(vec![vec![1; 20]]).await;
+    let mut migration = Migration::new(0, 1, 1, act_root, full_root, act_proofs, dor_proofs);
+    migration.moves.insert(vec![1; 20], 1);
+    let cert = certify(&migration.header_digest());
+    CertifiedMigration::new(migration, cert)
+}
+
+// Fixture: the migrator of shard 1, waiting for the account 1 from shard 0 from height 5.
+async fn migrator() -> (Migrator, Receiver<Migration>) {
+    let (tx_plan, rx_plan) = channel(1);
+    let (tx_migration, rx_migration) = channel(1);
+    let plan = MigrationPlan {
+        epoch: 1,
+        height: 5,
+        outgoing: BTreeMap::new(),
+        incoming: vec![(0, vec![vec![1; 20]].into_iter().collect())].into_iter().collect(),
+        accept_any: false,
+    };
+    tx_plan.send(plan).await.unwrap();
+    (Migrator::new(1, rx_plan, tx_migration), rx_migration)
+}
+
+fn transfer(addr: u8) -> GeneralTransaction {
+    let payload = vec![Frame { shardid: 1, rwset: vec![RWSet { addr: vec![addr; 20], value: Amount::new(1) }] }];
+    let tx = Transaction::new(vec![addr; 20], vec![addr; 20], Amount::new(1), payload, 1, 1, 1, 1, 0, None, None);
+    GeneralTransaction::TransferTx(tx)
+}
+
+#[tokio::test]
+async fn install_committed_migration() {
+    let migration = certified_migration(".db_test_migration_install_0_state").await;
+    let mut store = store(1, ".db_test_migration_install_1_state").await;
+    let (mut migrator, _rx_migration) = migrator().await;
+
+    // only the txs touching the in-flight account wait for the migration
+    assert!(migrator.begin_block(5, &mut store).await.is_empty());
+    assert!(migrator.hold(5, &transfer(1)));
+    assert!(!migrator.hold(5, &transfer(2)));
+    assert!(migrator.begin_block(6, &mut store).await.is_empty());
+    assert!(migrator.take_moves().is_empty());
+
+    // the migration committed at height 6 is installed before the next block, with its moves
+    migrator.deliver(6, &migration, &committees());
+    migrator.deliver(6, &migration, &committees());
+    let resumed = migrator.begin_block(7, &mut store).await;
+    assert_eq!(resumed.len(), 1);
+    assert_eq!(resumed[0].get_digest(), transfer(1).get_digest());
+    assert!(migrator.plans.is_empty());
+    assert!(!migrator.hold(7, &transfer(1)));
+    assert_eq!(migrator.take_moves(), migration.migration.moves);
+
+    let account: Account = bincode::deserialize(&store.get(&[1; 20]).await.unwrap()).unwrap();
+    assert_eq!(account.balance, Amount::new(1));
+}
+
+#[tokio::test]
+async fn ignore_uncertified_migration() {
+    let mut migration = certified_migration(".db_test_migration_uncertified_0_state").await;
+    let mut store = store(1, ".db_test_migration_uncertified_1_state").await;
+    let (mut migrator, _rx_migration) = migrator().await;
+    assert!(migrator.begin_block(5, &mut store).await.is_empty());
+    assert!(migrator.hold(5, &transfer(1)));
+
+    // a certificate over another header does not install the accounts
+    migration.cert = certify(&migration.digest());
+    migrator.deliver(5, &migration, &committees());
+    assert!(migrator.delivered.is_empty());
+    assert!(migrator.begin_block(6, &mut store).await.is_empty());
+    assert!(migrator.hold(6, &transfer(1)));
+}
@@ -0,0 +1,49 @@
+use super::*;
+use bytes::Bytes;
+use tokio::sync::oneshot;
+
+fn peer(port: u16) -> SocketAddr {
+    format!("127.0.0.1:{}", port).parse().unwrap()
+}
+
+#[test]
+fn drop_acknowledged_messages() {
+    let mut outstanding = OutstandingMessages::new("test_ack", 10);
+    let (tx_ack, handler) = oneshot::channel();
+    outstanding.insert(1, peer(1), handler);
+    let (_tx_pending, handler) = oneshot::channel();
+    outstanding.insert(1, peer(2), handler);
+
+    tx_ack.send(Bytes::from("Ack")).unwrap();
+    outstanding.cleanup(1);
+    assert_eq!(outstanding.len(), 1);
+    assert_eq!(
+        metrics::OUTSTANDING_MESSAGES.with_label_values(&["test_ack", "127.0.0.1:1"]).get(),
+        0
+    );
+    assert_eq!(
+        metrics::OUTSTANDING_MESSAGES.with_label_values(&["test_ack", "127.0.0.1:2"]).get(),
+        1
+    );
+}
+
+#[test]
+fn drop_messages_below_gc_depth() {
+    let mut outstanding = OutstandingMessages::new("test_gc", 2);
+    let mut senders = Vec::new();
+    for height in 1..=4 {
+        let (tx, handler) = oneshot::channel();
+        senders.push(tx);
+        outstanding.insert(height, peer(3), handler);
+    }
+
+    // the heights 1 and 2 are more than 2 heights behind height 5
+    outstanding.cleanup(5);
+    assert_eq!(outstanding.len(), 2);
+    outstanding.cleanup(10);
+    assert!(outstanding.is_empty());
+    assert_eq!(
+        metrics::OUTSTANDING_MESSAGES.with_label_values(&["test_gc", "127.0.0.1:3"]).get(),
+        0
+    );
+}
@@ -0,0 +1,55 @@
+use super::*;
+use crate::{Account2ShardHash, Amount, Frame, RWSet};
+
+// Fixture: an account of the given shard under the hash policy.
+fn account(id: u8, shard: u8) -> Address {
+    vec![id, shard]
+}
+
+// Fixture: a transfer whose first frame is executed by us (shard 0).
+fn transfer(sender: &Address, receiver: &Address, receiver_shard: ShardId) -> Transaction {
+    let mut payload = vec![Frame {
+        shardid: 0,
+        rwset: vec![RWSet { addr: sender.clone(), value: Amount::new(-1) }],
+    }];
+    let receiver_rwset = RWSet { addr: receiver.clone(), value: Amount::new(1) };
+    if receiver_shard == 0 {
+        payload[0].rwset.push(receiver_rwset);
+    } else {
+        payload.push(Frame { shardid: receiver_shard, rwset: vec![receiver_rwset] });
+    }
+    let involved_shard_num = payload.len();
+    Transaction::new(sender.clone(), receiver.clone(), Amount::new(1), payload, 2, involved_shard_num, 1, 0, 0, None, None)
+}
+
+#[test]
+fn account_moves_to_its_neighbors() {
+    let (a, b) = (account(1, 0), account(2, 1));
+    let (c, d) = (account(3, 0), account(4, 0));
+    let mut graph = InteractionGraph::new();
+    graph.observe(&transfer(&a, &b, 1));
+    graph.observe(&transfer(&c, &d, 0));
+    graph.observe(&transfer(&d, &c, 0));
+
+    let current = Account2ShardHash::new(2);
+    let (next, moves) = graph.partition(0, &current);
+    assert_eq!(moves.into_iter().collect::<Vec<_>>(), vec![(a.clone(), 1)]);
+    assert_eq!(next.get_shard(&a), 1);
+    assert_eq!(next.get_shard(&c), 0);
+    assert_eq!(next.get_shard(&d), 0);
+}
+
+#[test]
+fn moves_respect_load_balance() {
+    let (a, b) = (account(1, 0), account(2, 1));
+    let mut graph = InteractionGraph::new();
+    for _ in 0..3 {
+        graph.observe(&transfer(&a, &b, 1));
+    }
+
+    // moving `a` would put the whole load on shard 1
+    let current = Account2ShardHash::new(2);
+    let (next, moves) = graph.partition(0, &current);
+    assert!(moves.is_empty());
+    assert_eq!(next.get_shard(&a), 0);
+}
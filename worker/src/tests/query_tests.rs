@@ -0,0 +1,140 @@
+use super::*;
+use crate::amount::Amount;
+use crate::execution_result::ExecutionResult;
+use crate::receipt::{Receipt, ReceiptStatus};
+use mpt::Proof;
+use serde_json::json;
+use std::fs;
+use store::Store;
+use tokio::sync::mpsc::channel;
+
+// Fixture: an executor at height 5 of epoch 2, holding a single account.
+fn executor() -> Sender<StateQuery> {
+    let (tx_state_query, mut rx_state_query) = channel(1);
+    tokio::spawn(async move {
+        while let Some(query) = rx_state_query.recv().await {
+            let root = StateRoot { shard_id: 1, height: 5, act_root: vec![1; 32], full_root: vec![2; 32] };
+            match query {
+                StateQuery::Account(addr, reply) => {
+                    let account = Account { nonce: 3, balance: Amount::new(100), key: None };
+                    let _ = reply.send(Some(account).filter(|_| addr == vec![0xab; 20]));
+                }
+                StateQuery::StateRoot(reply) => {
+                    let _ = reply.send(root);
+                }
+                StateQuery::Proof(_, height, reply) => {
+                    // the last certified height is 4
+                    let root = StateRoot { height: 4, ..root };
+                    let proof = AccountProof::Active(Proof::from(vec![vec![0xcd; 4]]));
+                    let _ = reply.send(Some(StateProof { root, proof }).filter(|_| height == 4));
+                }
+                StateQuery::Progress(reply) => {
+                    let _ = reply.send((5, 2));
+                }
+                StateQuery::Moves(epoch, reply) => {
+                    let moves = vec![(vec![0xab; 20], 0)].into_iter().collect();
+                    let _ = reply.send(Some(moves).filter(|_| epoch == 2));
+                }
+            }
+        }
+    });
+    tx_state_query
+}
+
+// Fixture
+fn service(path: &str) -> QueryService {
+    let _ = fs::remove_dir_all(path);
+    QueryService::new(executor(), Store::new(path).unwrap(), CSMsgStore::new(2))
+}
+
+async fn call(service: &QueryService, method: &str, params: Value) -> RpcResponse {
+    let request = serde_json::to_vec(&RpcRequest::new(7, method, params)).unwrap();
+    service.handle(&request).await
+}
+
+#[tokio::test]
+async fn query_state() {
+    let service = service(".db_test_query_state");
+
+    let response = call(&service, "get_height", Value::Null).await;
+    assert_eq!(response.id, json!(7));
+    assert_eq!(response.into_result::<Height>(), Ok(5));
+    assert_eq!(call(&service, "get_epoch", Value::Null).await.into_result::<u64>(), Ok(2));
+    let moves = call(&service, "get_moves", json!([2])).await;
+    assert_eq!(moves.result, Some(json!({"ab".repeat(20): 0})));
+    assert_eq!(call(&service, "get_moves", json!({"epoch": 3})).await.result, Some(Value::Null));
+
+    let account = call(&service, "get_account", json!(["0x".to_owned() + &"ab".repeat(20)])).await;
+    assert_eq!(account.result, Some(json!({"nonce": 3, "balance": "100", "key": null})));
+    let absent = call(&service, "get_account", json!({"addr": "cd".repeat(20)})).await;
+    assert_eq!(absent.result, Some(Value::Null));
+
+    let root = call(&service, "get_state_root", Value::Null).await;
+    assert_eq!(root.result.as_ref().unwrap()["act_root"], json!("01".repeat(32)));
+    assert_eq!(root.into_result::<StateRoot>().unwrap().full_root, vec![2; 32]);
+
+    let proof = call(&service, "get_proof", json!(["ab".repeat(20), 4])).await;
+    assert_eq!(proof.result.as_ref().unwrap()["proof"], json!({"Active": ["cdcdcdcd"]}));
+    let proof: StateProof = proof.into_result().unwrap();
+    assert_eq!(proof.root.height, 4);
+    assert_eq!(proof.proof, AccountProof::Active(Proof::from(vec![vec![0xcd; 4]])));
+    let uncertified = call(&service, "get_proof", json!({"addr": "ab".repeat(20), "height": 5})).await;
+    assert_eq!(uncertified.result, Some(Value::Null));
+}
+
+#[tokio::test]
+async fn query_receipt_and_csmsg() {
+    let path = ".db_test_query_receipt";
+    let service = service(path);
+
+    let receipt = Receipt::new(Digest([9; 32]), ReceiptStatus::Relayed(0), 4, 1, vec![1], None);
+    service.receipt_store.clone().write(&receipt).await;
+    let response = call(&service, "get_receipt", json!(["09".repeat(32)])).await;
+    assert_eq!(response.result.as_ref().unwrap()["tx_digest"], json!("09".repeat(32)));
+    assert_eq!(response.into_result::<Option<Receipt>>(), Ok(Some(receipt)));
+
+    let id = CSMsgId::new(0, 1, 0, 0);
+    let response = call(&service, "get_csmsg_status", json!([id])).await;
+    assert_eq!(response.result, Some(Value::Null));
+    service.csmsg_store.clone().update_executed(id).await;
+    let response = call(&service, "get_csmsg_status", json!({"id": id})).await;
+    assert_eq!(response.result, Some(json!("Executed")));
+}
+
+#[tokio::test]
+async fn query_certificate() {
+    let service = service(".db_test_query_certificate");
+    let response = call(&service, "get_certified_height", Value::Null).await;
+    assert_eq!(response.into_result::<Option<Height>>(), Ok(None));
+
+    let result = ExecutionResult::new(4, vec![1; 32], vec![2; 32], Digest([3; 32]));
+    let certificate = ExecutionCertificate { shard_id: 1, result, votes: Vec::new() };
+    let mut store = service.store.clone();
+    store.write(certificate_key(4), bincode::serialize(&certificate).unwrap()).await;
+    store.write(CERTIFIED_HEIGHT_KEY.to_vec(), 4u64.to_le_bytes().to_vec()).await;
+
+    let response = call(&service, "get_certified_height", Value::Null).await;
+    assert_eq!(response.into_result::<Option<Height>>(), Ok(Some(4)));
+    let response = call(&service, "get_certificate", json!([4])).await;
+    assert_eq!(response.result.as_ref().unwrap()["result"]["act_root"], json!("01".repeat(32)));
+    assert_eq!(response.into_result::<Option<ExecutionCertificate>>(), Ok(Some(certificate)));
+    let response = call(&service, "get_certificate", json!({"height": 3})).await;
+    assert_eq!(response.result, Some(Value::Null));
+}
+
+#[tokio::test]
+async fn reject_invalid_requests() {
+    let service = service(".db_test_query_errors");
+
+    let response = service.handle(b"not json").await;
+    assert_eq!(response.error.map(|e| e.code), Some(PARSE_ERROR));
+
+    let response = call(&service, "get_balance", Value::Null).await;
+    assert_eq!(response.error.map(|e| e.code), Some(METHOD_NOT_FOUND));
+
+    let response = call(&service, "get_account", Value::Null).await;
+    assert_eq!(response.error.map(|e| e.code), Some(INVALID_PARAMS));
+
+    let response = call(&service, "get_receipt", json!(["0x0102"])).await;
+    assert_eq!(response.error.map(|e| e.code), Some(INVALID_PARAMS));
+}
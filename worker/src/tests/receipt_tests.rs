@@ -0,0 +1,47 @@
+use super::*;
+use std::fs;
+
+// Fixture
+fn receipt(status: ReceiptStatus) -> Receipt {
+    Receipt::new(Digest([1; 32]), status, 7, 1, vec![0, 1], None)
+}
+
+#[test]
+fn digest_ignores_latency() {
+    let mut observed = receipt(ReceiptStatus::Committed);
+    observed.latency_ms = Some(120);
+    assert_eq!(observed.digest(), receipt(ReceiptStatus::Committed).digest());
+}
+
+#[test]
+fn digest_covers_status() {
+    let digests = vec![
+        receipt(ReceiptStatus::Committed).digest(),
+        receipt(ReceiptStatus::Aborted(AmountError::Underflow)).digest(),
+        receipt(ReceiptStatus::Aborted(AmountError::Overflow)).digest(),
+        receipt(ReceiptStatus::Relayed(2)).digest(),
+        receipt(ReceiptStatus::Relayed(3)).digest(),
+        receipt(ReceiptStatus::RolledBack).digest(),
+        receipt(ReceiptStatus::Rejected).digest(),
+    ];
+    for (i, digest) in digests.iter().enumerate() {
+        assert!(digests[i + 1..].iter().all(|other| other != digest));
+    }
+}
+
+#[tokio::test]
+async fn lookup_by_tx_digest() {
+    let path = ".db_test_receipt_store";
+    let _ = fs::remove_dir_all(path);
+    let mut receipt_store = ReceiptStore::new(Store::new(path).unwrap());
+
+    let relayed = receipt(ReceiptStatus::Relayed(2));
+    assert_eq!(receipt_store.write(&relayed).await, relayed.digest());
+    assert_eq!(receipt_store.read(&relayed.tx_digest).await.unwrap(), Some(relayed));
+    assert_eq!(receipt_store.read(&Digest([2; 32])).await.unwrap(), None);
+
+    // a later receipt of the tx in our shard replaces the earlier one
+    let aborted = receipt(ReceiptStatus::Aborted(AmountError::Underflow));
+    receipt_store.write(&aborted).await;
+    assert_eq!(receipt_store.read(&aborted.tx_digest).await.unwrap(), Some(aborted));
+}
@@ -0,0 +1,137 @@
+use super::*;
+use config::{Authority, PrimaryAddresses};
+use crypto::{generate_keypair, generate_threshold_keys, Digest, SecretKey};
+use rand::rngs::StdRng;
+use rand::SeedableRng as _;
+
+// Fixture
+fn keys() -> Vec<(PublicKey, SecretKey)> {
+    let mut rng = StdRng::from_seed([0; 32]);
+    (0..4).map(|_| generate_keypair(&mut rng)).collect()
+}
+
+// Fixture: two shards with the same authorities.
+fn committees() -> Committees {
+    let committee = Committee {
+        authorities: keys()
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, _))| {
+                let primary = PrimaryAddresses {
+                    primary_to_primary: format!("127.0.0.1:{}", 100 + i).parse().unwrap(),
+                    worker_to_primary: format!("127.0.0.1:{}", 200 + i).parse().unwrap(),
+                };
+                let authority = Authority {
+                    stake: 1,
+                    primary,
+                    workers: HashMap::new(),
+                };
+                (name, authority)
+            })
+            .collect(),
+    };
+    let mut rng = StdRng::from_seed([1; 32]);
+    let (key_set, _) = generate_threshold_keys(2, 4, &mut rng);
+    Committees {
+        shards: (0..2).map(|shardid| (shardid, committee.clone())).collect(),
+        client: "127.0.0.1:0".parse().unwrap(),
+        shard_num: 2,
+        shard_size: 4,
+        threshold_keys: (0..2).map(|shardid| (shardid, key_set.clone())).collect(),
+    }
+}
+
+// Fixture
+fn result(height: Height, root: u8) -> ExecutionResult {
+    ExecutionResult::new(height, vec![root; 32], vec![root; 32], Digest::default())
+}
+
+// Fixture: the votes of the 4 replicas of shard 0 for the given results.
+async fn votes(results: Vec<ExecutionResult>) -> Vec<ExecutionVote> {
+    let mut votes = Vec::new();
+    for ((name, secret), result) in keys().into_iter().zip(results) {
+        let mut signature_service = SignatureService::new(secret);
+        votes.push(ExecutionVote::new(0, result, &name, &mut signature_service).await);
+    }
+    votes
+}
+
+fn tally() -> ResultTally {
+    ResultTally::new(committees().our_committee(&0).unwrap())
+}
+
+#[tokio::test]
+async fn certify_after_f_plus_one_votes() {
+    let mut tally = tally();
+    let mut votes = votes(vec![result(1, 1); 4]).await.into_iter();
+
+    // f = 1: a single vote may come from a faulty replica
+    assert!(tally.add_vote(votes.next().unwrap()).is_none());
+    let certificate = tally.add_vote(votes.next().unwrap()).unwrap();
+    assert_eq!(certificate.result, result(1, 1));
+    assert_eq!(certificate.votes.len(), 2);
+    assert!(certificate.verify(&committees()).is_ok());
+
+    // the height is settled, the late votes are ignored
+    assert!(tally.add_vote(votes.next().unwrap()).is_none());
+}
+
+#[tokio::test]
+async fn conflicting_votes_do_not_certify() {
+    let mut tally = tally();
+    let votes = votes(vec![result(1, 1), result(1, 2), result(1, 3), result(1, 2)]).await;
+    let certificates: Vec<_> = votes.into_iter().filter_map(|vote| tally.add_vote(vote)).collect();
+
+    // only the last vote completes f+1 matching votes
+    assert_eq!(certificates.len(), 1);
+    assert_eq!(certificates[0].result, result(1, 2));
+}
+
+#[tokio::test]
+async fn duplicate_votes_are_ignored() {
+    let mut tally = tally();
+    let vote = votes(vec![result(1, 1)]).await.pop().unwrap();
+    assert!(tally.add_vote(vote.clone()).is_none());
+    assert!(tally.add_vote(vote).is_none());
+}
+
+#[tokio::test]
+async fn certified_height_settles_lower_heights() {
+    let mut tally = tally();
+    let low = votes(vec![result(1, 1); 4]).await;
+    let high = votes(vec![result(2, 2); 4]).await;
+
+    assert!(tally.add_vote(low[0].clone()).is_none());
+    assert!(tally.add_vote(high[0].clone()).is_none());
+    assert!(tally.add_vote(high[1].clone()).is_some());
+
+    // height 1 is covered by the roots of height 2
+    assert!(tally.add_vote(low[1].clone()).is_none());
+}
+
+#[tokio::test]
+async fn verify_certificate() {
+    let all_committees = committees();
+    let votes = votes(vec![result(1, 1); 4]).await;
+    let certificate = ExecutionCertificate {
+        shard_id: 0,
+        result: result(1, 1),
+        votes: votes.iter().take(2).map(|vote| (vote.author, vote.signature.clone())).collect(),
+    };
+    assert!(certificate.verify(&all_committees).is_ok());
+
+    // not enough stake
+    let mut short = certificate.clone();
+    short.votes.pop();
+    assert!(short.verify(&all_committees).is_err());
+
+    // the same replica twice
+    let mut reused = certificate.clone();
+    reused.votes[1] = reused.votes[0].clone();
+    assert!(reused.verify(&all_committees).is_err());
+
+    // the votes are not for this result
+    let mut tampered = certificate;
+    tampered.result = result(1, 2);
+    assert!(tampered.verify(&all_committees).is_err());
+}
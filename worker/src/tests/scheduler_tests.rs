@@ -0,0 +1,76 @@
+use super::*;
+use crate::amount::Amount;
+use crate::messages::Transaction;
+use crate::state_store::INIT_BALANCE;
+use crate::RWSet;
+use crypto::PublicKey;
+
+// Fixture: a client tx of shard 0 moving funds between two accounts, signed with the genesis key
+// of its sender.
+fn transfer(sender: u8, receiver: u8, amount: i128) -> GeneralTransaction {
+    let payload = vec![Frame {
+        shardid: 0,
+        rwset: vec![
+            RWSet { addr: vec![sender; 20], value: Amount::new(-amount) },
+            RWSet { addr: vec![receiver; 20], value: Amount::new(amount) },
+        ],
+    }];
+    let mut tx = Transaction::new(vec![sender; 20], vec![receiver; 20], Amount::new(amount), payload, 1, 1, 1, 0, 0, None, None);
+    tx.sender_key = PublicKey([sender; 32]);
+    GeneralTransaction::TransferTx(tx)
+}
+
+#[test]
+fn conflicting_txs_are_not_independent() {
+    let txs = vec![
+        transfer(1, 2, 1),
+        transfer(3, 4, 1),
+        // receives from the first tx
+        transfer(2, 5, 1),
+        transfer(6, 7, 1),
+        // touches the second and the third tx
+        transfer(4, 5, 1),
+    ];
+    assert_eq!(independent_txs(&txs), vec![0, 1, 3]);
+    assert!(independent_txs(&[]).is_empty());
+}
+
+#[test]
+fn relayed_tx_conflicts_on_any_frame() {
+    let payload = vec![
+        Frame { shardid: 0, rwset: vec![RWSet { addr: vec![1; 20], value: Amount::new(-1) }] },
+        Frame { shardid: 1, rwset: vec![RWSet { addr: vec![2; 20], value: Amount::new(1) }] },
+    ];
+    let mut tx = Transaction::new(vec![1; 20], vec![2; 20], Amount::new(1), payload, 2, 2, 1, 0, 0, None, None);
+    tx.update_relay_info(0);
+
+    // the relayed tx executes the frame of shard 1, the first frame may be compensated
+    let txs = vec![GeneralTransaction::TransferTx(tx), transfer(1, 3, 1), transfer(4, 2, 1), transfer(5, 6, 1)];
+    assert_eq!(independent_txs(&txs), vec![0, 3]);
+}
+
+#[tokio::test]
+async fn speculate_independent_txs() {
+    let keys: Arc<KeyRegistry> = Arc::new((0..8).map(|addr| (vec![addr; 20], PublicKey([addr; 32]))).collect());
+    let accounts = (0..8).map(|addr| (vec![addr; 20], None)).collect();
+    let mut stale = transfer(6, 7, 1);
+    if let GeneralTransaction::TransferTx(tx) = &mut stale {
+        tx.nonce = 1;
+    }
+    let txs = vec![
+        transfer(0, 1, 10),
+        // conflicts with the first tx
+        transfer(1, 2, 10),
+        transfer(3, 4, INIT_BALANCE.wei() + 1),
+        stale,
+    ];
+
+    // the conflicting tx and the tx with a stale nonce are executed in block order
+    let speculations = speculate_block(0, &txs, &accounts, &keys).await;
+    assert_eq!(speculations.len(), 2);
+    let outcome = speculations[0].outcome.as_ref().unwrap();
+    assert_eq!(outcome[&vec![0; 20]].nonce, 1);
+    assert_eq!(outcome[&vec![0; 20]].balance, INIT_BALANCE.checked_sub(Amount::new(10)).unwrap());
+    assert_eq!(speculations[0].inputs[&vec![1; 20]].balance, INIT_BALANCE);
+    assert!(speculations[1].outcome.is_err());
+}
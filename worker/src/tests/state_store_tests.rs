@@ -0,0 +1,139 @@
+use super::*;
+use std::fs;
+
+// Fixture: an empty TStore of shard 0.
+fn tstore(path: &str) -> TStore {
+    let _ = fs::remove_dir_all(path);
+    let access_path = format!("{}_access", path);
+    let _ = fs::remove_dir_all(&access_path);
+    TStore {
+        shard_id: 0,
+        act_t: MMPTStore::new(),
+        full_t: MPTStore::new(path),
+        insert_dur: Vec::new(),
+        get_act_dur: Vec::new(),
+        get_full_dur: Vec::new(),
+        last_access: HashMap::new(),
+        touched: HashSet::new(),
+        access_log: AccessLog::new(Store::new(&access_path).unwrap()),
+        recovered: false,
+    }
+}
+
+// Fixture: the TStore of a restarted replica, with the same tries but only the persisted accesses.
+fn restarted(store: &TStore) -> TStore {
+    TStore {
+        shard_id: store.shard_id,
+        act_t: store.act_t.clone(),
+        full_t: store.full_t.clone(),
+        insert_dur: Vec::new(),
+        get_act_dur: Vec::new(),
+        get_full_dur: Vec::new(),
+        last_access: HashMap::new(),
+        touched: HashSet::new(),
+        access_log: AccessLog::new(store.access_log.store.clone()),
+        recovered: false,
+    }
+}
+
+fn account(balance: i128) -> Vec<u8> {
+    bincode::serialize(&Account { nonce: 0, balance: Amount::new(balance), key: None }).unwrap()
+}
+
+#[tokio::test]
+async fn evict_cold_accounts() {
+    let mut store = tstore(".db_test_tstore_evict_state");
+    store.insert(vec![1; 20], account(10)).await;
+    store.insert(vec![2; 20], account(20)).await;
+
+    // the accounts were accessed during the last 10 heights
+    assert_eq!(store.evict(10, 10).await, 0);
+    assert!(store.get(&[1; 20]).await.is_some());
+    assert_eq!(store.evict(20, 10).await, 1);
+
+    // the cold account is dormant, with its latest value in full_t
+    assert!(!store.act_t.contains(&[2; 20]).await.unwrap());
+    assert_eq!(store.full_t.get(&[2; 20]).await.unwrap(), Some(account(20)));
+    assert!(matches!(store.get_proof(&[2; 20]).await, AccountProof::Dormant(_, _)));
+    assert!(matches!(store.get_proof(&[1; 20]).await, AccountProof::Active(_)));
+
+    // and is activated again by the next access
+    assert_eq!(store.get(&[2; 20]).await, Some(account(20)));
+    assert_eq!(store.evict(30, 10).await, 1);
+    assert_eq!(store.evict(40, 10).await, 1);
+    assert!(store.last_access.is_empty());
+}
+
+#[tokio::test]
+async fn evict_same_roots() {
+    // the same accesses in another order evict the same accounts
+    let mut a = tstore(".db_test_tstore_evict_roots_0_state");
+    let mut b = tstore(".db_test_tstore_evict_roots_1_state");
+    for addr in 1..=5u8 {
+        a.insert(vec![addr; 20], account(addr as i128)).await;
+    }
+    let accs = (1..=5u8).rev().map(|addr| (vec![addr; 20], account(addr as i128))).collect();
+    b.insert_batch(accs).await;
+    assert_eq!(a.evict(5, 5).await, 0);
+    assert_eq!(b.evict(5, 5).await, 0);
+
+    for addr in [4u8, 2] {
+        a.get(&[addr; 20]).await;
+    }
+    b.peek(&[3; 20]).await;
+    b.insert_batch(vec![(vec![2; 20], account(2)), (vec![4; 20], account(4))].into_iter().collect()).await;
+    assert_eq!(a.evict(10, 5).await, 3);
+    assert_eq!(b.evict(10, 5).await, 3);
+    assert_eq!(a.root().await, b.root().await);
+}
+
+#[tokio::test]
+async fn evict_after_restart() {
+    let mut a = tstore(".db_test_tstore_restart_0_state");
+    let mut b = tstore(".db_test_tstore_restart_1_state");
+    for store in [&mut a, &mut b] {
+        for addr in 1..=5u8 {
+            store.insert(vec![addr; 20], account(addr as i128)).await;
+        }
+        assert_eq!(store.evict(5, 5).await, 0);
+        store.get(&[2; 20]).await;
+        store.get(&[4; 20]).await;
+        assert_eq!(store.evict(8, 5).await, 0);
+    }
+
+    // the restarted replica evicts the same accounts as the others
+    let mut b = restarted(&b);
+    assert_eq!(a.evict(10, 5).await, 3);
+    assert_eq!(b.evict(10, 5).await, 3);
+    assert_eq!(a.root().await, b.root().await);
+    assert_eq!(a.evict(13, 5).await, 2);
+    assert_eq!(b.evict(13, 5).await, 2);
+    assert_eq!(a.root().await, b.root().await);
+}
+
+#[tokio::test]
+async fn prove_at_retained_roots() {
+    let mut store = tstore(".db_test_tstore_retain_state");
+    store.insert(vec![1; 20], account(10)).await;
+    let (act_root, full_root) = store.root().await;
+    let (act_root, full_root) = (act_root.unwrap(), full_root.unwrap());
+    assert!(store.retain(act_root.clone(), full_root.clone()).await);
+
+    // the next heights change the state, the proofs are still served against the retained roots
+    store.insert(vec![1; 20], account(11)).await;
+    store.insert(vec![2; 20], account(20)).await;
+    let (next_act_root, next_full_root) = store.root().await;
+    match store.get_proof_at(act_root.clone(), full_root.clone(), &[1; 20]).await {
+        Some(AccountProof::Active(proof)) => {
+            let value = MerklePatriciaTrie::verify_proof(act_root.clone(), &[1; 20], proof).unwrap();
+            assert_eq!(value, Some(account(10)));
+        }
+        proof => panic!("Unexpected proof {:?}", proof),
+    }
+    let absent = store.get_proof_at(act_root.clone(), full_root.clone(), &[2; 20]).await;
+    assert!(matches!(absent, Some(AccountProof::Dormant(_, _))));
+
+    // until the roots of a later height are retained
+    assert!(store.retain(next_act_root.unwrap(), next_full_root.unwrap()).await);
+    assert!(store.get_proof_at(act_root, full_root, &[1; 20]).await.is_none());
+}
@@ -2,7 +2,7 @@ use std::{mem, collections::HashMap};
 use config::ShardId;
 use crypto::Digest;
 use permutation_iterator::Permutor;
-use crate::{RWSet, Address, messages::Amount};
+use crate::{RWSet, Address, amount::Amount};
 
 
 
@@ -129,4 +129,4 @@ pub fn random_select_brokers(brokers: Vec<Vec<u8>>, total_num: usize, target_num
       selected_brokers.push(broker);
   }
   selected_brokers
-}
\ No newline at end of file
+}
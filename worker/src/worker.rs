@@ -1,25 +1,37 @@
 use crate::cs_msg_verifier_serial::CSMsgVerifierSerial;
 use crate::csmsg_store::CSMsgStore;
+use crate::executor::{ExecutionDriver, Executor};
 use crate::executor_b::BExecutor;
+use crate::executor_2pc::TwoPCExecutor;
 use crate::executor_m::MExecutor;
 use crate::cs_msg_sender_b::Send2Broker;
 use crate::batch_fetcher::MissingBatchFetcher;
 use crate::{ExecutorType, Account2Shard, StateStore, StateTransition, AppendType};
+use crate::state_store::KeyRegistry;
 use crate::batch_maker::{Batch, BatchMaker};
 use crate::cs_msg_verifier::CSMsgVerifier;
 use crate::helper::Helper;
-use crate::messages::{Height, GeneralTransaction, CSMsg};
+use crate::messages::{Height, GeneralTransaction, CSMsg, CSMsgAck, CrossShardMessage, Migration};
+use crate::epoch::{EpochManager, EpochSchedule};
+use crate::migration::Migrator;
+use crate::migration_sender::MigrationSender;
+use crate::migration_verifier::MigrationVerifier;
 use crate::primary_connector::PrimaryConnector;
 use crate::cs_msg_sender::SendCSMsg;
+use crate::cs_msg_acker::CSMsgAcker;
 use crate::processor::{Processor, SerializedBatchMessage};
 use crate::quorum_waiter::QuorumWaiter;
 use crate::synchronizer::Synchronizer;
 use crate::executor_s::SExecutor;
+use crate::execution_result::ExecutionVote;
+use crate::result_checker::ResultChecker;
+use crate::receipt::ReceiptStore;
+use crate::query::{QueryService, StateQuery};
 use crate::tx_convertor::TxConvertor;
 use async_trait::async_trait;
 use bytes::Bytes;
 use config::{Committee, Parameters, WorkerId, ShardId, Committees};
-use crypto::{Digest, PublicKey, SignatureService, SecretKey};
+use crypto::{Digest, PublicKey, SignatureService, SecretKey, ThresholdSecretShare};
 use futures::sink::SinkExt as _;
 use log::{error, info, warn};
 use network::{MessageHandler, Receiver, Writer};
@@ -79,6 +91,7 @@ pub struct SynchronizationMessage {
 pub struct SendCSMessage {
   pub height: Height,
   pub target_shard: ShardId,
+  pub epoch: u64,
   pub tx: GeneralTransaction,
 }
 
@@ -115,6 +128,7 @@ impl Worker {
         append_type: AppendType, 
         name: PublicKey,
         secret: SecretKey,
+        threshold_share: Option<ThresholdSecretShare>,
         id: WorkerId,
         _cs_faults: usize,
         is_malicious: bool,
@@ -124,7 +138,10 @@ impl Worker {
         shardid: ShardId,
         all_committees: Committees,
         primary_store: Box<dyn StateStore + Send>, 
+        keys: Arc<KeyRegistry>,
         account_shard: Box<dyn Account2Shard + Send>,
+        epoch: u64,
+        epoch_schedule: Option<EpochSchedule>,
     ) {
 
         let mut all_id_pubkey_map: HashMap<(ShardId, NodeId), (PublicKey, SocketAddr)> = HashMap::new();
@@ -171,8 +188,10 @@ impl Worker {
         //   );
         // }
 
-        // Make the csmsg_status store.
-        let csmsg_store = CSMsgStore::new(committee.validity_threshold());
+        // Make the csmsg_status store, whose executed marks are persisted along with the batches.
+        let csmsg_store = CSMsgStore::new_with_store(
+          committee.validity_threshold(), store.clone(), all_committees.shard_num(), parameters.csmsg_gc_depth,
+        );
 
         // Define a worker instance.
         let worker = Self {
@@ -194,11 +213,19 @@ impl Worker {
         // Spawn all worker tasks.
         let (tx_primary, rx_primary) = channel(CHANNEL_CAPACITY);
         let(tx_process, rx_process) = channel(CHANNEL_CAPACITY);
+        let (tx_csmsg_ack, rx_csmsg_ack) = channel(CHANNEL_CAPACITY);
+        let (tx_execution_vote, rx_execution_vote) = channel(CHANNEL_CAPACITY);
+        let (tx_state_query, rx_state_query) = channel(CHANNEL_CAPACITY);
 
         worker.handle_primary_messages(tx_process);
-        worker.handle_clients_transactions(tx_primary.clone(), _cs_faults, append_type);
+        worker.handle_clients_transactions(
+          tx_primary.clone(), tx_csmsg_ack, tx_execution_vote, _cs_faults, append_type,
+        );
         worker.handle_workers_messages(tx_primary);
-        worker.handle_tx_processing(rx_process, secret, primary_store, account_shard);        
+        worker.handle_queries(tx_state_query);
+        worker.handle_tx_processing(
+          rx_process, rx_csmsg_ack, rx_execution_vote, rx_state_query, secret, threshold_share, primary_store, keys, account_shard, epoch, epoch_schedule,
+        );
 
         // The `PrimaryConnector` allows the worker to send messages to its primary.
         PrimaryConnector::spawn(
@@ -224,17 +251,45 @@ impl Worker {
         );
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn handle_tx_processing(
       &self, 
       rx_process: ChannelReceiver<ConversionMessage>,
+      rx_csmsg_ack: ChannelReceiver<CSMsgAck>,
+      rx_execution_vote: ChannelReceiver<ExecutionVote>,
+      rx_state_query: ChannelReceiver<StateQuery>,
       secret: SecretKey,
+      threshold_share: Option<ThresholdSecretShare>,
       primary_store: Box<dyn StateStore + Send>,
+      keys: Arc<KeyRegistry>,
       account_shard: Box<dyn Account2Shard + Send>,
+      epoch: u64,
+      epoch_schedule: Option<EpochSchedule>,
     ) {
-      let signature_service = SignatureService::new(secret);
+      let signature_service = SignatureService::new_with_threshold_share(secret, threshold_share);
       let(tx_csmsg, rx_csmsg) = channel(CHANNEL_CAPACITY);
       let(tx_execution, rx_execution) = channel(CHANNEL_CAPACITY);
 
+      // live migration: at each epoch boundary the epoch manager hands the migration plan to the
+      // migrator, which exports and installs accounts on behalf of the executor; the sender
+      // certifies our migrations and the verifier appends those of the other shards to our DAG
+      let (tx_plan, rx_plan) = channel(CHANNEL_CAPACITY);
+      let (tx_outgoing, rx_outgoing) = channel(CHANNEL_CAPACITY);
+      MigrationSender::spawn(
+        self.name,
+        signature_service.clone(),
+        self.all_id_pubkey_map.clone(),
+        rx_outgoing,
+      );
+      let migrator = Migrator::new(self.shardid, rx_plan, tx_outgoing);
+      let epoch_manager = EpochManager::new(
+        self.shardid,
+        self.all_committees.shard_num(),
+        epoch,
+        account_shard,
+        epoch_schedule,
+        tx_plan,
+      );
 
       let fetch_batch = MissingBatchFetcher::new(
         self.name, 
@@ -252,41 +307,53 @@ impl Worker {
         fetch_batch,
       );   
       
+      // the replicas of our shard exchange the execution result of every height, and an executor
+      // whose result differs from the one certified by f+1 of them halts
+      let result_checker = ResultChecker::spawn(
+        self.shardid,
+        self.name,
+        self.all_committees.clone(),
+        signature_service.clone(),
+        self.all_id_pubkey_map.clone(),
+        self.store.clone(),
+        rx_execution_vote,
+      );
+
       // create executor
-      let state_transition = StateTransition::new(primary_store);
-      match self.executor_type {
-        ExecutorType::SharDAG => {
-          SExecutor::spawn(
-            self.shardid,
-            rx_execution,
-            tx_csmsg,
-            state_transition,
-            account_shard,
-            self.csmsg_store.clone(),
-          );
+      let executor: Box<dyn Executor> = match self.executor_type {
+        ExecutorType::SharDAG => Box::new(SExecutor::new(&self.parameters)),
+        ExecutorType::Monoxide => Box::new(MExecutor),
+        ExecutorType::BrokerChain => Box::new(BExecutor),
+        ExecutorType::TwoPhaseCommit => Box::new(TwoPCExecutor::new(self.parameters.commit_timeout)),
+      };
+      ExecutionDriver::spawn(
+        executor,
+        self.shardid,
+        self.all_committees.clone(),
+        rx_execution,
+        tx_csmsg,
+        StateTransition::new(primary_store, keys),
+        epoch_manager,
+        self.csmsg_store.clone(),
+        migrator,
+        result_checker,
+        ReceiptStore::new(self.store.clone()),
+        rx_state_query,
+        self.parameters.evict_interval,
+        self.parameters.evict_idle,
+      );
 
-          SendCSMsg::spawn(
+      match self.executor_type {
+        ExecutorType::SharDAG | ExecutorType::Monoxide | ExecutorType::TwoPhaseCommit => {
+          // csmsgs are retransmitted until f+1 nodes of the target shard acknowledge their execution
+          CSMsgAcker::spawn(
             self.shardid,
-            self.nodeid,
-            self.is_malicious,
             self.all_committees.shard_num(),
-            self.committee.size(),
             self.name,
-            signature_service,      
+            signature_service.clone(),
             self.all_id_pubkey_map.clone(),
-            self.committee.quorum_threshold() as usize,
-            self.committee.validity_threshold() as usize,
-            rx_csmsg,         
-          );
-        },
-        ExecutorType::Monoxide => {
-          MExecutor::spawn(
-            self.shardid,
-            rx_execution,
-            tx_csmsg,
-            state_transition,
-            account_shard,
             self.csmsg_store.clone(),
+            self.parameters.csmsg_ack_interval,
           );
           SendCSMsg::spawn(
             self.shardid,
@@ -296,22 +363,17 @@ impl Worker {
             self.committee.size(),
             self.name,
             signature_service,      
+            self.all_committees.clone(),
             self.all_id_pubkey_map.clone(),
             self.committee.quorum_threshold() as usize,
             self.committee.validity_threshold() as usize,
+            self.parameters.csmsg_retry_delay,
             rx_csmsg,         
-          );            
+            rx_csmsg_ack,
+            self.store.clone(),
+          );
         },
         ExecutorType::BrokerChain => {
-          BExecutor::spawn(
-            self.shardid,
-            rx_execution,
-            tx_csmsg,
-            state_transition,
-            account_shard,
-            self.csmsg_store.clone(),
-          );
-
           let client_addr = self.all_committees.client;
           Send2Broker::spawn(
             self.shardid,
@@ -324,6 +386,7 @@ impl Worker {
             self.committee.quorum_threshold() as usize,
             self.committee.validity_threshold() as usize,
             client_addr,
+            self.parameters.sender_gc_depth,
             rx_csmsg,         
           );     
         }            
@@ -370,6 +433,8 @@ impl Worker {
     /// Spawn all tasks responsible to handle clients transactions.
     fn handle_clients_transactions(&self, 
       tx_primary: Sender<SerializedBatchDigestMessage>,
+      tx_csmsg_ack: Sender<CSMsgAck>,
+      tx_execution_vote: Sender<ExecutionVote>,
       _cs_faults: usize,
       _append_type: AppendType,
     ) {
@@ -378,6 +443,7 @@ impl Worker {
         let (tx_processor, rx_processor) = channel(CHANNEL_CAPACITY);
 
         let (tx_cross_shard_msg, rx_cross_shard_msg) = channel(CHANNEL_CAPACITY);
+        let (tx_migration, rx_migration) = channel(CHANNEL_CAPACITY);
         
         let tx_batch_maker_2 = tx_batch_maker.clone();
         // We first receive clients' transactions from the network.
@@ -402,7 +468,16 @@ impl Worker {
         Receiver::spawn(
             address_cross_shard,
             /* handler */
-            CrossShardReceiverHandler { tx_cross_shard_msg },
+            CrossShardReceiverHandler { tx_cross_shard_msg, tx_csmsg_ack, tx_migration, tx_execution_vote },
+        );
+
+        // certify the migrations of the other shards and append them to our DAG
+        MigrationVerifier::spawn(
+            self.shardid,
+            self.nodeid,
+            self.all_committees.clone(),
+            rx_migration,
+            tx_batch_maker_2.clone(),
         );
 
         // create CSMsgVerifier
@@ -412,7 +487,6 @@ impl Worker {
                 rx_cross_shard_msg,
                 tx_batch_maker_2,
                 self.all_committees.clone(),
-                self.committee.validity_threshold(),
                 self.committee.size(),
                 self.nodeid,
                 self.is_malicious,
@@ -517,6 +591,7 @@ impl Worker {
             /* own_batch */ true,
             self.csmsg_store.clone(), // unused
             self.committee.validity_threshold(),
+            self.all_committees.clone(),
         );
 
         info!(
@@ -571,6 +646,7 @@ impl Worker {
             /* own_batch */ false,
             self.csmsg_store.clone(),
             self.committee.validity_threshold(),
+            self.all_committees.clone(),
         );
 
         info!(
@@ -579,6 +655,32 @@ impl Worker {
         );
     }
 
+    /// Spawn the task answering the queries of clients.
+    fn handle_queries(&self, tx_state_query: Sender<StateQuery>) {
+        let mut address = self
+            .committee
+            .worker(&self.name, &self.id)
+            .expect("Our public key or worker id is not in the committee")
+            .query;
+        address.set_ip("0.0.0.0".parse().unwrap());
+        Receiver::spawn(
+            address,
+            /* handler */
+            QueryReceiverHandler {
+                query_service: QueryService::new(
+                    tx_state_query,
+                    self.store.clone(),
+                    self.csmsg_store.clone(),
+                ),
+            },
+        );
+
+        info!(
+            "[({}, {})] Worker {} listening to queries on {}",
+            self.shardid, self.nodeid, self.id, address
+        );
+    }
+
 }
 
 /// Defines how the network receiver handles incoming transactions.
@@ -611,6 +713,22 @@ impl MessageHandler for TxReceiverHandler {
     }
 }
 
+/// Defines how the network receiver answers the JSON-RPC queries of clients.
+#[derive(Clone)]
+struct QueryReceiverHandler {
+    query_service: QueryService,
+}
+
+#[async_trait]
+impl MessageHandler for QueryReceiverHandler {
+    async fn dispatch(&self, writer: &mut Writer, serialized: Bytes) -> Result<(), Box<dyn Error>> {
+        let response = self.query_service.handle(&serialized).await;
+        let bytes = serde_json::to_vec(&response).expect("Failed to serialize query response");
+        writer.send(Bytes::from(bytes)).await?;
+        Ok(())
+    }
+}
+
 /// Defines how the network receiver handles incoming workers messages.
 #[derive(Clone)]
 struct WorkerReceiverHandler {
@@ -674,6 +792,9 @@ impl MessageHandler for PrimaryReceiverHandler {
 #[derive(Clone)]
 struct CrossShardReceiverHandler {
     tx_cross_shard_msg: Sender<CSMsg>,
+    tx_csmsg_ack: Sender<CSMsgAck>,
+    tx_migration: Sender<Migration>,
+    tx_execution_vote: Sender<ExecutionVote>,
 }
 
 #[async_trait]
@@ -684,14 +805,29 @@ impl MessageHandler for CrossShardReceiverHandler {
       serialized: Bytes,
     ) -> Result<(), Box<dyn Error>> {
 
-        // Deserialize the message and send it to the batch maker.
+        // Deserialize the message and send it to the csmsg verifier, the csmsg sender, the migration verifier or the result checker.
         match bincode::deserialize(&serialized) {
             Err(e) => error!("Failed to deserialize cross shard msg: {}", e),
-            Ok(message) => self
+            Ok(CrossShardMessage::CSMsg(message)) => self
                 .tx_cross_shard_msg
                 .send(message)
                 .await
                 .expect("Failed to send cross shard msg"),
+            Ok(CrossShardMessage::CSMsgAck(ack)) => self
+                .tx_csmsg_ack
+                .send(ack)
+                .await
+                .expect("Failed to send csmsg ack"),
+            Ok(CrossShardMessage::Migration(migration)) => self
+                .tx_migration
+                .send(migration)
+                .await
+                .expect("Failed to send migration"),
+            Ok(CrossShardMessage::ExecutionVote(vote)) => self
+                .tx_execution_vote
+                .send(vote)
+                .await
+                .expect("Failed to send execution vote"),
         }
 
         // Give the change to schedule other tasks.